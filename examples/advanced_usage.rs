@@ -19,8 +19,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .expect("GOOGLE_API_KEY must be set");
 
     // Initialize clients
-    let chroma = ChromaClient::new(chroma_host);
-    let embeddings = EmbeddingClient::new(google_api_key);
+    let chroma = ChromaClient::try_new(chroma_host)?;
+    let embeddings = EmbeddingClient::try_new(google_api_key)?;
 
     // Collection name
     let collection_name = "advanced_demo";
@@ -62,6 +62,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 m.insert("year".to_string(), "2023".to_string());
                 m
             },
+            uri: None,
         },
         Document {
             id: Uuid::new_v4().to_string(),
@@ -74,6 +75,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 m.insert("year".to_string(), "2023".to_string());
                 m
             },
+            uri: None,
         },
         Document {
             id: Uuid::new_v4().to_string(),
@@ -86,6 +88,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 m.insert("year".to_string(), "2023".to_string());
                 m
             },
+            uri: None,
         },
         Document {
             id: Uuid::new_v4().to_string(),
@@ -98,6 +101,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 m.insert("year".to_string(), "2023".to_string());
                 m
             },
+            uri: None,
         },
         Document {
             id: Uuid::new_v4().to_string(),
@@ -110,6 +114,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 m.insert("year".to_string(), "2023".to_string());
                 m
             },
+            uri: None,
         },
     ];
 
@@ -136,12 +141,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let results = chroma.query(collection_name, vec![query_embedding], 3).await?;
     
     println!("Query: '{}'", query_text);
-    for (i, (id, doc, distance)) in results.ids[0]
-        .iter()
-        .zip(results.documents[0].iter())
-        .zip(results.distances[0].iter())
-        .enumerate()
-    {
+    for i in 0..results.ids_for(0).len() {
+        let distance = results.distance(0, i).unwrap_or_default();
+        let doc = results.document(0, i).unwrap_or_default();
         println!("  {}. [distance: {:.4}] {}", i + 1, distance, doc);
     }
 
@@ -157,12 +159,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     ).await?;
     
     println!("Query: 'easy to learn' (filtered by category=programming)");
-    for (i, (id, doc, distance)) in filtered_results.ids[0]
-        .iter()
-        .zip(filtered_results.documents[0].iter())
-        .zip(filtered_results.distances[0].iter())
-        .enumerate()
-    {
+    for i in 0..filtered_results.ids_for(0).len() {
+        let distance = filtered_results.distance(0, i).unwrap_or_default();
+        let doc = filtered_results.document(0, i).unwrap_or_default();
         println!("  {}. [distance: {:.4}] {}", i + 1, distance, doc);
     }
 
@@ -183,8 +182,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     ).await?;
     
     println!("Documents with difficulty=intermediate AND year=2023:");
-    for (i, doc) in intermediate_docs.documents[0].iter().enumerate() {
-        println!("  {}. {}", i + 1, doc);
+    for i in 0..intermediate_docs.ids_for(0).len() {
+        println!("  {}. {}", i + 1, intermediate_docs.document(0, i).unwrap_or_default());
     }
 
     // Get specific documents by ID
@@ -198,7 +197,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     ).await?;
     
     println!("Document with ID {}:", first_doc_id);
-    if let Some(doc) = specific_docs.documents[0].first() {
+    if let Some(doc) = specific_docs.document(0, 0) {
         println!("  Content: {}", doc);
     }
 
@@ -224,7 +223,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         None
     ).await?;
     
-    if let Some(doc) = updated_docs.documents[0].first() {
+    if let Some(doc) = updated_docs.document(0, 0) {
         println!("Updated content: {}", doc);
     }
 
@@ -2,80 +2,9 @@
 // This demonstrates what's ready for production deployment NOW
 
 
-use chromadb_demo::{ChromaClient, EmbeddingClient};
-use serde::{Deserialize, Serialize};
+use chromadb_demo::{ChromaClient, EmbeddingClient, StoredDocument, VectorStore};
 use std::collections::HashMap;
 use std::fs;
-use uuid::Uuid;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ProductionDocument {
-    pub id: String,
-    pub content: String,
-    pub embedding: Vec<f32>,
-    pub metadata: HashMap<String, String>,
-    pub created_at: chrono::DateTime<chrono::Utc>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct VectorStore {
-    pub documents: Vec<ProductionDocument>,
-    pub dimension: usize,
-    pub model: String,
-}
-
-impl VectorStore {
-    pub fn new() -> Self {
-        Self {
-            documents: Vec::new(),
-            dimension: 3072, // Gemini embedding dimension
-            model: "gemini-embedding-exp-03-07".to_string(),
-        }
-    }
-
-    pub fn add_document(&mut self, doc: ProductionDocument) {
-        self.documents.push(doc);
-    }
-
-    pub fn search(&self, query_embedding: &[f32], k: usize) -> Vec<(f32, &ProductionDocument)> {
-        let mut similarities: Vec<(f32, &ProductionDocument)> = self.documents
-            .iter()
-            .map(|doc| {
-                let similarity = cosine_similarity(query_embedding, &doc.embedding);
-                (similarity, doc)
-            })
-            .collect();
-
-        // Sort by similarity (descending)
-        similarities.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
-        
-        similarities.into_iter().take(k).collect()
-    }
-
-    pub fn save_to_file(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let json = serde_json::to_string_pretty(self)?;
-        fs::write(path, json)?;
-        Ok(())
-    }
-
-    pub fn load_from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let json = fs::read_to_string(path)?;
-        let store: VectorStore = serde_json::from_str(&json)?;
-        Ok(store)
-    }
-}
-
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
-    let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
-    
-    if norm_a == 0.0 || norm_b == 0.0 {
-        0.0
-    } else {
-        dot_product / (norm_a * norm_b)
-    }
-}
 
 /// 🚀 Production-Ready ChromaDB Demo
 /// =================================
@@ -192,7 +121,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // 1. ChromaDB Health Monitoring (PRODUCTION READY)
     println!("\n📊 1. Production Health Monitoring");
-    let chroma = ChromaClient::new(chroma_host);
+    let chroma = ChromaClient::try_new(chroma_host)?;
     
     match chroma.health_check().await {
         Ok(true) => println!("✅ ChromaDB is healthy and accessible"),
@@ -209,7 +138,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // 2. Gemini Embeddings (PRODUCTION READY)
     println!("\n🧠 2. Production Embedding Generation");
-    let embedding_client = EmbeddingClient::new(google_api_key);
+    let embedding_client = EmbeddingClient::try_new(google_api_key)?;
 
     let sample_documents = vec![
         ("rust-systems", "Rust is a systems programming language that runs blazingly fast and prevents segfaults.", "programming"),
@@ -230,7 +159,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         // Generate real embedding (PRODUCTION READY)
         let embedding = embedding_client.embed_text(content).await?;
         
-        let doc = ProductionDocument {
+        let doc = StoredDocument {
             id: id.to_string(),
             content: content.to_string(),
             embedding,
@@ -243,7 +172,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             created_at: chrono::Utc::now(),
         };
         
-        vector_store.add_document(doc);
+        vector_store.add_document(doc)?;
     }
     
     println!("✅ Generated {} embeddings with {} dimensions", 
@@ -276,7 +205,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         let query_embedding = embedding_client.embed_text(query).await?;
         
         // Search similar documents
-        let results = loaded_store.search(&query_embedding, 3);
+        let results = loaded_store.search(&query_embedding, 3)?;
         
         println!("  Top {} results:", results.len());
         for (i, (similarity, doc)) in results.iter().enumerate() {
@@ -16,7 +16,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .unwrap_or_else(|_| "http://localhost:8000".to_string());
 
     // Initialize ChromaDB client
-    let chroma = ChromaClient::new(chroma_host);
+    let chroma = ChromaClient::try_new(chroma_host)?;
 
     println!("🚀 Simple ChromaDB Demo");
     println!("======================");
@@ -71,6 +71,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 m.insert("language".to_string(), "rust".to_string());
                 m
             },
+            uri: None,
         },
         Document {
             id: Uuid::new_v4().to_string(),
@@ -81,6 +82,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 m.insert("type".to_string(), "vector".to_string());
                 m
             },
+            uri: None,
         },
     ];
 
@@ -106,14 +108,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     match chroma.query(collection_name, vec![query_embedding], 2).await {
         Ok(results) => {
             println!("✓ Query completed successfully");
-            println!("Found {} results:", results.ids[0].len());
+            println!("Found {} results:", results.ids_for(0).len());
             
-            for (i, ((_id, doc), distance)) in results.ids[0]
-                .iter()
-                .zip(results.documents[0].iter())
-                .zip(results.distances[0].iter())
-                .enumerate()
-            {
+            for i in 0..results.ids_for(0).len() {
+                let distance = results.distance(0, i).unwrap_or_default();
+                let doc = results.document(0, i).unwrap_or_default();
                 println!("  {}. [distance: {:.4}] {}", i + 1, distance, doc);
             }
         }
@@ -130,7 +129,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Ok(api_key) if !api_key.is_empty() && api_key != "your_google_api_key_here" => {
             println!("✓ Google API key found, testing real embeddings...");
             
-            let embedding_client = EmbeddingClient::new(api_key);
+            let embedding_client = EmbeddingClient::try_new(api_key)?;
             
             match embedding_client.embed_text("Test embedding generation with Gemini").await {
                 Ok(embedding) => {
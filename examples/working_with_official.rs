@@ -1,7 +1,7 @@
 // Simple working example using our custom client (which works) 
 // while we investigate the official chromadb crate API
 
-use chromadb_demo::{ChromaClient, EmbeddingClient, Document};
+use chromadb_demo::{ChromaClient, EmbeddingClient, Document, HttpClientFactory};
 use std::collections::HashMap;
 use uuid::Uuid;
 
@@ -23,7 +23,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Test with our working custom client first
     println!("\n1. Testing Custom ChromaDB Client");
-    let chroma = ChromaClient::new(chroma_host);
+    let chroma = ChromaClient::try_new(chroma_host)?;
 
     // Health check
     match chroma.health_check().await {
@@ -44,7 +44,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Try to create collection using PUT method (correct HTTP method)
     println!("Testing collection creation with PUT method...");
     
-    let client = reqwest::Client::new();
+    let client = HttpClientFactory::shared()?;
     let create_url = format!("http://localhost:8000/api/v1/collections/{}", collection_name);
     
     let response = client
@@ -73,6 +73,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     m.insert("category".to_string(), "test".to_string());
                     m
                 },
+                uri: None,
             },
             Document {
                 id: Uuid::new_v4().to_string(),
@@ -82,6 +83,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     m.insert("category".to_string(), "test".to_string());
                     m
                 },
+                uri: None,
             },
         ];
 
@@ -97,13 +99,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 
                 match chroma.query(collection_name, vec![query_embedding], 2).await {
                     Ok(results) => {
-                        println!("✓ Query successful, found {} results", results.ids[0].len());
-                        for (i, ((id, doc), distance)) in results.ids[0]
-                            .iter()
-                            .zip(results.documents[0].iter())
-                            .zip(results.distances[0].iter())
-                            .enumerate()
-                        {
+                        println!("✓ Query successful, found {} results", results.ids_for(0).len());
+                        for i in 0..results.ids_for(0).len() {
+                            let distance = results.distance(0, i).unwrap_or_default();
+                            let doc = results.document(0, i).unwrap_or_default();
                             println!("  {}. [distance: {:.4}] {}", i + 1, distance, doc);
                         }
                     }
@@ -147,7 +146,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Ok(api_key) if !api_key.is_empty() && api_key != "your_google_api_key_here" => {
             println!("✓ Google API key found, testing embeddings...");
             
-            let embedding_client = EmbeddingClient::new(api_key);
+            let embedding_client = EmbeddingClient::try_new(api_key)?;
             
             match embedding_client.embed_text("Hello, this is a test").await {
                 Ok(embedding) => {
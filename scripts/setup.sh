@@ -97,17 +97,17 @@ done
 
 # Build the Rust project
 print_step "Building Rust project..."
-cargo build
+cargo build --features full
 
 # Run tests
 print_step "Running tests..."
-cargo test
+cargo test --features full
 
 print_status "Setup completed successfully! 🎉"
 echo
 echo "Next steps:"
 echo "1. Edit .env file and add your Google API key"
-echo "2. Run the application: cargo run --bin chroma_client"
+echo "2. Run the application: cargo run --features cli --bin chroma_client"
 echo "3. Check ChromaDB logs: docker-compose logs -f chromadb"
 echo "4. Stop ChromaDB: docker-compose down"
 echo
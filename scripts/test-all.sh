@@ -42,7 +42,7 @@ fi
 
 # Build project
 echo "3. Building project..."
-if cargo build; then
+if cargo build --features full; then
     print_status "Project built successfully"
 else
     print_error "Build failed"
@@ -51,7 +51,7 @@ fi
 
 # Run unit tests
 echo "4. Running unit tests..."
-if cargo test; then
+if cargo test --features full; then
     print_status "Unit tests passed"
 else
     print_error "Unit tests failed"
@@ -60,7 +60,7 @@ fi
 
 # Test basic client functionality
 echo "5. Testing basic functionality..."
-if timeout 60 cargo run --bin chroma_client; then
+if timeout 60 cargo run --features cli --bin chroma_client; then
     print_status "Basic functionality test passed"
 else
     print_warning "Basic functionality test timed out or failed"
@@ -105,7 +105,7 @@ echo "✓ Basic functionality works"
 echo "✓ Code quality checks"
 echo
 echo "To run individual components:"
-echo "  Basic demo:     cargo run --bin chroma_client"
+echo "  Basic demo:     cargo run --features cli --bin chroma_client"
 echo "  Advanced demo:  cargo run --example advanced_usage"
 echo "  Unit tests:     cargo test"
 echo "  Formatting:     cargo fmt"
@@ -0,0 +1,156 @@
+use crate::error::{ChromaError, Result};
+use crate::models::Document;
+use serde_json::{json, Value};
+
+/// Metadata key holding a document's allowed groups, comma-separated.
+pub const ACL_FIELD: &str = "acl";
+
+/// Groups one document may list. Chroma metadata values are scalars, so
+/// each group is also written to its own `acl_<i>` slot for `$in` to match,
+/// and every query checks each slot.
+pub const MAX_ACL_GROUPS: usize = 8;
+
+/// Label `document` as readable by members of `groups` only, replacing
+/// any earlier label. Queries made with `QueryOptions::with_acl_groups`
+/// return it to callers in at least one of them; a document without a
+/// label is returned to no such caller.
+pub fn set_acl<I, S>(document: &mut Document, groups: I) -> Result<()>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut labels: Vec<String> = Vec::new();
+    for group in groups {
+        let group = group.as_ref().trim();
+        if group.is_empty() || group.contains(',') {
+            return Err(ChromaError::ValidationError(format!(
+                "Invalid ACL group '{}' on document {}: must be non-empty and contain no commas",
+                group, document.id
+            )));
+        }
+        if !labels.iter().any(|label| label == group) {
+            labels.push(group.to_string());
+        }
+    }
+    if labels.len() > MAX_ACL_GROUPS {
+        return Err(ChromaError::ValidationError(format!(
+            "Document {} lists {} ACL groups, over the limit of {}",
+            document.id,
+            labels.len(),
+            MAX_ACL_GROUPS
+        )));
+    }
+
+    for slot in 0..MAX_ACL_GROUPS {
+        match labels.get(slot) {
+            Some(group) => document.metadata.insert(slot_field(slot), group.clone()),
+            None => document.metadata.remove(&slot_field(slot)),
+        };
+    }
+    document.metadata.insert(ACL_FIELD.to_string(), labels.join(","));
+    Ok(())
+}
+
+/// The groups listed in a hit's `acl` metadata.
+pub fn acl_groups(metadata: Option<&Value>) -> Vec<&str> {
+    metadata
+        .and_then(|m| m.get(ACL_FIELD))
+        .and_then(Value::as_str)
+        .map(|acl| acl.split(',').map(str::trim).filter(|g| !g.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+/// Whether a member of `groups` may see a hit with `metadata`.
+pub fn is_allowed(metadata: Option<&Value>, groups: &[String]) -> bool {
+    acl_groups(metadata).iter().any(|label| groups.iter().any(|g| g == label))
+}
+
+/// The `where` clause matching documents readable by `groups`, combined
+/// with `where_filter` if there is one.
+pub(crate) fn acl_filter(groups: &[String], where_filter: Option<Value>) -> Value {
+    // `set_acl` never writes an empty group, so with no groups this matches
+    // nothing rather than sending an empty `$in` that Chroma rejects.
+    let groups: Vec<&str> = if groups.is_empty() {
+        vec![""]
+    } else {
+        groups.iter().map(String::as_str).collect()
+    };
+    let slots: Vec<Value> = (0..MAX_ACL_GROUPS)
+        .map(|slot| json!({ slot_field(slot): { "$in": groups } }))
+        .collect();
+    let acl_filter = json!({ "$or": slots });
+
+    match where_filter {
+        Some(filter) => json!({ "$and": [filter, acl_filter] }),
+        None => acl_filter,
+    }
+}
+
+fn slot_field(slot: usize) -> String {
+    format!("{}_{}", ACL_FIELD, slot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Include;
+    use crate::query::QueryOptions;
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_acl_groups_filter_queries_and_strip_unlabelled_hits() {
+        let mut doc = Document::builder().content("payroll").build();
+        set_acl(&mut doc, ["hr", "finance", "hr"]).unwrap();
+        assert_eq!(doc.metadata["acl"], "hr,finance");
+        assert_eq!(doc.metadata["acl_1"], "finance");
+        set_acl(&mut doc, ["hr"]).unwrap();
+        assert!(!doc.metadata.contains_key("acl_1"));
+        assert!(set_acl(&mut doc, ["a,b"]).is_err());
+        assert!(set_acl(&mut doc, (0..=MAX_ACL_GROUPS).map(|i| i.to_string())).is_err());
+
+        let sent = Arc::new(std::sync::Mutex::new(None));
+        let recorded = sent.clone();
+        let chroma = mock_chroma(move |request| {
+            if request.uri().path().ends_with("/query") {
+                let query: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                *recorded.lock().unwrap() = Some(query["where"].clone());
+                // As if the filter were lost on the way: one hit for another group.
+                r#"{"ids": [["ok", "secret", "unlabelled"]], "distances": [[0.1, 0.2, 0.3]],
+                    "metadatas": [[{"acl": "eng,hr"}, {"acl": "finance"}, {}]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+
+        let options = QueryOptions::new(5).with_filter(serde_json::json!({"lang": "en"})).with_acl_groups(["hr"]);
+        let hits = chroma.query_with_options("docs", vec![0.1], &options).await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["ok"]);
+
+        let filter = sent.lock().unwrap().clone().unwrap();
+        assert_eq!(filter["$and"][0], serde_json::json!({"lang": "en"}));
+        let slots = filter["$and"][1]["$or"].as_array().unwrap();
+        assert_eq!(slots.len(), MAX_ACL_GROUPS);
+        assert_eq!(slots[0], serde_json::json!({"acl_0": {"$in": ["hr"]}}));
+    }
+
+    #[tokio::test]
+    async fn test_acl_queries_request_metadatas_left_out_of_include() {
+        let sent = Arc::new(std::sync::Mutex::new(None));
+        let recorded = sent.clone();
+        let chroma = mock_chroma(move |request| {
+            if request.uri().path().ends_with("/query") {
+                let query: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                *recorded.lock().unwrap() = Some(query["include"].clone());
+                r#"{"ids": [["ok"]], "distances": [[0.1]], "documents": [["payroll"]], "metadatas": [[{"acl": "hr"}]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+
+        let options = QueryOptions::new(5).with_include(vec![Include::Documents]).with_acl_groups(["hr"]);
+        let hits = chroma.query_with_options("docs", vec![0.1], &options).await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["ok"]);
+        assert_eq!(sent.lock().unwrap().clone().unwrap(), serde_json::json!(["documents", "metadatas"]));
+    }
+}
@@ -0,0 +1,223 @@
+use crate::error::Result;
+use crate::middleware::{Middleware, Next};
+use futures::future::BoxFuture;
+use reqwest::{Request, Response};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+use uuid::Uuid;
+
+const DEFAULT_CAPACITY: usize = 1000;
+/// Chroma endpoints that change or remove existing records.
+const WRITE_ENDPOINTS: [&str; 3] = ["/upsert", "/update", "/delete"];
+
+/// Generated answers keyed by what produced them: the question, the ids of
+/// the chunks retrieved as context (in any order) and the model.
+///
+/// If retrieval returns the same chunks for the same question, the answer
+/// can't have changed unless one of those chunks did, so it is served from
+/// here instead of paying for another generation. Entries citing a chunk are
+/// dropped by `invalidate` when it is updated or deleted; add
+/// `AnswerCache::invalidator` as `ChromaClient` middleware to do that for
+/// every write the client makes.
+pub struct AnswerCache {
+    capacity: usize,
+    inner: Mutex<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    answers: HashMap<String, CachedAnswer>,
+    /// Chunk id to the keys of the answers citing it.
+    citations: HashMap<String, HashSet<String>>,
+    order: VecDeque<String>,
+}
+
+struct CachedAnswer {
+    answer: String,
+    chunk_ids: Vec<String>,
+}
+
+impl Default for AnswerCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl AnswerCache {
+    /// Keep at most `capacity` answers, evicting the oldest first.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// The context fingerprint an answer is stored under. Surrounding
+    /// whitespace in the question and the order of `chunk_ids` don't matter.
+    pub fn key(question: &str, chunk_ids: &[String], model: &str) -> String {
+        let mut ids: Vec<&str> = chunk_ids.iter().map(String::as_str).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        let question = Uuid::new_v5(&Uuid::NAMESPACE_OID, question.trim().as_bytes());
+        format!("{}:{}:{}", model, question.simple(), Uuid::new_v5(&question, ids.join("\n").as_bytes()).simple())
+    }
+
+    pub fn get(&self, question: &str, chunk_ids: &[String], model: &str) -> Option<String> {
+        let key = Self::key(question, chunk_ids, model);
+        self.inner.lock().unwrap().answers.get(&key).map(|cached| cached.answer.clone())
+    }
+
+    pub fn insert(&self, question: &str, chunk_ids: &[String], model: &str, answer: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let key = Self::key(question, chunk_ids, model);
+        let mut inner = self.inner.lock().unwrap();
+        inner.remove(&key);
+        for id in chunk_ids {
+            inner.citations.entry(id.clone()).or_default().insert(key.clone());
+        }
+        inner.answers.insert(
+            key.clone(),
+            CachedAnswer {
+                answer: answer.to_string(),
+                chunk_ids: chunk_ids.to_vec(),
+            },
+        );
+        inner.order.push_back(key);
+        while inner.order.len() > self.capacity {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.remove(&oldest);
+            }
+        }
+    }
+
+    /// Drop every answer citing one of `chunk_ids`. Returns how many were
+    /// dropped.
+    pub fn invalidate<I, S>(&self, chunk_ids: I) -> usize
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut inner = self.inner.lock().unwrap();
+        let keys: HashSet<String> = chunk_ids
+            .into_iter()
+            .filter_map(|id| inner.citations.get(id.as_ref()).cloned())
+            .flatten()
+            .collect();
+        for key in &keys {
+            inner.remove(key);
+        }
+        keys.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().answers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.answers.clear();
+        inner.citations.clear();
+        inner.order.clear();
+    }
+
+    /// Middleware invalidating this cache after each successful upsert,
+    /// update or delete the client sends. Deletes by `where` filter don't
+    /// say which records they hit, so they clear the whole cache.
+    pub fn invalidator(self: &Arc<Self>) -> AnswerCacheInvalidator {
+        AnswerCacheInvalidator { cache: Arc::clone(self) }
+    }
+}
+
+impl Entries {
+    fn remove(&mut self, key: &str) {
+        let Some(cached) = self.answers.remove(key) else {
+            return;
+        };
+        self.order.retain(|k| k != key);
+        for id in &cached.chunk_ids {
+            if let Some(keys) = self.citations.get_mut(id) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.citations.remove(id);
+                }
+            }
+        }
+    }
+}
+
+/// See `AnswerCache::invalidator`.
+pub struct AnswerCacheInvalidator {
+    cache: Arc<AnswerCache>,
+}
+
+impl Middleware for AnswerCacheInvalidator {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move {
+            let is_write = WRITE_ENDPOINTS.iter().any(|endpoint| request.url().path().ends_with(endpoint));
+            let changed = is_write.then(|| {
+                request
+                    .body()
+                    .and_then(|body| body.as_bytes())
+                    .and_then(|bytes| serde_json::from_slice::<Value>(bytes).ok())
+            });
+
+            let response = next.run(request).await?;
+            if let Some(body) = changed
+                && response.status().is_success()
+            {
+                let ids = body.as_ref().filter(|body| body.get("where").is_none_or(Value::is_null)).and_then(|body| {
+                    body.get("ids")?
+                        .as_array()
+                        .map(|ids| ids.iter().filter_map(Value::as_str).map(str::to_string).collect::<Vec<_>>())
+                });
+                match ids {
+                    Some(ids) => {
+                        let dropped = self.cache.invalidate(&ids);
+                        debug!("Invalidated {} cached answers citing {} changed records", dropped, ids.len());
+                    }
+                    None => self.cache.clear(),
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_answer_cache_drops_answers_citing_changed_chunks() {
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let cache = Arc::new(AnswerCache::new(10));
+        cache.insert("What is Rust?", &ids(&["a", "b"]), "gemini-pro", "A language.");
+        cache.insert("What is Go?", &ids(&["c"]), "gemini-pro", "Another language.");
+        assert_eq!(cache.get("  What is Rust? ", &ids(&["b", "a"]), "gemini-pro").as_deref(), Some("A language."));
+        assert_eq!(cache.get("What is Rust?", &ids(&["a"]), "gemini-pro"), None);
+        assert_eq!(cache.get("What is Rust?", &ids(&["a", "b"]), "gpt-4o"), None);
+
+        let chroma = mock_chroma(|_| {
+            r#"{"id": "c0ffee", "name": "docs"}"#
+        })
+        .with_middleware(cache.invalidator());
+
+        chroma.delete_documents("docs", ids(&["b"])).await.unwrap();
+        assert_eq!(cache.get("What is Rust?", &ids(&["a", "b"]), "gemini-pro"), None);
+        assert_eq!(cache.len(), 1);
+
+        chroma.delete_documents_with_filter("docs", None, Some(serde_json::json!({ "lang": "go" }))).await.unwrap();
+        assert!(cache.is_empty());
+    }
+}
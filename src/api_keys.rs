@@ -0,0 +1,132 @@
+use crate::error::{ChromaError, Result};
+use crate::rate_limit::RateLimiter;
+use crate::validation::validate_collection_name;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Keys shorter than this are rejected at load time; they are the only
+/// thing standing between the server and the network.
+const MIN_KEY_LEN: usize = 16;
+
+/// One client of the server, as listed in `API_KEYS_FILE`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiKey {
+    /// Shown in logs instead of the key itself.
+    pub name: String,
+    pub key: String,
+    /// Collection this key reads and writes; `None` uses `COLLECTION_NAME`.
+    #[serde(default)]
+    pub collection: Option<String>,
+    /// Sustained request rate, with bursts of up to a minute's worth;
+    /// `None` is unlimited.
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    /// Whether the key may call the `/admin` endpoints.
+    #[serde(default)]
+    pub admin: bool,
+    /// Groups whose documents the key may query (see `acl::set_acl`);
+    /// `None` skips access control.
+    #[serde(default)]
+    pub groups: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct KeysFile {
+    keys: Vec<ApiKey>,
+}
+
+/// The API keys a server accepts, each with its own rate limiter. Empty
+/// means authentication is off.
+///
+/// Rate limits are held in memory, so they start afresh when the keys are
+/// reloaded.
+#[derive(Debug, Default)]
+pub struct ApiKeys {
+    keys: Vec<Arc<ApiKey>>,
+    limiters: HashMap<String, RateLimiter>,
+}
+
+impl ApiKeys {
+    pub fn new(keys: Vec<ApiKey>) -> Result<Self> {
+        let mut names = HashSet::new();
+        let mut secrets = HashSet::new();
+        let mut limiters = HashMap::new();
+        for key in &keys {
+            if !names.insert(key.name.as_str()) {
+                return Err(ChromaError::ConfigError(format!("API key name '{}' is used twice", key.name)));
+            }
+            if key.key.len() < MIN_KEY_LEN {
+                return Err(ChromaError::ConfigError(format!(
+                    "API key '{}' is shorter than {} characters",
+                    key.name, MIN_KEY_LEN
+                )));
+            }
+            if !secrets.insert(key.key.as_str()) {
+                return Err(ChromaError::ConfigError(format!("API key '{}' duplicates another key", key.name)));
+            }
+            if let Some(collection) = &key.collection {
+                validate_collection_name(collection)
+                    .map_err(|e| ChromaError::ConfigError(format!("API key '{}': {}", key.name, e)))?;
+            }
+            if let Some(per_minute) = key.requests_per_minute {
+                let limiter = RateLimiter::per_second(f64::from(per_minute) / 60.0).with_burst(per_minute);
+                limiters.insert(key.name.clone(), limiter);
+            }
+        }
+
+        Ok(Self {
+            keys: keys.into_iter().map(Arc::new).collect(),
+            limiters,
+        })
+    }
+
+    /// Read a JSON file of the form
+    /// `{"keys": [{"name", "key", "collection", "requests_per_minute", "admin", "groups"}]}`.
+    /// It holds secrets, so keep it readable by the server's user only.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| ChromaError::ConfigError(format!("Cannot read {}: {}", path.display(), e)))?;
+        let file: KeysFile = serde_json::from_str(&text)
+            .map_err(|e| ChromaError::ConfigError(format!("Invalid API keys file {}: {}", path.display(), e)))?;
+        Self::new(file.keys)
+    }
+
+    /// The key matching `presented`. Every key is compared in full, so the
+    /// time taken doesn't reveal how close a guess was.
+    pub fn authenticate(&self, presented: &str) -> Option<Arc<ApiKey>> {
+        let mut found = None;
+        for key in &self.keys {
+            if constant_time_eq(key.key.as_bytes(), presented.as_bytes()) {
+                found = Some(key.clone());
+            }
+        }
+        found
+    }
+
+    /// Spend one request of `key`'s rate limit, or say how long until it
+    /// has one to spare.
+    pub async fn check_rate(&self, key: &ApiKey) -> std::result::Result<(), Duration> {
+        match self.limiters.get(&key.name) {
+            Some(limiter) => limiter.try_acquire().await,
+            None => Ok(()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
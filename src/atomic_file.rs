@@ -0,0 +1,195 @@
+use crate::error::{ChromaError, Result};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Trailer identifying a checksummed file: `data | len | crc32 | MAGIC`.
+const FOOTER_MAGIC: &[u8; 4] = b"CDBF";
+const FOOTER_LEN: usize = 8 + 4 + FOOTER_MAGIC.len();
+
+/// A file that only appears at its destination once it is complete.
+///
+/// Bytes go to a hidden temp file in the same directory, which `commit`
+/// syncs and renames over `path`. A crash or error before that leaves the
+/// previous file untouched, and dropping an uncommitted `AtomicFile` removes
+/// the temp file. With `with_checksum`, `commit` also appends a footer with
+/// the length and CRC-32 of the contents, which `read` verifies.
+pub struct AtomicFile {
+    path: PathBuf,
+    temp_path: PathBuf,
+    file: Option<BufWriter<File>>,
+    checksum: Option<(crc32fast::Hasher, u64)>,
+}
+
+impl AtomicFile {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let name = path
+            .file_name()
+            .ok_or_else(|| ChromaError::ValidationError(format!("{} is not a file path", path.display())))?;
+        let mut temp_name = std::ffi::OsString::from(".");
+        temp_name.push(name);
+        temp_name.push(format!(".tmp-{}", Uuid::new_v4()));
+        let temp_path = path.with_file_name(temp_name);
+
+        let file = File::create(&temp_path)?;
+        Ok(Self {
+            path,
+            temp_path,
+            file: Some(BufWriter::new(file)),
+            checksum: None,
+        })
+    }
+
+    /// Append a length and CRC-32 footer on commit.
+    pub fn with_checksum(mut self) -> Self {
+        self.checksum = Some((crc32fast::Hasher::new(), 0));
+        self
+    }
+
+    /// Flush and sync the contents, then move them into place.
+    pub fn commit(mut self) -> Result<()> {
+        let mut file = self.file.take().expect("AtomicFile committed twice");
+        if let Some((hasher, len)) = self.checksum.take() {
+            file.write_all(&len.to_le_bytes())?;
+            file.write_all(&hasher.finalize().to_le_bytes())?;
+            file.write_all(FOOTER_MAGIC)?;
+        }
+        let file = file.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        drop(file);
+
+        fs::rename(&self.temp_path, &self.path)?;
+        sync_parent(&self.path);
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let file = self.file.as_mut().ok_or_else(|| std::io::Error::other("AtomicFile already committed"))?;
+        let n = file.write(buf)?;
+        if let Some((hasher, len)) = &mut self.checksum {
+            hasher.update(&buf[..n]);
+            *len += n as u64;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// Make the rename itself durable. Best effort: not every platform lets a
+/// directory be opened and synced.
+fn sync_parent(path: &Path) {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// Atomically replace `path` with `data` plus a checksum footer.
+pub fn write(path: impl AsRef<Path>, data: &[u8]) -> Result<()> {
+    let mut file = AtomicFile::create(path)?.with_checksum();
+    file.write_all(data)?;
+    file.commit()
+}
+
+/// Atomically replace `path` with exactly `data`, for files other programs
+/// read (pid files, man pages, blobs).
+pub fn write_plain(path: impl AsRef<Path>, data: &[u8]) -> Result<()> {
+    let mut file = AtomicFile::create(path)?;
+    file.write_all(data)?;
+    file.commit()
+}
+
+/// Read a file written by `write`, verifying and stripping its footer.
+/// Files without a footer (written before checksums existed) are returned
+/// as they are.
+pub fn read(path: impl AsRef<Path>) -> Result<Vec<u8>> {
+    let path = path.as_ref();
+    let mut data = fs::read(path)?;
+    if data.len() < FOOTER_LEN || !data.ends_with(FOOTER_MAGIC) {
+        return Ok(data);
+    }
+
+    let footer = data.split_off(data.len() - FOOTER_LEN);
+    let len = u64::from_le_bytes(footer[..8].try_into().expect("8-byte length"));
+    let crc = u32::from_le_bytes(footer[8..12].try_into().expect("4-byte checksum"));
+    if len != data.len() as u64 || crc32fast::hash(&data) != crc {
+        return Err(ChromaError::ValidationError(format!(
+            "{} is corrupt: checksum mismatch",
+            path.display()
+        )));
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::Compression;
+    use crate::local_store::{StoredDocument, VectorStore};
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_atomic_writes_verify_checksums() {
+        use std::io::Write;
+
+        let dir = std::env::temp_dir().join(format!("atomic-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("store.json");
+
+        write(&path, b"first").unwrap();
+        assert_eq!(read(&path).unwrap(), b"first");
+
+        // An abandoned write leaves the previous contents and no temp file.
+        let mut pending = AtomicFile::create(&path).unwrap().with_checksum();
+        pending.write_all(b"half of the sec").unwrap();
+        drop(pending);
+        assert_eq!(read(&path).unwrap(), b"first");
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[0] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+        let error = read(&path).unwrap_err().to_string();
+        assert!(error.contains("checksum mismatch"), "{}", error);
+
+        // Files from before checksums existed still load.
+        std::fs::write(&path, b"{\"legacy\": true}").unwrap();
+        assert_eq!(read(&path).unwrap(), b"{\"legacy\": true}");
+
+        let mut store = VectorStore::new();
+        store.documents.push(StoredDocument {
+            id: "a".to_string(),
+            content: String::new(),
+            embedding: vec![1.0],
+            metadata: HashMap::new(),
+            created_at: chrono::Utc::now(),
+        });
+        store.save_with(path.to_str().unwrap(), Compression::None, None).unwrap();
+        assert_eq!(VectorStore::load_from_file(path.to_str().unwrap()).unwrap().documents.len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
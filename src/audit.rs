@@ -0,0 +1,219 @@
+use crate::chroma_client::ChromaClient;
+use crate::error::Result;
+use crate::models::{PARENT_ID_FIELD, SOURCE_FIELD};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+const DEFAULT_MIN_CHARS: usize = 20;
+/// Lengths are heavily skewed, so only far outliers count as too long.
+const DEFAULT_MAX_ZSCORE: f64 = 4.0;
+
+/// What `ChromaClient::audit` flags.
+#[derive(Debug, Clone)]
+pub struct AuditConfig {
+    /// Documents shorter than this many characters (empty ones included).
+    pub min_chars: usize,
+    /// Documents more than this many standard deviations longer than the
+    /// collection's mean length.
+    pub max_zscore: f64,
+    /// Metadata keys every document should have.
+    pub required_keys: Vec<String>,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            min_chars: DEFAULT_MIN_CHARS,
+            max_zscore: DEFAULT_MAX_ZSCORE,
+            required_keys: Vec::new(),
+        }
+    }
+}
+
+impl AuditConfig {
+    pub fn with_min_chars(mut self, min_chars: usize) -> Self {
+        self.min_chars = min_chars;
+        self
+    }
+
+    pub fn with_max_zscore(mut self, max_zscore: f64) -> Self {
+        self.max_zscore = max_zscore;
+        self
+    }
+
+    pub fn with_required_key(mut self, key: &str) -> Self {
+        self.required_keys.push(key.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    TooShort,
+    TooLong,
+    MissingMetadata,
+    /// The same logical document id (compared case-insensitively) is stored
+    /// under more than one `source`, so one ingest probably overwrote or
+    /// interleaved with another.
+    DuplicateId,
+}
+
+/// One entry of the cleanup worklist.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditFinding {
+    pub id: String,
+    pub kind: FindingKind,
+    pub detail: String,
+}
+
+/// Outliers in a collection, ordered by kind and id.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditReport {
+    pub collection: String,
+    pub scanned: usize,
+    pub mean_chars: f64,
+    pub stddev_chars: f64,
+    pub findings: Vec<AuditFinding>,
+}
+
+/// Read every record of `collection_name` through a snapshot and flag
+/// outliers per `config`.
+pub(crate) async fn audit(client: &ChromaClient, collection_name: &str, config: &AuditConfig) -> Result<AuditReport> {
+    let mut snapshot = client.snapshot(collection_name).await?;
+    let mut lengths: Vec<(String, usize)> = Vec::with_capacity(snapshot.len());
+    let mut findings = Vec::new();
+    // Lowercased parent id -> (sources, ids stored under it).
+    let mut parents: BTreeMap<String, (BTreeSet<String>, BTreeSet<String>)> = BTreeMap::new();
+
+    while let Some(page) = snapshot.next_page().await? {
+        for record in page {
+            let metadata = record.metadata.as_ref();
+            let chars = record.document.as_deref().map_or(0, |d| d.chars().count());
+            if chars < config.min_chars {
+                findings.push(AuditFinding {
+                    id: record.id.clone(),
+                    kind: FindingKind::TooShort,
+                    detail: format!("{} characters, under {}", chars, config.min_chars),
+                });
+            }
+
+            let missing: Vec<&str> = config
+                .required_keys
+                .iter()
+                .filter(|key| metadata.and_then(|m| m.get(key.as_str())).is_none_or(Value::is_null))
+                .map(String::as_str)
+                .collect();
+            if !missing.is_empty() {
+                findings.push(AuditFinding {
+                    id: record.id.clone(),
+                    kind: FindingKind::MissingMetadata,
+                    detail: format!("missing {}", missing.join(", ")),
+                });
+            }
+
+            let field = |key: &str| metadata.and_then(|m| m.get(key)).and_then(Value::as_str);
+            let parent = field(PARENT_ID_FIELD).unwrap_or(&record.id);
+            let source = field(SOURCE_FIELD).unwrap_or("");
+            let (sources, ids) = parents.entry(parent.trim().to_lowercase()).or_default();
+            sources.insert(source.to_string());
+            ids.insert(record.id.clone());
+
+            lengths.push((record.id, chars));
+        }
+    }
+
+    let scanned = lengths.len();
+    let (mean_chars, stddev_chars) = mean_and_stddev(lengths.iter().map(|(_, chars)| *chars as f64));
+    if stddev_chars > 0.0 {
+        for (id, chars) in &lengths {
+            let zscore = (*chars as f64 - mean_chars) / stddev_chars;
+            if zscore > config.max_zscore {
+                findings.push(AuditFinding {
+                    id: id.clone(),
+                    kind: FindingKind::TooLong,
+                    detail: format!("{} characters, {:.1} standard deviations above the mean", chars, zscore),
+                });
+            }
+        }
+    }
+
+    for (parent, (sources, ids)) in parents {
+        if sources.len() > 1 {
+            let sources: Vec<&str> = sources.iter().map(|s| if s.is_empty() { "(none)" } else { s }).collect();
+            for id in ids {
+                findings.push(AuditFinding {
+                    id,
+                    kind: FindingKind::DuplicateId,
+                    detail: format!("document '{}' comes from sources {}", parent, sources.join(", ")),
+                });
+            }
+        }
+    }
+
+    findings.sort_by(|a, b| (a.kind, &a.id).cmp(&(b.kind, &b.id)));
+    Ok(AuditReport {
+        collection: collection_name.to_string(),
+        scanned,
+        mean_chars,
+        stddev_chars,
+        findings,
+    })
+}
+
+fn mean_and_stddev(values: impl Iterator<Item = f64> + Clone) -> (f64, f64) {
+    let count = values.clone().count();
+    if count == 0 {
+        return (0.0, 0.0);
+    }
+    let mean = values.clone().sum::<f64>() / count as f64;
+    let variance = values.map(|v| (v - mean).powi(2)).sum::<f64>() / count as f64;
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_chroma;
+
+    #[tokio::test]
+    async fn test_audit_flags_outliers_missing_metadata_and_reused_ids() {
+        let mut rows: Vec<(String, String, serde_json::Value)> = (0..10)
+            .map(|i| (format!("n{}", i), "x".repeat(30), serde_json::json!({"source": "web", "lang": "en"})))
+            .collect();
+        rows.push(("long".into(), "y".repeat(600), serde_json::json!({"source": "web", "lang": "en"})));
+        rows.push(("a".into(), "short".into(), serde_json::json!({"source": "web", "lang": "en"})));
+        rows.push(("Guide".into(), "z".repeat(30), serde_json::json!({"source": "wiki", "lang": "en"})));
+        rows.push(("guide".into(), "z".repeat(30), serde_json::json!({"source": "pdf"})));
+        let body = serde_json::json!({
+            "ids": rows.iter().map(|r| &r.0).collect::<Vec<_>>(),
+            "documents": rows.iter().map(|r| &r.1).collect::<Vec<_>>(),
+            "metadatas": rows.iter().map(|r| &r.2).collect::<Vec<_>>(),
+        })
+        .to_string();
+        let chroma = mock_chroma(move |request| {
+            if request.uri().path().ends_with("/get") {
+                body.clone()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+
+        let config = AuditConfig::default().with_max_zscore(2.0).with_required_key("lang");
+        let report = chroma.audit("docs", &config).await.unwrap();
+        assert_eq!(report.scanned, 14);
+        let findings: Vec<(FindingKind, &str)> = report.findings.iter().map(|f| (f.kind, f.id.as_str())).collect();
+        assert_eq!(
+            findings,
+            vec![
+                (FindingKind::TooShort, "a"),
+                (FindingKind::TooLong, "long"),
+                (FindingKind::MissingMetadata, "guide"),
+                (FindingKind::DuplicateId, "Guide"),
+                (FindingKind::DuplicateId, "guide"),
+            ]
+        );
+        assert!(report.findings[3].detail.contains("pdf, wiki"), "{}", report.findings[3].detail);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::error::{ChromaError, Result};
+use crate::models::Document;
+use crate::pipeline::Pipeline;
+use crate::rate_limit::RateLimiter;
+use chrono::{Local, NaiveTime, Timelike};
+use serde::Serialize;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::info;
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// A daily time-of-day range in local time, e.g. `22:00-06:00`. Ranges
+/// whose end is before their start wrap past midnight; equal start and end
+/// mean the whole day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl TimeWindow {
+    pub fn new(start: NaiveTime, end: NaiveTime) -> Self {
+        Self { start, end }
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start == self.end {
+            true
+        } else if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// How long from `time` until the window next opens; zero if it is open.
+    pub fn until_open(&self, time: NaiveTime) -> Duration {
+        if self.contains(time) {
+            return Duration::ZERO;
+        }
+        let wait = (self.start.num_seconds_from_midnight() + SECONDS_PER_DAY
+            - time.num_seconds_from_midnight())
+            % SECONDS_PER_DAY;
+        Duration::from_secs(wait as u64)
+    }
+}
+
+impl FromStr for TimeWindow {
+    type Err = ChromaError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = || ChromaError::ConfigError(format!("Invalid time window '{}', expected HH:MM-HH:MM", value));
+        let (start, end) = value.split_once('-').ok_or_else(invalid)?;
+        let parse = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M").map_err(|_| invalid());
+        Ok(Self::new(parse(start)?, parse(end)?))
+    }
+}
+
+/// Throughput ceilings and allowed hours for `backfill`.
+#[derive(Debug, Clone)]
+pub struct BackfillConfig {
+    pub batch_size: usize,
+    /// Embedding requests per second (one request per batch).
+    pub max_embed_requests_per_second: Option<f64>,
+    /// Documents written to Chroma per second.
+    pub max_documents_per_second: Option<f64>,
+    /// Only run inside these windows; empty means any time.
+    pub windows: Vec<TimeWindow>,
+}
+
+impl Default for BackfillConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 32,
+            max_embed_requests_per_second: None,
+            max_documents_per_second: None,
+            windows: Vec::new(),
+        }
+    }
+}
+
+impl BackfillConfig {
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_max_embed_requests_per_second(mut self, per_second: f64) -> Self {
+        self.max_embed_requests_per_second = Some(per_second);
+        self
+    }
+
+    pub fn with_max_documents_per_second(mut self, per_second: f64) -> Self {
+        self.max_documents_per_second = Some(per_second);
+        self
+    }
+
+    pub fn with_window(mut self, window: TimeWindow) -> Self {
+        self.windows.push(window);
+        self
+    }
+
+    /// How long from `time` until backfilling may run.
+    pub fn until_allowed(&self, time: NaiveTime) -> Duration {
+        self.windows
+            .iter()
+            .map(|w| w.until_open(time))
+            .min()
+            .unwrap_or(Duration::ZERO)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BackfillReport {
+    pub documents: usize,
+    pub batches: usize,
+    /// Time spent waiting for a window to open, in seconds.
+    pub paused_secs: u64,
+}
+
+/// Ingest `documents` through `pipeline` in batches, staying under the
+/// configured rates and pausing whenever the current time is outside every
+/// window. Meant for bulk loads that share providers with live traffic.
+pub async fn backfill(
+    pipeline: &Pipeline,
+    documents: impl IntoIterator<Item = Document>,
+    config: &BackfillConfig,
+) -> Result<BackfillReport> {
+    let embed_limiter = RateLimiter::per_second(config.max_embed_requests_per_second.unwrap_or(0.0));
+    let write_limiter = RateLimiter::per_second(config.max_documents_per_second.unwrap_or(0.0));
+    let batch_size = config.batch_size.max(1);
+    let mut report = BackfillReport::default();
+    let mut documents = documents.into_iter().peekable();
+
+    while documents.peek().is_some() {
+        let wait = config.until_allowed(Local::now().time());
+        if !wait.is_zero() {
+            info!("Outside backfill windows, pausing for {:?}", wait);
+            tokio::time::sleep(wait).await;
+            report.paused_secs += wait.as_secs();
+        }
+
+        let batch: Vec<Document> = documents.by_ref().take(batch_size).collect();
+        embed_limiter.acquire().await;
+        write_limiter.acquire_many(batch.len() as u32).await;
+
+        report.documents += pipeline.ingest(batch).await?;
+        report.batches += 1;
+        if report.batches % 10 == 0 {
+            info!("Backfill progress: {} documents in {} batches", report.documents, report.batches);
+        }
+    }
+
+    info!("Backfill finished: {} documents in {} batches", report.documents, report.batches);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backfill_windows_wrap_midnight() {
+        use chrono::NaiveTime;
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+        let night: TimeWindow = "22:00-06:00".parse().unwrap();
+        assert!(night.contains(at(23, 30)));
+        assert!(night.contains(at(5, 59)));
+        assert!(!night.contains(at(6, 0)));
+        assert_eq!(night.until_open(at(21, 0)), std::time::Duration::from_secs(3600));
+
+        let config = BackfillConfig::default()
+            .with_window(night)
+            .with_window("12:00-13:00".parse().unwrap());
+        assert_eq!(config.until_allowed(at(11, 30)), std::time::Duration::from_secs(1800));
+        assert_eq!(config.until_allowed(at(12, 30)), std::time::Duration::ZERO);
+        assert!(BackfillConfig::default().until_allowed(at(9, 0)).is_zero());
+        assert!(matches!("25:00-01:00".parse::<TimeWindow>(), Err(ChromaError::ConfigError(_))));
+    }
+}
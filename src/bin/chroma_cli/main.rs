@@ -0,0 +1,873 @@
+mod output;
+
+use anyhow::{Context, bail};
+use chromadb_demo::atomic_file::{self, AtomicFile};
+use chromadb_demo::{
+    AuditConfig, AuditFinding, BackfillConfig, BackfillReport, ChromaClient, Chunker, CollectionMetadata, Compression,
+    CollectionResponse, DistanceSpace, Document, DriftConfig, DriftReport, EmbeddingClient, ExportConfig, ExportReport,
+    ImportConfig, ImportReport, OutdatedRecord, Pipeline, PreflightCheck, QueryHit, QueryOptions, RecordVersion,
+    FileCheck, ServerConfig, ShardInfo, StoreCipher, SyncPlan, TimeWindow, normalize_collection_name,
+};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use output::{OutputFormat, Record, render, render_one};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Debug, Parser)]
+#[command(name = "chroma-cli", version, about = "Manage ChromaDB collections and documents")]
+struct Cli {
+    /// ChromaDB server URL
+    #[arg(long, env = "CHROMA_HOST", default_value = "http://localhost:8000", global = true)]
+    host: String,
+
+    /// Output format
+    #[arg(long, short, value_enum, default_value_t = OutputFormat::Table, global = true)]
+    output: OutputFormat,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    #[command(flatten)]
+    Client(ClientCommand),
+    /// Check an export file or sharded export directory against its record
+    /// and file checksums without touching Chroma; exits 1 if damaged
+    VerifyExport { path: PathBuf },
+    /// Run an HTTP server that keeps the embedding pipeline warm
+    ///
+    /// Settings (CHROMA_HOST, COLLECTION_NAME, GOOGLE_API_KEY, SYNC_DIR,
+    /// EMBEDDING_CACHE_SIZE, SERVER_BIND) come from the config file, falling
+    /// back to the environment. SIGHUP or POST /admin/reload re-reads the file.
+    Serve {
+        /// Env-style config file to load and re-read on reload
+        #[arg(long)]
+        config: Option<PathBuf>,
+        /// Address to listen on (overrides SERVER_BIND)
+        #[arg(long)]
+        bind: Option<SocketAddr>,
+        /// Detach into the background and print the daemon's pid
+        #[arg(long)]
+        daemon: bool,
+        /// Write the server's pid here; removed on clean shutdown
+        #[arg(long)]
+        pid_file: Option<PathBuf>,
+        /// Where the daemon's logs go (discarded if omitted)
+        #[arg(long, requires = "daemon")]
+        log_file: Option<PathBuf>,
+    },
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Print the man page, or write pages for every subcommand to a directory
+    Man {
+        #[arg(long)]
+        out_dir: Option<PathBuf>,
+    },
+}
+
+/// Subcommands that talk to Chroma.
+#[derive(Debug, Subcommand)]
+enum ClientCommand {
+    /// Check that the server is reachable
+    Health,
+    /// Manage collections
+    #[command(subcommand)]
+    Collections(CollectionCommand),
+    /// Embed texts with Gemini (GOOGLE_API_KEY) and add them to a collection
+    Add {
+        collection: String,
+        #[arg(required = true)]
+        texts: Vec<String>,
+        /// Document ids, one per text (random UUIDs if omitted)
+        #[arg(long = "id")]
+        ids: Vec<String>,
+        /// Metadata applied to every document, as key=value
+        #[arg(long = "meta", value_parser = parse_key_value)]
+        metadata: Vec<(String, String)>,
+    },
+    /// Semantic search for a text (embedded with Gemini)
+    Query {
+        collection: String,
+        text: String,
+        #[arg(short = 'n', long, default_value_t = 5)]
+        n_results: u32,
+        /// Chroma `where` filter as JSON
+        #[arg(long = "where", value_parser = parse_json)]
+        where_filter: Option<Value>,
+        /// Return only the best chunk of each logical document
+        #[arg(long)]
+        group_by_parent: bool,
+    },
+    /// Fetch documents by id and/or metadata filter
+    Get {
+        collection: String,
+        #[arg(long, value_delimiter = ',')]
+        ids: Vec<String>,
+        /// Chroma `where` filter as JSON
+        #[arg(long = "where", value_parser = parse_json)]
+        where_filter: Option<Value>,
+        #[arg(long)]
+        limit: Option<u32>,
+    },
+    /// Delete documents by id
+    Delete {
+        collection: String,
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Count the documents in a collection
+    Count { collection: String },
+    /// Check Chroma, credentials, the collection and the embedding provider end to end
+    Preflight { collection: String },
+    /// Self-test: preflight, Chroma version and an ingest/query/delete round
+    /// trip in a scratch collection
+    Doctor {
+        #[arg(env = "COLLECTION_NAME", default_value = "documents")]
+        collection: String,
+        /// Write a diagnostic bundle (versions, timings, redacted config) as JSON
+        #[arg(long)]
+        bundle: Option<PathBuf>,
+    },
+    /// Re-embed a sample of stored documents and report how far they moved
+    Drift {
+        collection: String,
+        #[arg(long, default_value_t = 50)]
+        sample: u32,
+        /// Mean cosine below which the model counts as drifted
+        #[arg(long, default_value_t = 0.98)]
+        min_mean_cosine: f32,
+    },
+    /// List outlier documents to clean up: very short or long texts, missing
+    /// metadata keys and ids reused across sources
+    Audit {
+        collection: String,
+        /// Metadata key every document should have, repeatable
+        #[arg(long = "require")]
+        required_keys: Vec<String>,
+        /// Flag documents shorter than this many characters
+        #[arg(long, default_value_t = 20)]
+        min_chars: usize,
+        /// Flag documents this many standard deviations longer than the mean
+        #[arg(long, default_value_t = 4.0)]
+        max_zscore: f64,
+    },
+    /// List records written by another pipeline version or chunker
+    /// configuration, to target re-chunking at them
+    Outdated {
+        collection: String,
+        /// Expected pipeline_version stamp
+        #[arg(long, default_value = chromadb_demo::versioning::PIPELINE_VERSION)]
+        pipeline_version: String,
+        /// Expected chunk size; omit for collections ingested unchunked
+        #[arg(long)]
+        max_chars: Option<usize>,
+        /// Expected chunk overlap
+        #[arg(long, default_value_t = 100, requires = "max_chars")]
+        overlap: usize,
+    },
+    /// Write every record of a collection to a JSON Lines file
+    Export {
+        collection: String,
+        file: PathBuf,
+        /// Memory ceiling in MiB; larger collections spill their id list to disk
+        #[arg(long, default_value_t = 256)]
+        memory_limit_mb: usize,
+        /// Directory for spill files (default: the system temp dir)
+        #[arg(long)]
+        spill_dir: Option<PathBuf>,
+        /// Leave embeddings out of the export
+        #[arg(long)]
+        no_embeddings: bool,
+        /// Compress the output with zstd
+        #[arg(long)]
+        zstd: bool,
+        /// Encrypt each record with the store key (LOCAL_STORE_KEY or the OS
+        /// keyring)
+        #[arg(long)]
+        encrypt: bool,
+        /// Write this many shard files in parallel, plus a manifest.json,
+        /// into FILE as a directory
+        #[arg(long)]
+        shards: Option<usize>,
+    },
+    /// Upsert an export (a JSON Lines file, or a sharded export directory)
+    /// into a collection; the export must include embeddings
+    Import {
+        collection: String,
+        path: PathBuf,
+        /// Shards of a sharded export to import at once
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+        /// Records upserted per request; progress is checkpointed after each
+        #[arg(long, default_value_t = 256)]
+        batch_size: usize,
+        /// Continue an interrupted import from its checkpoint
+        #[arg(long)]
+        resume: bool,
+    },
+    /// Bulk-load the .txt/.md files in a directory without starving live traffic
+    Backfill {
+        collection: String,
+        dir: PathBuf,
+        #[arg(long, default_value_t = 32)]
+        batch_size: usize,
+        /// Ceiling on embedding requests per second
+        #[arg(long)]
+        embed_qps: Option<f64>,
+        /// Ceiling on documents written to Chroma per second
+        #[arg(long)]
+        docs_per_second: Option<f64>,
+        /// Only run inside this local-time window (HH:MM-HH:MM), repeatable
+        #[arg(long = "window")]
+        windows: Vec<TimeWindow>,
+    },
+    /// Make a collection hold exactly the .txt/.md files in a directory,
+    /// re-embedding only new and changed files and deleting removed ones
+    Mirror {
+        collection: String,
+        dir: PathBuf,
+        /// Only print the plan
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum CollectionCommand {
+    /// List all collections
+    List,
+    /// Create a collection
+    Create {
+        name: String,
+        /// Turn NAME into a valid collection name (e.g. "Q3 reports" -> "Q3_reports") instead of rejecting it
+        #[arg(long)]
+        normalize: bool,
+        #[arg(long, value_parser = parse_space, default_value = "cosine")]
+        space: DistanceSpace,
+        #[arg(long)]
+        description: Option<String>,
+    },
+    /// Show one collection
+    Get {
+        name: String,
+    },
+    /// Delete a collection and all its documents
+    Delete {
+        name: String,
+    },
+}
+
+#[derive(Serialize)]
+struct HealthRecord {
+    host: String,
+    healthy: bool,
+    error: Option<String>,
+}
+
+impl Record for HealthRecord {
+    const COLUMNS: &'static [&'static str] = &["host", "healthy", "error"];
+
+    fn values(&self) -> Vec<String> {
+        vec![self.host.clone(), self.healthy.to_string(), self.error.clone().unwrap_or_default()]
+    }
+}
+
+#[derive(Serialize)]
+struct CollectionRecord {
+    id: String,
+    name: String,
+    space: Option<DistanceSpace>,
+    description: Option<String>,
+}
+
+impl From<CollectionResponse> for CollectionRecord {
+    fn from(collection: CollectionResponse) -> Self {
+        let metadata = collection.metadata.unwrap_or_default();
+        Self {
+            id: collection.id,
+            name: collection.name,
+            space: metadata.space,
+            description: metadata.description,
+        }
+    }
+}
+
+impl Record for CollectionRecord {
+    const COLUMNS: &'static [&'static str] = &["id", "name", "space", "description"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            self.name.clone(),
+            self.space.map(|s| json_string(&s)).unwrap_or_default(),
+            self.description.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+#[derive(Serialize)]
+struct StatusRecord {
+    id: String,
+    status: &'static str,
+}
+
+impl Record for StatusRecord {
+    const COLUMNS: &'static [&'static str] = &["id", "status"];
+
+    fn values(&self) -> Vec<String> {
+        vec![self.id.clone(), self.status.to_string()]
+    }
+}
+
+#[derive(Serialize)]
+struct CountRecord {
+    collection: String,
+    count: usize,
+}
+
+impl Record for CountRecord {
+    const COLUMNS: &'static [&'static str] = &["collection", "count"];
+
+    fn values(&self) -> Vec<String> {
+        vec![self.collection.clone(), self.count.to_string()]
+    }
+}
+
+impl Record for PreflightCheck {
+    const COLUMNS: &'static [&'static str] = &["name", "status", "detail", "elapsed_ms"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.name.to_string(),
+            json_string(&self.status),
+            self.detail.clone(),
+            self.elapsed_ms.to_string(),
+        ]
+    }
+}
+
+impl Record for DriftReport {
+    const COLUMNS: &'static [&'static str] =
+        &["model", "sampled", "mean_cosine", "min_cosine", "dimension_mismatches", "drifted"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.model.clone(),
+            self.sampled.to_string(),
+            format!("{:.4}", self.mean_cosine),
+            format!("{:.4}", self.min_cosine),
+            self.dimension_mismatches.to_string(),
+            self.drifted.to_string(),
+        ]
+    }
+}
+
+impl Record for AuditFinding {
+    const COLUMNS: &'static [&'static str] = &["id", "kind", "detail"];
+
+    fn values(&self) -> Vec<String> {
+        vec![self.id.clone(), json_string(&self.kind), self.detail.clone()]
+    }
+}
+
+impl Record for OutdatedRecord {
+    const COLUMNS: &'static [&'static str] = &["id", "parent_id", "pipeline_version", "chunker_config_hash"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            self.parent_id.clone().unwrap_or_default(),
+            self.pipeline_version.clone().unwrap_or_default(),
+            self.chunker_config_hash.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+impl Record for SyncPlan {
+    const COLUMNS: &'static [&'static str] = &["add", "update", "delete", "unchanged"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.add.len().to_string(),
+            self.update.len().to_string(),
+            self.delete.len().to_string(),
+            self.unchanged.to_string(),
+        ]
+    }
+}
+
+impl Record for ExportReport {
+    const COLUMNS: &'static [&'static str] =
+        &["records_written", "records_skipped", "bytes_written", "spilled", "checksum"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.records_written.to_string(),
+            self.records_skipped.to_string(),
+            self.bytes_written.to_string(),
+            self.spilled.to_string(),
+            self.checksum.clone(),
+        ]
+    }
+}
+
+impl Record for ShardInfo {
+    const COLUMNS: &'static [&'static str] = &["file", "records", "bytes", "checksum"];
+
+    fn values(&self) -> Vec<String> {
+        vec![self.file.clone(), self.records.to_string(), self.bytes.to_string(), self.checksum.clone()]
+    }
+}
+
+impl Record for FileCheck {
+    const COLUMNS: &'static [&'static str] = &["file", "records", "checksum", "damaged", "ok"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.file.clone(),
+            match self.expected_records {
+                Some(expected) if expected != self.records => format!("{} (manifest: {})", self.records, expected),
+                _ => self.records.to_string(),
+            },
+            match &self.expected_checksum {
+                Some(expected) if *expected != self.checksum => format!("{} (manifest: {})", self.checksum, expected),
+                _ => self.checksum.clone(),
+            },
+            self.damaged.join(" "),
+            self.is_ok().to_string(),
+        ]
+    }
+}
+
+impl Record for ImportReport {
+    const COLUMNS: &'static [&'static str] = &["records_imported", "records_resumed", "batches"];
+
+    fn values(&self) -> Vec<String> {
+        vec![self.records_imported.to_string(), self.records_resumed.to_string(), self.batches.to_string()]
+    }
+}
+
+impl Record for BackfillReport {
+    const COLUMNS: &'static [&'static str] = &["documents", "batches", "paused_secs"];
+
+    fn values(&self) -> Vec<String> {
+        vec![self.documents.to_string(), self.batches.to_string(), self.paused_secs.to_string()]
+    }
+}
+
+#[derive(Serialize)]
+struct HitRecord {
+    rank: usize,
+    id: String,
+    distance: f32,
+    score: f32,
+    document: Option<String>,
+    metadata: Option<Value>,
+}
+
+impl HitRecord {
+    fn new(rank: usize, hit: QueryHit) -> Self {
+        Self {
+            rank,
+            id: hit.id,
+            distance: hit.distance,
+            score: hit.score,
+            document: hit.document,
+            metadata: hit.metadata,
+        }
+    }
+}
+
+impl Record for HitRecord {
+    const COLUMNS: &'static [&'static str] = &["rank", "id", "distance", "score", "document", "metadata"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.rank.to_string(),
+            self.id.clone(),
+            format!("{:.4}", self.distance),
+            format!("{:.4}", self.score),
+            self.document.clone().unwrap_or_default(),
+            self.metadata.as_ref().map(Value::to_string).unwrap_or_default(),
+        ]
+    }
+}
+
+#[derive(Serialize)]
+struct DocumentRecord {
+    id: String,
+    document: Option<String>,
+    metadata: Option<Value>,
+}
+
+impl Record for DocumentRecord {
+    const COLUMNS: &'static [&'static str] = &["id", "document", "metadata"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            self.document.clone().unwrap_or_default(),
+            self.metadata.as_ref().map(Value::to_string).unwrap_or_default(),
+        ]
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+
+    let cli = Cli::parse();
+
+    // Logs go to stderr so stdout stays machine-readable. The server logs
+    // requests and reloads, so it defaults to a chattier level.
+    let default_level = if matches!(cli.command, Command::Serve { .. }) { "info" } else { "warn" };
+    tracing_subscriber::fmt()
+        .with_env_filter(std::env::var("RUST_LOG").unwrap_or_else(|_| default_level.to_string()))
+        .with_writer(std::io::stderr)
+        .init();
+
+    let format = cli.output;
+    let command = match cli.command {
+        Command::Client(command) => command,
+        Command::VerifyExport { path } => {
+            let verification = chromadb_demo::verify_export(&path)?;
+            println!("{}", render(format, &verification.files)?);
+            if !verification.is_ok() {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Command::Serve { daemon: true, pid_file, log_file, .. } => {
+            return spawn_daemon(pid_file.as_deref(), log_file.as_deref());
+        }
+        Command::Serve { config, bind, pid_file, .. } => {
+            return run_server(config.as_deref(), bind, pid_file.as_deref()).await;
+        }
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "chroma-cli", &mut std::io::stdout());
+            return Ok(());
+        }
+        Command::Man { out_dir: None } => {
+            clap_mangen::Man::new(Cli::command()).render(&mut std::io::stdout())?;
+            return Ok(());
+        }
+        Command::Man { out_dir: Some(dir) } => {
+            std::fs::create_dir_all(&dir)?;
+            write_man_pages(&Cli::command(), "chroma-cli", &dir)?;
+            return Ok(());
+        }
+    };
+
+    let chroma = ChromaClient::try_new(cli.host.clone())?.with_content_hashes(true);
+    let rendered = match command {
+        ClientCommand::Health => {
+            let (healthy, error) = match chroma.health_check().await {
+                Ok(healthy) => (healthy, None),
+                Err(e) => (false, Some(e.to_string())),
+            };
+            let rendered = render_one(format, &HealthRecord { host: cli.host, healthy, error })?;
+            if !healthy {
+                println!("{}", rendered);
+                std::process::exit(1);
+            }
+            rendered
+        }
+        ClientCommand::Collections(command) => run_collection_command(&chroma, format, command).await?,
+        ClientCommand::Add { collection, texts, ids, metadata } => {
+            let ids = if ids.is_empty() {
+                texts.iter().map(|_| Uuid::new_v4().to_string()).collect()
+            } else if ids.len() == texts.len() {
+                ids
+            } else {
+                bail!("got {} --id values for {} texts", ids.len(), texts.len());
+            };
+
+            let embeddings = embedding_client()?;
+            let text_refs: Vec<&str> = texts.iter().map(String::as_str).collect();
+            let vectors = embeddings.embed_texts(&text_refs).await?;
+
+            let metadata: HashMap<String, String> = metadata.into_iter().collect();
+            let documents = ids
+                .iter()
+                .zip(texts)
+                .map(|(id, content)| Document { id: id.clone(), content, metadata: metadata.clone(), uri: None })
+                .collect();
+            chroma.add_documents(&collection, documents, vectors).await?;
+
+            let records: Vec<StatusRecord> =
+                ids.into_iter().map(|id| StatusRecord { id, status: "added" }).collect();
+            render(format, &records)?
+        }
+        ClientCommand::Query { collection, text, n_results, where_filter, group_by_parent } => {
+            let embedding = embedding_client()?.embed_text(&text).await?;
+            let mut options = QueryOptions::new(n_results);
+            if let Some(filter) = where_filter {
+                options = options.with_filter(filter);
+            }
+            if group_by_parent {
+                options = options.with_group_by_parent();
+            }
+
+            let hits = chroma.query_with_options(&collection, embedding, &options).await?;
+            let records: Vec<HitRecord> =
+                hits.into_iter().enumerate().map(|(i, hit)| HitRecord::new(i + 1, hit)).collect();
+            render(format, &records)?
+        }
+        ClientCommand::Get { collection, ids, where_filter, limit } => {
+            let ids = (!ids.is_empty()).then_some(ids);
+            let response = chroma.get_documents(&collection, ids, where_filter, limit).await?;
+            let records: Vec<DocumentRecord> = (0..response.ids_for(0).len())
+                .map(|i| DocumentRecord {
+                    id: response.ids_for(0)[i].clone(),
+                    document: response.document(0, i).map(str::to_string),
+                    metadata: response.metadata(0, i).cloned(),
+                })
+                .collect();
+            render(format, &records)?
+        }
+        ClientCommand::Delete { collection, ids } => {
+            chroma.delete_documents(&collection, ids.clone()).await?;
+            let records: Vec<StatusRecord> =
+                ids.into_iter().map(|id| StatusRecord { id, status: "deleted" }).collect();
+            render(format, &records)?
+        }
+        ClientCommand::Count { collection } => {
+            let count = chroma.count(&collection).await?;
+            render_one(format, &CountRecord { collection, count })?
+        }
+        ClientCommand::Preflight { collection } => {
+            let pipeline = Pipeline::new(Arc::new(chroma), Arc::new(embedding_client()?), &collection);
+            let report = pipeline.preflight().await;
+            let rendered = render(format, &report.checks)?;
+            if !report.is_ok() {
+                println!("{}", rendered);
+                std::process::exit(1);
+            }
+            rendered
+        }
+        ClientCommand::Doctor { collection, bundle } => {
+            let pipeline = Pipeline::new(Arc::new(chroma), Arc::new(embedding_client()?), &collection);
+            let report = pipeline.doctor().await;
+            if let Some(path) = bundle {
+                atomic_file::write_plain(&path, serde_json::to_string_pretty(&report)?.as_bytes())?;
+            }
+            let rendered = render(format, &report.checks)?;
+            if !report.is_ok() {
+                println!("{}", rendered);
+                std::process::exit(1);
+            }
+            rendered
+        }
+        ClientCommand::Drift { collection, sample, min_mean_cosine } => {
+            let config = DriftConfig::default().with_sample_size(sample).with_min_mean_cosine(min_mean_cosine);
+            let pipeline = Pipeline::new(Arc::new(chroma), Arc::new(embedding_client()?), &collection);
+            let report = pipeline.check_drift(&config).await?;
+            let rendered = render_one(format, &report)?;
+            if report.drifted {
+                println!("{}", rendered);
+                std::process::exit(1);
+            }
+            rendered
+        }
+        ClientCommand::Audit { collection, required_keys, min_chars, max_zscore } => {
+            let mut config = AuditConfig::default().with_min_chars(min_chars).with_max_zscore(max_zscore);
+            config.required_keys = required_keys;
+            let report = chroma.audit(&collection, &config).await?;
+            render(format, &report.findings)?
+        }
+        ClientCommand::Outdated { collection, pipeline_version, max_chars, overlap } => {
+            let chunker = max_chars.map(|max_chars| Chunker::new(max_chars, overlap));
+            let expected = RecordVersion::new(&pipeline_version, chunker.as_ref());
+            let report = chroma.find_outdated(&collection, &expected).await?;
+            render(format, &report.outdated)?
+        }
+        ClientCommand::Export { collection, file, memory_limit_mb, spill_dir, no_embeddings, zstd, encrypt, shards } => {
+            let mut config = ExportConfig::default()
+                .with_memory_limit(memory_limit_mb.saturating_mul(1024 * 1024))
+                .with_embeddings(!no_embeddings);
+            if zstd {
+                config = config.with_compression(Compression::zstd());
+            }
+            if encrypt {
+                let cipher = StoreCipher::load()?.context("--encrypt needs LOCAL_STORE_KEY or a key in the OS keyring")?;
+                config = config.with_encryption(cipher);
+            }
+            if let Some(dir) = spill_dir {
+                config = config.with_spill_dir(dir);
+            }
+            if let Some(shards) = shards {
+                let manifest = chroma.export_sharded(&collection, &file, shards, &config).await?;
+                render(format, &manifest.shards)?
+            } else {
+                // Only replace `file` once the whole export has been written.
+                let mut out = AtomicFile::create(&file)?;
+                let report = chroma.export(&collection, &mut out, &config).await?;
+                out.commit()?;
+                render_one(format, &report)?
+            }
+        }
+        ClientCommand::Import { collection, path, concurrency, batch_size, resume } => {
+            let config = ImportConfig::default()
+                .with_concurrency(concurrency)
+                .with_batch_size(batch_size)
+                .with_resume(resume);
+            let report = if path.is_dir() {
+                chroma.import_sharded(&collection, &path, &config).await?
+            } else {
+                chroma.import_file(&collection, &path, &config).await?
+            };
+            render_one(format, &report)?
+        }
+        ClientCommand::Backfill { collection, dir, batch_size, embed_qps, docs_per_second, windows } => {
+            let mut config = BackfillConfig::default().with_batch_size(batch_size);
+            config.max_embed_requests_per_second = embed_qps;
+            config.max_documents_per_second = docs_per_second;
+            config.windows = windows;
+
+            let pipeline = Pipeline::new(Arc::new(chroma), Arc::new(embedding_client()?), &collection);
+            pipeline.ensure_collection().await?;
+            let (documents, _) = chromadb_demo::pipeline::load_directory(&dir)?;
+            let report = chromadb_demo::backfill::backfill(&pipeline, documents, &config).await?;
+            render_one(format, &report)?
+        }
+        ClientCommand::Mirror { collection, dir, dry_run } => {
+            let pipeline = Pipeline::new(Arc::new(chroma), Arc::new(embedding_client()?), &collection);
+            let plan = if dry_run {
+                let (documents, _) = chromadb_demo::pipeline::load_directory(&dir)?;
+                let manifest = chromadb_demo::sync_plan::manifest_of(&documents, &pipeline.chroma().content_hasher()?);
+                pipeline.chroma().diff(&manifest, &collection).await?
+            } else {
+                pipeline.ensure_collection().await?;
+                pipeline.mirror_directory(&dir).await?
+            };
+            render_one(format, &plan)?
+        }
+    };
+
+    println!("{}", rendered);
+    Ok(())
+}
+
+async fn run_collection_command(
+    chroma: &ChromaClient,
+    format: OutputFormat,
+    command: CollectionCommand,
+) -> anyhow::Result<String> {
+    match command {
+        CollectionCommand::List => {
+            let records: Vec<CollectionRecord> =
+                chroma.list_collections().await?.into_iter().map(Into::into).collect();
+            render(format, &records)
+        }
+        CollectionCommand::Create { name, normalize, space, description } => {
+            let name = if normalize { normalize_collection_name(&name)? } else { name };
+            let mut metadata = CollectionMetadata::new(space);
+            metadata.description = description;
+            let collection = chroma.create_collection_with_metadata(&name, &metadata).await?;
+            render_one(format, &CollectionRecord::from(collection))
+        }
+        CollectionCommand::Get { name } => {
+            let collection = chroma.get_collection(&name).await?;
+            render_one(format, &CollectionRecord::from(collection))
+        }
+        CollectionCommand::Delete { name } => {
+            chroma.delete_collection(&name).await?;
+            render_one(format, &StatusRecord { id: name, status: "deleted" })
+        }
+    }
+}
+
+/// Write `<name>.1` for `command` and `<name>-<sub>.1` for each subcommand,
+/// recursively, as `man` expects for git-style tools.
+fn write_man_pages(command: &clap::Command, name: &str, dir: &Path) -> anyhow::Result<()> {
+    let mut page = Vec::new();
+    clap_mangen::Man::new(command.clone()).title(name).render(&mut page)?;
+    atomic_file::write_plain(dir.join(format!("{}.1", name)), &page)?;
+
+    for subcommand in command.get_subcommands().filter(|c| !c.is_hide_set()) {
+        write_man_pages(subcommand, &format!("{}-{}", name, subcommand.get_name()), dir)?;
+    }
+    Ok(())
+}
+
+async fn run_server(config_file: Option<&Path>, bind: Option<SocketAddr>, pid_file: Option<&Path>) -> anyhow::Result<()> {
+    let mut config = ServerConfig::load(config_file)?;
+    if let Some(bind) = bind {
+        config.bind = bind;
+    }
+    if let Some(path) = pid_file {
+        atomic_file::write_plain(path, std::process::id().to_string().as_bytes())?;
+    }
+
+    let result = chromadb_demo::server::serve(config).await;
+    if let Some(path) = pid_file {
+        let _ = std::fs::remove_file(path);
+    }
+    Ok(result?)
+}
+
+/// Re-run this command without `--daemon` as a detached background process,
+/// with its logs sent to `log_file`.
+fn spawn_daemon(pid_file: Option<&Path>, log_file: Option<&Path>) -> anyhow::Result<()> {
+    let args = std::env::args_os().skip(1).filter(|arg| arg != "--daemon");
+    let log = match log_file {
+        Some(path) => Stdio::from(std::fs::OpenOptions::new().create(true).append(true).open(path)?),
+        None => Stdio::null(),
+    };
+
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command.args(args).stdin(Stdio::null()).stdout(Stdio::null()).stderr(log);
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // Own process group, so the daemon doesn't get the terminal's Ctrl-C.
+        command.process_group(0);
+    }
+
+    let child = command.spawn().context("failed to start daemon")?;
+    if let Some(path) = pid_file {
+        atomic_file::write_plain(path, child.id().to_string().as_bytes())?;
+    }
+    println!("{}", child.id());
+    Ok(())
+}
+
+fn embedding_client() -> anyhow::Result<EmbeddingClient> {
+    let api_key = std::env::var("GOOGLE_API_KEY").context("GOOGLE_API_KEY must be set to embed text")?;
+    Ok(EmbeddingClient::try_new(api_key)?)
+}
+
+fn parse_key_value(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!("expected key=value, got '{}'", value))
+}
+
+fn parse_json(value: &str) -> Result<Value, String> {
+    serde_json::from_str(value).map_err(|e| format!("invalid JSON: {}", e))
+}
+
+fn parse_space(value: &str) -> Result<DistanceSpace, String> {
+    serde_json::from_value(Value::String(value.to_lowercase()))
+        .map_err(|_| format!("unknown distance space '{}' (expected l2, cosine or ip)", value))
+}
+
+fn json_string<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(Value::String(s)) => s,
+        Ok(other) => other.to_string(),
+        Err(_) => String::new(),
+    }
+}
@@ -0,0 +1,134 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// Widest a table cell gets before it is truncated. JSON and CSV are never
+/// truncated.
+const MAX_TABLE_CELL: usize = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns for reading in a terminal
+    Table,
+    /// Pretty-printed JSON, stable field names (for jq and scripts)
+    Json,
+    /// RFC 4180 CSV with a header row
+    Csv,
+}
+
+/// Something the CLI prints: a table row, a CSV record or a JSON object.
+///
+/// The JSON form is the `Serialize` impl, so field names there are part of
+/// the CLI's stable interface.
+pub trait Record: Serialize {
+    const COLUMNS: &'static [&'static str];
+
+    fn values(&self) -> Vec<String>;
+}
+
+/// Render a list of records. JSON output is always an array, even when empty.
+pub fn render<R: Record>(format: OutputFormat, records: &[R]) -> anyhow::Result<String> {
+    match format {
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(records)?),
+        OutputFormat::Csv => render_csv(records),
+        OutputFormat::Table => Ok(render_table(records)),
+    }
+}
+
+/// Render a single record. JSON output is a bare object.
+pub fn render_one<R: Record>(format: OutputFormat, record: &R) -> anyhow::Result<String> {
+    match format {
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(record)?),
+        _ => render(format, std::slice::from_ref(record)),
+    }
+}
+
+fn render_csv<R: Record>(records: &[R]) -> anyhow::Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(R::COLUMNS)?;
+    for record in records {
+        writer.write_record(record.values())?;
+    }
+    let bytes = writer.into_inner().map_err(|e| anyhow::anyhow!(e.to_string()))?;
+    Ok(String::from_utf8(bytes)?.trim_end().to_string())
+}
+
+fn render_table<R: Record>(records: &[R]) -> String {
+    let header: Vec<String> = R::COLUMNS.iter().map(|c| c.to_uppercase()).collect();
+    let rows: Vec<Vec<String>> = records
+        .iter()
+        .map(|record| record.values().iter().map(|v| table_cell(v)).collect())
+        .collect();
+
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    std::iter::once(&header)
+        .chain(&rows)
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = *width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn table_cell(value: &str) -> String {
+    let single_line = value.replace(['\n', '\r', '\t'], " ");
+    if single_line.chars().count() <= MAX_TABLE_CELL {
+        single_line
+    } else {
+        let truncated: String = single_line.chars().take(MAX_TABLE_CELL - 1).collect();
+        format!("{}…", truncated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Row {
+        id: String,
+        document: String,
+    }
+
+    impl Record for Row {
+        const COLUMNS: &'static [&'static str] = &["id", "document"];
+
+        fn values(&self) -> Vec<String> {
+            vec![self.id.clone(), self.document.clone()]
+        }
+    }
+
+    fn rows() -> Vec<Row> {
+        vec![
+            Row { id: "a".to_string(), document: "short".to_string() },
+            Row { id: "bbb".to_string(), document: "has, comma\nand newline".to_string() },
+        ]
+    }
+
+    #[test]
+    fn formats_table_csv_and_json() {
+        let table = render(OutputFormat::Table, &rows()).unwrap();
+        assert_eq!(table, "ID   DOCUMENT\na    short\nbbb  has, comma and newline");
+
+        let csv = render(OutputFormat::Csv, &rows()).unwrap();
+        assert_eq!(csv, "id,document\na,short\nbbb,\"has, comma\nand newline\"");
+
+        let json: serde_json::Value = serde_json::from_str(&render(OutputFormat::Json, &rows()).unwrap()).unwrap();
+        assert_eq!(json[1]["id"], "bbb");
+
+        let one: serde_json::Value =
+            serde_json::from_str(&render_one(OutputFormat::Json, &rows()[0]).unwrap()).unwrap();
+        assert_eq!(one["document"], "short");
+    }
+}
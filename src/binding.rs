@@ -0,0 +1,195 @@
+use crate::embeddings::{EmbeddingProvider, normalize_model};
+use crate::error::{ChromaError, Result};
+use crate::models::{CollectionMetadata, CollectionResponse};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+/// The provider, model and dimension a collection's vectors were embedded
+/// with, recorded in its metadata (`embedding:provider`, `embedding:model`,
+/// `dimension`) when `Pipeline::ensure_collection` creates it.
+///
+/// Vectors from different models live in unrelated spaces, so querying a
+/// collection with another model's embedding returns plausible-looking but
+/// meaningless neighbours. The binding lets the pipeline pick the right
+/// provider instead.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddingBinding {
+    pub provider: String,
+    pub model: String,
+    pub dimension: Option<usize>,
+}
+
+impl EmbeddingBinding {
+    /// The binding a collection filled by `provider` gets.
+    pub fn of(provider: &dyn EmbeddingProvider) -> Self {
+        Self {
+            provider: provider.provider_name().to_string(),
+            model: provider.model_name().to_string(),
+            dimension: provider.dimension(),
+        }
+    }
+
+    /// `None` for collections created before bindings were recorded.
+    pub fn from_metadata(metadata: &CollectionMetadata) -> Option<Self> {
+        Some(Self {
+            provider: metadata.embedding_provider.clone().unwrap_or_default(),
+            model: metadata.embedding_model.clone()?,
+            dimension: metadata.dimension,
+        })
+    }
+
+    /// Whether `provider` produces vectors compatible with this binding:
+    /// the same model (`models/` prefix optional) from the same provider.
+    pub fn matches(&self, provider: &dyn EmbeddingProvider) -> bool {
+        let same_provider = self.provider.is_empty() || self.provider == provider.provider_name();
+        same_provider && normalize_model(&self.model) == normalize_model(provider.model_name())
+    }
+}
+
+impl fmt::Display for EmbeddingBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.provider, self.model)?;
+        if let Some(dimension) = self.dimension {
+            write!(f, " ({} dimensions)", dimension)?;
+        }
+        Ok(())
+    }
+}
+
+impl CollectionMetadata {
+    pub fn with_binding(mut self, binding: &EmbeddingBinding) -> Self {
+        self.embedding_provider = Some(binding.provider.clone());
+        self.embedding_model = Some(binding.model.clone());
+        if binding.dimension.is_some() {
+            self.dimension = binding.dimension;
+        }
+        self
+    }
+
+    pub fn binding(&self) -> Option<EmbeddingBinding> {
+        EmbeddingBinding::from_metadata(self)
+    }
+}
+
+/// Embedding providers a pipeline may pick from, by the binding of the
+/// collection it serves.
+#[derive(Clone, Default)]
+pub struct ProviderRegistry {
+    providers: Vec<Arc<dyn EmbeddingProvider>>,
+}
+
+impl ProviderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(mut self, provider: Arc<dyn EmbeddingProvider>) -> Self {
+        self.providers.push(provider);
+        self
+    }
+
+    /// The first registered provider matching `binding`.
+    pub fn resolve(&self, binding: &EmbeddingBinding) -> Option<Arc<dyn EmbeddingProvider>> {
+        self.providers.iter().find(|p| binding.matches(p.as_ref())).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.providers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+}
+
+impl fmt::Debug for ProviderRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.providers.iter().map(|p| EmbeddingBinding::of(p.as_ref())))
+            .finish()
+    }
+}
+
+/// Refuse a query embedding of `len` dimensions if `collection` holds
+/// vectors of another size (as reported by Chroma, or declared in its
+/// metadata). `query` describes where the embedding came from.
+pub(crate) fn check_query_dimension(
+    collection: &CollectionResponse,
+    len: usize,
+    query: impl FnOnce() -> String,
+) -> Result<()> {
+    let metadata = collection.metadata.as_ref();
+    let Some(expected) = collection.dimension.or(metadata.and_then(|m| m.dimension)) else {
+        return Ok(());
+    };
+    if len == expected {
+        return Ok(());
+    }
+    Err(ChromaError::ModelMismatch {
+        collection: collection.name.clone(),
+        expected: match metadata.and_then(CollectionMetadata::binding) {
+            Some(binding) => EmbeddingBinding { dimension: Some(expected), ..binding }.to_string(),
+            None => format!("{}-dimensional", expected),
+        },
+        actual: query(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    use crate::models::DistanceSpace;
+    use crate::pipeline::Pipeline;
+    use crate::query::QueryOptions;
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_pipeline_embeds_with_the_collection_bound_provider() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct NamedEmbeddings(&'static str, AtomicUsize);
+
+        impl EmbeddingProvider for NamedEmbeddings {
+            fn embed<'a>(&'a self, texts: &'a [&'a str]) -> futures::future::BoxFuture<'a, Result<Vec<Vec<f32>>>> {
+                self.1.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async move { Ok(texts.iter().map(|_| vec![1.0, 0.0]).collect()) })
+            }
+
+            fn model_name(&self) -> &str {
+                self.0
+            }
+        }
+
+        let chroma = Arc::new(mock_chroma(|request| {
+            if request.uri().path().ends_with("/query") {
+                r#"{"ids": [["a"]], "distances": [[0.1]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs", "metadata": {"hnsw:space": "cosine", "embedding:provider": "custom", "embedding:model": "models/text-v2"}}"#
+            }
+        }));
+
+        let default = Arc::new(NamedEmbeddings("text-v1", AtomicUsize::new(0)));
+        let bound = Arc::new(NamedEmbeddings("text-v2", AtomicUsize::new(0)));
+        let unbound = Pipeline::new(chroma.clone(), default.clone(), "docs");
+        let error = unbound.query("rust", &QueryOptions::new(1)).await.unwrap_err();
+        assert!(matches!(error, ChromaError::ModelMismatch { .. }));
+        let error = error.to_string();
+        assert!(error.contains("custom/models/text-v2") && error.contains("custom/text-v1"), "{}", error);
+        assert_eq!(default.1.load(Ordering::SeqCst), 0);
+
+        let pipeline = Pipeline::new(chroma, default.clone(), "docs")
+            .with_providers(ProviderRegistry::new().register(bound.clone()));
+        let result = pipeline.query("rust", &QueryOptions::new(1).with_explain()).await.unwrap();
+        assert_eq!(result.hits[0].id, "a");
+        assert_eq!(result.explain.unwrap().embedding_model.as_deref(), Some("text-v2"));
+        assert_eq!((default.1.load(Ordering::SeqCst), bound.1.load(Ordering::SeqCst)), (0, 1));
+
+        let metadata = CollectionMetadata::new(DistanceSpace::Cosine).with_binding(&EmbeddingBinding::of(default.as_ref()));
+        let json = serde_json::to_value(&metadata).unwrap();
+        assert_eq!(json["embedding:model"], "text-v1");
+        assert_eq!(serde_json::from_value::<CollectionMetadata>(json).unwrap().binding(), Some(EmbeddingBinding::of(default.as_ref())));
+    }
+}
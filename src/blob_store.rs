@@ -0,0 +1,129 @@
+use crate::atomic_file;
+use crate::error::{ChromaError, Result};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use std::path::{Component, Path, PathBuf};
+use url::Url;
+
+/// Storage for the original files behind indexed documents.
+///
+/// Chroma only holds the extracted text; the artifact it came from (PDF,
+/// image, HTML page) goes to a blob store and its URI into the document's
+/// `uris` field, so a search result can be traced back to the source. The
+/// crate ships `FileBlobStore`; object stores such as S3 plug in by
+/// implementing this trait.
+pub trait BlobStore: Send + Sync {
+    /// Store `data` under `key` and return the URI to record for it.
+    fn put<'a>(&'a self, key: &'a str, data: Bytes) -> BoxFuture<'a, Result<String>>;
+
+    /// Read back the artifact behind a URI returned by `put`.
+    fn get<'a>(&'a self, uri: &'a str) -> BoxFuture<'a, Result<Bytes>>;
+}
+
+/// Keeps blobs as files under a root directory and hands out `file://` URIs.
+#[derive(Debug, Clone)]
+pub struct FileBlobStore {
+    root: PathBuf,
+}
+
+impl FileBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Path for `key`, which may contain `/` separated directories but must
+    /// stay inside the root.
+    fn path_for(&self, key: &str) -> Result<PathBuf> {
+        let relative = Path::new(key);
+        let is_contained = !key.is_empty() && relative.components().all(|c| matches!(c, Component::Normal(_)));
+        if !is_contained {
+            return Err(ChromaError::ValidationError(format!("Invalid blob key '{}'", key)));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+impl BlobStore for FileBlobStore {
+    fn put<'a>(&'a self, key: &'a str, data: Bytes) -> BoxFuture<'a, Result<String>> {
+        Box::pin(async move {
+            let path = self.path_for(key)?;
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let target = path.clone();
+            tokio::task::spawn_blocking(move || atomic_file::write_plain(&target, &data))
+                .await
+                .map_err(|e| ChromaError::IoError(std::io::Error::other(e)))??;
+
+            let path = tokio::fs::canonicalize(&path).await?;
+            Url::from_file_path(&path)
+                .map(String::from)
+                .map_err(|_| ChromaError::ValidationError(format!("Blob path {} is not absolute", path.display())))
+        })
+    }
+
+    fn get<'a>(&'a self, uri: &'a str) -> BoxFuture<'a, Result<Bytes>> {
+        Box::pin(async move {
+            let path = Url::parse(uri)
+                .ok()
+                .filter(|url| url.scheme() == "file")
+                .and_then(|url| url.to_file_path().ok())
+                .ok_or_else(|| ChromaError::ValidationError(format!("Not a file URI: '{}'", uri)))?;
+
+            // URIs come back from Chroma; only serve files this store wrote.
+            let path = tokio::fs::canonicalize(&path).await?;
+            let root = tokio::fs::canonicalize(&self.root).await?;
+            if !path.starts_with(&root) {
+                return Err(ChromaError::ValidationError(format!(
+                    "Blob URI '{}' is outside {}", uri, self.root.display()
+                )));
+            }
+            Ok(Bytes::from(tokio::fs::read(&path).await?))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chroma_client::ChromaClient;
+    use crate::models::{Document, QueryHit};
+    use crate::test_support::MOCK_URL;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_blob_store_round_trips_source_files() {
+        let root = std::env::temp_dir().join(format!("blobs-{}", Uuid::new_v4()));
+        let client = ChromaClient::try_new(MOCK_URL.to_string()).unwrap().with_blob_store(FileBlobStore::new(&root));
+
+        let mut document = Document {
+            id: "reports/q3".to_string(),
+            content: "extracted text".to_string(),
+            metadata: HashMap::new(),
+            uri: None,
+        };
+        client.store_source(&mut document, b"%PDF-1.7".to_vec()).await.unwrap();
+        let uri = document.uri.clone().unwrap();
+        assert!(uri.starts_with("file://") && uri.ends_with("reports/q3"), "{}", uri);
+
+        let hit = |uri: Option<String>| QueryHit {
+            id: document.id.clone(),
+            document: None,
+            metadata: None,
+            distance: 0.1,
+            score: 0.9,
+            uri,
+        };
+        assert_eq!(client.fetch_source(&hit(Some(uri))).await.unwrap().unwrap(), &b"%PDF-1.7"[..]);
+        assert!(client.fetch_source(&hit(None)).await.unwrap().is_none());
+        assert!(client.fetch_source(&hit(Some("file:///etc/hostname".to_string()))).await.is_err());
+
+        document.id = "../escape".to_string();
+        assert!(matches!(
+            client.store_source(&mut document, b"x".to_vec()).await,
+            Err(ChromaError::ValidationError(_))
+        ));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
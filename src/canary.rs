@@ -0,0 +1,251 @@
+use crate::pipeline::Pipeline;
+use crate::query::QueryOptions;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::{info, warn};
+
+/// A query with known-good answers: each expected id must appear among the
+/// top `within` hits.
+///
+/// A handful of these, run regularly against production, catch failures
+/// that never raise an error: a corrupted index, documents re-embedded with
+/// the wrong model, a filter that silently stopped matching.
+#[derive(Debug, Clone)]
+pub struct CanaryQuery {
+    pub name: String,
+    pub text: String,
+    pub expected_ids: Vec<String>,
+    pub within: u32,
+}
+
+impl CanaryQuery {
+    /// Expect `expected_ids` among the top 5 hits for `text`.
+    pub fn new<I, S>(name: &str, text: &str, expected_ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            name: name.to_string(),
+            text: text.to_string(),
+            expected_ids: expected_ids.into_iter().map(Into::into).collect(),
+            within: 5,
+        }
+    }
+
+    pub fn within(mut self, within: u32) -> Self {
+        self.within = within.max(1);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CanaryResult {
+    pub name: String,
+    pub passed: bool,
+    /// Expected ids that were not in the top hits.
+    pub missing: Vec<String>,
+    pub top_ids: Vec<String>,
+    pub latency_ms: u64,
+    /// The query failed outright.
+    pub error: Option<String>,
+}
+
+/// Outcome of one run over every canary query.
+#[derive(Debug, Clone, Serialize)]
+pub struct CanaryReport {
+    pub checked_at: DateTime<Utc>,
+    pub results: Vec<CanaryResult>,
+}
+
+impl CanaryReport {
+    pub fn is_ok(&self) -> bool {
+        self.results.iter().all(|r| r.passed)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CanaryResult> {
+        self.results.iter().filter(|r| !r.passed)
+    }
+}
+
+/// Run each canary once through `pipeline`.
+pub(crate) async fn run(pipeline: &Pipeline, canaries: &[CanaryQuery]) -> CanaryReport {
+    let mut results = Vec::with_capacity(canaries.len());
+    for canary in canaries {
+        let started = Instant::now();
+        let outcome = pipeline.query(&canary.text, &QueryOptions::new(canary.within)).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        let result = match outcome {
+            Ok(result) => {
+                let top_ids: Vec<String> = result.hits.into_iter().map(|hit| hit.id).collect();
+                let missing: Vec<String> =
+                    canary.expected_ids.iter().filter(|id| !top_ids.contains(id)).cloned().collect();
+                CanaryResult {
+                    name: canary.name.clone(),
+                    passed: missing.is_empty(),
+                    missing,
+                    top_ids,
+                    latency_ms,
+                    error: None,
+                }
+            }
+            Err(e) => CanaryResult {
+                name: canary.name.clone(),
+                passed: false,
+                missing: canary.expected_ids.clone(),
+                top_ids: Vec::new(),
+                latency_ms,
+                error: Some(e.to_string()),
+            },
+        };
+        results.push(result);
+    }
+    CanaryReport { checked_at: Utc::now(), results }
+}
+
+type AlertFn = Arc<dyn Fn(&CanaryReport) + Send + Sync>;
+
+/// Background task running canary queries on an interval.
+///
+/// Every run is logged (`warn!` per failing canary, with the missing ids);
+/// an optional alert callback receives each report, e.g. to export metrics or
+/// page someone when `is_ok` turns false. The task stops when the returned
+/// `CanaryHandle` is dropped.
+///
+/// ```no_run
+/// # async fn example(pipeline: std::sync::Arc<chromadb_demo::Pipeline>) {
+/// use chromadb_demo::canary::{CanaryMonitor, CanaryQuery};
+/// use std::time::Duration;
+///
+/// let canaries = vec![CanaryQuery::new("refunds", "how do I get a refund", ["faq-refunds"]).within(3)];
+/// let handle = CanaryMonitor::new(pipeline, canaries, Duration::from_secs(300))
+///     .on_report(|report| if !report.is_ok() { eprintln!("canaries failing") })
+///     .spawn();
+/// # }
+/// ```
+pub struct CanaryMonitor {
+    pipeline: Arc<Pipeline>,
+    canaries: Vec<CanaryQuery>,
+    interval: Duration,
+    alert: Option<AlertFn>,
+}
+
+impl CanaryMonitor {
+    pub fn new(pipeline: Arc<Pipeline>, canaries: Vec<CanaryQuery>, interval: Duration) -> Self {
+        Self { pipeline, canaries, interval, alert: None }
+    }
+
+    /// Called with every report, passing or not.
+    pub fn on_report<F>(mut self, alert: F) -> Self
+    where
+        F: Fn(&CanaryReport) + Send + Sync + 'static,
+    {
+        self.alert = Some(Arc::new(alert));
+        self
+    }
+
+    /// Start running on the current Tokio runtime; the first run is
+    /// immediate.
+    pub fn spawn(self) -> CanaryHandle {
+        let latest = Arc::new(Mutex::new(None));
+        let task = tokio::spawn(self.run_loop(latest.clone()));
+        CanaryHandle { latest, task }
+    }
+
+    async fn run_loop(self, latest: Arc<Mutex<Option<CanaryReport>>>) {
+        let mut ticks = tokio::time::interval(self.interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticks.tick().await;
+            let report = run(&self.pipeline, &self.canaries).await;
+            for failure in report.failures() {
+                warn!(
+                    "Canary '{}' failed: missing {:?} from top hits {:?}{}",
+                    failure.name,
+                    failure.missing,
+                    failure.top_ids,
+                    failure.error.as_deref().map(|e| format!(" ({})", e)).unwrap_or_default()
+                );
+            }
+            if report.is_ok() {
+                info!("All {} canary queries passed", report.results.len());
+            }
+            if let Some(alert) = &self.alert {
+                alert(&report);
+            }
+            *latest.lock().unwrap() = Some(report);
+        }
+    }
+}
+
+/// Running `CanaryMonitor`. Dropping it stops the monitor.
+pub struct CanaryHandle {
+    latest: Arc<Mutex<Option<CanaryReport>>>,
+    task: JoinHandle<()>,
+}
+
+impl CanaryHandle {
+    /// The most recent report, `None` until the first run completes.
+    pub fn latest(&self) -> Option<CanaryReport> {
+        self.latest.lock().unwrap().clone()
+    }
+}
+
+impl Drop for CanaryHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{FixedEmbeddings, mock_chroma};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_canaries_flag_missing_expected_hits() {
+        let chroma = mock_chroma(|request| {
+            if request.uri().path().ends_with("/query") {
+                r#"{"ids": [["faq-refunds", "faq-shipping"]], "distances": [[0.1, 0.2]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+        let pipeline = Arc::new(Pipeline::new(Arc::new(chroma), Arc::new(FixedEmbeddings), "docs"));
+
+        let canaries = vec![
+            CanaryQuery::new("refunds", "refund", ["faq-refunds"]).within(2),
+            CanaryQuery::new("returns", "returns", ["faq-returns"]),
+        ];
+        let report = pipeline.run_canaries(&canaries).await;
+        assert!(!report.is_ok());
+        let failures: Vec<_> = report.failures().collect();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].name, "returns");
+        assert_eq!(failures[0].missing, vec!["faq-returns".to_string()]);
+
+        let alerts = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let seen = alerts.clone();
+        let monitor = CanaryMonitor::new(pipeline, canaries, std::time::Duration::from_secs(3600))
+            .on_report(move |report| {
+                if !report.is_ok() {
+                    seen.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                }
+            })
+            .spawn();
+        for _ in 0..100 {
+            if monitor.latest().is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(!monitor.latest().unwrap().is_ok());
+        assert_eq!(alerts.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+}
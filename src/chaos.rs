@@ -0,0 +1,237 @@
+use crate::error::{ChromaError, Result};
+use crate::http_client::HttpClientFactory;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tracing::debug;
+
+/// A fault to inject into one request passing through a `ChaosProxy`.
+#[derive(Debug, Clone)]
+pub enum Fault {
+    /// Respond immediately with this status and a short error body.
+    Status(u16),
+    /// Respond `200 OK` with a truncated JSON body.
+    MalformedJson,
+    /// Wait, then handle the request normally.
+    Delay(Duration),
+    /// Never respond, so the client's request timeout fires.
+    Hang,
+}
+
+#[derive(Debug, Clone)]
+struct Stub {
+    path_contains: String,
+    status: u16,
+    body: String,
+}
+
+#[derive(Default)]
+struct ChaosState {
+    faults: VecDeque<Fault>,
+    stubs: Vec<Stub>,
+    requests: Vec<String>,
+}
+
+/// Local HTTP proxy that injects scripted faults, for deterministically testing
+/// retry and fallback behaviour of the clients.
+///
+/// Point a client at `url()`. Each request consumes the next queued fault; once
+/// the queue is empty, requests are answered by the first matching stub, then
+/// forwarded to the upstream (if any), and otherwise get a `404`.
+///
+/// ```no_run
+/// # async fn demo() -> chromadb_demo::Result<()> {
+/// use chromadb_demo::chaos::{ChaosProxy, Fault};
+/// use chromadb_demo::ChromaClient;
+///
+/// let proxy = ChaosProxy::start(Some("http://localhost:8000")).await?;
+/// proxy.inject(Fault::Status(503));
+/// proxy.inject(Fault::MalformedJson);
+///
+/// let chroma = ChromaClient::try_new(proxy.url())?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ChaosProxy {
+    addr: SocketAddr,
+    state: Arc<Mutex<ChaosState>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl ChaosProxy {
+    pub async fn start(upstream: Option<&str>) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+
+        let state = Arc::new(Mutex::new(ChaosState::default()));
+        let upstream = upstream.map(|u| u.trim_end_matches('/').to_string());
+        let http_client = HttpClientFactory::build()?;
+
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            let upstream = upstream.clone();
+            let http_client = http_client.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    handle(state.clone(), upstream.clone(), http_client.clone(), request)
+                }))
+            }
+        });
+
+        let server = Server::from_tcp(listener)
+            .map_err(|e| ChromaError::ApiError(format!("Failed to start chaos proxy: {}", e)))?
+            .serve(make_service);
+
+        let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+        tokio::spawn(server.with_graceful_shutdown(async {
+            shutdown_rx.await.ok();
+        }));
+
+        debug!("Chaos proxy listening on {}", addr);
+
+        Ok(Self {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Queue a fault for the next request that has no fault yet.
+    pub fn inject(&self, fault: Fault) {
+        self.inject_times(fault, 1);
+    }
+
+    pub fn inject_times(&self, fault: Fault, times: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.faults.extend(std::iter::repeat_n(fault, times));
+    }
+
+    /// Answer requests whose path contains `path_contains` with a canned response
+    /// instead of forwarding them.
+    pub fn stub(&self, path_contains: &str, status: u16, body: &str) {
+        self.state.lock().unwrap().stubs.push(Stub {
+            path_contains: path_contains.to_string(),
+            status,
+            body: body.to_string(),
+        });
+    }
+
+    /// `METHOD path` of every request received so far.
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn pending_faults(&self) -> usize {
+        self.state.lock().unwrap().faults.len()
+    }
+}
+
+impl Drop for ChaosProxy {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+async fn handle(
+    state: Arc<Mutex<ChaosState>>,
+    upstream: Option<String>,
+    http_client: reqwest::Client,
+    request: Request<Body>,
+) -> std::result::Result<Response<Body>, Infallible> {
+    let path = request
+        .uri()
+        .path_and_query()
+        .map(|p| p.to_string())
+        .unwrap_or_else(|| "/".to_string());
+
+    let (fault, stub) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(format!("{} {}", request.method(), path));
+        let fault = state.faults.pop_front();
+        let stub = state.stubs.iter().find(|s| path.contains(&s.path_contains)).cloned();
+        (fault, stub)
+    };
+
+    match fault {
+        Some(Fault::Status(status)) => {
+            debug!("Chaos proxy injecting status {} for {}", status, path);
+            return Ok(respond(status, &format!("{{\"error\":\"injected fault {}\"}}", status)));
+        }
+        Some(Fault::MalformedJson) => {
+            debug!("Chaos proxy injecting malformed JSON for {}", path);
+            return Ok(respond(200, "{\"ids\": [[\"truncated"));
+        }
+        Some(Fault::Hang) => {
+            debug!("Chaos proxy hanging request {}", path);
+            futures::future::pending::<()>().await;
+        }
+        Some(Fault::Delay(delay)) => {
+            debug!("Chaos proxy delaying {} by {:?}", path, delay);
+            tokio::time::sleep(delay).await;
+        }
+        None => {}
+    }
+
+    if let Some(stub) = stub {
+        return Ok(respond(stub.status, &stub.body));
+    }
+
+    match upstream {
+        Some(upstream) => Ok(forward(&http_client, &upstream, &path, request).await),
+        None => Ok(respond(404, "{\"error\":\"no stub or upstream\"}")),
+    }
+}
+
+async fn forward(
+    http_client: &reqwest::Client,
+    upstream: &str,
+    path: &str,
+    request: Request<Body>,
+) -> Response<Body> {
+    let method = request.method().clone();
+    let content_type = request.headers().get(hyper::header::CONTENT_TYPE).cloned();
+
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(e) => return respond(502, &format!("{{\"error\":\"{}\"}}", e)),
+    };
+
+    let mut upstream_request = http_client.request(method, format!("{}{}", upstream, path));
+    if let Some(content_type) = content_type {
+        upstream_request = upstream_request.header(reqwest::header::CONTENT_TYPE, content_type);
+    }
+
+    match upstream_request.body(body).send().await {
+        Ok(response) => {
+            let status = response.status().as_u16();
+            let body = response.bytes().await.unwrap_or_default();
+            Response::builder()
+                .status(status)
+                .header(hyper::header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body))
+                .unwrap_or_else(|_| Response::new(Body::empty()))
+        }
+        Err(e) => respond(502, &format!("{{\"error\":\"upstream unavailable: {}\"}}", e)),
+    }
+}
+
+fn respond(status: u16, body: &str) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR))
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap_or_else(|_| Response::new(Body::empty()))
+}
@@ -1,50 +1,128 @@
+use crate::audit::{self, AuditConfig, AuditReport};
+use crate::binding::{self, EmbeddingBinding};
+use crate::blob_store::BlobStore;
+use crate::chunk_gc::{self, ChunkGcReport, ChunkManifest};
+use crate::collection::Collection;
+use crate::collection_cache::{CollectionCache, Lookup};
+use crate::compat::{Compat, CompatShim, ServerVersion};
+use crate::content_hash::{self, ContentHasher};
+use crate::embeddings::EmbeddingProvider;
+use crate::encryption::FieldEncryption;
+use crate::freshness::Freshness;
+use crate::endpoints::{self, EndpointRole, EndpointStatus, Endpoints};
 use crate::error::{ChromaError, Result};
+use crate::export::{self, ExportConfig, ExportReport};
+use crate::http_client::{ClientIdentity, HttpClientFactory};
+use crate::import::{self, ImportConfig, ImportReport};
+use crate::locks::{self, LeaseConfig, LockRegistry};
+use crate::middleware::{Middleware, Next, with_attempt};
 use crate::models::*;
-use reqwest::Client;
+use crate::query::{QueryCursor, QueryOptions, QueryPage};
+use crate::query_cache::{Cached, QueryCache, QueryCachePolicy, QueryKey};
+use crate::quota::{self, Quota, QuotaTracker, QuotaUsage, Usage};
+use crate::sampling;
+use crate::schema::{self, KnownFields, SchemaMode};
+use crate::shards::{self, ShardManifest};
+use crate::similar::{self, Recommendation};
+use crate::singleflight::SingleFlight;
+use crate::scope::ScopedCollection;
+use crate::snapshot::Snapshot;
+use crate::spaces::NamedSpaces;
+use crate::sync_plan::{self, Manifest, SyncPlan};
+use crate::transport::Transport;
+use crate::validation::{PayloadLimits, validate_collection_name};
+use crate::versioning::{self, OutdatedReport, RecordVersion};
+use bytes::Bytes;
+use reqwest::header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::Duration;
 use tracing::{debug, info, warn, error};
 use url::Url;
+use uuid::Uuid;
 
+const DEFAULT_TENANT: &str = "default_tenant";
+const DEFAULT_DATABASE: &str = "default_database";
+/// Sent with every write; Chroma ignores it, but gateways in front of it
+/// can use it to drop duplicate deliveries.
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+/// Ids looked up per request by `exists`.
+const EXISTS_PAGE_SIZE: usize = 1000;
+/// Texts embedded and added per round by `add_texts`.
+const ADD_TEXTS_BATCH_SIZE: usize = 100;
+
+/// Client for one Chroma deployment.
+///
+/// Cloning is cheap: clones share the connection pool, collection cache and
+/// endpoint health behind an `Arc`, so a client can be handed to every
+/// request handler. Configuring a clone with a `with_*` method detaches it
+/// from the others.
+#[derive(Clone)]
 pub struct ChromaClient {
+    inner: Arc<ClientInner>,
+}
+
+#[derive(Clone)]
+struct ClientInner {
     base_url: String,
+    tenant: String,
+    database: String,
     http_client: Client,
     max_retries: u32,
     retry_delay: Duration,
+    field_encryption: Option<FieldEncryption>,
+    payload_limits: PayloadLimits,
+    collection_payload_limits: HashMap<String, PayloadLimits>,
+    schema_mode: SchemaMode,
+    compat: Compat,
+    /// Detected the first time a response needs adapting; `None` if the
+    /// server didn't say.
+    server_version: Arc<OnceLock<Option<ServerVersion>>>,
+    collection_cache: CollectionCache,
+    endpoints: Endpoints,
+    identity: ClientIdentity,
+    middleware: Vec<Arc<dyn Middleware>>,
+    transport: Transport,
+    blob_store: Option<Arc<dyn BlobStore>>,
+    locks: Arc<LockRegistry>,
+    write_lease: Option<LeaseConfig>,
+    coalesce_queries: bool,
+    content_hashes: bool,
+    query_flights: Arc<SingleFlight<QueryKey, QueryResponse>>,
+    query_cache: Option<Arc<QueryCache>>,
+    query_cache_policy: QueryCachePolicy,
+    collection_query_cache_policies: HashMap<String, QueryCachePolicy>,
+    quota: Quota,
+    collection_quotas: HashMap<String, Quota>,
+    quota_tracker: Arc<QuotaTracker>,
 }
 
 impl ChromaClient {
+    /// Create a client for a known-good URL such as a constant.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `base_url` is invalid, and also if the environment holds
+    /// bad configuration such as a malformed `CHROMA_FAILOVER_HOSTS`,
+    /// `CHROMA_READ_REPLICAS` or `CLIENT_APP_ID`.
+    #[deprecated(since = "0.1.0", note = "panics on invalid configuration; use `try_new`")]
     pub fn new(base_url: String) -> Self {
-        // Validate and normalize URL
-        let base_url = Self::validate_url(&base_url)
-            .unwrap_or_else(|_| {
-                warn!("Invalid URL provided, using default: http://localhost:8000");
-                "http://localhost:8000".to_string()
-            });
+        Self::try_new(base_url).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-        let connection_timeout = Duration::from_millis(
-            std::env::var("CONNECTION_TIMEOUT_MS")
-                .unwrap_or_else(|_| "30000".to_string())
-                .parse()
-                .unwrap_or(30000)
-        );
+    /// Create a client, rejecting an invalid `base_url` with a
+    /// `ChromaError::ConfigError` instead of guessing a replacement.
+    pub fn try_new(base_url: String) -> Result<Self> {
+        let base_url = validate_url(&base_url)?;
+        let endpoints = Endpoints::from_env(&base_url)?;
 
-        let request_timeout = Duration::from_millis(
-            std::env::var("REQUEST_TIMEOUT_MS")
-                .unwrap_or_else(|_| "60000".to_string())
-                .parse()
-                .unwrap_or(60000)
-        );
-
-        let http_client = Client::builder()
-            .connect_timeout(connection_timeout)
-            .timeout(request_timeout)
-            .pool_max_idle_per_host(10)
-            .pool_idle_timeout(Duration::from_secs(90))
-            .tcp_keepalive(Duration::from_secs(60))
-            .build()
-            .expect("Failed to create HTTP client");
+        let http_client = HttpClientFactory::shared()?;
 
         let max_retries = std::env::var("MAX_RETRIES")
             .unwrap_or_else(|_| "3".to_string())
@@ -58,25 +136,683 @@ impl ChromaClient {
                 .unwrap_or(1000)
         );
 
+        let tenant = std::env::var("CHROMA_TENANT")
+            .unwrap_or_else(|_| DEFAULT_TENANT.to_string());
+        let database = std::env::var("CHROMA_DATABASE")
+            .unwrap_or_else(|_| DEFAULT_DATABASE.to_string());
+
         info!("ChromaClient initialized with base_url: {}", base_url);
 
-        Self {
-            base_url,
-            http_client,
-            max_retries,
-            retry_delay,
+        let transport = Transport::reqwest(http_client.clone());
+
+        Ok(Self {
+            inner: Arc::new(ClientInner {
+                base_url,
+                tenant,
+                database,
+                http_client,
+                max_retries,
+                retry_delay,
+                field_encryption: None,
+                payload_limits: PayloadLimits::default(),
+                collection_payload_limits: HashMap::new(),
+                schema_mode: SchemaMode::from_env(),
+                compat: Compat::default(),
+                server_version: Arc::default(),
+                collection_cache: CollectionCache::from_env(),
+                endpoints,
+                identity: ClientIdentity::from_env()?,
+                middleware: crate::middleware::from_env(),
+                transport,
+                blob_store: None,
+                locks: Arc::default(),
+                write_lease: None,
+                coalesce_queries: true,
+                content_hashes: false,
+                query_flights: Arc::default(),
+                query_cache: None,
+                query_cache_policy: QueryCachePolicy::new(Duration::ZERO),
+                collection_query_cache_policies: HashMap::new(),
+                quota: Quota::default(),
+                collection_quotas: HashMap::new(),
+                quota_tracker: Arc::default(),
+            }),
+        })
+    }
+
+    /// Settings of this client for a `with_*` builder method, copied first if
+    /// the client has been cloned.
+    fn inner_mut(&mut self) -> &mut ClientInner {
+        Arc::make_mut(&mut self.inner)
+    }
+
+    /// How to handle response fields the models don't know (see `SchemaMode`).
+    pub fn with_schema_mode(mut self, schema_mode: SchemaMode) -> Self {
+        self.inner_mut().schema_mode = schema_mode;
+        self
+    }
+
+    /// Adapt responses from servers in the shim's version range before they
+    /// are decoded, on top of the built-in shims (see `Compat`). Use it when
+    /// a Chroma upgrade renames or wraps a response field before this crate
+    /// has been updated for it.
+    pub fn with_compat_shim(mut self, shim: CompatShim) -> Self {
+        self.inner_mut().compat.push(shim);
+        self
+    }
+
+    /// Assume the server runs `version` instead of asking it, e.g. when the
+    /// version endpoint is blocked by a gateway.
+    pub fn with_server_version(mut self, version: ServerVersion) -> Self {
+        self.inner_mut().server_version = Arc::new(OnceLock::from(Some(version)));
+        self
+    }
+
+    /// Identify the deploying application on every request: adds an
+    /// `X-Client-App` header and appends `app_id` to the `User-Agent`.
+    /// Overrides `CLIENT_APP_ID`.
+    pub fn with_app_id(mut self, app_id: &str) -> Result<Self> {
+        let identity = self.inner.identity.clone().with_app_id(app_id)?;
+        self.inner_mut().identity = identity;
+        Ok(self)
+    }
+
+    /// Run every request through `middleware`, in the order added.
+    pub fn with_middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.inner_mut().middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Use an app-managed `reqwest::Client` (and its connection pool) instead of
+    /// the shared one. Replaces any custom transport.
+    pub fn with_http_client(mut self, http_client: Client) -> Self {
+        self.inner_mut().transport = Transport::reqwest(http_client.clone());
+        self.inner_mut().http_client = http_client;
+        self
+    }
+
+    /// Send requests through `transport` instead of the built-in reqwest client,
+    /// e.g. a tower stack shared with an `EmbeddingClient`.
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.inner_mut().transport = transport;
+        self
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let mut request = request.build()?;
+        self.inner.identity.apply(&mut request);
+        if self.inner.endpoints.len() == 1 {
+            return Next::new(&self.inner.transport, &self.inner.middleware).run(request).await;
+        }
+
+        let write = endpoints::is_write(&request);
+        let candidates = self.inner.endpoints.candidates(&request);
+        let mut pending = Some(request);
+        for (n, &index) in candidates.iter().enumerate() {
+            let Some(current) = pending.take() else { break };
+            // Streaming bodies can't be resent; those get a single endpoint.
+            let is_last = n + 1 == candidates.len();
+            pending = if is_last { None } else { current.try_clone() };
+            let mut attempt = current;
+            *attempt.url_mut() = self.inner.endpoints.rewrite(index, attempt.url());
+
+            let result = Next::new(&self.inner.transport, &self.inner.middleware).run(attempt).await;
+            let reachable = match &result {
+                Ok(response) => !endpoints::is_unavailable(response.status()),
+                Err(e) => !is_connection_failure(e),
+            };
+            if self.inner.endpoints.record(index, write, reachable) {
+                warn!("Chroma endpoint {} is unavailable, failing over", self.inner.endpoints.url(index));
+                // Replicas share the primary's collection ids; failover
+                // endpoints may be independent servers with their own.
+                if self.inner.endpoints.role(index) != EndpointRole::Replica {
+                    self.inner.collection_cache.clear();
+                }
+            }
+            if reachable || pending.is_none() {
+                return result;
+            }
+        }
+        Err(ChromaError::ConfigError("No Chroma endpoints configured".to_string()))
+    }
+
+    /// Add Chroma endpoints to fail over to when the primary (`base_url`) or
+    /// an earlier endpoint is down, in priority order. Extends
+    /// `CHROMA_FAILOVER_HOSTS`.
+    pub fn with_failover_endpoints(mut self, urls: &[&str]) -> Result<Self> {
+        for url in urls {
+            self.inner_mut().endpoints.push(url, EndpointRole::Failover)?;
+        }
+        Ok(self)
+    }
+
+    /// Spread queries and other reads round-robin across read replicas;
+    /// writes keep going to the primary (or its failover endpoints). Extends
+    /// `CHROMA_READ_REPLICAS`. Replicas that fail are dropped from rotation
+    /// for the endpoint cooldown.
+    pub fn with_read_replicas(mut self, urls: &[&str]) -> Result<Self> {
+        for url in urls {
+            self.inner_mut().endpoints.push(url, EndpointRole::Replica)?;
+        }
+        Ok(self)
+    }
+
+    /// How long a failed endpoint is skipped before being tried again;
+    /// overrides `CHROMA_ENDPOINT_COOLDOWN_SECS`.
+    pub fn with_endpoint_cooldown(mut self, cooldown: Duration) -> Self {
+        self.inner_mut().endpoints.set_cooldown(cooldown);
+        self
+    }
+
+    /// Health of every configured endpoint, as last observed.
+    pub fn endpoint_statuses(&self) -> Vec<EndpointStatus> {
+        self.inner.endpoints.statuses()
+    }
+
+    /// Ping every endpoint's heartbeat and update its health, so a recovered
+    /// endpoint rejoins rotation without waiting for its cooldown.
+    pub async fn check_endpoints(&self) -> Vec<EndpointStatus> {
+        for index in 0..self.inner.endpoints.len() {
+            let request = self.inner.http_client.get(format!("{}/api/v2/heartbeat", self.inner.base_url)).build();
+            let reachable = match request {
+                Ok(mut request) => {
+                    self.inner.identity.apply(&mut request);
+                    *request.url_mut() = self.inner.endpoints.rewrite(index, request.url());
+                    match Next::new(&self.inner.transport, &self.inner.middleware).run(request).await {
+                        Ok(response) => response.status().is_success(),
+                        Err(_) => false,
+                    }
+                }
+                Err(_) => false,
+            };
+            self.inner.endpoints.record(index, false, reachable);
+        }
+        self.inner.endpoints.statuses()
+    }
+
+    /// Send writes to the primary again after they failed over. Writes stay
+    /// on the endpoint that last accepted one until it fails, so call this
+    /// once the primary has caught up.
+    pub fn reset_write_endpoint(&self) {
+        self.inner.endpoints.reset_writes();
+    }
+
+    async fn decode_response<T: DeserializeOwned + KnownFields>(
+        &self,
+        response: reqwest::Response,
+    ) -> Result<T> {
+        let mut value: serde_json::Value = response.json().await?;
+        self.adapt::<T>(&mut value).await;
+        schema::decode(value, self.inner.schema_mode)
+    }
+
+    /// Undo known differences between Chroma versions in `value`. Responses
+    /// the models fully understand are left alone, so the server's version
+    /// is only looked up (once per client) when one doesn't.
+    async fn adapt<T: KnownFields>(&self, value: &mut serde_json::Value) {
+        if schema::unknown_fields::<T>(value).is_empty() {
+            return;
+        }
+        let Some(version) = self.server_version().await else {
+            return;
+        };
+        for change in self.inner.compat.adapt::<T>(version, value) {
+            debug!("Chroma {}: {}", version, change);
+        }
+    }
+
+    async fn server_version(&self) -> Option<ServerVersion> {
+        if let Some(version) = self.inner.server_version.get() {
+            return *version;
+        }
+        let version = match self.version().await {
+            Ok(version) => ServerVersion::parse(&version),
+            Err(e) => {
+                debug!("Could not detect the Chroma version: {}", e);
+                None
+            }
+        };
+        *self.inner.server_version.get_or_init(|| version)
+    }
+
+    /// Whether identical queries issued while one is already in flight wait
+    /// for its response instead of sending their own (on by default), so a
+    /// burst of the same query after a cache expiry costs Chroma one
+    /// request. Queries match when they hit the same collection with the
+    /// same body; the shared request is retried once on behalf of all of
+    /// them, and its failure is every caller's failure.
+    pub fn with_query_coalescing(mut self, coalesce: bool) -> Self {
+        self.inner_mut().coalesce_queries = coalesce;
+        self
+    }
+
+    /// Cache up to `capacity` query responses, served according to `policy`
+    /// (see `QueryCachePolicy` for stale-while-revalidate). A response is
+    /// keyed by collection and exact request, and dropped when this client
+    /// writes to the collection; writes by other processes show up once it
+    /// expires. Off by default.
+    pub fn with_query_cache(mut self, capacity: usize, policy: QueryCachePolicy) -> Self {
+        self.inner_mut().query_cache = Some(Arc::new(QueryCache::new(capacity)));
+        self.inner_mut().query_cache_policy = policy;
+        self
+    }
+
+    /// Override the query cache policy for one collection, e.g. a longer
+    /// stale window for a collection that is rebuilt nightly.
+    pub fn with_collection_query_cache_policy(mut self, collection_name: &str, policy: QueryCachePolicy) -> Self {
+        self.inner_mut().collection_query_cache_policies.insert(collection_name.to_string(), policy);
+        self
+    }
+
+    fn query_cache_policy_for(&self, collection_name: &str) -> &QueryCachePolicy {
+        self.inner.collection_query_cache_policies
+            .get(collection_name)
+            .unwrap_or(&self.inner.query_cache_policy)
+    }
+
+    fn invalidate_queries(&self, collection_name: &str) {
+        if let Some(cache) = &self.inner.query_cache {
+            cache.invalidate(collection_name);
+        }
+    }
+
+    /// Limits applied to every collection this client writes to (see
+    /// `Quota`); writes that would exceed them fail with
+    /// `ChromaError::QuotaExceeded` before anything is sent. Unlimited by
+    /// default.
+    pub fn with_quota(mut self, quota: Quota) -> Self {
+        self.inner_mut().quota = quota;
+        self
+    }
+
+    /// Override the quota for one collection, e.g. a tenant on a bigger plan.
+    pub fn with_collection_quota(mut self, collection_name: &str, quota: Quota) -> Self {
+        self.inner_mut().collection_quotas.insert(collection_name.to_string(), quota);
+        self
+    }
+
+    fn quota_for(&self, collection_name: &str) -> &Quota {
+        self.inner.collection_quotas
+            .get(collection_name)
+            .unwrap_or(&self.inner.quota)
+    }
+
+    /// `collection_name`'s quota and estimated usage. The first call for a
+    /// collection counts its documents and samples their sizes in Chroma;
+    /// later ones are answered from what this client has written since.
+    pub async fn quota_usage(&self, collection_name: &str) -> Result<QuotaUsage> {
+        let usage = self.current_usage(collection_name).await?;
+        let quota = self.quota_for(collection_name);
+        Ok(QuotaUsage {
+            collection: collection_name.to_string(),
+            documents: usage.documents,
+            bytes: usage.bytes,
+            max_documents: quota.max_documents,
+            max_bytes: quota.max_bytes,
+        })
+    }
+
+    async fn current_usage(&self, collection_name: &str) -> Result<Usage> {
+        if let Some(usage) = self.inner.quota_tracker.get(collection_name) {
+            return Ok(usage);
+        }
+        let documents = self.count(collection_name).await? as u64;
+        let request = GetRequest {
+            limit: Some(quota::USAGE_SAMPLE),
+            include: Some(vec![Include::Documents, Include::Embeddings]),
+            ..GetRequest::default()
+        };
+        let sample = self.send_get(collection_name, &request).await?;
+        let usage = Usage::estimate(documents, &sample);
+        self.inner.quota_tracker.set(collection_name, usage);
+        Ok(usage)
+    }
+
+    /// The usage `request` adds to `collection_name`, or `QuotaExceeded` if
+    /// that would break its quota. Upserts only count ids not stored yet.
+    async fn check_quota(
+        &self,
+        collection_name: &str,
+        quota: &Quota,
+        request: &AddRequest,
+        operation: &str,
+    ) -> Result<Usage> {
+        let usage = self.current_usage(collection_name).await?;
+        let added = match operation {
+            "upsert" => {
+                let collection_url = self.collection_url(collection_name).await?;
+                let missing = self
+                    .execute_with_retry("check_quota", || self.missing_ids(&collection_url, &request.ids))
+                    .await?;
+                Usage::of_request(&request.select(&missing)?)
+            }
+            // Updates replace existing records: no new documents, and at
+            // most the size of the new versions.
+            "update" => Usage { documents: 0, ..Usage::of_request(request) },
+            _ => Usage::of_request(request),
+        };
+        match quota.violation(usage, added) {
+            Some(reason) => Err(ChromaError::QuotaExceeded(format!(
+                "Collection '{}' would hold {}",
+                collection_name, reason
+            ))),
+            None => Ok(added),
+        }
+    }
+
+    /// Override the `MAX_RETRIES` / `RETRY_DELAY_MS` settings. The delay grows
+    /// linearly with each attempt.
+    pub fn with_retries(mut self, max_retries: u32, retry_delay: Duration) -> Self {
+        self.inner_mut().max_retries = max_retries;
+        self.inner_mut().retry_delay = retry_delay;
+        self
+    }
+
+    /// How long collection lookups are served from memory before being
+    /// revalidated; overrides `COLLECTION_CACHE_TTL_SECS`. `Duration::ZERO`
+    /// disables the cache.
+    pub fn with_collection_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.inner_mut().collection_cache = CollectionCache::new(ttl);
+        self
+    }
+
+    /// Forget the cached lookup for `name`, e.g. after another process
+    /// dropped and recreated it under a new id.
+    pub fn invalidate_collection(&self, name: &str) {
+        self.inner.collection_cache.invalidate(name);
+    }
+
+    /// Forget every cached collection lookup.
+    pub fn invalidate_collections(&self) {
+        self.inner.collection_cache.clear();
+    }
+
+    /// Default limits applied to add requests before they are sent.
+    pub fn with_payload_limits(mut self, limits: PayloadLimits) -> Self {
+        self.inner_mut().payload_limits = limits;
+        self
+    }
+
+    /// Override payload limits (e.g. maximum document length) for one collection.
+    pub fn with_collection_payload_limits(mut self, collection_name: &str, limits: PayloadLimits) -> Self {
+        self.inner_mut().collection_payload_limits.insert(collection_name.to_string(), limits);
+        self
+    }
+
+    fn payload_limits_for(&self, collection_name: &str) -> &PayloadLimits {
+        self.inner.collection_payload_limits
+            .get(collection_name)
+            .unwrap_or(&self.inner.payload_limits)
+    }
+
+    /// Encrypt document text (and configured metadata values) before upload and
+    /// decrypt them transparently on query/get.
+    pub fn with_field_encryption(mut self, field_encryption: FieldEncryption) -> Self {
+        self.inner_mut().field_encryption = Some(field_encryption);
+        self
+    }
+
+    /// Whether document text is stored encrypted (see `with_field_encryption`).
+    pub fn encrypts_documents(&self) -> bool {
+        self.inner.field_encryption.is_some()
+    }
+
+    /// Stamp every add, upsert and update with hashes of the document's
+    /// normalized text (off by default), which `find_by_content`,
+    /// `find_by_content_prefix` and `diff` match on. With field encryption
+    /// the hashes are keyed, see `content_hasher`.
+    pub fn with_content_hashes(mut self, enabled: bool) -> Self {
+        self.inner_mut().content_hashes = enabled;
+        self
+    }
+
+    /// How content hashes are computed for this client: keyed by the field
+    /// encryption key when there is one, so encrypted collections don't
+    /// carry hashes of their plaintext. Fails if content hashes are off.
+    pub fn content_hasher(&self) -> Result<ContentHasher> {
+        if !self.inner.content_hashes {
+            return Err(ChromaError::ConfigError(
+                "Content hashes are off; enable them with with_content_hashes".to_string(),
+            ));
         }
+        Ok(match &self.inner.field_encryption {
+            Some(encryption) => encryption.content_hasher(),
+            None => ContentHasher::Plain,
+        })
+    }
+
+    fn stamp_content_hashes(&self, mut documents: Vec<Document>) -> Vec<Document> {
+        if let Ok(hasher) = self.content_hasher() {
+            documents.iter_mut().for_each(|document| hasher.stamp(document));
+        }
+        documents
+    }
+
+    fn encrypt_request(&self, request: &mut AddRequest) -> Result<()> {
+        match &self.inner.field_encryption {
+            Some(encryption) => encryption.encrypt_request(request),
+            None => Ok(()),
+        }
+    }
+
+    fn decrypt_response(&self, mut response: QueryResponse) -> Result<QueryResponse> {
+        if let Some(encryption) = &self.inner.field_encryption {
+            encryption.decrypt_response(&mut response)?;
+        }
+        Ok(response)
+    }
+
+    /// Keep original files in `blob_store`; see `store_source` and
+    /// `fetch_source`.
+    pub fn with_blob_store(mut self, blob_store: impl BlobStore + 'static) -> Self {
+        self.inner_mut().blob_store = Some(Arc::new(blob_store));
+        self
     }
 
-    fn validate_url(url: &str) -> Result<String> {
-        let parsed = Url::parse(url)
-            .map_err(|e| ChromaError::ApiError(format!("Invalid URL: {}", e)))?;
-        
-        if !matches!(parsed.scheme(), "http" | "https") {
-            return Err(ChromaError::ApiError("URL must use HTTP or HTTPS".to_string()));
+    fn blob_store(&self) -> Result<&dyn BlobStore> {
+        self.inner.blob_store
+            .as_deref()
+            .ok_or_else(|| ChromaError::ConfigError("No blob store configured".to_string()))
+    }
+
+    /// Upload the original artifact for `document` to the blob store, keyed
+    /// by document id, and point the document's `uri` at it. Call before
+    /// adding the document.
+    pub async fn store_source(&self, document: &mut Document, data: impl Into<Bytes>) -> Result<()> {
+        let uri = self.blob_store()?.put(&document.id, data.into()).await?;
+        document.uri = Some(uri);
+        Ok(())
+    }
+
+    /// Fetch the original artifact behind a search result. `None` if the hit
+    /// has no URI, e.g. because the query didn't include `Include::Uris`.
+    pub async fn fetch_source(&self, hit: &QueryHit) -> Result<Option<Bytes>> {
+        match &hit.uri {
+            Some(uri) => self.blob_store()?.get(uri).await.map(Some),
+            None => Ok(None),
         }
-        
-        Ok(url.trim_end_matches('/').to_string())
+    }
+
+    /// Also take a lease record in Chroma in `with_collection_lock`, so
+    /// workers in other processes are serialized too. See `LeaseConfig`.
+    pub fn with_write_leases(mut self, lease: LeaseConfig) -> Self {
+        self.inner_mut().write_lease = Some(lease);
+        self
+    }
+
+    /// Run `f` while holding the write lock on `collection_name`.
+    ///
+    /// Ingestion workers that read, modify and write the same records (e.g.
+    /// update-then-delete) wrap those steps in this to keep from losing each
+    /// other's updates. The lock is advisory and always covers every clone
+    /// of this client; with `with_write_leases` it also covers other
+    /// processes using the same lease convention. The lease is released even
+    /// if `f` fails.
+    ///
+    /// ```no_run
+    /// # async fn example(client: chromadb_demo::ChromaClient) -> chromadb_demo::Result<()> {
+    /// let worker = client.clone();
+    /// client
+    ///     .with_collection_lock("docs", || async move {
+    ///         worker.delete_documents("docs", vec!["old-chunk".to_string()]).await
+    ///     })
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn with_collection_lock<F, Fut, T>(&self, collection_name: &str, f: F) -> Result<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let lock = self.inner.locks.lock_for(collection_name);
+        let _guard = lock.lock().await;
+
+        let Some(lease) = &self.inner.write_lease else {
+            return f().await;
+        };
+        locks::acquire_lease(self, collection_name, lease).await?;
+        let result = f().await;
+        let released = locks::release_lease(self, collection_name, lease).await;
+        let value = result?;
+        released?;
+        Ok(value)
+    }
+
+    /// Handle on `collection_name` that encodes structured metadata with
+    /// registered codecs. See `Collection`.
+    pub fn collection(&self, collection_name: &str) -> Collection<'_> {
+        Collection::new(self, collection_name)
+    }
+
+    /// Handle on `collection_name` that confines every read, write and delete
+    /// to documents owned by `owner_id`.
+    pub fn scoped_to(&self, collection_name: &str, owner_id: &str) -> ScopedCollection<'_> {
+        ScopedCollection::new(self, collection_name, owner_id)
+    }
+
+    /// Per-source indexing timestamps and SLAs for `collection_name`. See
+    /// `Freshness`.
+    pub fn freshness(&self, collection_name: &str) -> Freshness<'_> {
+        Freshness::new(self, collection_name)
+    }
+
+    /// Handle on the sibling collections `{base}__{space}` that hold one
+    /// embedding per named space (title, body, ...) for the same records.
+    pub fn named_spaces(&self, base: &str) -> NamedSpaces<'_> {
+        NamedSpaces::new(self, base)
+    }
+
+    /// Record the current contents of `collection_name` for a consistent
+    /// export while writes continue. See `Snapshot`.
+    pub async fn snapshot(&self, collection_name: &str) -> Result<Snapshot<'_>> {
+        Snapshot::capture(self, collection_name).await
+    }
+
+    /// What to add, update and delete so `collection_name` mirrors
+    /// `manifest`, judged by the `content_hash` metadata written with each
+    /// document (see `with_content_hashes`); only ids and metadata are read. Apply it with
+    /// `Pipeline::apply_plan`.
+    pub async fn diff(&self, manifest: &Manifest, collection_name: &str) -> Result<SyncPlan> {
+        sync_plan::diff(self, manifest, collection_name).await
+    }
+
+    /// Chunks in `collection_name` whose parent is gone from `manifest` or
+    /// now splits into fewer chunks, without deleting them.
+    pub async fn find_orphaned_chunks(&self, manifest: &ChunkManifest, collection_name: &str) -> Result<ChunkGcReport> {
+        chunk_gc::find(self, manifest, collection_name).await
+    }
+
+    /// Delete the chunks `find_orphaned_chunks` reports and return what was
+    /// deleted. `manifest` must cover every parent still in the source (see
+    /// `chunk_gc::chunk_manifest`): chunks of parents missing from it go.
+    pub async fn collect_orphaned_chunks(
+        &self,
+        manifest: &ChunkManifest,
+        collection_name: &str,
+    ) -> Result<ChunkGcReport> {
+        let report = chunk_gc::find(self, manifest, collection_name).await?;
+        chunk_gc::collect(self, &report, collection_name).await?;
+        info!(
+            "Collected {} orphaned and {} stale chunks from {}",
+            report.orphaned.len(), report.stale.len(), collection_name
+        );
+        Ok(report)
+    }
+
+    /// Records of `collection_name` whose `pipeline_version` or
+    /// `chunker_config_hash` stamp differs from `expected`, or that have
+    /// none; only ids and metadata are read.
+    pub async fn find_outdated(&self, collection_name: &str, expected: &RecordVersion) -> Result<OutdatedReport> {
+        versioning::find_outdated(self, collection_name, expected).await
+    }
+
+    /// Scan `collection_name` for outliers worth cleaning up: very short or
+    /// long documents, missing metadata and ids reused across sources. See
+    /// `AuditConfig`.
+    pub async fn audit(&self, collection_name: &str, config: &AuditConfig) -> Result<AuditReport> {
+        audit::audit(self, collection_name, config).await
+    }
+
+    /// Stream every record of `collection_name` to `writer` as JSON Lines
+    /// within a bounded amount of memory. See `ExportConfig`.
+    pub async fn export<W: std::io::Write>(
+        &self,
+        collection_name: &str,
+        writer: W,
+        config: &ExportConfig,
+    ) -> Result<ExportReport> {
+        export::export(self, collection_name, writer, config).await
+    }
+
+    /// Export `collection_name` into `shards` JSON Lines files under `dir`,
+    /// written in parallel, plus a `manifest.json`. Records are sharded by a
+    /// hash of their id, so the same record always lands in the same shard.
+    pub async fn export_sharded(
+        &self,
+        collection_name: &str,
+        dir: &Path,
+        shards: usize,
+        config: &ExportConfig,
+    ) -> Result<ShardManifest> {
+        shards::export_sharded(self, collection_name, dir, shards, config).await
+    }
+
+    /// Upsert the records of an `export` (with embeddings) back into
+    /// `collection_name`.
+    pub async fn import<R: std::io::BufRead + Send>(&self, collection_name: &str, reader: R) -> Result<ImportReport> {
+        import::import(self, collection_name, reader).await
+    }
+
+    /// Import an export file, checkpointing progress next to it so a failed
+    /// run can continue where it stopped (`ImportConfig::with_resume`).
+    pub async fn import_file(&self, collection_name: &str, path: &Path, config: &ImportConfig) -> Result<ImportReport> {
+        import::import_file(self, collection_name, path, config).await
+    }
+
+    /// Import an `export_sharded` directory, several shards at a time, with
+    /// the same checkpointing as `import_file`.
+    pub async fn import_sharded(
+        &self,
+        collection_name: &str,
+        dir: &Path,
+        config: &ImportConfig,
+    ) -> Result<ImportReport> {
+        shards::import_sharded(self, collection_name, dir, config).await
+    }
+
+    /// Collection routes in the v2 API are nested under a tenant and database.
+    fn collections_url(&self) -> String {
+        format!(
+            "{}/api/v2/tenants/{}/databases/{}/collections",
+            self.inner.base_url, self.inner.tenant, self.inner.database
+        )
+    }
+
+    /// Record routes (add/get/query/...) address collections by id, not name.
+    async fn collection_url(&self, collection_name: &str) -> Result<String> {
+        let collection = self.get_collection(collection_name).await?;
+        Ok(format!("{}/{}", self.collections_url(), collection.id))
     }
 
     async fn execute_with_retry<T, F, Fut>(&self, operation_name: &str, mut f: F) -> Result<T>
@@ -86,19 +822,19 @@ impl ChromaClient {
     {
         let mut retries = 0;
         loop {
-            match f().await {
+            match with_attempt(retries, f()).await {
                 Ok(result) => {
                     if retries > 0 {
                         info!("{} succeeded after {} retries", operation_name, retries);
                     }
                     return Ok(result);
                 }
-                Err(e) if retries < self.max_retries && Self::is_retryable_error(&e) => {
+                Err(e) if retries < self.inner.max_retries && Self::is_retryable_error(&e) => {
                     retries += 1;
-                    let delay = self.retry_delay * retries;
+                    let delay = self.inner.retry_delay.saturating_mul(retries);
                     warn!(
                         "{} failed (attempt {}/{}): {}. Retrying in {:?}",
-                        operation_name, retries, self.max_retries + 1, e, delay
+                        operation_name, retries, self.inner.max_retries.saturating_add(1), e, delay
                     );
                     tokio::time::sleep(delay).await;
                 }
@@ -115,6 +851,7 @@ impl ChromaClient {
             ChromaError::RequestError(reqwest_error) => {
                 reqwest_error.is_timeout() || reqwest_error.is_connect()
             }
+            ChromaError::ConnectionError(_) => true,
             ChromaError::ApiError(msg) => {
                 // Retry on 5xx server errors
                 msg.contains("500") || msg.contains("502") || msg.contains("503") || msg.contains("504")
@@ -125,10 +862,9 @@ impl ChromaClient {
 
     pub async fn health_check(&self) -> Result<bool> {
         self.execute_with_retry("health_check", || async {
-            let response = self.http_client
-                .get(&format!("{}/api/v2/heartbeat", self.base_url))
-                .send()
-                .await?;
+            let http_request = self.inner.http_client
+                .get(format!("{}/api/v2/heartbeat", self.inner.base_url));
+            let response = self.send(http_request).await?;
             
             if response.status().is_success() {
                 debug!("ChromaDB health check passed");
@@ -143,18 +879,44 @@ impl ChromaClient {
         }).await
     }
 
+    /// The server's version string, e.g. `"1.0.12"`.
+    pub async fn version(&self) -> Result<String> {
+        let http_request = self.inner.http_client.get(format!("{}/api/v2/version", self.inner.base_url));
+        let response = self.send(http_request).await?;
+
+        if response.status().is_success() {
+            Ok(response.json().await?)
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            Err(ChromaError::ApiError(format!("Version check failed with status {}: {}", status, error_text)))
+        }
+    }
+
+    /// Create a cosine-distance collection.
     pub async fn create_collection(&self, name: &str) -> Result<CollectionResponse> {
-        let response = self.http_client
-            .post(&format!("{}/api/v2/collections", self.base_url))
+        self.create_collection_with_metadata(name, &CollectionMetadata::new(DistanceSpace::Cosine)).await
+    }
+
+    pub async fn create_collection_with_metadata(
+        &self,
+        name: &str,
+        metadata: &CollectionMetadata,
+    ) -> Result<CollectionResponse> {
+        validate_collection_name(name)?;
+        metadata.validate()?;
+
+        let http_request = self.inner.http_client
+            .post(self.collections_url())
             .json(&json!({
                 "name": name,
-                "metadata": {"hnsw:space": "cosine"}
-            }))
-            .send()
-            .await?;
+                "metadata": metadata
+            }));
+        let response = self.send(http_request).await?;
+        self.inner.collection_cache.invalidate(name);
 
         if response.status().is_success() {
-            Ok(response.json().await?)
+            self.decode_response(response).await
         } else {
             Err(ChromaError::CollectionError(
                 format!("Failed to create collection: {}", response.status())
@@ -162,26 +924,110 @@ impl ChromaClient {
         }
     }
 
+    pub async fn list_collections(&self) -> Result<Vec<CollectionResponse>> {
+        let http_request = self.inner.http_client.get(self.collections_url());
+        let response = self.send(http_request).await?;
+
+        if response.status().is_success() {
+            let collections: Vec<serde_json::Value> = response.json().await?;
+            let mut decoded = Vec::with_capacity(collections.len());
+            for mut collection in collections {
+                self.adapt::<CollectionResponse>(&mut collection).await;
+                decoded.push(schema::decode(collection, self.inner.schema_mode)?);
+            }
+            Ok(decoded)
+        } else {
+            Err(ChromaError::CollectionError(
+                format!("Failed to list collections: {}", response.status())
+            ))
+        }
+    }
+
+    /// Look up a collection by name. Results are cached (see
+    /// `with_collection_cache_ttl`).
     pub async fn get_collection(&self, name: &str) -> Result<CollectionResponse> {
-        let response = self.http_client
-            .get(&format!("{}/api/v2/collections/{}", self.base_url, name))
-            .send()
-            .await?;
+        let mut http_request = self.inner.http_client
+            .get(format!("{}/{}", self.collections_url(), name));
+        match self.inner.collection_cache.lookup(name) {
+            Lookup::Fresh(collection) => return Ok(collection),
+            Lookup::Revalidate(etag) => http_request = http_request.header(IF_NONE_MATCH, etag),
+            Lookup::Fetch => {}
+        }
+        let response = self.send(http_request).await?;
 
+        if response.status() == StatusCode::NOT_MODIFIED
+            && let Some(collection) = self.inner.collection_cache.touch(name)
+        {
+            return Ok(collection);
+        }
         if response.status().is_success() {
-            Ok(response.json().await?)
+            let etag = response
+                .headers()
+                .get(ETAG)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            let collection: CollectionResponse = self.decode_response(response).await?;
+            self.inner.collection_cache.store(name, &collection, etag);
+            Ok(collection)
         } else {
+            self.inner.collection_cache.invalidate(name);
             Err(ChromaError::CollectionError(
                 format!("Collection not found: {}", name)
             ))
         }
     }
 
+    /// Rename a collection and/or replace its metadata. Index (`hnsw:*`)
+    /// settings can't be changed after creation.
+    pub async fn modify_collection(
+        &self,
+        name: &str,
+        new_name: Option<&str>,
+        new_metadata: Option<&CollectionMetadata>,
+    ) -> Result<()> {
+        if let Some(new_name) = new_name {
+            validate_collection_name(new_name)?;
+        }
+        if let Some(metadata) = new_metadata {
+            metadata.validate_update()?;
+        }
+
+        let collection_url = self.collection_url(name).await?;
+        let mut request = serde_json::Map::new();
+        if let Some(new_name) = new_name {
+            request.insert("new_name".to_string(), json!(new_name));
+        }
+        if let Some(metadata) = new_metadata {
+            request.insert("new_metadata".to_string(), serde_json::to_value(metadata)?);
+        }
+
+        let http_request = self.inner.http_client.put(collection_url).json(&request);
+        let response = self.send(http_request).await?;
+        self.inner.collection_cache.invalidate(name);
+        self.invalidate_queries(name);
+        self.inner.quota_tracker.forget(name);
+        if let Some(new_name) = new_name {
+            self.inner.collection_cache.invalidate(new_name);
+        }
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            Err(ChromaError::CollectionError(
+                format!("Failed to modify collection {}: {} {}", name, status, error_text)
+            ))
+        }
+    }
+
     pub async fn delete_collection(&self, name: &str) -> Result<()> {
-        let response = self.http_client
-            .delete(&format!("{}/api/v2/collections/{}", self.base_url, name))
-            .send()
-            .await?;
+        let http_request = self.inner.http_client
+            .delete(format!("{}/{}", self.collections_url(), name));
+        let response = self.send(http_request).await?;
+        self.inner.collection_cache.invalidate(name);
+        self.invalidate_queries(name);
+        self.inner.quota_tracker.forget(name);
 
         if response.status().is_success() {
             Ok(())
@@ -198,33 +1044,262 @@ impl ChromaClient {
         documents: Vec<Document>,
         embeddings: Vec<Vec<f32>>,
     ) -> Result<()> {
-        let ids: Vec<String> = documents.iter().map(|d| d.id.clone()).collect();
-        let docs: Vec<String> = documents.iter().map(|d| d.content.clone()).collect();
-        let metadatas: Vec<HashMap<String, String>> = 
-            documents.iter().map(|d| d.metadata.clone()).collect();
-
-        let request = AddRequest {
-            ids,
-            embeddings,
-            metadatas,
-            documents: docs,
+        self.write_documents(collection_name, documents, embeddings, "add").await
+    }
+
+    /// Add documents, replacing any that already exist with the same id.
+    pub async fn upsert_documents(
+        &self,
+        collection_name: &str,
+        documents: Vec<Document>,
+        embeddings: Vec<Vec<f32>>,
+    ) -> Result<()> {
+        self.write_documents(collection_name, documents, embeddings, "upsert").await
+    }
+
+    /// Embed `texts` with `provider` and add them, `ADD_TEXTS_BATCH_SIZE` at
+    /// a time, each batch written before the next is embedded. `metadatas`,
+    /// when given, pairs up with `texts`. Every text gets a random id; the
+    /// ids are returned in input order. Fails with
+    /// `ChromaError::ModelMismatch` before embedding anything if the
+    /// collection is bound to another model.
+    pub async fn add_texts(
+        &self,
+        collection_name: &str,
+        texts: &[&str],
+        metadatas: Option<Vec<HashMap<String, String>>>,
+        provider: &dyn EmbeddingProvider,
+    ) -> Result<Vec<String>> {
+        if let Some(metadatas) = &metadatas
+            && metadatas.len() != texts.len()
+        {
+            return Err(ChromaError::ValidationError(format!(
+                "Got {} metadatas for {} texts",
+                metadatas.len(),
+                texts.len()
+            )));
+        }
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let collection = self.get_collection(collection_name).await?;
+        if let Some(binding) = collection.metadata.as_ref().and_then(CollectionMetadata::binding)
+            && !binding.matches(provider)
+        {
+            return Err(ChromaError::ModelMismatch {
+                collection: collection_name.to_string(),
+                expected: binding.to_string(),
+                actual: EmbeddingBinding::of(provider).to_string(),
+            });
+        }
+
+        let mut metadatas = metadatas.map(Vec::into_iter);
+        let mut ids = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(ADD_TEXTS_BATCH_SIZE) {
+            let embeddings = provider.embed(batch).await?;
+            if embeddings.len() != batch.len() {
+                return Err(ChromaError::EmbeddingError(format!(
+                    "{} returned {} embeddings for {} texts",
+                    provider.model_name(),
+                    embeddings.len(),
+                    batch.len()
+                )));
+            }
+            let documents: Vec<Document> = batch
+                .iter()
+                .map(|text| {
+                    let mut document = Document::from(*text);
+                    if let Some(metadata) = metadatas.as_mut().and_then(Iterator::next) {
+                        document.metadata = metadata;
+                    }
+                    document
+                })
+                .collect();
+            ids.extend(documents.iter().map(|d| d.id.clone()));
+            self.add_documents(collection_name, documents, embeddings).await?;
+        }
+        info!("Embedded and added {} texts to {}", ids.len(), collection_name);
+        Ok(ids)
+    }
+
+    async fn write_documents(
+        &self,
+        collection_name: &str,
+        documents: Vec<Document>,
+        embeddings: Vec<Vec<f32>>,
+        operation: &str,
+    ) -> Result<()> {
+        let mut request = AddRequest::from_documents(&self.stamp_content_hashes(documents), embeddings);
+        // Document lengths are checked as written; batches are sized by what
+        // is actually sent, ciphertext included.
+        let limits = self.payload_limits_for(collection_name);
+        limits.validate(&request)?;
+        self.encrypt_request(&mut request)?;
+        let batches = limits.plan_batches(&request)?;
+        if batches.len() > 1 {
+            debug!("Splitting {} of {} documents into {} batches", operation, request.ids.len(), batches.len());
+        }
+
+        let quota = *self.quota_for(collection_name);
+        let added = if quota.is_unlimited() {
+            None
+        } else {
+            Some(self.check_quota(collection_name, &quota, &request, operation).await?)
         };
 
-        let response = self.http_client
-            .post(&format!(
-                "{}/api/v2/collections/{}/add",
-                self.base_url, collection_name
-            ))
-            .json(&request)
-            .send()
-            .await?;
+        let collection_url = self.collection_url(collection_name).await?;
+        let mut written = Ok(());
+        for range in batches {
+            written = self.send_write_batch(&collection_url, operation, &request.slice(range)).await;
+            if written.is_err() {
+                break;
+            }
+        }
+        // Earlier batches may have landed even if a later one failed.
+        self.invalidate_queries(collection_name);
+        match (&written, added) {
+            // The size of the records an update replaced isn't known, so its
+            // usage is re-read instead.
+            (Ok(()), Some(added)) if operation != "update" => self.inner.quota_tracker.add(collection_name, added),
+            (_, Some(_)) => self.inner.quota_tracker.forget(collection_name),
+            _ => {}
+        }
+
+        written
+    }
+
+    /// Send one write batch with retries. Every attempt carries the same
+    /// idempotency key, and before an `add` is re-sent its ids are looked up,
+    /// so a batch that was applied but whose response got lost is not added
+    /// a second time. Upserts are naturally idempotent and are simply re-sent.
+    async fn send_write_batch(
+        &self,
+        collection_url: &str,
+        operation: &str,
+        batch: &AddRequest,
+    ) -> Result<()> {
+        let idempotency_key = Uuid::new_v4().to_string();
+        let attempts = AtomicU32::new(0);
+
+        self.execute_with_retry(operation, || async {
+            let is_retry = attempts.fetch_add(1, Ordering::SeqCst) > 0;
+            if !is_retry || operation != "add" {
+                return self.send_write_request(collection_url, operation, batch, &idempotency_key).await;
+            }
+
+            let missing = self.missing_ids(collection_url, &batch.ids).await?;
+            if missing.is_empty() {
+                info!("{} batch {} was already applied, not resending", operation, idempotency_key);
+                return Ok(());
+            }
+            if missing.len() < batch.ids.len() {
+                debug!("Resending {} of {} ids in batch {}", missing.len(), batch.ids.len(), idempotency_key);
+            }
+            self.send_write_request(collection_url, operation, &batch.select(&missing)?, &idempotency_key).await
+        }).await
+    }
+
+    /// "More like this": the `k` documents nearest to the stored embedding
+    /// of `id`, without `id` itself. Nothing is re-embedded, so related
+    /// content can be recommended from ids alone.
+    pub async fn similar_to(&self, collection_name: &str, id: &str, k: u32) -> Result<Vec<QueryHit>> {
+        self.recommend(collection_name, &Recommendation::new([id]), &QueryOptions::new(k)).await
+    }
+
+    /// Documents like `recommendation`'s positive examples and unlike its
+    /// negative ones, found with a query vector combined client-side from
+    /// their stored embeddings (see `Recommendation`). `options` applies as
+    /// for `query_with_options`; the examples are always left out.
+    pub async fn recommend(
+        &self,
+        collection_name: &str,
+        recommendation: &Recommendation,
+        options: &QueryOptions,
+    ) -> Result<Vec<QueryHit>> {
+        similar::recommend(self, collection_name, recommendation, options).await
+    }
+
+    /// Up to `n` documents matching `where_filter`, picked uniformly at
+    /// random (reservoir sampling over paged gets), for spot-checking a
+    /// corpus or drawing an eval set without exporting it. Returned like
+    /// `get_documents`, in random order.
+    pub async fn sample(
+        &self,
+        collection_name: &str,
+        n: usize,
+        where_filter: Option<serde_json::Value>,
+    ) -> Result<QueryResponse> {
+        sampling::sample(self, collection_name, n, where_filter, None).await
+    }
+
+    /// `sample` with a fixed seed, so an eval set can be drawn again from an
+    /// unchanged collection.
+    pub async fn sample_with_seed(
+        &self,
+        collection_name: &str,
+        n: usize,
+        where_filter: Option<serde_json::Value>,
+        seed: u64,
+    ) -> Result<QueryResponse> {
+        sampling::sample(self, collection_name, n, where_filter, Some(seed)).await
+    }
+
+    /// Whether each of `ids` is stored in the collection, in the same order,
+    /// so an ingestion job can split a batch into adds and updates instead
+    /// of upserting blindly. Ids are looked up a thousand per request, with
+    /// nothing but the ids fetched.
+    pub async fn exists(&self, collection_name: &str, ids: &[String]) -> Result<Vec<bool>> {
+        let mut stored = HashSet::new();
+        for page in ids.chunks(EXISTS_PAGE_SIZE) {
+            let request = GetRequest {
+                ids: Some(page.to_vec()),
+                include: Some(Vec::new()),
+                ..GetRequest::default()
+            };
+            let response = self.send_get(collection_name, &request).await?;
+            stored.extend(response.ids_for(0).iter().cloned());
+        }
+        Ok(ids.iter().map(|id| stored.contains(id)).collect())
+    }
+
+    /// The subset of `ids` not stored in the collection. Called from inside
+    /// `send_write_batch`'s retry loop, so each lookup is sent once rather
+    /// than retried on its own.
+    async fn missing_ids(&self, collection_url: &str, ids: &[String]) -> Result<HashSet<String>> {
+        let mut missing: HashSet<String> = ids.iter().cloned().collect();
+        for page in ids.chunks(EXISTS_PAGE_SIZE) {
+            let request = GetRequest {
+                ids: Some(page.to_vec()),
+                include: Some(Vec::new()),
+                ..GetRequest::default()
+            };
+            for id in self.send_get_once(collection_url, &request).await?.ids_for(0) {
+                missing.remove(id);
+            }
+        }
+        Ok(missing)
+    }
+
+    async fn send_write_request(
+        &self,
+        collection_url: &str,
+        operation: &str,
+        request: &AddRequest,
+        idempotency_key: &str,
+    ) -> Result<()> {
+        let http_request = self.inner.http_client
+            .post(format!("{}/{}", collection_url, operation))
+            .header(IDEMPOTENCY_KEY_HEADER, idempotency_key)
+            .json(request);
+        let response = self.send(http_request).await?;
 
         if response.status().is_success() {
             Ok(())
         } else {
+            let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
             Err(ChromaError::ApiError(
-                format!("Failed to add documents: {}", error_text)
+                format!("Failed to {} documents with status {}: {}", operation, status, error_text)
             ))
         }
     }
@@ -245,26 +1320,86 @@ impl ChromaClient {
         n_results: u32,
         where_filter: Option<serde_json::Value>,
     ) -> Result<QueryResponse> {
-        self.execute_with_retry("query", || async {
-            let request = QueryRequest {
-                query_embeddings: query_embeddings.clone(),
-                n_results,
-                where_filter: where_filter.clone(),
-            };
+        let request = QueryRequest {
+            query_embeddings,
+            n_results,
+            where_filter,
+            include: None,
+        };
+        self.send_query(collection_name, &request).await
+    }
 
-            let response = self.http_client
-                .post(&format!(
-                    "{}/api/v2/collections/{}/query",
-                    self.base_url, collection_name
-                ))
-                .json(&request)
-                .send()
-                .await?;
+    /// Send a fully specified query, e.g. one with a custom `include` set.
+    pub async fn send_query(&self, collection_name: &str, request: &QueryRequest) -> Result<QueryResponse> {
+        let collection = self.get_collection(collection_name).await?;
+        for embedding in &request.query_embeddings {
+            binding::check_query_dimension(&collection, embedding.len(), || format!("{}-dimensional", embedding.len()))?;
+        }
+        let query_url = format!("{}/{}/query", self.collections_url(), collection.id);
+        let key = (query_url, Bytes::from(serde_json::to_vec(request)?));
+
+        let Some(cache) = &self.inner.query_cache else {
+            let response = self.fetch_query(key).await?;
+            return self.decrypt_response(response);
+        };
+        match cache.lookup(&key, self.query_cache_policy_for(collection_name)) {
+            Cached::Fresh(response) => return self.decrypt_response(response),
+            Cached::Stale { response, refresh } => {
+                if refresh {
+                    self.refresh_query(cache.clone(), collection_name, key);
+                }
+                return self.decrypt_response(response);
+            }
+            Cached::Miss => {}
+        }
+        let generation = cache.generation(collection_name);
+        let response = self.fetch_query(key.clone()).await?;
+        cache.store(collection_name, generation, key, response.clone());
+
+        self.decrypt_response(response)
+    }
+
+    /// Send a query, sharing the request with identical ones in flight if
+    /// coalescing is on.
+    async fn fetch_query(&self, (query_url, body): QueryKey) -> Result<QueryResponse> {
+        if !self.inner.coalesce_queries {
+            return self.post_query(&query_url, body).await;
+        }
+        let client = self.clone();
+        let key = (query_url.clone(), body.clone());
+        self.inner
+            .query_flights
+            .run(key, move || async move { client.post_query(&query_url, body).await })
+            .await
+    }
+
+    /// Re-fetch a stale cached response without making the caller wait.
+    fn refresh_query(&self, cache: Arc<QueryCache>, collection_name: &str, key: QueryKey) {
+        let client = self.clone();
+        let collection_name = collection_name.to_string();
+        let generation = cache.generation(&collection_name);
+        tokio::spawn(async move {
+            match client.fetch_query(key.clone()).await {
+                Ok(response) => cache.store(&collection_name, generation, key, response),
+                Err(e) => {
+                    warn!("Background refresh of a cached query on {} failed: {}", collection_name, e);
+                    cache.refresh_failed(&key);
+                }
+            }
+        });
+    }
+
+    async fn post_query(&self, query_url: &str, body: Bytes) -> Result<QueryResponse> {
+        self.execute_with_retry("query", || async {
+            let http_request = self.inner.http_client
+                .post(query_url)
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.clone());
+            let response = self.send(http_request).await?;
 
             if response.status().is_success() {
-                let query_response: QueryResponse = response.json().await?;
-                debug!("Query returned {} results", 
-                    query_response.ids.get(0).map(|ids| ids.len()).unwrap_or(0));
+                let query_response: QueryResponse = self.decode_response(response).await?;
+                debug!("Query returned {} results", query_response.ids_for(0).len());
                 Ok(query_response)
             } else {
                 let status = response.status();
@@ -276,6 +1411,37 @@ impl ChromaClient {
         }).await
     }
 
+    /// Query a single embedding and return flattened hits, re-ranked client-side
+    /// according to `options` (e.g. a recency boost).
+    pub async fn query_with_options(
+        &self,
+        collection_name: &str,
+        query_embedding: Vec<f32>,
+        options: &QueryOptions,
+    ) -> Result<Vec<QueryHit>> {
+        let response = self.send_query(collection_name, &options.to_request(query_embedding)).await?;
+
+        Ok(options.rerank(response.into_hits()))
+    }
+
+    /// Fetch one page of `options.n_results` hits. Pass the returned
+    /// `next_cursor` back in to continue; `None` starts from the first page.
+    pub async fn query_paginated(
+        &self,
+        collection_name: &str,
+        query_embedding: Vec<f32>,
+        options: &QueryOptions,
+        cursor: Option<&QueryCursor>,
+    ) -> Result<QueryPage> {
+        let cursor = cursor.cloned().unwrap_or_default();
+
+        let mut window = options.clone();
+        window.n_results = cursor.window(options.n_results);
+
+        let hits = self.query_with_options(collection_name, query_embedding, &window).await?;
+        Ok(cursor.next_page(hits, options.n_results))
+    }
+
     pub async fn get_documents(
         &self,
         collection_name: &str,
@@ -283,40 +1449,38 @@ impl ChromaClient {
         where_filter: Option<serde_json::Value>,
         limit: Option<u32>,
     ) -> Result<QueryResponse> {
-        self.execute_with_retry("get_documents", || async {
-            let mut request = json!({});
-            
-            if let Some(ids) = &ids {
-                request["ids"] = json!(ids);
-            }
-            
-            if let Some(filter) = &where_filter {
-                request["where"] = filter.clone();
-            }
-            
-            if let Some(limit) = limit {
-                request["limit"] = json!(limit);
-            }
+        let request = GetRequest { ids, where_filter, limit, ..GetRequest::default() };
+        self.send_get(collection_name, &request).await
+    }
 
-            let response = self.http_client
-                .post(&format!(
-                    "{}/api/v2/collections/{}/get",
-                    self.base_url, collection_name
-                ))
-                .json(&request)
-                .send()
-                .await?;
+    /// Send a fully specified get, e.g. one page of a collection scan.
+    pub async fn send_get(&self, collection_name: &str, request: &GetRequest) -> Result<QueryResponse> {
+        let collection_url = self.collection_url(collection_name).await?;
+        let response = self
+            .execute_with_retry("get_documents", || self.send_get_once(&collection_url, request))
+            .await?;
 
-            if response.status().is_success() {
-                Ok(response.json().await?)
-            } else {
-                let status = response.status();
-                let error_text = response.text().await.unwrap_or_default();
-                Err(ChromaError::ApiError(
-                    format!("Get documents failed with status {}: {}", status, error_text)
-                ))
-            }
-        }).await
+        self.decrypt_response(response)
+    }
+
+    /// One `/get` request, without retries or decryption.
+    async fn send_get_once(&self, collection_url: &str, request: &GetRequest) -> Result<QueryResponse> {
+        let http_request = self.inner.http_client
+            .post(format!("{}/get", collection_url))
+            .json(request);
+
+        let response = self.send(http_request).await?;
+
+        if response.status().is_success() {
+            let get_response: GetResponse = self.decode_response(response).await?;
+            Ok(QueryResponse::from(get_response))
+        } else {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            Err(ChromaError::ApiError(
+                format!("Get documents failed with status {}: {}", status, error_text)
+            ))
+        }
     }
 
     pub async fn update_documents(
@@ -325,39 +1489,7 @@ impl ChromaClient {
         documents: Vec<Document>,
         embeddings: Vec<Vec<f32>>,
     ) -> Result<()> {
-        self.execute_with_retry("update_documents", || async {
-            let ids: Vec<String> = documents.iter().map(|d| d.id.clone()).collect();
-            let docs: Vec<String> = documents.iter().map(|d| d.content.clone()).collect();
-            let metadatas: Vec<HashMap<String, String>> = 
-                documents.iter().map(|d| d.metadata.clone()).collect();
-
-            let request = json!({
-                "ids": ids,
-                "embeddings": embeddings,
-                "metadatas": metadatas,
-                "documents": docs,
-            });
-
-            let response = self.http_client
-                .post(&format!(
-                    "{}/api/v2/collections/{}/update",
-                    self.base_url, collection_name
-                ))
-                .json(&request)
-                .send()
-                .await?;
-
-            if response.status().is_success() {
-                info!("Successfully updated {} documents", documents.len());
-                Ok(())
-            } else {
-                let status = response.status();
-                let error_text = response.text().await.unwrap_or_default();
-                Err(ChromaError::ApiError(
-                    format!("Update documents failed with status {}: {}", status, error_text)
-                ))
-            }
-        }).await
+        self.write_documents(collection_name, documents, embeddings, "update").await
     }
 
     pub async fn delete_documents(
@@ -365,14 +1497,34 @@ impl ChromaClient {
         collection_name: &str,
         ids: Vec<String>,
     ) -> Result<()> {
-        let response = self.http_client
-            .post(&format!(
-                "{}/api/v2/collections/{}/delete",
-                self.base_url, collection_name
-            ))
-            .json(&json!({ "ids": ids }))
-            .send()
-            .await?;
+        self.delete_documents_with_filter(collection_name, Some(ids), None).await
+    }
+
+    /// Delete documents matching `ids` and/or `where_filter`. When both are given
+    /// only ids that also match the filter are deleted.
+    pub async fn delete_documents_with_filter(
+        &self,
+        collection_name: &str,
+        ids: Option<Vec<String>>,
+        where_filter: Option<serde_json::Value>,
+    ) -> Result<()> {
+        let mut request = serde_json::Map::new();
+
+        if let Some(ids) = ids {
+            request.insert("ids".to_string(), json!(ids));
+        }
+
+        if let Some(filter) = where_filter {
+            request.insert("where".to_string(), filter);
+        }
+
+        let collection_url = self.collection_url(collection_name).await?;
+        let http_request = self.inner.http_client
+            .post(format!("{}/delete", collection_url))
+            .json(&request);
+        let response = self.send(http_request).await?;
+        self.invalidate_queries(collection_name);
+        self.inner.quota_tracker.forget(collection_name);
 
         if response.status().is_success() {
             Ok(())
@@ -383,14 +1535,51 @@ impl ChromaClient {
         }
     }
 
+    /// Ids of the documents whose normalized content (see
+    /// `content_hash::normalize_text`) equals `text`, e.g. to check whether
+    /// an article has been indexed already. Matches on the `content_hash`
+    /// metadata written with content hashes on (see `with_content_hashes`),
+    /// so documents written without them aren't found.
+    pub async fn find_by_content(&self, collection_name: &str, text: &str) -> Result<Vec<String>> {
+        let hash = self.content_hasher()?.hash(&content_hash::normalize_text(text));
+        let request = GetRequest {
+            where_filter: Some(json!({ content_hash::CONTENT_HASH_FIELD: { "$eq": hash } })),
+            include: Some(Vec::new()),
+            ..GetRequest::default()
+        };
+        Ok(self.send_get(collection_name, &request).await?.ids_for(0).to_vec())
+    }
+
+    /// Ids of the documents whose normalized content starts with `text`,
+    /// which must be at least `content_hash::PREFIX_LENGTHS[0]` characters
+    /// long once normalized. Candidates are found by prefix hash and then
+    /// checked against their full text.
+    pub async fn find_by_content_prefix(&self, collection_name: &str, text: &str) -> Result<Vec<String>> {
+        let normalized = content_hash::normalize_text(text);
+        let request = GetRequest {
+            where_filter: Some(self.content_hasher()?.prefix_filter(&normalized)?),
+            include: Some(vec![Include::Documents]),
+            ..GetRequest::default()
+        };
+        let response = self.send_get(collection_name, &request).await?;
+        Ok(response
+            .ids_for(0)
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| {
+                response
+                    .document(0, *i)
+                    .is_some_and(|document| content_hash::normalize_text(document).starts_with(&normalized))
+            })
+            .map(|(_, id)| id.clone())
+            .collect())
+    }
+
     pub async fn count(&self, collection_name: &str) -> Result<usize> {
-        let response = self.http_client
-            .get(&format!(
-                "{}/api/v2/collections/{}/count",
-                self.base_url, collection_name
-            ))
-            .send()
-            .await?;
+        let collection_url = self.collection_url(collection_name).await?;
+        let http_request = self.inner.http_client
+            .get(format!("{}/count", collection_url));
+        let response = self.send(http_request).await?;
 
         if response.status().is_success() {
             Ok(response.json().await?)
@@ -401,3 +1590,265 @@ impl ChromaClient {
         }
     }
 }
+
+/// The endpoint couldn't be reached at all, as opposed to rejecting the
+/// request.
+fn is_connection_failure(error: &ChromaError) -> bool {
+    match error {
+        ChromaError::RequestError(e) => e.is_connect() || e.is_timeout(),
+        ChromaError::ConnectionError(_) => true,
+        _ => false,
+    }
+}
+
+/// Check that `url` is an absolute HTTP(S) URL and strip any trailing slash.
+pub(crate) fn validate_url(url: &str) -> Result<String> {
+    let parsed = Url::parse(url)
+        .map_err(|e| ChromaError::ConfigError(format!("Invalid URL '{}': {}", url, e)))?;
+
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(ChromaError::ConfigError(format!("URL '{}' must use HTTP or HTTPS", url)));
+    }
+
+    Ok(url.trim_end_matches('/').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::EmbeddingClient;
+    use crate::test_support::{FixedEmbeddings, mock_chroma};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_chroma_client_creation() {
+        let client = ChromaClient::try_new("http://localhost:8000/".to_string()).unwrap();
+        assert_eq!(client.inner.base_url, "http://localhost:8000");
+    }
+
+    #[tokio::test]
+    async fn test_exists_pages_id_lookups() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let gets = Arc::new(AtomicUsize::new(0));
+        let counter = gets.clone();
+        let chroma = mock_chroma(move |request| {
+            if request.uri().path().ends_with("/get") {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                assert_eq!(body["include"], serde_json::json!([]));
+                // Even-numbered ids are stored.
+                let stored: Vec<&serde_json::Value> = body["ids"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .filter(|id| id.as_str().unwrap()[3..].parse::<u32>().unwrap() % 2 == 0)
+                    .collect();
+                serde_json::json!({ "ids": stored }).to_string()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+
+        let ids: Vec<String> = (0..2500).map(|i| format!("doc{}", i)).collect();
+        let exists = chroma.exists("docs", &ids).await.unwrap();
+        assert_eq!(exists.len(), 2500);
+        assert!(exists.iter().enumerate().all(|(i, exists)| *exists == (i % 2 == 0)));
+        assert_eq!(gets.load(Ordering::SeqCst), 3);
+        assert!(chroma.exists("docs", &[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_routes_address_collections_by_tenant_database_and_id() {
+        let paths = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = paths.clone();
+        let chroma = mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            seen.lock().unwrap().push(format!("{} {}", request.method(), path));
+            if path.ends_with("/get") {
+                // v2 get responses are flat, not one row per query.
+                r#"{"ids": ["a"], "documents": ["doc a"]}"#
+            } else if path.ends_with("/query") {
+                r#"{"ids": [["a"]], "distances": [[0.1]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+
+        chroma.query("docs", vec![vec![0.1]], 1).await.unwrap();
+        let request = GetRequest { ids: Some(vec!["a".to_string()]), ..GetRequest::default() };
+        let got = chroma.send_get("docs", &request).await.unwrap();
+        assert_eq!(got.ids, vec![vec!["a".to_string()]]);
+        assert_eq!(got.documents, Some(vec![vec![Some("doc a".to_string())]]));
+
+        let prefix = format!("/api/v2/tenants/{}/databases/{}/collections", chroma.inner.tenant, chroma.inner.database);
+        assert_eq!(
+            *paths.lock().unwrap(),
+            vec![
+                format!("GET {}/docs", prefix),
+                format!("POST {}/c0ffee/query", prefix),
+                format!("POST {}/c0ffee/get", prefix),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_try_new_rejects_invalid_configuration() {
+        assert!(matches!(
+            ChromaClient::try_new("localhost:8000".to_string()),
+            Err(ChromaError::ConfigError(_))
+        ));
+        assert!(matches!(
+            ChromaClient::try_new("ftp://chroma.internal".to_string()),
+            Err(ChromaError::ConfigError(_))
+        ));
+        assert!(ChromaClient::try_new("https://chroma.internal/".to_string()).is_ok());
+
+        assert!(matches!(
+            EmbeddingClient::try_new("  ".to_string()),
+            Err(ChromaError::ConfigError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_add_texts_embeds_and_adds_in_batches() {
+        use std::sync::Mutex;
+
+        let adds = Arc::new(Mutex::new(Vec::new()));
+        let recorded = adds.clone();
+        let chroma = mock_chroma(move |request| {
+            if request.uri().path().ends_with("/add") {
+                recorded.lock().unwrap().push(serde_json::from_slice::<serde_json::Value>(request.body()).unwrap());
+                "true"
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+
+        let texts: Vec<String> = (0..150).map(|i| "x".repeat(i + 1)).collect();
+        let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let metadatas = (0..150).map(|i| HashMap::from([("n".to_string(), i.to_string())])).collect();
+        let ids = chroma.add_texts("docs", &texts, Some(metadatas), &FixedEmbeddings).await.unwrap();
+
+        {
+            let adds = adds.lock().unwrap();
+            assert_eq!(adds.iter().map(|add| add["ids"].as_array().unwrap().len()).collect::<Vec<_>>(), vec![100, 50]);
+            assert_eq!(adds[1]["ids"][49], ids[149].as_str());
+            assert_eq!(adds[1]["embeddings"][49], serde_json::json!([150.0]));
+            assert_eq!(adds[1]["metadatas"][49]["n"], "149");
+        }
+
+        let error = chroma.add_texts("docs", &texts, Some(Vec::new()), &FixedEmbeddings).await.unwrap_err();
+        assert!(matches!(error, ChromaError::ValidationError(_)), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_add_retry_skips_batch_that_was_applied() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use crate::transport::{BoxError, HttpResponse};
+
+        let adds = Arc::new(AtomicUsize::new(0));
+        let add_count = adds.clone();
+        let client = mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            let result: std::result::Result<HttpResponse, BoxError> = if path.ends_with("/add") {
+                assert!(request.headers().contains_key("idempotency-key"));
+                add_count.fetch_add(1, Ordering::SeqCst);
+                // Chroma stored the batch but the response never arrived.
+                Err("connection reset by peer".into())
+            } else if path.ends_with("/get") {
+                Ok(HttpResponse::new(r#"{"ids": ["a", "b"]}"#.into()))
+            } else {
+                Ok(HttpResponse::new(r#"{"id": "c0ffee", "name": "docs"}"#.into()))
+            };
+            result
+        })
+        .with_retries(2, std::time::Duration::from_millis(1));
+
+        let documents = ["a", "b"]
+            .iter()
+            .map(|id| Document { id: id.to_string(), content: "text".to_string(), metadata: HashMap::new(), uri: None })
+            .collect();
+        client.add_documents("docs", documents, vec![vec![0.1], vec![0.2]]).await.unwrap();
+        assert_eq!(adds.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_add_retry_lookups_share_the_batch_retries() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use crate::transport::{BoxError, HttpResponse};
+
+        let gets = Arc::new(AtomicUsize::new(0));
+        let get_count = gets.clone();
+        let client = mock_chroma(move |request| -> std::result::Result<HttpResponse, BoxError> {
+            let path = request.uri().path();
+            if path.ends_with("/get") {
+                get_count.fetch_add(1, Ordering::SeqCst);
+            }
+            if path.ends_with("/add") || path.ends_with("/get") {
+                Err("connection reset by peer".into())
+            } else {
+                Ok(HttpResponse::new(r#"{"id": "c0ffee", "name": "docs"}"#.into()))
+            }
+        })
+        .with_retries(2, std::time::Duration::from_millis(1));
+
+        let document = Document { id: "a".to_string(), content: "text".to_string(), metadata: HashMap::new(), uri: None };
+        let error = client.add_documents("docs", vec![document], vec![vec![0.1]]).await.unwrap_err();
+        assert!(matches!(error, ChromaError::ConnectionError(_)), "{}", error);
+        // One lookup per retry of the batch, not a retry loop of its own.
+        assert_eq!(gets.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_only_connection_failures_are_retried() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use crate::transport::{BoxError, HttpResponse};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let client = mock_chroma(move |request| -> std::result::Result<HttpResponse, BoxError> {
+            counter.fetch_add(1, Ordering::SeqCst);
+            if request.uri().path().ends_with("/heartbeat") {
+                Err("connection reset by peer".into())
+            } else {
+                Err(Box::new(ChromaError::TransportError("unsupported request".to_string())))
+            }
+        })
+        .with_retries(2, std::time::Duration::from_millis(1));
+
+        assert!(matches!(client.health_check().await, Err(ChromaError::ConnectionError(_))));
+        assert_eq!(calls.swap(0, Ordering::SeqCst), 3);
+        assert!(matches!(client.count("docs").await, Err(ChromaError::TransportError(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cloned_clients_share_state_across_tasks() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        fn assert_shareable<T: Clone + Send + Sync + 'static>() {}
+        assert_shareable::<ChromaClient>();
+        assert_shareable::<EmbeddingClient>();
+
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let counter = lookups.clone();
+        let client = mock_chroma(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            r#"{"id": "c0ffee", "name": "docs"}"#
+        });
+
+        client.get_collection("docs").await.unwrap();
+        let clone = client.clone();
+        let found = tokio::spawn(async move { clone.get_collection("docs").await.unwrap().id }).await.unwrap();
+        assert_eq!(found, "c0ffee");
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        // Reconfiguring a clone leaves the original untouched.
+        let detached = client.clone().with_collection_cache_ttl(std::time::Duration::ZERO);
+        detached.get_collection("docs").await.unwrap();
+        client.get_collection("docs").await.unwrap();
+        assert_eq!(lookups.load(Ordering::SeqCst), 2);
+    }
+}
@@ -33,7 +33,7 @@ impl ChromaDBWrapper {
             .await
             .map_err(|e| ChromaError::ApiError(format!("Failed to create ChromaDB client: {}", e)))?;
 
-        let embedding_client = EmbeddingClient::new(google_api_key);
+        let embedding_client = EmbeddingClient::try_new(google_api_key)?;
 
         info!("ChromaDB official client initialized with URL: {}", chroma_url);
 
@@ -178,42 +178,38 @@ impl ChromaDBWrapper {
             Ok(results) => {
                 debug!("Query returned {} results", results.ids.len());
 
-                // Extract first query results (we only sent one query)
-                let query_results = if !results.ids.is_empty() {
-                    QueryResult {
-                        ids: results.ids[0].clone(),
-                        documents: results
-                            .documents
-                            .map(|docs| docs[0].clone())
-                            .unwrap_or_default(),
-                        distances: results.distances.map(|d| d[0].clone()).unwrap_or_default(),
-                        metadatas: results
-                            .metadatas
-                            .map(|metas| {
-                                metas[0]
-                                    .iter()
-                                    .map(|meta| {
-                                        let mut map = HashMap::new();
-                                        if let Value::Object(obj) = meta {
-                                            for (k, v) in obj {
-                                                if let Value::String(s) = v {
-                                                    map.insert(k.clone(), s.clone());
-                                                }
+                // Extract first query results (we only sent one query); a
+                // server that returns no rows yields an empty result.
+                let query_results = QueryResult {
+                    ids: results.ids.into_iter().next().unwrap_or_default(),
+                    documents: results
+                        .documents
+                        .and_then(|docs| docs.into_iter().next())
+                        .unwrap_or_default(),
+                    distances: results
+                        .distances
+                        .and_then(|d| d.into_iter().next())
+                        .unwrap_or_default(),
+                    metadatas: results
+                        .metadatas
+                        .and_then(|metas| metas.into_iter().next())
+                        .map(|metas| {
+                            metas
+                                .iter()
+                                .map(|meta| {
+                                    let mut map = HashMap::new();
+                                    if let Value::Object(obj) = meta {
+                                        for (k, v) in obj {
+                                            if let Value::String(s) = v {
+                                                map.insert(k.clone(), s.clone());
                                             }
                                         }
-                                        map
-                                    })
-                                    .collect()
-                            })
-                            .unwrap_or_default(),
-                    }
-                } else {
-                    QueryResult {
-                        ids: vec![],
-                        documents: vec![],
-                        distances: vec![],
-                        metadatas: vec![],
-                    }
+                                    }
+                                    map
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default(),
                 };
 
                 Ok(query_results)
@@ -0,0 +1,179 @@
+use crate::chroma_client::ChromaClient;
+use crate::chunking::Chunker;
+use crate::error::Result;
+use crate::models::{CHUNK_INDEX_FIELD, Document, GetRequest, Include, PARENT_ID_FIELD};
+use crate::snapshot;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+
+const SCAN_PAGE_SIZE: u32 = 1000;
+const DELETE_BATCH_SIZE: usize = 500;
+
+/// The parents a chunked source currently holds: parent id to how many
+/// chunks it splits into now.
+pub type ChunkManifest = BTreeMap<String, usize>;
+
+/// The manifest of `documents` as `chunker` would store them through
+/// `Pipeline::ingest_chunked`.
+pub fn chunk_manifest(documents: &[Document], chunker: &Chunker) -> ChunkManifest {
+    documents.iter().map(|d| (d.id.clone(), chunker.split(d).len())).collect()
+}
+
+/// Chunks left behind by deleted or shrunk parents, from
+/// `ChromaClient::find_orphaned_chunks`. Ids are sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ChunkGcReport {
+    /// Chunks scanned, i.e. records with a `parent_id`.
+    pub scanned: usize,
+    /// Chunks whose parent is no longer in the manifest.
+    pub orphaned: Vec<String>,
+    /// Chunks whose `chunk_index` is past the end of their parent's
+    /// current chunks.
+    pub stale: Vec<String>,
+}
+
+impl ChunkGcReport {
+    /// Every chunk to delete.
+    pub fn ids(&self) -> impl Iterator<Item = &String> {
+        self.orphaned.iter().chain(&self.stale)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orphaned.is_empty() && self.stale.is_empty()
+    }
+}
+
+/// Compare the chunks stored in `collection_name` with `manifest`, reading
+/// only ids and metadata. Records without a `parent_id` weren't chunked and
+/// are left alone.
+pub(crate) async fn find(
+    client: &ChromaClient,
+    manifest: &ChunkManifest,
+    collection_name: &str,
+) -> Result<ChunkGcReport> {
+    let mut report = ChunkGcReport::default();
+
+    snapshot::scan(client, collection_name, SCAN_PAGE_SIZE, vec![Include::Metadatas], |id, _, metadata| {
+        let Some(parent) = metadata.and_then(|m| m.get(PARENT_ID_FIELD)).and_then(Value::as_str) else {
+            return;
+        };
+        report.scanned += 1;
+        match manifest.get(parent) {
+            None => report.orphaned.push(id),
+            Some(&chunks) => {
+                if is_past_end(metadata, chunks) {
+                    report.stale.push(id);
+                }
+            }
+        }
+    })
+    .await?;
+
+    report.orphaned.sort();
+    report.stale.sort();
+    Ok(report)
+}
+
+/// `find` for just the parents in `manifest`, fetched by `parent_id` rather
+/// than by scanning the collection, so only stale chunks are reported.
+pub(crate) async fn find_stale(
+    client: &ChromaClient,
+    manifest: &ChunkManifest,
+    collection_name: &str,
+) -> Result<ChunkGcReport> {
+    let parent_ids: Vec<&String> = manifest.keys().collect();
+    let request = GetRequest {
+        where_filter: Some(json!({ PARENT_ID_FIELD: { "$in": parent_ids } })),
+        include: Some(vec![Include::Metadatas]),
+        ..GetRequest::default()
+    };
+    let response = client.send_get(collection_name, &request).await?;
+
+    let mut stale: Vec<String> = response
+        .ids_for(0)
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| {
+            let metadata = response.metadata(0, *i);
+            metadata
+                .and_then(|m| m.get(PARENT_ID_FIELD))
+                .and_then(Value::as_str)
+                .and_then(|parent| manifest.get(parent))
+                .is_some_and(|&chunks| is_past_end(metadata, chunks))
+        })
+        .map(|(_, id)| id.clone())
+        .collect();
+    stale.sort();
+    Ok(ChunkGcReport { scanned: response.ids_for(0).len(), orphaned: Vec::new(), stale })
+}
+
+/// Whether a chunk's `chunk_index` is at or past `chunks`.
+fn is_past_end(metadata: Option<&Value>, chunks: usize) -> bool {
+    // Chunker writes the index as a string; older chunks may hold a number.
+    let index = match metadata.and_then(|m| m.get(CHUNK_INDEX_FIELD)) {
+        Some(Value::String(s)) => s.parse::<u64>().ok(),
+        other => other.and_then(Value::as_u64),
+    };
+    index.is_some_and(|index| index >= chunks as u64)
+}
+
+/// Delete everything `report` found, in batches.
+pub(crate) async fn collect(client: &ChromaClient, report: &ChunkGcReport, collection_name: &str) -> Result<()> {
+    let ids: Vec<String> = report.ids().cloned().collect();
+    for batch in ids.chunks(DELETE_BATCH_SIZE) {
+        client.delete_documents(collection_name, batch.to_vec()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_orphaned_and_stale_chunks_are_collected() {
+        use std::sync::Mutex;
+
+        let stored = serde_json::json!({
+            "ids": ["a#0", "a#1", "b#0", "c"],
+            "metadatas": [
+                {"parent_id": "a", "chunk_index": "0"},
+                {"parent_id": "a", "chunk_index": "1"},
+                {"parent_id": "b", "chunk_index": "0"},
+                {"source": "notes"},
+            ],
+        })
+        .to_string();
+        let deleted: Arc<Mutex<Vec<serde_json::Value>>> = Arc::default();
+        let recorded = deleted.clone();
+        let chroma = mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            if path.ends_with("/get") {
+                stored.clone()
+            } else if path.ends_with("/delete") {
+                let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                recorded.lock().unwrap().push(body["ids"].clone());
+                "true".to_string()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+
+        // "a" now fits in one chunk and "b" is gone from the source.
+        let documents = vec![Document::builder().id("a").content("short now").build()];
+        let manifest = chunk_manifest(&documents, &Chunker::default());
+        assert_eq!(manifest["a"], 1);
+
+        let report = chroma.find_orphaned_chunks(&manifest, "docs").await.unwrap();
+        assert_eq!(report.scanned, 3);
+        assert_eq!(report.orphaned, vec!["b#0".to_string()]);
+        assert_eq!(report.stale, vec!["a#1".to_string()]);
+        assert!(deleted.lock().unwrap().is_empty());
+
+        chroma.collect_orphaned_chunks(&manifest, "docs").await.unwrap();
+        assert_eq!(*deleted.lock().unwrap(), vec![serde_json::json!(["b#0", "a#1"])]);
+    }
+}
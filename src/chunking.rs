@@ -0,0 +1,173 @@
+use crate::models::{CHUNK_END_FIELD, CHUNK_INDEX_FIELD, CHUNK_START_FIELD, Document, PARENT_ID_FIELD};
+use crate::versioning::CHUNKER_CONFIG_HASH_FIELD;
+use uuid::Uuid;
+
+/// Splits a logical document into overlapping chunks, each stored as its own
+/// vector.
+///
+/// Chunk ids are `{parent_id}#{index}` and every chunk carries the parent's
+/// metadata plus `parent_id` and `chunk_index`, which is what
+/// `QueryOptions::with_group_by_parent` groups on, and the `chunk_start`/
+/// `chunk_end` character range `QueryOptions::with_collapse_overlapping`
+/// compares. Chunks also carry the `chunker_config_hash` of the settings
+/// that cut them, and end at whitespace where possible so words aren't cut
+/// in half.
+#[derive(Debug, Clone, Copy)]
+pub struct Chunker {
+    max_chars: usize,
+    overlap: usize,
+}
+
+impl Default for Chunker {
+    fn default() -> Self {
+        Self::new(1000, 100)
+    }
+}
+
+impl Chunker {
+    /// `overlap` is clamped below `max_chars` so every chunk makes progress.
+    pub fn new(max_chars: usize, overlap: usize) -> Self {
+        let max_chars = max_chars.max(1);
+        Self {
+            max_chars,
+            overlap: overlap.min(max_chars - 1),
+        }
+    }
+
+    /// Identifies these settings, so records cut with other ones can be
+    /// found and re-chunked.
+    pub fn config_hash(&self) -> String {
+        let config = format!("max_chars={};overlap={}", self.max_chars, self.overlap);
+        Uuid::new_v5(&Uuid::NAMESPACE_OID, config.as_bytes()).simple().to_string()[..16].to_string()
+    }
+
+    pub fn split(&self, document: &Document) -> Vec<Document> {
+        let config_hash = self.config_hash();
+        self.split_text(&document.content)
+            .into_iter()
+            .enumerate()
+            .map(|(index, (start, end, content))| {
+                let mut metadata = document.metadata.clone();
+                metadata.insert(PARENT_ID_FIELD.to_string(), document.id.clone());
+                metadata.insert(CHUNK_INDEX_FIELD.to_string(), index.to_string());
+                metadata.insert(CHUNK_START_FIELD.to_string(), start.to_string());
+                metadata.insert(CHUNK_END_FIELD.to_string(), end.to_string());
+                metadata.insert(CHUNKER_CONFIG_HASH_FIELD.to_string(), config_hash.clone());
+                Document {
+                    id: format!("{}#{}", document.id, index),
+                    content,
+                    metadata,
+                    uri: None,
+                }
+            })
+            .collect()
+    }
+
+    /// Chunks with the `[start, end)` character range each was cut from.
+    fn split_text(&self, text: &str) -> Vec<(usize, usize, String)> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut chunks = Vec::new();
+        let mut start = 0;
+
+        while start < chars.len() {
+            let mut end = (start + self.max_chars).min(chars.len());
+            // Back off to the last whitespace, unless that would leave less
+            // than half a chunk.
+            if end < chars.len()
+                && let Some(space) = chars[start..end].iter().rposition(|c| c.is_whitespace())
+                && space >= self.max_chars / 2
+            {
+                end = start + space + 1;
+            }
+
+            let chunk: String = chars[start..end].iter().collect();
+            if !chunk.trim().is_empty() {
+                chunks.push((start, end, chunk.trim().to_string()));
+            }
+            if end == chars.len() {
+                break;
+            }
+            start = end.saturating_sub(self.overlap).max(start + 1);
+        }
+
+        chunks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QueryHit;
+    use crate::query::QueryOptions;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_chunks_group_back_to_their_parent() {
+        let document = Document {
+            id: "guide".to_string(),
+            content: "alpha beta gamma delta epsilon zeta eta theta".to_string(),
+            metadata: HashMap::from([("lang".to_string(), "en".to_string())]),
+            uri: None,
+        };
+        let chunks = Chunker::new(20, 6).split(&document);
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks[1].id, "guide#1");
+        assert_eq!(chunks[1].metadata["parent_id"], "guide");
+        assert_eq!(chunks[1].metadata["lang"], "en");
+        assert!(chunks.iter().all(|c| c.content.chars().count() <= 20));
+
+        let hit = |id: &str, parent: Option<&str>, distance: f32| QueryHit {
+            id: id.to_string(),
+            document: None,
+            metadata: parent.map(|p| serde_json::json!({ "parent_id": p })),
+            distance,
+            score: 1.0 - distance,
+            uri: None,
+        };
+        let hits = vec![
+            hit("guide#0", Some("guide"), 0.3),
+            hit("guide#2", Some("guide"), 0.1),
+            hit("faq#0", Some("faq"), 0.2),
+            hit("standalone", None, 0.4),
+        ];
+        let grouped = QueryOptions::new(3).with_group_by_parent().rerank(hits);
+        let ids: Vec<&str> = grouped.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, vec!["guide#2", "faq#0", "standalone"]);
+    }
+
+    #[test]
+    fn test_overlapping_chunks_collapse_to_the_best_hit() {
+        let document = Document::builder()
+            .id("guide")
+            .content("alpha beta gamma delta epsilon zeta eta theta iota kappa lambda mu")
+            .build();
+        let chunks = Chunker::new(20, 6).split(&document);
+        let span = |chunk: &Document| {
+            let field = |key: &str| chunk.metadata[key].parse::<usize>().unwrap();
+            (field("chunk_start"), field("chunk_end"))
+        };
+        assert!(span(&chunks[1]).0 < span(&chunks[0]).1);
+
+        let hit = |chunk: &Document, distance: f32| QueryHit {
+            id: chunk.id.clone(),
+            document: Some(chunk.content.clone()),
+            metadata: Some(serde_json::to_value(&chunk.metadata).unwrap()),
+            distance,
+            score: 1.0 - distance,
+            uri: None,
+        };
+        let last = chunks.len() - 1;
+        let hits = vec![
+            hit(&chunks[0], 0.2),
+            hit(&chunks[1], 0.1),
+            hit(&chunks[last], 0.3),
+            QueryHit { id: "faq#0".to_string(), metadata: Some(serde_json::json!({ "parent_id": "faq", "chunk_index": "0" })), ..hit(&chunks[0], 0.4) },
+            QueryHit { id: "faq#1".to_string(), metadata: Some(serde_json::json!({ "parent_id": "faq", "chunk_index": "1" })), ..hit(&chunks[0], 0.5) },
+        ];
+        assert!(span(&chunks[last]).0 >= span(&chunks[1]).1);
+
+        let collapsed = QueryOptions::new(10).with_collapse_overlapping().rerank(hits);
+        let ids: Vec<&str> = collapsed.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, vec!["guide#1", chunks[last].id.as_str(), "faq#0"]);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::error::{ChromaError, Result};
+use crate::models::QueryResponse;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Converts a structured metadata value to the string Chroma stores and
+/// back.
+///
+/// Chroma metadata values are scalars, so richer per-document payloads are
+/// stored encoded under a single key. Register a codec for that key on a
+/// `Collection` and it is applied on writes and undone on reads.
+pub trait MetadataCodec: Send + Sync {
+    fn encode(&self, value: &Value) -> Result<String>;
+    fn decode(&self, stored: &str) -> Result<Value>;
+}
+
+/// Stores values as JSON text. The default for keys without a codec.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl MetadataCodec for JsonCodec {
+    fn encode(&self, value: &Value) -> Result<String> {
+        Ok(value.to_string())
+    }
+
+    fn decode(&self, stored: &str) -> Result<Value> {
+        Ok(serde_json::from_str(stored)?)
+    }
+}
+
+/// Codecs by metadata key.
+#[derive(Clone, Default)]
+pub struct MetadataCodecs {
+    codecs: HashMap<String, Arc<dyn MetadataCodec>>,
+}
+
+impl fmt::Debug for MetadataCodecs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.codecs.keys()).finish()
+    }
+}
+
+impl MetadataCodecs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, key: &str, codec: impl MetadataCodec + 'static) {
+        self.codecs.insert(key.to_string(), Arc::new(codec));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.codecs.is_empty()
+    }
+
+    /// Encode `value` with the codec for `key`, or as JSON if there is none.
+    pub fn encode(&self, key: &str, value: &Value) -> Result<String> {
+        match self.codecs.get(key) {
+            Some(codec) => codec.encode(value),
+            None => JsonCodec.encode(value),
+        }
+    }
+
+    /// Check that every value under a registered key decodes, so a call site
+    /// that set a raw string by hand fails before the write instead of on
+    /// every later read.
+    pub fn check(&self, metadata: &HashMap<String, String>) -> Result<()> {
+        for (key, stored) in metadata {
+            if let Some(codec) = self.codecs.get(key) {
+                codec.decode(stored).map_err(|e| {
+                    ChromaError::ValidationError(format!("Metadata '{}' does not decode: {}", key, e))
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace encoded values in one metadata object with their decoded form.
+    pub fn decode_metadata(&self, metadata: &mut Value) -> Result<()> {
+        if let Value::Object(map) = metadata {
+            for (key, value) in map.iter_mut() {
+                if let (Some(codec), Value::String(stored)) = (self.codecs.get(key), &*value) {
+                    *value = codec.decode(stored)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn decode_response(&self, response: &mut QueryResponse) -> Result<()> {
+        for metadata in response.metadatas.iter_mut().flatten().flatten().flatten() {
+            self.decode_metadata(metadata)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Document;
+    use crate::query::QueryOptions;
+    use crate::test_support::mock_chroma;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_collection_codecs_round_trip_metadata() {
+        use std::sync::Mutex;
+
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Provenance {
+            source: String,
+            revision: u32,
+        }
+
+        let stored = Arc::new(Mutex::new(None));
+        let recorded = stored.clone();
+        let client = mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            if path.ends_with("/upsert") {
+                let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                *recorded.lock().unwrap() = Some(body["metadatas"][0]["provenance"].clone());
+                "true".to_string()
+            } else if path.ends_with("/query") {
+                let provenance = recorded.lock().unwrap().clone().unwrap();
+                serde_json::json!({"ids": [["a"]], "distances": [[0.1]], "metadatas": [[{"provenance": provenance}]]})
+                    .to_string()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+        let docs = client.collection("docs").with_codec("provenance", JsonCodec);
+
+        let provenance = Provenance { source: "wiki".to_string(), revision: 3 };
+        let mut document = Document { id: "a".to_string(), content: "text".to_string(), metadata: HashMap::new(), uri: None };
+        docs.set_metadata(&mut document, "provenance", &provenance).unwrap();
+        docs.upsert_documents(vec![document], vec![vec![0.1]]).await.unwrap();
+        assert!(stored.lock().unwrap().as_ref().unwrap().is_string());
+
+        let hits = docs.query_with_options(vec![0.1], &QueryOptions::new(1)).await.unwrap();
+        assert_eq!(hits[0].metadata_as::<Provenance>("provenance").unwrap(), Some(provenance));
+
+        let mut raw = Document { id: "b".to_string(), content: "text".to_string(), metadata: HashMap::new(), uri: None };
+        raw.metadata.insert("provenance".to_string(), "not json".to_string());
+        assert!(matches!(
+            docs.add_documents(vec![raw], vec![vec![0.1]]).await,
+            Err(ChromaError::ValidationError(_))
+        ));
+    }
+}
@@ -0,0 +1,111 @@
+use crate::chroma_client::ChromaClient;
+use crate::codec::{MetadataCodec, MetadataCodecs};
+use crate::error::Result;
+use crate::models::*;
+use crate::query::QueryOptions;
+use serde::Serialize;
+use serde_json::Value;
+
+/// A handle on one collection that encodes and decodes structured metadata.
+///
+/// Keys with a registered `MetadataCodec` hold an encoded string in Chroma
+/// but come back from `get_documents` and `query_with_options` as the
+/// decoded JSON value, ready for `QueryHit::metadata_as`. Use
+/// `set_metadata` to store a value under such a key.
+///
+/// ```no_run
+/// # use chromadb_demo::{ChromaClient, Document, codec::JsonCodec};
+/// # use std::collections::HashMap;
+/// # #[derive(serde::Serialize, serde::Deserialize)]
+/// # struct Provenance { source: String, revision: u32 }
+/// # async fn example(client: ChromaClient) -> chromadb_demo::Result<()> {
+/// let docs = client.collection("docs").with_codec("provenance", JsonCodec);
+///
+/// let mut doc = Document { id: "a".into(), content: "text".into(), metadata: HashMap::new(), uri: None };
+/// docs.set_metadata(&mut doc, "provenance", &Provenance { source: "wiki".into(), revision: 3 })?;
+/// docs.upsert_documents(vec![doc], vec![vec![0.1; 768]]).await?;
+///
+/// let response = docs.get_documents(Some(vec!["a".into()]), None, None).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Collection<'a> {
+    client: &'a ChromaClient,
+    name: String,
+    codecs: MetadataCodecs,
+}
+
+impl<'a> Collection<'a> {
+    pub(crate) fn new(client: &'a ChromaClient, name: &str) -> Self {
+        Self {
+            client,
+            name: name.to_string(),
+            codecs: MetadataCodecs::new(),
+        }
+    }
+
+    /// Encode and decode the metadata stored under `key` with `codec`.
+    pub fn with_codec(mut self, key: &str, codec: impl MetadataCodec + 'static) -> Self {
+        self.codecs.register(key, codec);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Store `value` under `key` in `document`'s metadata, encoded with the
+    /// key's codec (JSON if none is registered).
+    pub fn set_metadata<T: Serialize>(&self, document: &mut Document, key: &str, value: &T) -> Result<()> {
+        let encoded = self.codecs.encode(key, &serde_json::to_value(value)?)?;
+        document.metadata.insert(key.to_string(), encoded);
+        Ok(())
+    }
+
+    pub async fn add_documents(&self, documents: Vec<Document>, embeddings: Vec<Vec<f32>>) -> Result<()> {
+        self.check(&documents)?;
+        self.client.add_documents(&self.name, documents, embeddings).await
+    }
+
+    pub async fn upsert_documents(&self, documents: Vec<Document>, embeddings: Vec<Vec<f32>>) -> Result<()> {
+        self.check(&documents)?;
+        self.client.upsert_documents(&self.name, documents, embeddings).await
+    }
+
+    pub async fn update_documents(&self, documents: Vec<Document>, embeddings: Vec<Vec<f32>>) -> Result<()> {
+        self.check(&documents)?;
+        self.client.update_documents(&self.name, documents, embeddings).await
+    }
+
+    pub async fn delete_documents(&self, ids: Vec<String>) -> Result<()> {
+        self.client.delete_documents(&self.name, ids).await
+    }
+
+    pub async fn get_documents(
+        &self,
+        ids: Option<Vec<String>>,
+        where_filter: Option<Value>,
+        limit: Option<u32>,
+    ) -> Result<QueryResponse> {
+        let mut response = self.client.get_documents(&self.name, ids, where_filter, limit).await?;
+        self.codecs.decode_response(&mut response)?;
+        Ok(response)
+    }
+
+    pub async fn query_with_options(&self, query_embedding: Vec<f32>, options: &QueryOptions) -> Result<Vec<QueryHit>> {
+        let mut hits = self.client.query_with_options(&self.name, query_embedding, options).await?;
+        for hit in hits.iter_mut() {
+            if let Some(metadata) = hit.metadata.as_mut() {
+                self.codecs.decode_metadata(metadata)?;
+            }
+        }
+        Ok(hits)
+    }
+
+    fn check(&self, documents: &[Document]) -> Result<()> {
+        if self.codecs.is_empty() {
+            return Ok(());
+        }
+        documents.iter().try_for_each(|doc| self.codecs.check(&doc.metadata))
+    }
+}
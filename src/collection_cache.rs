@@ -0,0 +1,142 @@
+use crate::models::CollectionResponse;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const DEFAULT_TTL: Duration = Duration::from_secs(30);
+
+/// Name → collection lookups cached by `ChromaClient`.
+///
+/// Every record operation resolves the collection name to its id first, so
+/// without a cache each write or query costs an extra round trip. Entries are
+/// served from memory for the TTL; after that the next lookup revalidates
+/// with `If-None-Match` when Chroma sent an `ETag` (a `304` keeps the entry)
+/// and refetches otherwise. Collections dropped or recreated by another
+/// process stay cached until the TTL runs out, unless the client's
+/// `invalidate_collection` is called.
+#[derive(Debug)]
+pub(crate) struct CollectionCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, CachedCollection>>,
+}
+
+#[derive(Debug)]
+struct CachedCollection {
+    collection: CollectionResponse,
+    etag: Option<String>,
+    fetched_at: Instant,
+}
+
+/// A detached copy starts empty with the same TTL.
+impl Clone for CollectionCache {
+    fn clone(&self) -> Self {
+        Self::new(self.ttl)
+    }
+}
+
+/// What `get_collection` should do for a name.
+pub(crate) enum Lookup {
+    Fresh(CollectionResponse),
+    /// Expired, but can be revalidated with this ETag.
+    Revalidate(String),
+    Fetch,
+}
+
+impl CollectionCache {
+    /// TTL from `COLLECTION_CACHE_TTL_SECS` (default 30, `0` disables).
+    pub(crate) fn from_env() -> Self {
+        let ttl = std::env::var("COLLECTION_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TTL);
+        Self::new(ttl)
+    }
+
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn lookup(&self, name: &str) -> Lookup {
+        if self.ttl.is_zero() {
+            return Lookup::Fetch;
+        }
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        match entries.get(name) {
+            Some(entry) if entry.fetched_at.elapsed() < self.ttl => Lookup::Fresh(entry.collection.clone()),
+            Some(CachedCollection { etag: Some(etag), .. }) => Lookup::Revalidate(etag.clone()),
+            _ => Lookup::Fetch,
+        }
+    }
+
+    pub(crate) fn store(&self, name: &str, collection: &CollectionResponse, etag: Option<String>) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let entry = CachedCollection {
+            collection: collection.clone(),
+            etag,
+            fetched_at: Instant::now(),
+        };
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(name.to_string(), entry);
+    }
+
+    /// Mark an entry fresh again after a `304 Not Modified`.
+    pub(crate) fn touch(&self, name: &str) -> Option<CollectionResponse> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = entries.get_mut(name)?;
+        entry.fetched_at = Instant::now();
+        Some(entry.collection.clone())
+    }
+
+    pub(crate) fn invalidate(&self, name: &str) {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).remove(name);
+    }
+
+    pub(crate) fn clear(&self) {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_collection_lookups_are_cached_and_revalidated() {
+        use std::sync::Mutex;
+
+        let lookups = Arc::new(Mutex::new(Vec::new()));
+        let recorded = lookups.clone();
+        let client = mock_chroma(move |request| {
+            let if_none_match = request.headers().get("if-none-match").map(|v| v.to_str().unwrap().to_string());
+            recorded.lock().unwrap().push(if_none_match.clone());
+            if if_none_match.as_deref() == Some("\"v1\"") {
+                http::Response::builder().status(304).body(Vec::new().into()).unwrap()
+            } else {
+                http::Response::builder()
+                    .header("etag", "\"v1\"")
+                    .body(r#"{"id": "c0ffee", "name": "docs"}"#.into())
+                    .unwrap()
+            }
+        })
+        .with_collection_cache_ttl(std::time::Duration::from_millis(50));
+
+        assert_eq!(client.get_collection("docs").await.unwrap().id, "c0ffee");
+        assert_eq!(client.get_collection("docs").await.unwrap().id, "c0ffee");
+        assert_eq!(lookups.lock().unwrap().len(), 1);
+
+        tokio::time::sleep(std::time::Duration::from_millis(60)).await;
+        assert_eq!(client.get_collection("docs").await.unwrap().id, "c0ffee");
+        client.invalidate_collection("docs");
+        client.get_collection("docs").await.unwrap();
+        assert_eq!(*lookups.lock().unwrap(), vec![None, Some("\"v1\"".to_string()), None]);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::models::{CollectionResponse, GetResponse, QueryResponse};
+use crate::schema::KnownFields;
+use serde_json::Value;
+use std::fmt;
+
+/// A Chroma server version, as reported by `/api/v2/version`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ServerVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl ServerVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self { major, minor, patch }
+    }
+
+    /// Parse `"1.0.12"`; a missing minor or patch counts as 0 and anything
+    /// after the patch number (`-rc1`, `+build`) is ignored.
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut parts = version.trim().trim_start_matches('v').splitn(3, '.');
+        let number = |part: Option<&str>| -> Option<u32> {
+            let digits: String = part?.chars().take_while(char::is_ascii_digit).collect();
+            digits.parse().ok()
+        };
+        let major = number(parts.next())?;
+        let minor = number(parts.next()).unwrap_or(0);
+        let patch = number(parts.next()).unwrap_or(0);
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The responses a shim can adapt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseKind {
+    Collection,
+    Query,
+    Get,
+}
+
+impl ResponseKind {
+    fn model_name(self) -> &'static str {
+        match self {
+            ResponseKind::Collection => CollectionResponse::NAME,
+            ResponseKind::Query => QueryResponse::NAME,
+            ResponseKind::Get => GetResponse::NAME,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Adaptation {
+    /// Move top-level field `from` to `to`, unless `to` is already set.
+    Rename { from: String, to: String },
+    /// Replace the response with the value under `field`, for servers that
+    /// wrap it in an envelope.
+    Unwrap { field: String },
+    /// Remove a top-level field the models have no use for.
+    Remove { field: String },
+}
+
+/// One known difference between Chroma versions, undone before a response
+/// is decoded when the server's version is in `[since, before)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatShim {
+    pub kind: ResponseKind,
+    pub since: Option<ServerVersion>,
+    pub before: Option<ServerVersion>,
+    pub adaptation: Adaptation,
+}
+
+impl CompatShim {
+    pub fn rename(kind: ResponseKind, from: &str, to: &str) -> Self {
+        Self::new(kind, Adaptation::Rename { from: from.to_string(), to: to.to_string() })
+    }
+
+    pub fn unwrap_envelope(kind: ResponseKind, field: &str) -> Self {
+        Self::new(kind, Adaptation::Unwrap { field: field.to_string() })
+    }
+
+    pub fn remove(kind: ResponseKind, field: &str) -> Self {
+        Self::new(kind, Adaptation::Remove { field: field.to_string() })
+    }
+
+    fn new(kind: ResponseKind, adaptation: Adaptation) -> Self {
+        Self { kind, since: None, before: None, adaptation }
+    }
+
+    /// Only apply to servers at `version` or later.
+    pub fn since(mut self, version: ServerVersion) -> Self {
+        self.since = Some(version);
+        self
+    }
+
+    /// Only apply to servers older than `version`.
+    pub fn before(mut self, version: ServerVersion) -> Self {
+        self.before = Some(version);
+        self
+    }
+
+    fn applies_to(&self, model: &str, version: ServerVersion) -> bool {
+        self.kind.model_name() == model
+            && self.since.is_none_or(|since| version >= since)
+            && self.before.is_none_or(|before| version < before)
+    }
+
+    /// Adapt `value`, returning whether anything changed.
+    fn apply(&self, value: &mut Value) -> bool {
+        let Some(fields) = value.as_object_mut() else {
+            return false;
+        };
+        match &self.adaptation {
+            Adaptation::Rename { from, to } => {
+                if fields.contains_key(to) {
+                    return false;
+                }
+                match fields.remove(from) {
+                    Some(moved) => {
+                        fields.insert(to.clone(), moved);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Adaptation::Unwrap { field } => match fields.remove(field) {
+                Some(inner) => {
+                    *value = inner;
+                    true
+                }
+                None => false,
+            },
+            Adaptation::Remove { field } => fields.remove(field).is_some(),
+        }
+    }
+}
+
+impl fmt::Display for CompatShim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.adaptation {
+            Adaptation::Rename { from, to } => write!(f, "renamed {} to {}", from, to)?,
+            Adaptation::Unwrap { field } => write!(f, "unwrapped {}", field)?,
+            Adaptation::Remove { field } => write!(f, "removed {}", field)?,
+        }
+        write!(f, " in the {} response", self.kind.model_name())
+    }
+}
+
+/// The shims a client applies: the built-in ones for releases this crate
+/// knows about, plus any added with `ChromaClient::with_compat_shim` for a
+/// server upgrade that lands before the crate catches up.
+#[derive(Debug, Clone)]
+pub struct Compat {
+    shims: Vec<CompatShim>,
+}
+
+impl Default for Compat {
+    /// Chroma before 1.0 reports the include list of queries and gets as
+    /// `included`, next to an always-null `data`.
+    fn default() -> Self {
+        let v1 = ServerVersion::new(1, 0, 0);
+        let shims = [ResponseKind::Query, ResponseKind::Get]
+            .into_iter()
+            .flat_map(|kind| {
+                [
+                    CompatShim::rename(kind, "included", "include").before(v1),
+                    CompatShim::remove(kind, "data").before(v1),
+                ]
+            })
+            .collect();
+        Self { shims }
+    }
+}
+
+impl Compat {
+    pub(crate) fn push(&mut self, shim: CompatShim) {
+        self.shims.push(shim);
+    }
+
+    /// Apply the shims for `T` on a server at `version`, in the order they
+    /// were added. Returns what was changed, for logging.
+    pub(crate) fn adapt<T: KnownFields>(&self, version: ServerVersion, value: &mut Value) -> Vec<String> {
+        self.shims
+            .iter()
+            .filter(|shim| shim.applies_to(T::NAME, version))
+            .filter(|shim| shim.apply(value))
+            .map(|shim| shim.to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ChromaError;
+    use crate::schema::SchemaMode;
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_compat_shims_adapt_responses_of_older_and_newer_servers() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let version_lookups = Arc::new(AtomicUsize::new(0));
+        let looked_up = version_lookups.clone();
+        let client = mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            if path.ends_with("/version") {
+                looked_up.fetch_add(1, Ordering::SeqCst);
+                r#""0.5.23""#.to_string()
+            } else if path.ends_with("/query") {
+                r#"{"ids": [["a"]], "distances": [[0.1]], "included": ["distances"], "data": null}"#.to_string()
+            } else if path.ends_with("/get") {
+                r#"{"ids": ["a"], "documents": ["alpha"], "included": ["documents"], "data": null}"#.to_string()
+            } else {
+                r#"{"collection": {"id": "c0ffee", "name": "docs"}}"#.to_string()
+            }
+        })
+        .with_schema_mode(SchemaMode::Strict)
+        .with_compat_shim(
+                CompatShim::unwrap_envelope(ResponseKind::Collection, "collection").since(ServerVersion::new(0, 5, 0)),
+            );
+
+        let response = client.query("docs", vec![vec![0.1]], 1).await.unwrap();
+        assert_eq!(response.ids, vec![vec!["a".to_string()]]);
+        let response = client.get_documents("docs", None, None, None).await.unwrap();
+        assert_eq!(response.documents, Some(vec![vec![Some("alpha".to_string())]]));
+        assert_eq!(version_lookups.load(Ordering::SeqCst), 1);
+
+        // Shims only apply within their version range.
+        let older = client.clone().with_server_version(ServerVersion::parse("v0.4.24").unwrap());
+        older.invalidate_collection("docs");
+        assert!(matches!(older.get_collection("docs").await, Err(ChromaError::SchemaError(_))));
+        assert_eq!(version_lookups.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::error::{ChromaError, Result};
+use crate::models::Document;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// Metadata key holding the hash of a document's normalized content.
+pub const CONTENT_HASH_FIELD: &str = "content_hash";
+
+/// Lengths, in normalized characters, of the prefixes hashed into
+/// `content_prefix_<n>` for `ChromaClient::find_by_content_prefix`. A
+/// prefix lookup uses the longest one that fits in the search text.
+pub const PREFIX_LENGTHS: [usize; 3] = [32, 128, 512];
+
+/// Text as it is compared for content lookups: lowercased, with runs of
+/// whitespace collapsed to one space and the ends trimmed, so re-wrapped or
+/// re-indented copies of an article still match.
+pub fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Hash of already normalized text, as stored in the metadata.
+pub fn content_hash(normalized: &str) -> String {
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, normalized.as_bytes()).simple().to_string()
+}
+
+/// How content and prefix hashes are computed, from
+/// `ChromaClient::content_hasher`.
+///
+/// `Plain` hashes are `content_hash`, which anyone can recompute to confirm
+/// a guess at the text, so they are only written next to documents stored in
+/// the clear. `Keyed` hashes are HMAC-SHA256 under a key derived from the
+/// field encryption key (see `FieldEncryption::content_hasher`).
+#[derive(Clone)]
+pub enum ContentHasher {
+    Plain,
+    Keyed([u8; 32]),
+}
+
+impl std::fmt::Debug for ContentHasher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Plain => f.write_str("Plain"),
+            Self::Keyed(_) => f.write_str("Keyed(..)"),
+        }
+    }
+}
+
+impl ContentHasher {
+    /// Hash of already normalized text, as stored in the metadata.
+    pub fn hash(&self, normalized: &str) -> String {
+        match self {
+            Self::Plain => content_hash(normalized),
+            Self::Keyed(key) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+                mac.update(normalized.as_bytes());
+                mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+            }
+        }
+    }
+
+    /// Record the content and prefix hashes of `document`, replacing any
+    /// from an earlier version of its text.
+    pub(crate) fn stamp(&self, document: &mut Document) {
+        let normalized = normalize_text(&document.content);
+        document.metadata.insert(CONTENT_HASH_FIELD.to_string(), self.hash(&normalized));
+
+        for length in PREFIX_LENGTHS {
+            let field = prefix_field(length);
+            match prefix(&normalized, length) {
+                Some(prefix) => document.metadata.insert(field, self.hash(prefix)),
+                None => document.metadata.remove(&field),
+            };
+        }
+    }
+
+    /// The `where` clause for documents whose normalized content starts
+    /// with `normalized`, before checking the rest of the text: the longest
+    /// stored prefix hash that fits in it.
+    pub(crate) fn prefix_filter(&self, normalized: &str) -> Result<serde_json::Value> {
+        PREFIX_LENGTHS
+            .into_iter()
+            .rev()
+            .find_map(|length| {
+                let prefix = prefix(normalized, length)?;
+                Some(serde_json::json!({ prefix_field(length): { "$eq": self.hash(prefix) } }))
+            })
+            .ok_or_else(|| {
+                ChromaError::ValidationError(format!(
+                    "Prefix lookups need at least {} characters of normalized text, got {}",
+                    PREFIX_LENGTHS[0],
+                    normalized.chars().count()
+                ))
+            })
+    }
+}
+
+/// The first `length` characters of `text`, if it has that many.
+fn prefix(text: &str, length: usize) -> Option<&str> {
+    match text.char_indices().nth(length) {
+        Some((end, _)) => Some(&text[..end]),
+        None => (text.chars().count() == length).then_some(text),
+    }
+}
+
+fn prefix_field(length: usize) -> String {
+    format!("content_prefix_{}", length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_find_by_content_matches_normalized_text_and_prefixes() {
+        use std::sync::Mutex;
+
+        let article = format!("The  Quick brown fox.\n\n{}", "Jumps over the lazy dog. ".repeat(10));
+        let requests: Arc<Mutex<Vec<serde_json::Value>>> = Arc::default();
+        let recorded = requests.clone();
+        let stored = article.clone();
+        let chroma = mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            if path.ends_with("/get") || path.ends_with("/add") {
+                let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                recorded.lock().unwrap().push(body);
+                serde_json::json!({
+                    "ids": ["article", "other"],
+                    "documents": [stored, "the quick brown fox. jumps over the lazy cat."],
+                })
+                .to_string()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        })
+        .with_content_hashes(true);
+
+        let doc = Document::builder().id("article").content(article.as_str()).build();
+        chroma.add_documents("docs", vec![doc], vec![vec![0.1]]).await.unwrap();
+        let hash = content_hash(&normalize_text(&article));
+        assert_eq!(requests.lock().unwrap()[0]["metadatas"][0]["content_hash"], hash.as_str());
+        assert!(requests.lock().unwrap()[0]["metadatas"][0]["content_prefix_512"].is_null());
+
+        let rewrapped = article.split_whitespace().collect::<Vec<_>>().join("\n").to_uppercase();
+        chroma.find_by_content("docs", &rewrapped).await.unwrap();
+        assert_eq!(requests.lock().unwrap()[1]["where"]["content_hash"]["$eq"], hash.as_str());
+
+        let ids = chroma.find_by_content_prefix("docs", "the quick brown fox. JUMPS over the lazy dog.").await.unwrap();
+        assert_eq!(ids, vec!["article"]);
+        assert!(requests.lock().unwrap()[2]["where"]["content_prefix_32"].is_object());
+        assert!(matches!(
+            chroma.find_by_content_prefix("docs", "the quick brown").await,
+            Err(ChromaError::ValidationError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_content_hashes_are_opt_in_and_keyed_under_field_encryption() {
+        use crate::encryption::{FieldEncryption, StoreCipher};
+        use std::sync::Mutex;
+
+        let text = "Confidential quarterly figures, not for the index. ".repeat(4);
+        let sent: Arc<Mutex<Vec<serde_json::Value>>> = Arc::default();
+        let recorded = sent.clone();
+        let chroma = mock_chroma(move |request| {
+            if request.uri().path().ends_with("/add") {
+                recorded.lock().unwrap().push(serde_json::from_slice(request.body()).unwrap());
+            }
+            r#"{"id": "c0ffee", "name": "docs"}"#
+        });
+        let doc = Document::builder().id("memo").content(text.as_str()).build();
+
+        chroma.add_documents("docs", vec![doc.clone()], vec![vec![0.1]]).await.unwrap();
+        assert!(sent.lock().unwrap()[0]["metadatas"][0].get(CONTENT_HASH_FIELD).is_none());
+        assert!(matches!(chroma.find_by_content("docs", &text).await, Err(ChromaError::ConfigError(_))));
+
+        let key = StoreCipher::generate_key();
+        let encryption = || FieldEncryption::new(StoreCipher::from_base64(&key).unwrap());
+        let chroma = chroma.with_content_hashes(true).with_field_encryption(encryption());
+        chroma.add_documents("docs", vec![doc], vec![vec![0.1]]).await.unwrap();
+
+        let metadata = sent.lock().unwrap()[1]["metadatas"][0].clone();
+        let normalized = normalize_text(&text);
+        let plain: Vec<String> = std::iter::once(normalized.as_str())
+            .chain(PREFIX_LENGTHS.into_iter().filter_map(|length| prefix(&normalized, length)))
+            .map(content_hash)
+            .collect();
+        assert_eq!(plain.len(), 3);
+        assert!(plain.iter().all(|hash| !metadata.to_string().contains(hash.as_str())), "{}", metadata);
+        assert_eq!(metadata[CONTENT_HASH_FIELD], encryption().content_hasher().hash(&normalized).as_str());
+    }
+}
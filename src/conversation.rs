@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+const DEFAULT_REWRITE_TURNS: usize = 6;
+/// Longer turns (usually answers) are cut to this many characters in the
+/// rewrite prompt; the gist is enough to resolve a reference.
+const MAX_TURN_CHARS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatTurn {
+    pub role: Role,
+    pub content: String,
+}
+
+/// The turns of a multi-turn chat, oldest first, as passed to
+/// `Pipeline::answer_in_conversation`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Conversation {
+    pub turns: Vec<ChatTurn>,
+}
+
+impl Conversation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, role: Role, content: impl Into<String>) {
+        self.turns.push(ChatTurn { role, content: content.into() });
+    }
+
+    /// The last `n` turns.
+    pub fn recent(&self, n: usize) -> &[ChatTurn] {
+        &self.turns[self.turns.len().saturating_sub(n)..]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.turns.is_empty()
+    }
+}
+
+/// Settings for rewriting follow-up questions ("what about its memory
+/// safety?") into standalone queries before retrieval, since the follow-up
+/// alone matches nothing useful. Enable with `Pipeline::with_query_rewriting`;
+/// the first question of a conversation is never rewritten.
+#[derive(Debug, Clone)]
+pub struct RewriteConfig {
+    /// Recent turns shown to the model.
+    pub max_turns: usize,
+    /// Prompt template to use; expects `history` and `question`.
+    pub template: String,
+}
+
+impl Default for RewriteConfig {
+    fn default() -> Self {
+        Self {
+            max_turns: DEFAULT_REWRITE_TURNS,
+            template: crate::prompt::REWRITE_TEMPLATE.to_string(),
+        }
+    }
+}
+
+impl RewriteConfig {
+    pub fn with_max_turns(mut self, max_turns: usize) -> Self {
+        self.max_turns = max_turns.max(1);
+        self
+    }
+
+    pub fn with_template(mut self, template: &str) -> Self {
+        self.template = template.to_string();
+        self
+    }
+}
+
+/// `User: …` / `Assistant: …` lines for the rewrite prompt.
+pub(crate) fn format_history(turns: &[ChatTurn]) -> String {
+    let mut history = String::new();
+    for turn in turns {
+        let speaker = match turn.role {
+            Role::User => "User",
+            Role::Assistant => "Assistant",
+        };
+        let content: String = turn.content.trim().chars().take(MAX_TURN_CHARS).collect();
+        let _ = writeln!(history, "{}: {}", speaker, content.replace('\n', " "));
+    }
+    history.trim_end().to_string()
+}
+
+/// The standalone question in a model's reply: its first non-empty line,
+/// without a `Standalone question:` label or wrapping quotes. `None` if the
+/// reply has no usable text.
+pub(crate) fn clean_rewrite(reply: &str) -> Option<String> {
+    let line = reply.lines().map(str::trim).find(|line| !line.is_empty())?;
+    let line = line.strip_prefix("Standalone question:").unwrap_or(line).trim();
+    let line = line.trim_matches(|c| matches!(c, '"' | '\'' | '`' | '“' | '”')).trim();
+    (!line.is_empty()).then(|| line.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    use crate::error::Result;
+    use crate::llm::{Generation, GenerationRequest, LlmProvider};
+    use crate::pipeline::Pipeline;
+    use crate::query::QueryOptions;
+    use crate::test_support::{FixedEmbeddings, mock_chroma};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_follow_up_questions_are_rewritten_before_retrieval() {
+        use std::sync::Mutex;
+
+        struct EchoLlm(Mutex<Vec<String>>);
+
+        impl LlmProvider for EchoLlm {
+            fn generate<'a>(&'a self, request: &'a GenerationRequest) -> futures::future::BoxFuture<'a, Result<Generation>> {
+                self.0.lock().unwrap().push(request.prompt.clone());
+                let text = if request.prompt.ends_with("Standalone question:") {
+                    "Standalone question: \"What about Rust's memory safety?\"\n".to_string()
+                } else {
+                    let question = request.prompt.lines().find_map(|l| l.strip_prefix("Question: ")).unwrap_or_default();
+                    format!("About: {}", question)
+                };
+                Box::pin(async move { Ok(Generation { text, model: "echo".to_string(), prompt_tokens: None, completion_tokens: None }) })
+            }
+
+            fn model_name(&self) -> &str {
+                "echo"
+            }
+        }
+
+        let chroma = Arc::new(mock_chroma(|request| {
+            if request.uri().path().ends_with("/query") {
+                r#"{"ids": [["a"]], "distances": [[0.1]], "documents": [["Rust has no GC."]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        }));
+        let llm = Arc::new(EchoLlm(Mutex::new(Vec::new())));
+        let pipeline = Pipeline::new(chroma, Arc::new(FixedEmbeddings), "docs")
+            .with_llm(llm.clone())
+            .with_query_rewriting(RewriteConfig::default().with_max_turns(2));
+
+        let mut conversation = Conversation::new();
+        let first = pipeline.answer_in_conversation(&mut conversation, "Tell me about Rust", &QueryOptions::new(1)).await.unwrap();
+        assert_eq!(first.standalone_question, None);
+        assert_eq!(llm.0.lock().unwrap().len(), 1);
+
+        let follow_up = pipeline.answer_in_conversation(&mut conversation, "what about its memory safety?", &QueryOptions::new(1)).await.unwrap();
+        assert_eq!(follow_up.standalone_question.as_deref(), Some("What about Rust's memory safety?"));
+        assert_eq!(follow_up.text, "About: What about Rust's memory safety?");
+        assert_eq!(conversation.turns.len(), 4);
+        assert_eq!(conversation.turns[2], ChatTurn { role: Role::User, content: "what about its memory safety?".to_string() });
+
+        let prompts = llm.0.lock().unwrap();
+        assert!(prompts[1].contains("User: Tell me about Rust\nAssistant: About: Tell me about Rust"));
+    }
+}
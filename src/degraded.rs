@@ -0,0 +1,353 @@
+use crate::error::{ChromaError, Result};
+use crate::models::{Document, QueryHit};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+
+const DEFAULT_KEYWORD_CANDIDATES: u32 = 1000;
+const DEFAULT_OUTBOX_CAPACITY: usize = 10_000;
+/// Query terms sent to Chroma as `$contains` clauses; the rest only count
+/// towards scoring.
+const MAX_KEYWORD_TERMS: usize = 16;
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "does", "for", "from", "how", "in", "is", "it", "of", "on",
+    "or", "that", "the", "this", "to", "was", "what", "when", "where", "which", "who", "why", "with",
+];
+
+/// What a pipeline does when the embedding provider is down, instead of
+/// failing (enable with `Pipeline::with_degraded_mode`):
+///
+/// - queries fall back to keyword search: candidates containing a query
+///   term are fetched from Chroma and ranked with BM25, and the result is
+///   flagged `degraded`. Not with field encryption, where Chroma only holds
+///   ciphertext to search: those queries still fail;
+/// - `Pipeline::ingest_or_queue` parks documents in an in-memory outbox until
+///   `Pipeline::flush_outbox` succeeds. The outbox doesn't survive a
+///   restart.
+///
+/// Only outages count (transport, API and embedding errors); invalid input
+/// and configuration errors still fail.
+#[derive(Debug, Clone)]
+pub struct DegradedMode {
+    /// Documents fetched for keyword ranking. IDF is computed over these
+    /// candidates only, so scores are approximate.
+    pub keyword_candidates: u32,
+    /// Documents the outbox holds before ingestion fails outright.
+    pub outbox_capacity: usize,
+}
+
+impl Default for DegradedMode {
+    fn default() -> Self {
+        Self {
+            keyword_candidates: DEFAULT_KEYWORD_CANDIDATES,
+            outbox_capacity: DEFAULT_OUTBOX_CAPACITY,
+        }
+    }
+}
+
+impl DegradedMode {
+    pub fn with_keyword_candidates(mut self, candidates: u32) -> Self {
+        self.keyword_candidates = candidates.max(1);
+        self
+    }
+
+    pub fn with_outbox_capacity(mut self, capacity: usize) -> Self {
+        self.outbox_capacity = capacity;
+        self
+    }
+}
+
+/// Outcome of `Pipeline::ingest_or_queue`.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct IngestOutcome {
+    pub upserted: usize,
+    /// Documents parked in the outbox because the embedding provider is
+    /// down.
+    pub queued: usize,
+    pub degraded: bool,
+}
+
+/// Documents waiting for the embedding provider to come back, oldest first.
+#[derive(Debug)]
+pub(crate) struct Outbox {
+    capacity: usize,
+    documents: Mutex<VecDeque<Document>>,
+}
+
+impl Outbox {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            documents: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Queue `documents`, replacing queued versions with the same id. All or
+    /// nothing: fails if they don't fit.
+    pub(crate) fn push(&self, documents: Vec<Document>, cause: &ChromaError) -> Result<()> {
+        let mut queued = self.documents.lock().unwrap();
+        let incoming: HashSet<&str> = documents.iter().map(|d| d.id.as_str()).collect();
+        let kept = queued.iter().filter(|d| !incoming.contains(d.id.as_str())).count();
+        if kept + documents.len() > self.capacity {
+            return Err(ChromaError::EmbeddingError(format!(
+                "Embedding provider unavailable and the outbox is full ({} documents): {}",
+                self.capacity, cause
+            )));
+        }
+        queued.retain(|d| !incoming.contains(d.id.as_str()));
+        queued.extend(documents);
+        Ok(())
+    }
+
+    /// Take up to `n` documents off the front.
+    pub(crate) fn take(&self, n: usize) -> Vec<Document> {
+        let mut queued = self.documents.lock().unwrap();
+        let n = n.min(queued.len());
+        queued.drain(..n).collect()
+    }
+
+    /// Put documents that failed to flush back at the front, unless a newer
+    /// version was queued meanwhile.
+    pub(crate) fn restore(&self, documents: Vec<Document>) {
+        let mut queued = self.documents.lock().unwrap();
+        let newer: HashSet<String> = queued.iter().map(|d| d.id.clone()).collect();
+        for document in documents.into_iter().rev() {
+            if !newer.contains(&document.id) {
+                queued.push_front(document);
+            }
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.documents.lock().unwrap().len()
+    }
+}
+
+/// Whether an embedding failure means the provider is unavailable, as
+/// opposed to a request it will never accept.
+pub(crate) fn is_provider_outage(error: &ChromaError) -> bool {
+    matches!(
+        error,
+        ChromaError::RequestError(_)
+            | ChromaError::TransportError(_)
+            | ChromaError::ConnectionError(_)
+            | ChromaError::ApiError(_)
+            | ChromaError::EmbeddingError(_)
+    )
+}
+
+/// Lowercased alphanumeric words of `text`.
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Distinct query terms, without stopwords unless that leaves nothing.
+fn query_terms(query: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let words: Vec<String> = tokenize(query).into_iter().filter(|word| seen.insert(word.clone())).collect();
+    let content: Vec<String> = words.iter().filter(|word| !STOPWORDS.contains(&word.as_str())).cloned().collect();
+    if content.is_empty() { words } else { content }
+}
+
+/// A `where_document` filter matching documents that contain any query
+/// term. Chroma's `$contains` is case-sensitive, so each term is matched as
+/// typed and lowercased. `None` if the query has no words.
+pub(crate) fn keyword_filter(query: &str) -> Option<Value> {
+    let terms = query_terms(query);
+    let mut clauses = Vec::new();
+    let mut seen = HashSet::new();
+    for word in query.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+        if terms.contains(&word.to_lowercase()) {
+            for variant in [word.to_string(), word.to_lowercase()] {
+                if seen.insert(variant.clone()) {
+                    clauses.push(json!({ "$contains": variant }));
+                }
+            }
+        }
+    }
+    clauses.truncate(MAX_KEYWORD_TERMS);
+    match clauses.len() {
+        0 => None,
+        1 => clauses.pop(),
+        _ => Some(json!({ "$or": clauses })),
+    }
+}
+
+/// Rank `hits` by Okapi BM25 against `query`, best first. Hits sharing no
+/// term with the query are dropped. Scores replace `score`; `distance` is
+/// set to `f32::MAX`, as there is no vector distance.
+pub fn bm25_rank(query: &str, hits: Vec<QueryHit>) -> Vec<QueryHit> {
+    let terms = query_terms(query);
+    let documents: Vec<Vec<String>> = hits
+        .iter()
+        .map(|hit| tokenize(hit.document.as_deref().unwrap_or_default()))
+        .collect();
+    if terms.is_empty() || documents.is_empty() {
+        return Vec::new();
+    }
+
+    let total = documents.len() as f32;
+    let average_len = (documents.iter().map(Vec::len).sum::<usize>() as f32 / total).max(1.0);
+    let document_frequency: HashMap<&str, usize> = terms
+        .iter()
+        .map(|term| (term.as_str(), documents.iter().filter(|words| words.contains(term)).count()))
+        .collect();
+
+    let mut ranked: Vec<QueryHit> = hits
+        .into_iter()
+        .zip(&documents)
+        .filter_map(|(mut hit, words)| {
+            let mut score = 0.0;
+            for term in &terms {
+                let frequency = words.iter().filter(|word| *word == term).count() as f32;
+                if frequency == 0.0 {
+                    continue;
+                }
+                let df = document_frequency[term.as_str()] as f32;
+                let idf = (1.0 + (total - df + 0.5) / (df + 0.5)).ln();
+                let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * words.len() as f32 / average_len);
+                score += idf * frequency * (BM25_K1 + 1.0) / (frequency + norm);
+            }
+            (score > 0.0).then(|| {
+                hit.score = score;
+                hit.distance = f32::MAX;
+                hit
+            })
+        })
+        .collect();
+    ranked.sort_by(QueryHit::rank_cmp);
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::EmbeddingProvider;
+    use crate::pipeline::Pipeline;
+    use crate::query::QueryOptions;
+    use crate::test_support::mock_chroma;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_degraded_mode_falls_back_to_keywords_and_queues_ingests() {
+        use std::sync::Mutex;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        struct FlakyEmbeddings(Arc<AtomicBool>);
+
+        impl EmbeddingProvider for FlakyEmbeddings {
+            fn embed<'a>(&'a self, texts: &'a [&'a str]) -> futures::future::BoxFuture<'a, Result<Vec<Vec<f32>>>> {
+                let down = self.0.load(Ordering::SeqCst);
+                Box::pin(async move {
+                    if down {
+                        return Err(ChromaError::EmbeddingError("503 Service Unavailable".to_string()));
+                    }
+                    Ok(texts.iter().map(|t| vec![t.len() as f32]).collect())
+                })
+            }
+
+            fn model_name(&self) -> &str {
+                "flaky"
+            }
+        }
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+        let chroma = mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            seen.lock().unwrap().push((path.clone(), request.body().clone()));
+            if path.ends_with("/query") {
+                r#"{"ids": [["a"]], "distances": [[0.1]]}"#
+            } else if path.ends_with("/get") {
+                r#"{"ids": ["a", "b", "c"], "documents": ["Rust has no garbage collector.", "Rust and Rust again: memory safety in Rust.", "Go has a garbage collector."], "metadatas": [null, null, null]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+        let down = Arc::new(AtomicBool::new(true));
+        let embedder = Arc::new(FlakyEmbeddings(down.clone()));
+
+        let strict = Pipeline::new(Arc::new(chroma.clone()), embedder.clone(), "docs");
+        assert!(strict.query("rust memory", &QueryOptions::new(2)).await.is_err());
+
+        let pipeline = Pipeline::new(Arc::new(chroma), embedder, "docs").with_degraded_mode(DegradedMode::default());
+        let result = pipeline.query("Rust memory", &QueryOptions::new(2)).await.unwrap();
+        assert!(result.degraded);
+        let ids: Vec<&str> = result.hits.iter().map(|hit| hit.id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "a"]);
+        let get_body: serde_json::Value = {
+            let requests = requests.lock().unwrap();
+            let (_, body) = requests.iter().find(|(path, _)| path.ends_with("/get")).unwrap();
+            serde_json::from_slice(body).unwrap()
+        };
+        assert_eq!(get_body["where_document"]["$or"][0], serde_json::json!({ "$contains": "Rust" }));
+
+        let document = |id: &str| Document { id: id.to_string(), content: "text".to_string(), metadata: HashMap::new(), uri: None };
+        let outcome = pipeline.ingest_or_queue(vec![document("d1"), document("d2")]).await.unwrap();
+        assert_eq!((outcome.upserted, outcome.queued, outcome.degraded), (0, 2, true));
+        assert_eq!(pipeline.outbox_len(), 2);
+        assert!(pipeline.flush_outbox().await.is_err());
+        assert_eq!(pipeline.outbox_len(), 2);
+
+        down.store(false, Ordering::SeqCst);
+        assert_eq!(pipeline.flush_outbox().await.unwrap(), 2);
+        assert_eq!(pipeline.outbox_len(), 0);
+        assert!(requests.lock().unwrap().iter().any(|(path, _)| path.ends_with("/upsert")));
+        assert!(!pipeline.query("Rust memory", &QueryOptions::new(2)).await.unwrap().degraded);
+    }
+
+    #[tokio::test]
+    async fn test_keyword_fallback_keeps_acl_filters_and_skips_encrypted_collections() {
+        use crate::encryption::{FieldEncryption, StoreCipher};
+        use std::sync::Mutex;
+
+        struct DownEmbeddings;
+
+        impl EmbeddingProvider for DownEmbeddings {
+            fn embed<'a>(&'a self, _: &'a [&'a str]) -> futures::future::BoxFuture<'a, Result<Vec<Vec<f32>>>> {
+                Box::pin(async { Err(ChromaError::EmbeddingError("503 Service Unavailable".to_string())) })
+            }
+
+            fn model_name(&self) -> &str {
+                "down"
+            }
+        }
+
+        let gets = Arc::new(Mutex::new(Vec::new()));
+        let seen = gets.clone();
+        let chroma = mock_chroma(move |request| {
+            if request.uri().path().ends_with("/get") {
+                seen.lock().unwrap().push(serde_json::from_slice::<serde_json::Value>(request.body()).unwrap());
+                r#"{"ids": ["a"], "documents": ["Rust payroll"], "metadatas": [{"acl": "hr"}]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+        let options = QueryOptions::new(2).with_filter(serde_json::json!({"lang": "en"})).with_acl_groups(["hr"]);
+
+        let pipeline = Pipeline::new(Arc::new(chroma.clone()), Arc::new(DownEmbeddings), "docs")
+            .with_degraded_mode(DegradedMode::default());
+        assert!(pipeline.query("Rust", &options).await.unwrap().degraded);
+        assert_eq!(gets.lock().unwrap()[0]["where"], options.request_filter().unwrap());
+        assert!(gets.lock().unwrap()[0]["where"]["$and"][1]["$or"].is_array());
+
+        let encryption = FieldEncryption::new(StoreCipher::from_base64(&StoreCipher::generate_key()).unwrap());
+        let encrypted = Pipeline::new(Arc::new(chroma.with_field_encryption(encryption)), Arc::new(DownEmbeddings), "docs")
+            .with_degraded_mode(DegradedMode::default());
+        let error = encrypted.query("Rust", &options).await.unwrap_err();
+        assert!(matches!(error, ChromaError::EmbeddingError(_)), "{}", error);
+        assert_eq!(gets.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::error::{ChromaError, Result};
+use crate::models::{GetRequest, Include};
+use crate::pipeline::Pipeline;
+use crate::vector_ops::cosine_similarity;
+use serde::Serialize;
+use tracing::warn;
+
+const DEFAULT_SAMPLE_SIZE: u32 = 50;
+/// Re-embedding the same text with the same model is near-deterministic;
+/// anything much below this means the vectors no longer line up.
+const DEFAULT_MIN_MEAN_COSINE: f32 = 0.98;
+
+/// Sample size and alert threshold for `Pipeline::check_drift`.
+#[derive(Debug, Clone)]
+pub struct DriftConfig {
+    pub sample_size: u32,
+    /// Below this mean cosine between stored and fresh vectors the model is
+    /// considered to have drifted.
+    pub min_mean_cosine: f32,
+}
+
+impl Default for DriftConfig {
+    fn default() -> Self {
+        Self {
+            sample_size: DEFAULT_SAMPLE_SIZE,
+            min_mean_cosine: DEFAULT_MIN_MEAN_COSINE,
+        }
+    }
+}
+
+impl DriftConfig {
+    pub fn with_sample_size(mut self, sample_size: u32) -> Self {
+        self.sample_size = sample_size.max(1);
+        self
+    }
+
+    pub fn with_min_mean_cosine(mut self, min_mean_cosine: f32) -> Self {
+        self.min_mean_cosine = min_mean_cosine;
+        self
+    }
+}
+
+/// How far freshly computed embeddings are from the stored ones.
+#[derive(Debug, Clone, Serialize)]
+pub struct DriftReport {
+    pub model: String,
+    pub sampled: usize,
+    pub mean_cosine: f32,
+    pub min_cosine: f32,
+    /// Documents whose fresh embedding has a different dimension than the
+    /// stored one; these count as cosine 0.
+    pub dimension_mismatches: usize,
+    pub min_mean_cosine: f32,
+    pub drifted: bool,
+}
+
+/// Re-embed a sample of stored documents with the pipeline's current model
+/// and compare against their stored vectors.
+pub(crate) async fn check(pipeline: &Pipeline, config: &DriftConfig) -> Result<DriftReport> {
+    let request = GetRequest {
+        limit: Some(config.sample_size),
+        include: Some(vec![Include::Documents, Include::Embeddings]),
+        ..GetRequest::default()
+    };
+    let response = pipeline.chroma().send_get(pipeline.collection(), &request).await?;
+
+    let mut texts = Vec::new();
+    let mut stored = Vec::new();
+    for i in 0..response.ids_for(0).len() {
+        if let (Some(text), Some(embedding)) = (response.document(0, i), response.embedding(0, i)) {
+            texts.push(text);
+            stored.push(embedding);
+        }
+    }
+    if texts.is_empty() {
+        return Err(ChromaError::ValidationError(format!(
+            "No documents with stored text and embeddings in '{}' to check for drift",
+            pipeline.collection()
+        )));
+    }
+
+    let fresh = pipeline.embedder().embed(&texts).await?;
+    let mut dimension_mismatches = 0;
+    let similarities: Vec<f32> = stored
+        .iter()
+        .zip(&fresh)
+        .map(|(stored, fresh)| {
+            if stored.len() == fresh.len() {
+                cosine_similarity(stored, fresh)
+            } else {
+                dimension_mismatches += 1;
+                0.0
+            }
+        })
+        .collect();
+
+    let mean_cosine = similarities.iter().sum::<f32>() / similarities.len() as f32;
+    let min_cosine = similarities.iter().copied().fold(f32::INFINITY, f32::min);
+    let drifted = mean_cosine < config.min_mean_cosine || dimension_mismatches > 0;
+    if drifted {
+        warn!(
+            "Embedding drift in '{}': mean cosine {:.4} over {} documents ({} dimension mismatches)",
+            pipeline.collection(), mean_cosine, similarities.len(), dimension_mismatches
+        );
+    }
+
+    Ok(DriftReport {
+        model: pipeline.embedder().model_name().to_string(),
+        sampled: similarities.len(),
+        mean_cosine,
+        min_cosine,
+        dimension_mismatches,
+        min_mean_cosine: config.min_mean_cosine,
+        drifted,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{FixedEmbeddings, mock_chroma};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_drift_check_compares_fresh_and_stored_vectors() {
+        // FixedEmbeddings embeds "ab" as [2.0]; one stored vector points the other way.
+        let chroma = mock_chroma(|request| {
+            if request.uri().path().ends_with("/get") {
+                r#"{"ids": ["a", "b"], "documents": ["ab", "ab"], "embeddings": [[1.0], [-1.0]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+        let pipeline = Pipeline::new(Arc::new(chroma), Arc::new(FixedEmbeddings), "docs");
+
+        let report = pipeline.check_drift(&DriftConfig::default().with_sample_size(2)).await.unwrap();
+        assert_eq!(report.sampled, 2);
+        assert_eq!(report.mean_cosine, 0.0);
+        assert_eq!(report.min_cosine, -1.0);
+        assert!(report.drifted);
+
+        let lenient = DriftConfig::default().with_min_mean_cosine(-0.5);
+        assert!(!pipeline.check_drift(&lenient).await.unwrap().drifted);
+    }
+}
@@ -1,15 +1,29 @@
+use crate::chroma_client::validate_url;
 use crate::error::{ChromaError, Result};
+use crate::http_client::{ClientIdentity, HttpClientFactory};
+use crate::middleware::{Middleware, Next, with_attempt};
+use crate::schema::{self, KnownFields, SchemaMode};
+use crate::transport::Transport;
+use futures::future::BoxFuture;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use std::sync::Arc;
 use std::time::Duration;
 use tracing::{debug, info, warn};
 
-const GEMINI_API_BASE: &str = "https://generativelanguage.googleapis.com/v1beta";
-const EMBEDDING_MODEL: &str = "models/gemini-embedding-exp-03-07";
+const DEFAULT_GEMINI_API_BASE: &str = "https://generativelanguage.googleapis.com";
+const DEFAULT_GEMINI_API_VERSION: &str = "v1beta";
+const DEFAULT_EMBEDDING_MODEL: &str = "gemini-embedding-exp-03-07";
 const MAX_BATCH_SIZE: usize = 100; // Conservative batch limit  // 10
 const EMBEDDING_DIMENSION: usize = 3072; // Updated based on actual Gemini response
 
+/// Retired embedding models and their successors.
+const MODEL_REPLACEMENTS: &[(&str, &str)] = &[
+    ("models/embedding-001", "models/gemini-embedding-001"),
+    ("models/text-embedding-004", "models/gemini-embedding-001"),
+    ("models/gemini-embedding-exp-03-07", "models/gemini-embedding-001"),
+];
+
 #[derive(Debug, Serialize)]
 struct EmbedRequest {
     requests: Vec<EmbedContentRequest>,
@@ -34,36 +48,77 @@ struct Part {
 }
 
 #[derive(Debug, Deserialize)]
-struct EmbedResponse {
-    embeddings: Vec<ContentEmbedding>,
+struct ContentEmbedding {
+    values: Vec<f32>,
 }
 
 #[derive(Debug, Deserialize)]
-struct ContentEmbedding {
-    values: Vec<f32>,
+struct EmbedContentResponse {
+    embedding: ContentEmbedding,
+}
+
+impl KnownFields for EmbedContentResponse {
+    const NAME: &'static str = "Gemini embedContent";
+    const FIELDS: &'static [&'static str] = &["embedding"];
 }
 
+/// Gemini embedding client. Cheap to clone; clones share one set of settings
+/// and the HTTP connection pool.
+#[derive(Clone)]
 pub struct EmbeddingClient {
+    inner: Arc<EmbeddingClientInner>,
+}
+
+#[derive(Clone)]
+struct EmbeddingClientInner {
     client: Client,
+    base_url: String,
+    api_version: String,
+    model: String,
     api_key: String,
     max_retries: u32,
     retry_delay: Duration,
+    schema_mode: SchemaMode,
+    expected_dimension: usize,
+    strict_dimensions: bool,
+    identity: ClientIdentity,
+    middleware: Vec<Arc<dyn Middleware>>,
+    transport: Transport,
 }
 
 impl EmbeddingClient {
+    /// # Panics
+    ///
+    /// Panics if the configuration is invalid, including a malformed
+    /// `GEMINI_API_BASE` or `CLIENT_APP_ID`; see `try_new`.
+    #[deprecated(since = "0.1.0", note = "panics on invalid configuration; use `try_new`")]
     pub fn new(api_key: String) -> Self {
-        let timeout = Duration::from_millis(
-            std::env::var("REQUEST_TIMEOUT_MS")
-                .unwrap_or_else(|_| "60000".to_string())
-                .parse()
-                .unwrap_or(60000)
-        );
+        Self::try_new(api_key).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Create a client, rejecting an empty API key or invalid base URL with a
+    /// `ChromaError::ConfigError`.
+    ///
+    /// The endpoint defaults to Google's public `v1beta` API and can be pointed
+    /// elsewhere (regional endpoints, proxies such as LiteLLM) with
+    /// `GEMINI_API_BASE`, `GEMINI_API_VERSION` and `GEMINI_EMBEDDING_MODEL`, or
+    /// the matching `with_*` methods. `GEMINI_EMBEDDING_DIMENSION` sets the
+    /// expected vector size and `EMBEDDING_STRICT_DIMENSIONS=false` downgrades
+    /// mismatches to warnings.
+    pub fn try_new(api_key: String) -> Result<Self> {
+        if api_key.trim().is_empty() {
+            return Err(ChromaError::ConfigError("Gemini API key is empty".to_string()));
+        }
 
-        let client = Client::builder()
-            .timeout(timeout)
-            .connection_verbose(true)
-            .build()
-            .expect("Failed to create HTTP client");
+        let base_url = validate_url(
+            &std::env::var("GEMINI_API_BASE").unwrap_or_else(|_| DEFAULT_GEMINI_API_BASE.to_string()),
+        )?;
+        let api_version = std::env::var("GEMINI_API_VERSION")
+            .unwrap_or_else(|_| DEFAULT_GEMINI_API_VERSION.to_string());
+        let model = std::env::var("GEMINI_EMBEDDING_MODEL")
+            .unwrap_or_else(|_| DEFAULT_EMBEDDING_MODEL.to_string());
+
+        let client = HttpClientFactory::shared()?;
 
         let max_retries = std::env::var("MAX_RETRIES")
             .unwrap_or_else(|_| "3".to_string())
@@ -77,14 +132,120 @@ impl EmbeddingClient {
                 .unwrap_or(1000)
         );
 
-        Self {
-            client,
-            api_key,
-            max_retries,
-            retry_delay,
+        let expected_dimension = std::env::var("GEMINI_EMBEDDING_DIMENSION")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(EMBEDDING_DIMENSION);
+        let strict_dimensions = std::env::var("EMBEDDING_STRICT_DIMENSIONS")
+            .map(|v| !matches!(v.trim(), "0" | "false"))
+            .unwrap_or(true);
+
+        let transport = Transport::reqwest(client.clone());
+
+        Ok(Self {
+            inner: Arc::new(EmbeddingClientInner {
+                client,
+                base_url,
+                api_version: api_version.trim_matches('/').to_string(),
+                model: normalize_model(&model),
+                api_key,
+                max_retries,
+                retry_delay,
+                schema_mode: SchemaMode::from_env(),
+                expected_dimension,
+                strict_dimensions,
+                identity: ClientIdentity::from_env()?,
+                middleware: crate::middleware::from_env(),
+                transport,
+            }),
+        })
+    }
+
+    /// Settings for a `with_*` builder method, copied first if the client
+    /// has been cloned.
+    fn inner_mut(&mut self) -> &mut EmbeddingClientInner {
+        Arc::make_mut(&mut self.inner)
+    }
+
+    /// Send requests to `base_url` (scheme and host, without the API version).
+    pub fn with_base_url(mut self, base_url: &str) -> Result<Self> {
+        self.inner_mut().base_url = validate_url(base_url)?;
+        Ok(self)
+    }
+
+    /// API version path segment, e.g. `v1beta` or `v1`.
+    pub fn with_api_version(mut self, api_version: &str) -> Self {
+        self.inner_mut().api_version = api_version.trim_matches('/').to_string();
+        self
+    }
+
+    /// Embedding model, with or without the `models/` prefix.
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.inner_mut().model = normalize_model(model);
+        self
+    }
+
+    /// Vector size the model is expected to return (default 3072).
+    pub fn with_expected_dimension(mut self, expected_dimension: usize) -> Self {
+        self.inner_mut().expected_dimension = expected_dimension;
+        self
+    }
+
+    /// Fail with `ChromaError::DimensionMismatch` when the API returns a
+    /// vector of another size (the default), or only log a warning.
+    pub fn with_strict_dimensions(mut self, strict: bool) -> Self {
+        self.inner_mut().strict_dimensions = strict;
+        self
+    }
+
+    /// Fully qualified model name, e.g. `models/gemini-embedding-exp-03-07`.
+    pub fn model(&self) -> &str {
+        &self.inner.model
+    }
+
+    fn endpoint(&self, method: &str) -> String {
+        if self.inner.api_version.is_empty() {
+            format!("{}/{}:{}", self.inner.base_url, self.inner.model, method)
+        } else {
+            format!("{}/{}/{}:{}", self.inner.base_url, self.inner.api_version, self.inner.model, method)
         }
     }
 
+    /// Identify the deploying application on every request: adds an
+    /// `X-Client-App` header and appends `app_id` to the `User-Agent`.
+    /// Overrides `CLIENT_APP_ID`.
+    pub fn with_app_id(mut self, app_id: &str) -> Result<Self> {
+        let identity = self.inner.identity.clone().with_app_id(app_id)?;
+        self.inner_mut().identity = identity;
+        Ok(self)
+    }
+
+    /// Run every request through `middleware`, in the order added.
+    pub fn with_middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.inner_mut().middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Use an app-managed `reqwest::Client` (and its connection pool) instead of
+    /// the shared one. Replaces any custom transport.
+    pub fn with_http_client(mut self, client: Client) -> Self {
+        self.inner_mut().transport = Transport::reqwest(client.clone());
+        self.inner_mut().client = client;
+        self
+    }
+
+    /// Send requests through `transport` instead of the built-in reqwest client.
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.inner_mut().transport = transport;
+        self
+    }
+
+    /// How to handle response fields the models don't know (see `SchemaMode`).
+    pub fn with_schema_mode(mut self, schema_mode: SchemaMode) -> Self {
+        self.inner_mut().schema_mode = schema_mode;
+        self
+    }
+
     pub async fn embed_text(&self, text: &str) -> Result<Vec<f32>> {
         self.embed_texts(&[text])
             .await?
@@ -116,7 +277,7 @@ impl EmbeddingClient {
         let requests: Vec<EmbedContentRequest> = texts
             .iter()
             .map(|text| EmbedContentRequest {
-                model: EMBEDDING_MODEL.to_string(),
+                model: self.inner.model.clone(),
                 content: Content {
                     parts: vec![Part {
                         text: text.to_string(),
@@ -130,23 +291,24 @@ impl EmbeddingClient {
 
         let mut retries = 0;
         loop {
-            match self.call_embedding_api(&request_body).await {
+            match with_attempt(retries, self.call_embedding_api(&request_body)).await {
                 Ok(embeddings) => {
                     debug!("Successfully generated {} embeddings", embeddings.len());
                     return Ok(embeddings);
                 }
-                Err(e) if retries < self.max_retries => {
+                Err(e @ (ChromaError::ModelDeprecated { .. } | ChromaError::DimensionMismatch { .. })) => return Err(e),
+                Err(e) if retries < self.inner.max_retries => {
                     retries += 1;
                     warn!(
                         "Embedding request failed (attempt {}/{}): {}. Retrying in {:?}",
-                        retries, self.max_retries + 1, e, self.retry_delay
+                        retries, self.inner.max_retries.saturating_add(1), e, self.inner.retry_delay
                     );
-                    tokio::time::sleep(self.retry_delay * retries).await;
+                    tokio::time::sleep(self.inner.retry_delay.saturating_mul(retries)).await;
                 }
                 Err(e) => {
                     return Err(ChromaError::EmbeddingError(format!(
                         "Failed to generate embeddings after {} retries: {}",
-                        self.max_retries, e
+                        self.inner.max_retries, e
                     )));
                 }
             }
@@ -158,20 +320,21 @@ impl EmbeddingClient {
         
         // Process each request individually (following working rag.rs pattern)
         for embed_request in &request.requests {
-            let url = format!("{}:embedContent", embed_request.model);
-            let full_url = format!("{}/{}?key={}", GEMINI_API_BASE, url, self.api_key);
+            let full_url = format!("{}?key={}", self.endpoint("embedContent"), self.inner.api_key);
             
             let request_body = serde_json::json!({
                 "content": embed_request.content
             });
 
-            let response = self
+            let mut http_request = self
+                .inner
                 .client
                 .post(&full_url)
                 .header("Content-Type", "application/json")
                 .json(&request_body)
-                .send()
-                .await?;
+                .build()?;
+            self.inner.identity.apply(&mut http_request);
+            let response = Next::new(&self.inner.transport, &self.inner.middleware).run(http_request).await?;
 
             // Add delay between requests to avoid rate limiting (from rag.rs)
             tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -179,6 +342,12 @@ impl EmbeddingClient {
             if !response.status().is_success() {
                 let status = response.status();
                 let error_text = response.text().await.unwrap_or_default();
+                if is_model_unavailable(status, &error_text, &self.inner.model) {
+                    return Err(ChromaError::ModelDeprecated {
+                        model: self.inner.model.clone(),
+                        replacement: suggested_replacement(&self.inner.model).map(str::to_string),
+                    });
+                }
                 return Err(ChromaError::EmbeddingError(format!(
                     "Gemini API error {}: {}",
                     status, error_text
@@ -186,19 +355,24 @@ impl EmbeddingClient {
             }
 
             let response_json: serde_json::Value = response.json().await?;
-            
-            let embedding_values = response_json["embedding"]["values"]
-                .as_array()
-                .ok_or_else(|| ChromaError::EmbeddingError("Invalid embedding response format".to_string()))?
-                .iter()
-                .map(|v| v.as_f64().unwrap_or(0.0) as f32)
-                .collect::<Vec<f32>>();
-
-            if embedding_values.len() != EMBEDDING_DIMENSION {
+
+            // Typed parsing: a non-numeric value is an error, not a silent 0.0.
+            let parsed: EmbedContentResponse = schema::decode(response_json, self.inner.schema_mode)?;
+            let embedding_values = parsed.embedding.values;
+
+            let expected = self.inner.expected_dimension;
+            if embedding_values.len() != expected {
+                if self.inner.strict_dimensions {
+                    return Err(ChromaError::DimensionMismatch {
+                        model: self.inner.model.clone(),
+                        expected,
+                        actual: embedding_values.len(),
+                    });
+                }
                 warn!(
                     "Unexpected embedding dimension: {} (expected {})",
                     embedding_values.len(),
-                    EMBEDDING_DIMENSION
+                    expected
                 );
             }
             
@@ -212,3 +386,135 @@ impl EmbeddingClient {
         EMBEDDING_DIMENSION
     }
 }
+
+/// Anything that turns texts into embedding vectors, so pipelines can swap
+/// Gemini for another provider (or a stub in tests).
+pub trait EmbeddingProvider: Send + Sync {
+    fn embed<'a>(&'a self, texts: &'a [&'a str]) -> BoxFuture<'a, Result<Vec<Vec<f32>>>>;
+
+    /// Model identifier, used to tell embeddings from different models apart.
+    fn model_name(&self) -> &str;
+
+    /// Service the model is served by, recorded with the model in a
+    /// collection's `EmbeddingBinding`.
+    fn provider_name(&self) -> &str {
+        "custom"
+    }
+
+    /// Dimension of the vectors `embed` returns, if known up front.
+    fn dimension(&self) -> Option<usize> {
+        None
+    }
+
+    /// Task type the provider embeds with (e.g. Gemini's
+    /// `RETRIEVAL_QUERY`), if it sends one.
+    fn task_type(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl EmbeddingProvider for EmbeddingClient {
+    fn embed<'a>(&'a self, texts: &'a [&'a str]) -> BoxFuture<'a, Result<Vec<Vec<f32>>>> {
+        Box::pin(self.embed_texts(texts))
+    }
+
+    fn model_name(&self) -> &str {
+        self.model()
+    }
+
+    fn provider_name(&self) -> &str {
+        "gemini"
+    }
+
+    fn dimension(&self) -> Option<usize> {
+        Some(self.inner.expected_dimension)
+    }
+}
+
+/// Successor of a retired embedding model, with or without the `models/`
+/// prefix.
+pub fn suggested_replacement(model: &str) -> Option<&'static str> {
+    let model = normalize_model(model);
+    MODEL_REPLACEMENTS
+        .iter()
+        .find(|(retired, _)| *retired == model)
+        .map(|(_, replacement)| *replacement)
+}
+
+/// Gemini answers 404 naming the model for unknown models and 400 with a
+/// "deprecated" or "not supported" message for retired ones. Other 404s,
+/// e.g. from a wrong base URL or a proxy, are plain HTTP errors.
+fn is_model_unavailable(status: reqwest::StatusCode, body: &str, model: &str) -> bool {
+    let body = body.to_lowercase();
+    let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+    (status == reqwest::StatusCode::NOT_FOUND && body.contains(&name))
+        || (status == reqwest::StatusCode::BAD_REQUEST
+            && (body.contains("deprecated") || body.contains("is not supported") || body.contains("not found")))
+}
+
+pub(crate) fn normalize_model(model: &str) -> String {
+    let model = model.trim_matches('/');
+    if model.starts_with("models/") || model.starts_with("tunedModels/") {
+        model.to_string()
+    } else {
+        format!("models/{}", model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_transport;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_embedding_client_creation() {
+        let client = EmbeddingClient::try_new("test_api_key".to_string()).unwrap();
+        assert_eq!(client.inner.api_key, "test_api_key");
+    }
+
+    #[tokio::test]
+    async fn test_embedding_dimension() {
+        let dimension = EmbeddingClient::get_embedding_dimension();
+        assert_eq!(dimension, 3072);
+    }
+
+    #[tokio::test]
+    async fn test_strict_mode_rejects_unexpected_dimensions() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let service = mock_transport(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            r#"{"embedding": {"values": [0.1, 0.2]}}"#
+        });
+        let embeddings = EmbeddingClient::try_new("key".to_string())
+            .unwrap()
+            .with_transport(service)
+            .with_expected_dimension(3);
+
+        match embeddings.embed_text("hi").await {
+            Err(ChromaError::DimensionMismatch { expected: 3, actual: 2, .. }) => {}
+            other => panic!("expected DimensionMismatch, got {:?}", other),
+        }
+        // A wrong dimension won't fix itself, so it isn't retried.
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let lenient = embeddings.with_strict_dimensions(false);
+        assert_eq!(lenient.embed_text("hi").await.unwrap(), vec![0.1, 0.2]);
+    }
+
+    #[test]
+    fn test_only_404s_naming_the_model_mean_it_is_unavailable() {
+        use reqwest::StatusCode;
+
+        let model = "models/gemini-embedding-exp-03-07";
+        let named = r#"{"error": {"message": "models/gemini-embedding-exp-03-07 is not found"}}"#;
+        assert!(is_model_unavailable(StatusCode::NOT_FOUND, named, model));
+        assert!(!is_model_unavailable(StatusCode::NOT_FOUND, "<html>404 Not Found</html>", model));
+        assert!(!is_model_unavailable(StatusCode::NOT_FOUND, "", model));
+        assert!(is_model_unavailable(StatusCode::BAD_REQUEST, "Model is deprecated", model));
+        assert!(!is_model_unavailable(StatusCode::INTERNAL_SERVER_ERROR, named, model));
+    }
+}
@@ -0,0 +1,371 @@
+use crate::content_hash::ContentHasher;
+use crate::error::{ChromaError, Result};
+use crate::models::{AddRequest, Document, QueryResponse};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Prefix identifying encrypted payloads, followed by a format version byte.
+const MAGIC: &[u8; 4] = b"CDBE";
+const FORMAT_VERSION: u8 = 1;
+const NONCE_LEN: usize = 12;
+/// Label the content hash key is derived under, so it differs from the
+/// encryption key it comes from.
+const CONTENT_HASH_KEY_LABEL: &[u8] = b"chromadb-demo content hash v1";
+const KEY_ENV_VAR: &str = "LOCAL_STORE_KEY";
+/// OS keyring entry (service, user) read when `LOCAL_STORE_KEY` is unset.
+#[cfg(feature = "keyring")]
+const KEYRING_ENTRY: (&str, &str) = ("chromadb-demo", KEY_ENV_VAR);
+
+/// AES-256-GCM cipher for data persisted to local disk.
+///
+/// Output layout is `MAGIC | version | nonce | ciphertext+tag`, with a fresh
+/// random nonce per call, so the same cipher can be reused for store files and
+/// export archives alike.
+#[derive(Clone)]
+pub struct StoreCipher {
+    cipher: Aes256Gcm,
+    content_hash_key: [u8; 32],
+}
+
+impl std::fmt::Debug for StoreCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StoreCipher(..)")
+    }
+}
+
+impl StoreCipher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC takes keys of any length");
+        mac.update(CONTENT_HASH_KEY_LABEL);
+        Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+            content_hash_key: mac.finalize().into_bytes().into(),
+        }
+    }
+
+    /// Build a cipher from a base64-encoded 32-byte key.
+    pub fn from_base64(key: &str) -> Result<Self> {
+        let bytes = BASE64
+            .decode(key.trim())
+            .map_err(|e| ChromaError::EncryptionError(format!("Invalid base64 key: {}", e)))?;
+
+        let key: [u8; 32] = bytes.try_into().map_err(|bytes: Vec<u8>| {
+            ChromaError::EncryptionError(format!(
+                "Encryption key must be 32 bytes, got {}",
+                bytes.len()
+            ))
+        })?;
+
+        Ok(Self::new(&key))
+    }
+
+    /// Read the key from `LOCAL_STORE_KEY`, returning `None` when it is unset so
+    /// callers can fall back to plaintext storage.
+    pub fn from_env() -> Result<Option<Self>> {
+        match std::env::var(KEY_ENV_VAR) {
+            Ok(key) if !key.trim().is_empty() => Self::from_base64(&key).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// The configured key: `LOCAL_STORE_KEY`, or else, with the `keyring`
+    /// feature, the OS keyring entry written by `save_to_keyring`. `None`
+    /// when neither holds one.
+    pub fn load() -> Result<Option<Self>> {
+        if let Some(cipher) = Self::from_env()? {
+            return Ok(Some(cipher));
+        }
+        #[cfg(feature = "keyring")]
+        return Self::from_keyring();
+        #[cfg(not(feature = "keyring"))]
+        Ok(None)
+    }
+
+    /// Read the key from the OS keyring, returning `None` when there is no
+    /// entry or no keyring to read (e.g. a headless machine without a secret
+    /// service). A stored key that doesn't decode is an error.
+    #[cfg(feature = "keyring")]
+    pub fn from_keyring() -> Result<Option<Self>> {
+        Self::from_keyring_password(keyring_entry().map(|entry| entry.get_password()))
+    }
+
+    #[cfg(feature = "keyring")]
+    fn from_keyring_password(password: Result<keyring::Result<String>>) -> Result<Option<Self>> {
+        match password {
+            Ok(Ok(key)) => Self::from_base64(&key).map(Some),
+            Ok(Err(keyring::Error::NoEntry)) => Ok(None),
+            Ok(Err(e @ keyring::Error::BadEncoding(_))) => {
+                Err(ChromaError::EncryptionError(format!("Invalid key in the keyring: {}", e)))
+            }
+            Ok(Err(e)) => {
+                tracing::warn!("OS keyring unavailable, no store key: {}", e);
+                Ok(None)
+            }
+            Err(e) => {
+                tracing::warn!("{}, no store key", e);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Store a base64-encoded key (see `generate_key`) in the OS keyring for
+    /// `load` to find.
+    #[cfg(feature = "keyring")]
+    pub fn save_to_keyring(key: &str) -> Result<()> {
+        Self::from_base64(key)?;
+        keyring_entry()?
+            .set_password(key.trim())
+            .map_err(|e| ChromaError::EncryptionError(format!("Failed to write the keyring: {}", e)))
+    }
+
+    /// Generate a new random key, base64-encoded for storing in `LOCAL_STORE_KEY`.
+    pub fn generate_key() -> String {
+        BASE64.encode(Aes256Gcm::generate_key(OsRng))
+    }
+
+    pub fn is_encrypted(data: &[u8]) -> bool {
+        data.starts_with(MAGIC)
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| ChromaError::EncryptionError("Encryption failed".to_string()))?;
+
+        let mut out = Vec::with_capacity(MAGIC.len() + 1 + NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(MAGIC);
+        out.push(FORMAT_VERSION);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    /// Encrypt `text` as `enc:v1:` followed by base64, for values that must
+    /// stay text: document fields sent to Chroma and export lines.
+    pub fn encrypt_text(&self, text: &str) -> Result<String> {
+        Ok(format!("{}{}", TEXT_PREFIX, BASE64.encode(self.encrypt(text.as_bytes())?)))
+    }
+
+    /// Whether `text` was written by `encrypt_text`.
+    pub fn is_encrypted_text(text: &str) -> bool {
+        text.starts_with(TEXT_PREFIX)
+    }
+
+    /// Decrypt text written by `encrypt_text`; text without the prefix is
+    /// returned unchanged.
+    pub fn decrypt_text(&self, text: &str) -> Result<String> {
+        let Some(encoded) = text.strip_prefix(TEXT_PREFIX) else {
+            return Ok(text.to_string());
+        };
+
+        let encrypted = BASE64
+            .decode(encoded)
+            .map_err(|e| ChromaError::EncryptionError(format!("Invalid encrypted field: {}", e)))?;
+
+        String::from_utf8(self.decrypt(&encrypted)?)
+            .map_err(|e| ChromaError::EncryptionError(format!("Decrypted field is not UTF-8: {}", e)))
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let header_len = MAGIC.len() + 1;
+        if !Self::is_encrypted(data) || data.len() < header_len + NONCE_LEN {
+            return Err(ChromaError::EncryptionError(
+                "Data is not in the encrypted store format".to_string(),
+            ));
+        }
+
+        if data[MAGIC.len()] != FORMAT_VERSION {
+            return Err(ChromaError::EncryptionError(format!(
+                "Unsupported encryption format version {}",
+                data[MAGIC.len()]
+            )));
+        }
+
+        let (nonce, ciphertext) = data[header_len..].split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                ChromaError::EncryptionError(
+                    "Decryption failed: wrong key or corrupted data".to_string(),
+                )
+            })
+    }
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_entry() -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_ENTRY.0, KEYRING_ENTRY.1)
+        .map_err(|e| ChromaError::EncryptionError(format!("Failed to open the keyring: {}", e)))
+}
+
+/// Prefix marking encrypted text: document and metadata values sent to
+/// Chroma, and encrypted export lines.
+const TEXT_PREFIX: &str = "enc:v1:";
+
+/// Client-side encryption of document text and selected metadata values.
+///
+/// Embeddings stay in the clear so similarity search keeps working, but Chroma
+/// only ever sees ciphertext for the protected fields. Encrypted metadata values
+/// use a random nonce, so they can no longer be matched by `where` filters.
+#[derive(Clone)]
+pub struct FieldEncryption {
+    cipher: StoreCipher,
+    metadata_keys: HashSet<String>,
+}
+
+impl FieldEncryption {
+    pub fn new(cipher: StoreCipher) -> Self {
+        Self {
+            cipher,
+            metadata_keys: HashSet::new(),
+        }
+    }
+
+    /// Also encrypt the values stored under these metadata keys.
+    pub fn with_metadata_keys<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.metadata_keys.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Hashes content with a key derived from the encryption key, so the
+    /// content hashes stored next to encrypted documents can't be checked
+    /// against guessed text by anyone without it.
+    pub fn content_hasher(&self) -> ContentHasher {
+        ContentHasher::Keyed(self.cipher.content_hash_key)
+    }
+
+    pub fn encrypt_value(&self, value: &str) -> Result<String> {
+        self.cipher.encrypt_text(value)
+    }
+
+    /// Decrypt a value written by `encrypt_value`; values without the
+    /// encryption prefix are returned unchanged.
+    pub fn decrypt_value(&self, value: &str) -> Result<String> {
+        self.cipher.decrypt_text(value)
+    }
+
+    pub fn encrypt_document(&self, mut document: Document) -> Result<Document> {
+        document.content = self.encrypt_value(&document.content)?;
+
+        for (key, value) in document.metadata.iter_mut() {
+            if self.metadata_keys.contains(key) {
+                *value = self.encrypt_value(value)?;
+            }
+        }
+
+        Ok(document)
+    }
+
+    /// Encrypt the documents and protected metadata values of a write
+    /// request in place.
+    pub fn encrypt_request(&self, request: &mut AddRequest) -> Result<()> {
+        for document in request.documents.iter_mut() {
+            *document = self.encrypt_value(document)?;
+        }
+
+        for metadata in request.metadatas.iter_mut() {
+            for (key, value) in metadata.iter_mut() {
+                if self.metadata_keys.contains(key) {
+                    *value = self.encrypt_value(value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn decrypt_response(&self, response: &mut QueryResponse) -> Result<()> {
+        for document in response.documents.iter_mut().flatten().flatten().flatten() {
+            *document = self.decrypt_value(document)?;
+        }
+
+        for metadata in response.metadatas.iter_mut().flatten().flatten().flatten() {
+            if let Value::Object(map) = metadata {
+                for (key, value) in map.iter_mut() {
+                    if let (true, Value::String(s)) = (self.metadata_keys.contains(key), &*value) {
+                        *value = Value::String(self.decrypt_value(s)?);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_store_cipher_round_trip() {
+        let cipher = StoreCipher::from_base64(&StoreCipher::generate_key()).unwrap();
+        let encrypted = cipher.encrypt(b"proprietary embeddings").unwrap();
+
+        assert!(StoreCipher::is_encrypted(&encrypted));
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), b"proprietary embeddings");
+
+        let other = StoreCipher::from_base64(&StoreCipher::generate_key()).unwrap();
+        assert!(other.decrypt(&encrypted).is_err());
+    }
+
+    #[test]
+    fn test_field_encryption_round_trip() {
+        let cipher = StoreCipher::from_base64(&StoreCipher::generate_key()).unwrap();
+        let encryption = FieldEncryption::new(cipher).with_metadata_keys(["author"]);
+
+        let mut metadata = HashMap::new();
+        metadata.insert("author".to_string(), "alice".to_string());
+        metadata.insert("category".to_string(), "legal".to_string());
+        let doc = Document {
+            id: "doc-1".to_string(),
+            content: "confidential text".to_string(),
+            metadata,
+            uri: None,
+        };
+
+        let encrypted = encryption.encrypt_document(doc).unwrap();
+        assert_ne!(encrypted.content, "confidential text");
+        assert_ne!(encrypted.metadata["author"], "alice");
+        assert_eq!(encrypted.metadata["category"], "legal");
+
+        let mut response = QueryResponse {
+            ids: vec![vec![encrypted.id.clone()]],
+            embeddings: None,
+            documents: Some(vec![vec![Some(encrypted.content.clone())]]),
+            metadatas: Some(vec![vec![Some(serde_json::to_value(&encrypted.metadata).unwrap())]]),
+            distances: Some(vec![vec![0.1]]),
+            uris: None,
+        };
+        encryption.decrypt_response(&mut response).unwrap();
+
+        assert_eq!(response.document(0, 0), Some("confidential text"));
+        assert_eq!(response.metadata(0, 0).unwrap()["author"], "alice");
+    }
+
+    #[cfg(feature = "keyring")]
+    #[test]
+    fn test_unavailable_keyring_means_no_key() {
+        let read = StoreCipher::from_keyring_password;
+        let unavailable = keyring::Error::NoStorageAccess("no secret service".into());
+        assert!(read(Ok(Err(unavailable))).unwrap().is_none());
+        assert!(read(Ok(Err(keyring::Error::NoEntry))).unwrap().is_none());
+        assert!(read(Err(ChromaError::EncryptionError("Failed to open the keyring".to_string()))).unwrap().is_none());
+        assert!(read(Ok(Ok(StoreCipher::generate_key()))).unwrap().is_some());
+        assert!(read(Ok(Ok("not a key".to_string()))).is_err());
+        assert!(read(Ok(Err(keyring::Error::BadEncoding(vec![0xff])))).is_err());
+    }
+}
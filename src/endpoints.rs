@@ -0,0 +1,352 @@
+use crate::chroma_client::validate_url;
+use crate::error::{ChromaError, Result};
+use reqwest::{Method, Request, StatusCode};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use url::Url;
+
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Chroma base URLs a `ChromaClient` can send to: the primary, failover
+/// endpoints in priority order, and read replicas.
+///
+/// Reads round-robin across healthy read replicas. Without any (or when all
+/// are down) they go to the first healthy primary/failover endpoint, so they
+/// fail back to the primary as soon as it recovers. Replicas lag behind the
+/// primary, so a query right after a write may not see it yet.
+///
+/// Writes never go to replicas. They stick to the endpoint that accepted the
+/// last write for as long as it stays healthy, so a flapping primary doesn't
+/// split one write stream across data centers. An endpoint that refuses
+/// connections, times out or answers 502/503/504 is skipped for a cooldown
+/// period; if every endpoint is marked down, all are tried anyway.
+#[derive(Debug)]
+pub(crate) struct Endpoints {
+    endpoints: Vec<Endpoint>,
+    cooldown: Duration,
+    write_endpoint: AtomicUsize,
+    next_replica: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: Url,
+    role: EndpointRole,
+    down_until: Mutex<Option<Instant>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EndpointRole {
+    Primary,
+    Failover,
+    /// Serves reads only.
+    Replica,
+}
+
+/// Health of one endpoint, as reported by `ChromaClient::endpoint_statuses`.
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointStatus {
+    pub url: String,
+    pub role: EndpointRole,
+    pub healthy: bool,
+    /// Writes are currently routed here.
+    pub writes: bool,
+}
+
+impl Endpoint {
+    fn parse(url: &str, role: EndpointRole) -> Result<Self> {
+        let url = validate_url(url)?;
+        Ok(Self {
+            url: Url::parse(&url).map_err(|e| ChromaError::ConfigError(format!("Invalid URL '{}': {}", url, e)))?,
+            role,
+            down_until: Mutex::new(None),
+        })
+    }
+
+    fn is_healthy(&self) -> bool {
+        match *self.down_until.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some(until) => Instant::now() >= until,
+            None => true,
+        }
+    }
+}
+
+/// A detached copy keeps the endpoint list, health marks and write routing
+/// as they are now; afterwards the two track failures independently.
+impl Clone for Endpoints {
+    fn clone(&self) -> Self {
+        Self {
+            endpoints: self.endpoints.clone(),
+            cooldown: self.cooldown,
+            write_endpoint: AtomicUsize::new(self.write_endpoint.load(Ordering::Relaxed)),
+            next_replica: AtomicUsize::new(self.next_replica.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Clone for Endpoint {
+    fn clone(&self) -> Self {
+        Self {
+            url: self.url.clone(),
+            role: self.role,
+            down_until: Mutex::new(*self.down_until.lock().unwrap_or_else(|e| e.into_inner())),
+        }
+    }
+}
+
+impl Endpoints {
+    /// `primary` plus the comma-separated `CHROMA_FAILOVER_HOSTS` and
+    /// `CHROMA_READ_REPLICAS`. Failed endpoints are skipped for
+    /// `CHROMA_ENDPOINT_COOLDOWN_SECS` (default 30).
+    pub(crate) fn from_env(primary: &str) -> Result<Self> {
+        let cooldown = std::env::var("CHROMA_ENDPOINT_COOLDOWN_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_COOLDOWN);
+        let mut endpoints = Self {
+            endpoints: vec![Endpoint::parse(primary, EndpointRole::Primary)?],
+            cooldown,
+            write_endpoint: AtomicUsize::new(0),
+            next_replica: AtomicUsize::new(0),
+        };
+        for (key, role) in [
+            ("CHROMA_FAILOVER_HOSTS", EndpointRole::Failover),
+            ("CHROMA_READ_REPLICAS", EndpointRole::Replica),
+        ] {
+            if let Ok(hosts) = std::env::var(key) {
+                for host in hosts.split(',').map(str::trim).filter(|h| !h.is_empty()) {
+                    endpoints.push(host, role)?;
+                }
+            }
+        }
+        Ok(endpoints)
+    }
+
+    pub(crate) fn push(&mut self, url: &str, role: EndpointRole) -> Result<()> {
+        self.endpoints.push(Endpoint::parse(url, role)?);
+        Ok(())
+    }
+
+    pub(crate) fn set_cooldown(&mut self, cooldown: Duration) {
+        self.cooldown = cooldown;
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    pub(crate) fn url(&self, index: usize) -> &Url {
+        &self.endpoints[index].url
+    }
+
+    pub(crate) fn role(&self, index: usize) -> EndpointRole {
+        self.endpoints[index].role
+    }
+
+    /// Endpoint indices to try for `request`, best first.
+    pub(crate) fn candidates(&self, request: &Request) -> Vec<usize> {
+        let (replicas, mut order): (Vec<usize>, Vec<usize>) =
+            (0..self.endpoints.len()).partition(|&i| self.endpoints[i].role == EndpointRole::Replica);
+
+        if is_write(request) {
+            let pinned = self.write_endpoint.load(Ordering::Relaxed);
+            order.retain(|&i| i != pinned);
+            order.insert(0, pinned);
+        } else if !replicas.is_empty() {
+            let start = self.next_replica.fetch_add(1, Ordering::Relaxed) % replicas.len();
+            let mut rotation: Vec<usize> = replicas[start..].iter().chain(&replicas[..start]).copied().collect();
+            rotation.append(&mut order);
+            order = rotation;
+        }
+        // Stable sort: healthy endpoints first, each group in the order above.
+        order.sort_by_key(|&i| !self.endpoints[i].is_healthy());
+        order
+    }
+
+    /// `url` (built against the primary) pointed at endpoint `index`.
+    pub(crate) fn rewrite(&self, index: usize, url: &Url) -> Url {
+        let primary = &self.endpoints[0].url;
+        let target = &self.endpoints[index].url;
+        if index == 0 {
+            return url.clone();
+        }
+
+        let mut rewritten = target.clone();
+        let relative = url
+            .path()
+            .strip_prefix(primary.path().trim_end_matches('/'))
+            .unwrap_or(url.path());
+        rewritten.set_path(&format!("{}{}", target.path().trim_end_matches('/'), relative));
+        rewritten.set_query(url.query());
+        rewritten
+    }
+
+    /// Record the outcome of sending `request` to endpoint `index`. Returns
+    /// true when a healthy endpoint was just marked down.
+    pub(crate) fn record(&self, index: usize, request_was_write: bool, reachable: bool) -> bool {
+        let mut down_until = self.endpoints[index].down_until.lock().unwrap_or_else(|e| e.into_inner());
+        if reachable {
+            *down_until = None;
+            if request_was_write {
+                self.write_endpoint.store(index, Ordering::Relaxed);
+            }
+            false
+        } else {
+            let was_healthy = down_until.is_none_or(|until| Instant::now() >= until);
+            *down_until = Some(Instant::now() + self.cooldown);
+            was_healthy
+        }
+    }
+
+    /// Route writes to the primary again, e.g. once it has caught up after
+    /// an outage.
+    pub(crate) fn reset_writes(&self) {
+        self.write_endpoint.store(0, Ordering::Relaxed);
+    }
+
+    pub(crate) fn statuses(&self) -> Vec<EndpointStatus> {
+        let writes = self.write_endpoint.load(Ordering::Relaxed);
+        self.endpoints
+            .iter()
+            .enumerate()
+            .map(|(i, endpoint)| EndpointStatus {
+                url: endpoint.url.to_string(),
+                role: endpoint.role,
+                healthy: endpoint.is_healthy(),
+                writes: i == writes,
+            })
+            .collect()
+    }
+}
+
+/// Reads are GETs plus the POST-based query and get routes.
+pub(crate) fn is_write(request: &Request) -> bool {
+    match *request.method() {
+        Method::GET | Method::HEAD => false,
+        Method::POST => {
+            let path = request.url().path();
+            !(path.ends_with("/query") || path.ends_with("/get"))
+        }
+        _ => true,
+    }
+}
+
+/// Statuses meaning the endpoint itself (or the proxy in front of it) is
+/// unavailable, as opposed to the request being wrong.
+pub(crate) fn is_unavailable(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chroma_client::ChromaClient;
+    use crate::models::Document;
+    use crate::query::QueryOptions;
+    use crate::test_support::mock_transport;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_failover_to_secondary_endpoint() {
+        use std::sync::Mutex;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use crate::transport::{BoxError, HttpResponse};
+
+        let primary_up = Arc::new(AtomicBool::new(false));
+        let hosts = Arc::new(Mutex::new(Vec::new()));
+        let (up, seen) = (primary_up.clone(), hosts.clone());
+        let service = mock_transport(move |request| {
+            let host = request.uri().host().unwrap_or_default().to_string();
+            seen.lock().unwrap().push(host.clone());
+            let result: std::result::Result<HttpResponse, BoxError> = if host == "primary" && !up.load(Ordering::SeqCst) {
+                Err("connection refused".into())
+            } else if request.uri().path().ends_with("/upsert") {
+                Ok(HttpResponse::new("true".into()))
+            } else {
+                Ok(HttpResponse::new(r#"{"id": "c0ffee", "name": "docs"}"#.into()))
+            };
+            result
+        });
+        let client = ChromaClient::try_new("http://primary:8000".to_string())
+            .unwrap()
+            .with_failover_endpoints(&["http://secondary:8000"])
+            .unwrap()
+            .with_transport(service)
+            .with_retries(0, std::time::Duration::ZERO);
+
+        client.get_collection("docs").await.unwrap();
+        assert_eq!(*hosts.lock().unwrap(), vec!["primary", "secondary"]);
+        let statuses = client.endpoint_statuses();
+        assert!(!statuses[0].healthy && statuses[1].healthy);
+
+        let document = Document { id: "a".to_string(), content: "text".to_string(), metadata: HashMap::new(), uri: None };
+        client.upsert_documents("docs", vec![document.clone()], vec![vec![0.1]]).await.unwrap();
+        assert!(client.endpoint_statuses()[1].writes);
+
+        // The primary recovers: reads fail back, writes stay on the secondary.
+        primary_up.store(true, Ordering::SeqCst);
+        assert!(client.check_endpoints().await.iter().all(|s| s.healthy));
+        hosts.lock().unwrap().clear();
+        client.invalidate_collections();
+        client.upsert_documents("docs", vec![document], vec![vec![0.1]]).await.unwrap();
+        assert_eq!(*hosts.lock().unwrap(), vec!["primary", "secondary"]);
+
+        client.reset_write_endpoint();
+        assert!(client.endpoint_statuses()[0].writes);
+    }
+
+    #[tokio::test]
+    async fn test_reads_round_robin_across_replicas() {
+        use std::sync::Mutex;
+        use crate::transport::{BoxError, HttpResponse};
+
+        let hosts = Arc::new(Mutex::new(Vec::new()));
+        let seen = hosts.clone();
+        let service = mock_transport(move |request| {
+            let host = request.uri().host().unwrap_or_default().to_string();
+            let path = request.uri().path().to_string();
+            seen.lock().unwrap().push(host.clone());
+            let result: std::result::Result<HttpResponse, BoxError> = if host == "replica-b" {
+                Ok(http::Response::builder().status(503).body(Vec::new().into()).unwrap())
+            } else if path.ends_with("/query") {
+                Ok(HttpResponse::new(r#"{"ids": [["a"]], "distances": [[0.1]]}"#.into()))
+            } else if path.ends_with("/delete") {
+                Ok(HttpResponse::new("[]".into()))
+            } else {
+                Ok(HttpResponse::new(r#"{"id": "c0ffee", "name": "docs"}"#.into()))
+            };
+            result
+        });
+        let client = ChromaClient::try_new("http://primary:8000".to_string())
+            .unwrap()
+            .with_read_replicas(&["http://replica-a:8000", "http://replica-b:8000"])
+            .unwrap()
+            .with_transport(service)
+            .with_retries(0, std::time::Duration::ZERO);
+
+        for _ in 0..3 {
+            client.query_with_options("docs", vec![0.0], &QueryOptions::new(1)).await.unwrap();
+        }
+        // Collection lookup on replica-a (then cached), queries on replica-b
+        // (down, falls through to replica-a), then replica-a only.
+        assert_eq!(
+            *hosts.lock().unwrap(),
+            vec!["replica-a", "replica-b", "replica-a", "replica-a", "replica-a"]
+        );
+        let statuses = client.endpoint_statuses();
+        assert_eq!(statuses[2].role, EndpointRole::Replica);
+        assert!(!statuses[2].healthy);
+
+        hosts.lock().unwrap().clear();
+        client.delete_documents("docs", vec!["a".to_string()]).await.unwrap();
+        assert_eq!(*hosts.lock().unwrap(), vec!["primary"]);
+    }
+}
@@ -16,6 +16,74 @@ pub enum ChromaError {
     
     #[error("Collection error: {0}")]
     CollectionError(String),
+
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Encryption error: {0}")]
+    EncryptionError(String),
+
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+
+    #[error("Schema error: {0}")]
+    SchemaError(String),
+
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+
+    #[error("Transport error: {0}")]
+    TransportError(String),
+
+    /// The request got no response: the connection failed, timed out or
+    /// was reset. Unlike `TransportError`, trying again may succeed.
+    #[error("Connection error: {0}")]
+    ConnectionError(String),
+
+    #[error("Indexer error: {0}")]
+    IndexerError(String),
+
+    #[error("Generation error: {0}")]
+    GenerationError(String),
+
+    /// A write would take a collection over its `Quota`.
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    /// The embedding API no longer serves `model`. `replacement` is the
+    /// successor from the crate's model registry, if it knows one.
+    #[error("Embedding model '{model}' is deprecated or unavailable{}", replacement_hint(.replacement))]
+    ModelDeprecated {
+        model: String,
+        replacement: Option<String>,
+    },
+
+    /// The embedding API returned a vector of the wrong size. Storing it
+    /// would corrupt the collection, so strict mode refuses to.
+    #[error("Embedding model '{model}' returned {actual} dimensions, expected {expected}")]
+    DimensionMismatch {
+        model: String,
+        expected: usize,
+        actual: usize,
+    },
+
+    /// A query embedding doesn't belong to the collection's vector space:
+    /// wrong dimension, or embedded by a model other than the one the
+    /// collection is bound to. Chroma would answer with meaningless
+    /// distances, so the query is refused.
+    #[error("Collection '{collection}' holds {expected} vectors, but the query is {actual}")]
+    ModelMismatch {
+        collection: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+fn replacement_hint(replacement: &Option<String>) -> String {
+    replacement
+        .as_ref()
+        .map(|r| format!("; migrate to '{}'", r))
+        .unwrap_or_default()
 }
 
 pub type Result<T> = std::result::Result<T, ChromaError>;
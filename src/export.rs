@@ -0,0 +1,484 @@
+use crate::chroma_client::ChromaClient;
+use crate::compression::Compression;
+use crate::encryption::StoreCipher;
+use crate::error::{ChromaError, Result};
+use crate::models::{GetRequest, Include, QueryResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+const DEFAULT_MEMORY_LIMIT: usize = 256 * 1024 * 1024;
+/// Records fetched before the real per-record size is known.
+const INITIAL_PAGE_SIZE: usize = 16;
+const MAX_PAGE_SIZE: usize = 1000;
+const ID_SCAN_PAGE_SIZE: u32 = 1000;
+/// Share of the memory limit the id list may use before it spills to disk.
+pub(crate) const ID_MEMORY_SHARE: usize = 4;
+
+/// One line of an export: a record as JSON, absent fields omitted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportRecord {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    /// CRC-32 of the record's other fields, so a damaged line is caught on
+    /// import. Absent in exports that predate it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
+impl ExportRecord {
+    /// The checksum of this record as serialized without its `checksum`.
+    pub fn compute_checksum(&self) -> Result<String> {
+        let unsigned = ExportRecord { checksum: None, ..self.clone() };
+        Ok(crc32_hex(&serde_json::to_vec(&unsigned)?))
+    }
+
+    /// Whether the stored checksum matches the contents; records without
+    /// one pass.
+    pub fn checksum_matches(&self) -> Result<bool> {
+        match &self.checksum {
+            Some(checksum) => Ok(*checksum == self.compute_checksum()?),
+            None => Ok(true),
+        }
+    }
+
+    /// Approximate bytes held in memory while the record is buffered.
+    fn memory_bytes(&self) -> usize {
+        self.id.len()
+            + self.document.as_ref().map_or(0, String::len)
+            + self.metadata.as_ref().map_or(0, |m| m.to_string().len())
+            + self.embedding.as_ref().map_or(0, |e| e.len() * std::mem::size_of::<f32>())
+            + self.uri.as_ref().map_or(0, String::len)
+    }
+}
+
+/// Settings for `ChromaClient::export`.
+#[derive(Debug, Clone)]
+pub struct ExportConfig {
+    /// Rough ceiling on memory used for buffered records and the id list.
+    pub memory_limit: usize,
+    /// Where the id list spills once it outgrows its share of the limit.
+    pub spill_dir: PathBuf,
+    pub include_embeddings: bool,
+    /// Compress the output stream; `compression::decoder` reads either form.
+    pub compression: Compression,
+    /// Encrypt each line with this key. Import and verification decrypt with
+    /// the configured store key (see `StoreCipher::load`).
+    pub encryption: Option<StoreCipher>,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            spill_dir: std::env::temp_dir(),
+            include_embeddings: true,
+            compression: Compression::None,
+            encryption: None,
+        }
+    }
+}
+
+impl ExportConfig {
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = bytes.max(1);
+        self
+    }
+
+    pub fn with_spill_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spill_dir = dir.into();
+        self
+    }
+
+    pub fn with_embeddings(mut self, include: bool) -> Self {
+        self.include_embeddings = include;
+        self
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn with_encryption(mut self, cipher: StoreCipher) -> Self {
+        self.encryption = Some(cipher);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExportReport {
+    pub records_written: usize,
+    /// Records deleted between listing ids and reading them.
+    pub records_skipped: usize,
+    /// Bytes written to the destination, after any compression.
+    pub bytes_written: u64,
+    /// The id list didn't fit in memory and was spooled to disk.
+    pub spilled: bool,
+    /// CRC-32 of the bytes written, as hex.
+    pub checksum: String,
+}
+
+/// Stream every record of `collection_name` to `writer` as JSON Lines.
+///
+/// Ids are listed first (spilling to a temp file past a quarter of the
+/// memory limit), then records are read by id in pages sized so that one
+/// page of decoded records stays under the limit, and written out one line
+/// at a time. Only a page is ever held in memory, whatever the collection
+/// size. Records added during the export are not included.
+pub(crate) async fn export<W: Write>(
+    client: &ChromaClient,
+    collection_name: &str,
+    writer: W,
+    config: &ExportConfig,
+) -> Result<ExportReport> {
+    let mut ids = IdSpool::new(config.memory_limit / ID_MEMORY_SHARE, &config.spill_dir);
+    list_ids(client, collection_name, |id| ids.push(id)).await?;
+
+    let spilled = ids.is_spilled();
+    let report = write_records(client, collection_name, ids.drain()?, writer, config).await?;
+    info!(
+        "Exported {} records from {} ({} bytes, {} skipped)",
+        report.records_written, collection_name, report.bytes_written, report.records_skipped
+    );
+    Ok(ExportReport { spilled, ..report })
+}
+
+/// Every id in `collection_name`, in listing order.
+pub(crate) async fn list_ids(
+    client: &ChromaClient,
+    collection_name: &str,
+    mut push: impl FnMut(String) -> Result<()>,
+) -> Result<()> {
+    let mut offset = 0;
+    loop {
+        let request = GetRequest {
+            limit: Some(ID_SCAN_PAGE_SIZE),
+            offset: Some(offset),
+            include: Some(Vec::new()),
+            ..GetRequest::default()
+        };
+        let response = client.send_get(collection_name, &request).await?;
+        let page = response.ids.into_iter().next().unwrap_or_default();
+        let count = page.len() as u32;
+        for id in page {
+            push(id)?;
+        }
+        if count < ID_SCAN_PAGE_SIZE {
+            return Ok(());
+        }
+        offset += count;
+    }
+}
+
+/// Read the records behind `ids` page by page and write them to `writer`
+/// as JSON Lines, keeping one page under `config.memory_limit`. With
+/// encryption each line is the record's JSON encrypted as
+/// `StoreCipher::encrypt_text`.
+pub(crate) async fn write_records<W: Write>(
+    client: &ChromaClient,
+    collection_name: &str,
+    mut pending: impl Iterator<Item = std::io::Result<String>>,
+    writer: W,
+    config: &ExportConfig,
+) -> Result<ExportReport> {
+    let mut include = vec![Include::Documents, Include::Metadatas, Include::Uris];
+    if config.include_embeddings {
+        include.push(Include::Embeddings);
+    }
+
+    let mut report = ExportReport::default();
+    let sink = CountingWriter { inner: BufWriter::new(writer), written: 0, crc: crc32fast::Hasher::new() };
+    let mut out = match config.compression {
+        Compression::None => Output::Plain(sink),
+        Compression::Zstd { level } => Output::Zstd(zstd::stream::write::Encoder::new(sink, level)?),
+    };
+    let mut page_size = INITIAL_PAGE_SIZE;
+    loop {
+        let page_ids = pending.by_ref().take(page_size).collect::<std::io::Result<Vec<String>>>()?;
+        if page_ids.is_empty() {
+            break;
+        }
+        let request = GetRequest {
+            ids: Some(page_ids.clone()),
+            include: Some(include.clone()),
+            ..GetRequest::default()
+        };
+        let mut found: HashMap<String, ExportRecord> = records(client.send_get(collection_name, &request).await?)
+            .into_iter()
+            .map(|record| (record.id.clone(), record))
+            .collect();
+        // Chroma doesn't promise to answer in request order; keep listing order.
+        let mut records: Vec<ExportRecord> = page_ids.iter().filter_map(|id| found.remove(id)).collect();
+        report.records_skipped += page_ids.len() - records.len();
+
+        let page_bytes: usize = records.iter().map(ExportRecord::memory_bytes).sum();
+        for record in &mut records {
+            record.checksum = Some(record.compute_checksum()?);
+            match &config.encryption {
+                Some(cipher) => out.write_all(cipher.encrypt_text(&serde_json::to_string(record)?)?.as_bytes())?,
+                None => serde_json::to_writer(&mut out, record)?,
+            }
+            out.write_all(b"\n")?;
+        }
+        report.records_written += records.len();
+
+        if let Some(per_record) = page_bytes.checked_div(records.len()) {
+            page_size = (config.memory_limit / per_record.max(1)).clamp(1, MAX_PAGE_SIZE);
+            debug!("Export page size {} (~{} bytes per record)", page_size, per_record);
+        }
+    }
+    let mut sink = out.finish()?;
+    sink.flush()?;
+    report.bytes_written = sink.written;
+    report.checksum = format!("{:08x}", sink.crc.finalize());
+    Ok(report)
+}
+
+/// Parses export lines, decrypting encrypted ones. The store key is only
+/// looked up once an encrypted line turns up, so plaintext exports don't
+/// need one.
+#[derive(Default)]
+pub(crate) struct RecordParser {
+    cipher: Option<StoreCipher>,
+}
+
+impl RecordParser {
+    /// The record on `line`. The outer error means no key could be loaded
+    /// for an encrypted line; the inner one means the line itself is
+    /// unreadable.
+    pub(crate) fn parse(&mut self, line: &str) -> Result<Result<ExportRecord>> {
+        if !StoreCipher::is_encrypted_text(line) {
+            return Ok(serde_json::from_str(line).map_err(Into::into));
+        }
+        let cipher = match &self.cipher {
+            Some(cipher) => cipher,
+            None => self.cipher.insert(StoreCipher::load()?.ok_or_else(|| {
+                ChromaError::EncryptionError("export is encrypted but no store key is configured".to_string())
+            })?),
+        };
+        Ok(cipher.decrypt_text(line).and_then(|json| Ok(serde_json::from_str(&json)?)))
+    }
+}
+
+/// The rows of a get response as owned records.
+fn records(response: QueryResponse) -> Vec<ExportRecord> {
+    fn first_row<T>(rows: Option<Vec<Vec<T>>>) -> std::vec::IntoIter<T> {
+        rows.and_then(|r| r.into_iter().next()).unwrap_or_default().into_iter()
+    }
+
+    let ids = response.ids.into_iter().next().unwrap_or_default();
+    let mut documents = first_row(response.documents);
+    let mut metadatas = first_row(response.metadatas);
+    let mut embeddings = first_row(response.embeddings);
+    let mut uris = first_row(response.uris);
+    ids.into_iter()
+        .map(|id| ExportRecord {
+            id,
+            document: documents.next().flatten(),
+            metadata: metadatas.next().flatten().filter(|m| !m.is_null()),
+            embedding: embeddings.next(),
+            uri: uris.next().flatten(),
+            checksum: None,
+        })
+        .collect()
+}
+
+/// CRC-32 of `bytes` as 8 hex digits, the form export checksums take.
+pub(crate) fn crc32_hex(bytes: &[u8]) -> String {
+    format!("{:08x}", crc32fast::hash(bytes))
+}
+
+struct CountingWriter<W: Write> {
+    inner: W,
+    written: u64,
+    crc: crc32fast::Hasher,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        self.crc.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The export stream, compressed or not.
+enum Output<W: Write> {
+    Plain(CountingWriter<W>),
+    Zstd(zstd::stream::write::Encoder<'static, CountingWriter<W>>),
+}
+
+impl<W: Write> Output<W> {
+    /// Write out any buffered compressed data and hand back the sink.
+    fn finish(self) -> std::io::Result<CountingWriter<W>> {
+        match self {
+            Output::Plain(sink) => Ok(sink),
+            Output::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Output<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Output::Plain(sink) => sink.write(buf),
+            Output::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Output::Plain(sink) => sink.flush(),
+            Output::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Ids to export, kept in memory up to `limit` bytes and in a temp file
+/// (one JSON string per line) beyond that. The file is removed on drop.
+pub(crate) struct IdSpool {
+    limit: usize,
+    dir: PathBuf,
+    memory: Vec<String>,
+    memory_bytes: usize,
+    file: Option<(PathBuf, BufWriter<File>)>,
+}
+
+impl IdSpool {
+    pub(crate) fn new(limit: usize, dir: &std::path::Path) -> Self {
+        Self { limit, dir: dir.to_path_buf(), memory: Vec::new(), memory_bytes: 0, file: None }
+    }
+
+    pub(crate) fn is_spilled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    pub(crate) fn push(&mut self, id: String) -> Result<()> {
+        if let Some((_, file)) = &mut self.file {
+            serde_json::to_writer(&mut *file, &id)?;
+            file.write_all(b"\n")?;
+            return Ok(());
+        }
+
+        self.memory_bytes += id.len() + std::mem::size_of::<String>();
+        self.memory.push(id);
+        if self.memory_bytes > self.limit {
+            let path = self.dir.join(format!("chroma-export-ids-{}.jsonl", Uuid::new_v4()));
+            debug!("Export id list exceeds {} bytes; spilling to {}", self.limit, path.display());
+            let mut file = BufWriter::new(File::create(&path)?);
+            for id in self.memory.drain(..) {
+                serde_json::to_writer(&mut file, &id)?;
+                file.write_all(b"\n")?;
+            }
+            self.memory_bytes = 0;
+            self.file = Some((path, file));
+        }
+        Ok(())
+    }
+
+    /// Every id pushed, in order.
+    pub(crate) fn drain(&mut self) -> Result<Box<dyn Iterator<Item = std::io::Result<String>> + Send + '_>> {
+        match &mut self.file {
+            Some((path, file)) => {
+                file.flush()?;
+                let lines = BufReader::new(File::open(&*path)?).lines();
+                Ok(Box::new(lines.map(|line| Ok(serde_json::from_str(&line?)?))))
+            }
+            None => Ok(Box::new(self.memory.drain(..).map(Ok))),
+        }
+    }
+}
+
+impl Drop for IdSpool {
+    fn drop(&mut self) {
+        if let Some((path, _)) = self.file.take() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_chroma;
+
+    #[tokio::test]
+    async fn test_export_streams_in_listing_order_and_spills_ids() {
+        let client = mock_chroma(|request| {
+            if request.uri().path().ends_with("/get") {
+                let get: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                match get["ids"].as_array() {
+                    // "c" was deleted after the ids were listed; answer out of order.
+                    Some(ids) => {
+                        let ids: Vec<&str> = ids.iter().rev().filter_map(|v| v.as_str()).filter(|id| *id != "c").collect();
+                        serde_json::json!({
+                            "ids": ids,
+                            "documents": ids.iter().map(|id| format!("doc {}", id)).collect::<Vec<_>>(),
+                            "metadatas": ids.iter().map(|_| serde_json::Value::Null).collect::<Vec<_>>(),
+                            "embeddings": ids.iter().map(|_| vec![0.5; 64]).collect::<Vec<_>>(),
+                        })
+                    }
+                    None => {
+                        let all = ["a", "b", "c", "d", "e"];
+                        let offset = get["offset"].as_u64().unwrap() as usize;
+                        serde_json::json!({ "ids": all.get(offset..).unwrap_or_default() })
+                    }
+                }
+                .to_string()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+
+        let config = ExportConfig::default().with_memory_limit(300);
+        let mut out = Vec::new();
+        let report = client.export("docs", &mut out, &config).await.unwrap();
+        assert!(report.spilled);
+        assert_eq!((report.records_written, report.records_skipped), (4, 1));
+        assert_eq!(report.bytes_written, out.len() as u64);
+
+        let records: Vec<ExportRecord> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let ids: Vec<&str> = records.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "d", "e"]);
+        assert_eq!(records[0].document.as_deref(), Some("doc a"));
+        assert_eq!(records[0].embedding.as_ref().map(Vec::len), Some(64));
+        assert!(records[0].metadata.is_none());
+
+        let cipher = StoreCipher::from_base64(&StoreCipher::generate_key()).unwrap();
+        let mut out = Vec::new();
+        client.export("docs", &mut out, &config.clone().with_encryption(cipher.clone())).await.unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(!text.contains("doc a"));
+
+        let mut parser = RecordParser { cipher: Some(cipher) };
+        let decrypted: Vec<ExportRecord> = text.lines().map(|line| parser.parse(line).unwrap().unwrap()).collect();
+        assert_eq!(decrypted, records);
+        assert!(decrypted.iter().all(|r| r.checksum_matches().unwrap()));
+
+        let other_key = StoreCipher::from_base64(&StoreCipher::generate_key()).unwrap();
+        let mut parser = RecordParser { cipher: Some(other_key) };
+        assert!(parser.parse(text.lines().next().unwrap()).unwrap().is_err());
+    }
+}
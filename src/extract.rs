@@ -0,0 +1,293 @@
+use crate::error::{ChromaError, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+
+/// Attempts `Pipeline::extract` makes before giving up on the model's JSON.
+pub const DEFAULT_EXTRACTION_ATTEMPTS: u32 = 3;
+
+/// A value extracted from retrieved context by `Pipeline::extract`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Extraction<T> {
+    pub value: T,
+    pub model: String,
+    /// Ids of the chunks packed into the prompt, best-ranked first.
+    pub context_ids: Vec<String>,
+    /// Generations it took to get valid output; above 1 means the model
+    /// had to be corrected.
+    pub attempts: u32,
+}
+
+/// Types that can describe themselves as a JSON Schema, for the `schema`
+/// argument of `Pipeline::extract`. Implemented for the primitive and
+/// container types; implement it for an extraction target next to its
+/// `Deserialize` derive:
+///
+/// ```
+/// use chromadb_demo::extract::ExtractionSchema;
+/// use serde_json::{Value, json};
+///
+/// #[derive(serde::Deserialize)]
+/// struct Person {
+///     name: String,
+///     born: Option<u32>,
+/// }
+///
+/// impl ExtractionSchema for Person {
+///     fn json_schema() -> Value {
+///         json!({
+///             "type": "object",
+///             "properties": { "name": String::json_schema(), "born": Option::<u32>::json_schema() },
+///             "required": ["name"],
+///         })
+///     }
+/// }
+/// ```
+pub trait ExtractionSchema {
+    fn json_schema() -> Value;
+}
+
+macro_rules! schema_type {
+    ($schema_type:literal: $($t:ty),+) => {
+        $(impl ExtractionSchema for $t {
+            fn json_schema() -> Value {
+                json!({ "type": $schema_type })
+            }
+        })+
+    };
+}
+
+schema_type!("string": String, char);
+schema_type!("boolean": bool);
+schema_type!("integer": i8, i16, i32, i64, u8, u16, u32, u64, usize, isize);
+schema_type!("number": f32, f64);
+
+impl<T: ExtractionSchema> ExtractionSchema for Option<T> {
+    fn json_schema() -> Value {
+        let mut schema = T::json_schema();
+        if let Some(t) = schema.get("type").and_then(Value::as_str).map(str::to_string) {
+            schema["type"] = json!([t, "null"]);
+        }
+        schema
+    }
+}
+
+impl<T: ExtractionSchema> ExtractionSchema for Vec<T> {
+    fn json_schema() -> Value {
+        json!({ "type": "array", "items": T::json_schema() })
+    }
+}
+
+impl<T: ExtractionSchema> ExtractionSchema for HashMap<String, T> {
+    fn json_schema() -> Value {
+        json!({ "type": "object", "additionalProperties": T::json_schema() })
+    }
+}
+
+impl<T: ExtractionSchema> ExtractionSchema for BTreeMap<String, T> {
+    fn json_schema() -> Value {
+        HashMap::<String, T>::json_schema()
+    }
+}
+
+/// Parse a model response as `T`, after checking it against `schema`.
+///
+/// Models like to wrap JSON in a Markdown code fence even when told not to,
+/// so one is stripped first. The error says what was wrong, so it can be fed
+/// back to the model.
+pub fn parse_response<T: DeserializeOwned>(response: &str, schema: &Value) -> Result<T> {
+    let value: Value = serde_json::from_str(strip_code_fence(response))
+        .map_err(|e| ChromaError::ValidationError(format!("response is not valid JSON: {}", e)))?;
+    let violations = validate(&value, schema);
+    if !violations.is_empty() {
+        return Err(ChromaError::ValidationError(format!(
+            "response doesn't match the schema: {}",
+            violations.join("; ")
+        )));
+    }
+    serde_json::from_value(value)
+        .map_err(|e| ChromaError::ValidationError(format!("response doesn't fit the target type: {}", e)))
+}
+
+fn strip_code_fence(response: &str) -> &str {
+    let trimmed = response.trim();
+    let Some(fenced) = trimmed.strip_prefix("```") else {
+        return trimmed;
+    };
+    // Drop the info string (`json`) on the opening line.
+    let body = fenced.split_once('\n').map_or("", |(_, body)| body);
+    body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+}
+
+/// Check `value` against the commonly used subset of JSON Schema: `type`,
+/// `enum`, `properties`, `required`, `additionalProperties` and `items`. Returns one message per violation, each prefixed with the
+/// JSON Pointer of the offending value; empty means valid. Keywords outside
+/// the subset are ignored.
+pub fn validate(value: &Value, schema: &Value) -> Vec<String> {
+    let mut violations = Vec::new();
+    validate_at(value, schema, "", &mut violations);
+    violations
+}
+
+fn validate_at(value: &Value, schema: &Value, path: &str, violations: &mut Vec<String>) {
+    let at = if path.is_empty() { "/" } else { path };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| has_type(value, t)) {
+            violations.push(format!("{}: expected {}, got {}", at, types.join(" or "), type_name(value)));
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+        && !allowed.contains(value)
+    {
+        violations.push(format!("{}: {} is not one of {}", at, value, Value::Array(allowed.clone())));
+    }
+
+    match value {
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for required in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+                if let Some(key) = required.as_str()
+                    && !object.contains_key(key)
+                {
+                    violations.push(format!("{}: missing required property '{}'", at, key));
+                }
+            }
+            for (key, field) in object {
+                let field_path = format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"));
+                match (properties.and_then(|p| p.get(key)), schema.get("additionalProperties")) {
+                    (Some(field_schema), _) => validate_at(field, field_schema, &field_path, violations),
+                    (None, Some(Value::Bool(false))) => violations.push(format!("{}: unexpected property", field_path)),
+                    (None, Some(extra @ Value::Object(_))) => validate_at(field, extra, &field_path, violations),
+                    (None, _) => {}
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(item, item_schema, &format!("{}/{}", path, i), violations);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "integer" => value.as_i64().is_some() || value.as_u64().is_some(),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    use crate::llm::{Generation, GenerationRequest, LlmProvider};
+    use crate::pipeline::Pipeline;
+    use crate::query::QueryOptions;
+    use crate::test_support::{FixedEmbeddings, mock_chroma};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_extraction_retries_until_output_fits_the_schema() {
+        use std::sync::Mutex;
+
+        #[derive(Debug, serde::Deserialize, PartialEq)]
+        struct Language {
+            name: String,
+            year: Option<u32>,
+        }
+
+        impl ExtractionSchema for Language {
+            fn json_schema() -> serde_json::Value {
+                serde_json::json!({
+                    "type": "object",
+                    "properties": { "name": String::json_schema(), "year": Option::<u32>::json_schema() },
+                    "required": ["name"],
+                    "additionalProperties": false,
+                })
+            }
+        }
+
+        struct ScriptedLlm {
+            responses: Mutex<Vec<&'static str>>,
+            prompts: Mutex<Vec<GenerationRequest>>,
+        }
+
+        impl LlmProvider for ScriptedLlm {
+            fn generate<'a>(&'a self, request: &'a GenerationRequest) -> futures::future::BoxFuture<'a, Result<Generation>> {
+                self.prompts.lock().unwrap().push(request.clone());
+                let text = self.responses.lock().unwrap().remove(0).to_string();
+                Box::pin(async move { Ok(Generation { text, model: "scripted".to_string(), prompt_tokens: None, completion_tokens: None }) })
+            }
+
+            fn model_name(&self) -> &str {
+                "scripted"
+            }
+        }
+
+        let schema = Vec::<Language>::json_schema();
+        let violations = validate(&serde_json::json!([{ "name": "Rust", "year": "2015", "paradigm": "multi" }, {}]), &schema);
+        assert_eq!(
+            violations,
+            vec![
+                "/0/paradigm: unexpected property",
+                "/0/year: expected integer or null, got string",
+                "/1: missing required property 'name'",
+            ]
+        );
+
+        let chroma = Arc::new(mock_chroma(|request| {
+            if request.uri().path().ends_with("/query") {
+                r#"{"ids": [["a"]], "distances": [[0.1]], "documents": [["Rust appeared in 2015."]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        }));
+        let llm = Arc::new(ScriptedLlm {
+            responses: Mutex::new(vec!["Sure! Here it is:", r#"[{"name": 1}]"#, "```json\n[{\"name\": \"Rust\", \"year\": 2015}]\n```"]),
+            prompts: Mutex::new(Vec::new()),
+        });
+        let pipeline = Pipeline::new(chroma.clone(), Arc::new(FixedEmbeddings), "docs").with_llm(llm.clone());
+
+        let extraction = pipeline.extract::<Vec<Language>>("Which languages?", &schema, &QueryOptions::new(1)).await.unwrap();
+        assert_eq!(extraction.value, vec![Language { name: "Rust".to_string(), year: Some(2015) }]);
+        assert_eq!(extraction.attempts, 3);
+        assert_eq!(extraction.context_ids, vec!["a"]);
+
+        {
+            let prompts = llm.prompts.lock().unwrap();
+            assert!(prompts.iter().all(|request| request.json && request.prompt.contains("[a] Rust appeared in 2015.")));
+            assert!(prompts[2].prompt.contains("/0/name: expected string, got number"));
+        }
+
+        let failing = Pipeline::new(chroma, Arc::new(FixedEmbeddings), "docs")
+            .with_llm(Arc::new(ScriptedLlm { responses: Mutex::new(vec!["no"]), prompts: Mutex::new(Vec::new()) }))
+            .with_extraction_attempts(1);
+        let error = failing.extract::<Vec<Language>>("Which languages?", &schema, &QueryOptions::new(1)).await.unwrap_err();
+        assert!(matches!(error, ChromaError::GenerationError(_)));
+    }
+}
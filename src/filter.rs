@@ -0,0 +1,366 @@
+use crate::error::{ChromaError, Result};
+use serde_json::{Map, Number, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// A metadata value usable in a `where` filter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataValue {
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl MetadataValue {
+    pub fn to_json(&self) -> Result<Value> {
+        match self {
+            MetadataValue::Str(s) => Ok(Value::String(s.clone())),
+            MetadataValue::Int(i) => Ok(Value::Number((*i).into())),
+            MetadataValue::Float(f) => Number::from_f64(*f).map(Value::Number).ok_or_else(|| {
+                ChromaError::ValidationError(format!("Metadata value {} is not a finite number", f))
+            }),
+            MetadataValue::Bool(b) => Ok(Value::Bool(*b)),
+        }
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self> {
+        match value {
+            Value::String(s) => Ok(MetadataValue::Str(s.clone())),
+            Value::Bool(b) => Ok(MetadataValue::Bool(*b)),
+            Value::Number(n) => n
+                .as_i64()
+                .map(MetadataValue::Int)
+                .or_else(|| n.as_f64().map(MetadataValue::Float))
+                .ok_or_else(|| ChromaError::ValidationError(format!("Unsupported number {}", n))),
+            other => Err(ChromaError::ValidationError(format!(
+                "Unsupported metadata value: {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl From<&str> for MetadataValue {
+    fn from(value: &str) -> Self {
+        MetadataValue::Str(value.to_string())
+    }
+}
+
+impl From<String> for MetadataValue {
+    fn from(value: String) -> Self {
+        MetadataValue::Str(value)
+    }
+}
+
+impl From<i64> for MetadataValue {
+    fn from(value: i64) -> Self {
+        MetadataValue::Int(value)
+    }
+}
+
+impl From<f64> for MetadataValue {
+    fn from(value: f64) -> Self {
+        MetadataValue::Float(value)
+    }
+}
+
+impl From<bool> for MetadataValue {
+    fn from(value: bool) -> Self {
+        MetadataValue::Bool(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+impl Comparison {
+    fn operator(self) -> &'static str {
+        match self {
+            Comparison::Eq => "$eq",
+            Comparison::Ne => "$ne",
+            Comparison::Gt => "$gt",
+            Comparison::Gte => "$gte",
+            Comparison::Lt => "$lt",
+            Comparison::Lte => "$lte",
+        }
+    }
+
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Eq => ordering == Ordering::Equal,
+            Comparison::Ne => ordering != Ordering::Equal,
+            Comparison::Gt => ordering == Ordering::Greater,
+            Comparison::Gte => ordering != Ordering::Less,
+            Comparison::Lt => ordering == Ordering::Less,
+            Comparison::Lte => ordering != Ordering::Greater,
+        }
+    }
+
+    fn from_operator(operator: &str) -> Option<Self> {
+        match operator {
+            "$eq" => Some(Comparison::Eq),
+            "$ne" => Some(Comparison::Ne),
+            "$gt" => Some(Comparison::Gt),
+            "$gte" => Some(Comparison::Gte),
+            "$lt" => Some(Comparison::Lt),
+            "$lte" => Some(Comparison::Lte),
+            _ => None,
+        }
+    }
+}
+
+/// Typed builder for Chroma `where` metadata filters.
+///
+/// ```
+/// use chromadb_demo::filter::Filter;
+///
+/// let filter = Filter::eq("category", "programming").and(Filter::gte("year", 2020_i64));
+/// let where_clause = filter.to_json().unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Compare(String, Comparison, MetadataValue),
+    In(String, Vec<MetadataValue>),
+    NotIn(String, Vec<MetadataValue>),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+}
+
+impl Filter {
+    pub fn eq(key: &str, value: impl Into<MetadataValue>) -> Self {
+        Filter::Compare(key.to_string(), Comparison::Eq, value.into())
+    }
+
+    pub fn ne(key: &str, value: impl Into<MetadataValue>) -> Self {
+        Filter::Compare(key.to_string(), Comparison::Ne, value.into())
+    }
+
+    pub fn gt(key: &str, value: impl Into<MetadataValue>) -> Self {
+        Filter::Compare(key.to_string(), Comparison::Gt, value.into())
+    }
+
+    pub fn gte(key: &str, value: impl Into<MetadataValue>) -> Self {
+        Filter::Compare(key.to_string(), Comparison::Gte, value.into())
+    }
+
+    pub fn lt(key: &str, value: impl Into<MetadataValue>) -> Self {
+        Filter::Compare(key.to_string(), Comparison::Lt, value.into())
+    }
+
+    pub fn lte(key: &str, value: impl Into<MetadataValue>) -> Self {
+        Filter::Compare(key.to_string(), Comparison::Lte, value.into())
+    }
+
+    pub fn is_in<V: Into<MetadataValue>>(key: &str, values: impl IntoIterator<Item = V>) -> Self {
+        Filter::In(key.to_string(), values.into_iter().map(Into::into).collect())
+    }
+
+    pub fn not_in<V: Into<MetadataValue>>(key: &str, values: impl IntoIterator<Item = V>) -> Self {
+        Filter::NotIn(key.to_string(), values.into_iter().map(Into::into).collect())
+    }
+
+    /// Combine with `other`, flattening nested `$and`s.
+    pub fn and(self, other: Filter) -> Self {
+        match (self, other) {
+            (Filter::And(mut a), Filter::And(b)) => {
+                a.extend(b);
+                Filter::And(a)
+            }
+            (Filter::And(mut a), f) => {
+                a.push(f);
+                Filter::And(a)
+            }
+            (f, Filter::And(mut b)) => {
+                b.insert(0, f);
+                Filter::And(b)
+            }
+            (a, b) => Filter::And(vec![a, b]),
+        }
+    }
+
+    /// Combine with `other`, flattening nested `$or`s.
+    pub fn or(self, other: Filter) -> Self {
+        match (self, other) {
+            (Filter::Or(mut a), Filter::Or(b)) => {
+                a.extend(b);
+                Filter::Or(a)
+            }
+            (Filter::Or(mut a), f) => {
+                a.push(f);
+                Filter::Or(a)
+            }
+            (f, Filter::Or(mut b)) => {
+                b.insert(0, f);
+                Filter::Or(b)
+            }
+            (a, b) => Filter::Or(vec![a, b]),
+        }
+    }
+
+    /// Render as a Chroma `where` clause. Single-element `$and`/`$or` groups
+    /// are unwrapped since Chroma requires at least two operands.
+    pub fn to_json(&self) -> Result<Value> {
+        match self {
+            Filter::Compare(key, op, value) => Ok(single(key, single(op.operator(), value.to_json()?))),
+            Filter::In(key, values) => Ok(single(key, single("$in", values_to_json(values)?))),
+            Filter::NotIn(key, values) => Ok(single(key, single("$nin", values_to_json(values)?))),
+            Filter::And(filters) => group("$and", filters),
+            Filter::Or(filters) => group("$or", filters),
+        }
+    }
+
+    /// Evaluate against string metadata, as kept by the local store, the
+    /// way Chroma would: numeric and boolean operands match values that
+    /// parse as such, a document without the key never matches (not even
+    /// `$ne`/`$nin`), and ordering comparisons need a numeric operand.
+    pub fn matches(&self, metadata: &HashMap<String, String>) -> Result<bool> {
+        match self {
+            Filter::Compare(key, op, value) => {
+                if matches!(value, MetadataValue::Str(_) | MetadataValue::Bool(_))
+                    && !matches!(op, Comparison::Eq | Comparison::Ne)
+                {
+                    return Err(ChromaError::ValidationError(format!(
+                        "{} on '{}' needs a numeric operand",
+                        op.operator(),
+                        key
+                    )));
+                }
+                Ok(metadata
+                    .get(key)
+                    .and_then(|stored| compare_stored(stored, value))
+                    .is_some_and(|ordering| op.holds(ordering)))
+            }
+            Filter::In(key, values) | Filter::NotIn(key, values) => {
+                let Some(stored) = metadata.get(key) else {
+                    return Ok(false);
+                };
+                let found = values.iter().any(|v| compare_stored(stored, v) == Some(Ordering::Equal));
+                Ok(found == matches!(self, Filter::In(..)))
+            }
+            Filter::And(filters) | Filter::Or(filters) if filters.is_empty() => {
+                Err(ChromaError::ValidationError("Empty $and/$or filter".to_string()))
+            }
+            Filter::And(filters) => {
+                for filter in filters {
+                    if !filter.matches(metadata)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Filter::Or(filters) => {
+                for filter in filters {
+                    if filter.matches(metadata)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    /// Parse a `where` clause built by `to_json` (or written by hand). Shorthand
+    /// equality such as `{"category": "news"}` is accepted as well.
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let map = value.as_object().ok_or_else(|| invalid(value))?;
+
+        let mut filters = map
+            .iter()
+            .map(|(key, condition)| match key.as_str() {
+                "$and" | "$or" => {
+                    let operands = condition
+                        .as_array()
+                        .ok_or_else(|| invalid(condition))?
+                        .iter()
+                        .map(Filter::from_json)
+                        .collect::<Result<Vec<_>>>()?;
+                    Ok(if key == "$and" { Filter::And(operands) } else { Filter::Or(operands) })
+                }
+                _ => parse_condition(key, condition),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        match filters.len() {
+            1 => Ok(filters.remove(0)),
+            0 => Err(invalid(value)),
+            _ => Ok(Filter::And(filters)),
+        }
+    }
+}
+
+fn parse_condition(key: &str, condition: &Value) -> Result<Filter> {
+    let Some(ops) = condition.as_object() else {
+        return Ok(Filter::eq(key, MetadataValue::from_json(condition)?));
+    };
+
+    let (operator, operand) = match ops.iter().next() {
+        Some(entry) if ops.len() == 1 => entry,
+        _ => return Err(invalid(condition)),
+    };
+
+    match operator.as_str() {
+        "$in" | "$nin" => {
+            let values = operand
+                .as_array()
+                .ok_or_else(|| invalid(operand))?
+                .iter()
+                .map(MetadataValue::from_json)
+                .collect::<Result<Vec<_>>>()?;
+            Ok(if operator == "$in" {
+                Filter::In(key.to_string(), values)
+            } else {
+                Filter::NotIn(key.to_string(), values)
+            })
+        }
+        op => {
+            let comparison = Comparison::from_operator(op).ok_or_else(|| {
+                ChromaError::ValidationError(format!("Unsupported filter operator '{}'", op))
+            })?;
+            Ok(Filter::Compare(key.to_string(), comparison, MetadataValue::from_json(operand)?))
+        }
+    }
+}
+
+/// How a stored string compares to `value`, or `None` if it isn't a value
+/// of the same type.
+fn compare_stored(stored: &str, value: &MetadataValue) -> Option<Ordering> {
+    match value {
+        MetadataValue::Str(s) => Some(stored.cmp(s.as_str())),
+        MetadataValue::Int(i) => stored.parse::<f64>().ok()?.partial_cmp(&(*i as f64)),
+        MetadataValue::Float(f) => stored.parse::<f64>().ok()?.partial_cmp(f),
+        MetadataValue::Bool(b) => Some(stored.parse::<bool>().ok()?.cmp(b)),
+    }
+}
+
+fn single(key: &str, value: Value) -> Value {
+    let mut map = Map::new();
+    map.insert(key.to_string(), value);
+    Value::Object(map)
+}
+
+fn values_to_json(values: &[MetadataValue]) -> Result<Value> {
+    Ok(Value::Array(values.iter().map(MetadataValue::to_json).collect::<Result<_>>()?))
+}
+
+fn group(operator: &str, filters: &[Filter]) -> Result<Value> {
+    match filters {
+        [] => Err(ChromaError::ValidationError(format!("Empty {} filter", operator))),
+        [only] => only.to_json(),
+        _ => Ok(single(
+            operator,
+            Value::Array(filters.iter().map(Filter::to_json).collect::<Result<_>>()?),
+        )),
+    }
+}
+
+fn invalid(value: &Value) -> ChromaError {
+    ChromaError::ValidationError(format!("Invalid where filter: {}", value))
+}
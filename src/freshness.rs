@@ -0,0 +1,163 @@
+use crate::chroma_client::ChromaClient;
+use crate::error::Result;
+use crate::models::*;
+use crate::spaces::space_collection;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Space name of the control collection, e.g. `docs__freshness`.
+pub const FRESHNESS_SPACE: &str = "freshness";
+const LAST_INDEXED_AT_FIELD: &str = "last_indexed_at";
+const SLA_SECS_FIELD: &str = "sla_secs";
+/// Control records carry no meaningful vector, but Chroma requires one.
+const PLACEHOLDER_EMBEDDING: [f32; 1] = [1.0];
+
+/// Per-source indexing timestamps for one collection, kept in a sibling
+/// control collection (`{collection}__freshness`).
+///
+/// Ingestion jobs call `mark_indexed` after re-syncing a source (a
+/// directory, a wiki space, a feed) along with how often it is supposed to be
+/// re-synced; `report` then lists the sources that missed that SLA, so a
+/// knowledge-base section that silently stopped updating shows up.
+pub struct Freshness<'a> {
+    client: &'a ChromaClient,
+    control_collection: String,
+}
+
+/// Indexing state of one source.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceFreshness {
+    pub source: String,
+    pub last_indexed_at: Option<DateTime<Utc>>,
+    pub sla_secs: u64,
+    pub age_secs: Option<u64>,
+    /// Not re-indexed within `sla_secs`, or never successfully recorded.
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FreshnessReport {
+    pub checked_at: DateTime<Utc>,
+    pub sources: Vec<SourceFreshness>,
+}
+
+impl FreshnessReport {
+    pub fn is_fresh(&self) -> bool {
+        self.sources.iter().all(|s| !s.stale)
+    }
+
+    pub fn stale(&self) -> impl Iterator<Item = &SourceFreshness> {
+        self.sources.iter().filter(|s| s.stale)
+    }
+
+    /// Build a report from the records of a control collection.
+    pub(crate) fn from_response(response: &QueryResponse, now: DateTime<Utc>) -> Self {
+        let mut sources: Vec<SourceFreshness> = response
+            .ids_for(0)
+            .iter()
+            .enumerate()
+            .map(|(i, source)| {
+                let metadata = response.metadata(0, i);
+                let field = |key: &str| metadata.and_then(|m| m.get(key));
+                let last_indexed_at = field(LAST_INDEXED_AT_FIELD)
+                    .and_then(Value::as_str)
+                    .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+                    .map(|ts| ts.with_timezone(&Utc));
+                let sla_secs = field(SLA_SECS_FIELD)
+                    .and_then(|v| v.as_u64().or_else(|| v.as_str()?.parse().ok()))
+                    .unwrap_or(0);
+                let age_secs = last_indexed_at.map(|ts| (now - ts).num_seconds().max(0) as u64);
+
+                SourceFreshness {
+                    source: source.clone(),
+                    last_indexed_at,
+                    sla_secs,
+                    age_secs,
+                    stale: age_secs.is_none_or(|age| age > sla_secs),
+                }
+            })
+            .collect();
+        sources.sort_by(|a, b| a.source.cmp(&b.source));
+        Self { checked_at: now, sources }
+    }
+}
+
+impl<'a> Freshness<'a> {
+    pub(crate) fn new(client: &'a ChromaClient, collection_name: &str) -> Self {
+        Self {
+            client,
+            control_collection: space_collection(collection_name, FRESHNESS_SPACE),
+        }
+    }
+
+    pub fn control_collection(&self) -> &str {
+        &self.control_collection
+    }
+
+    /// Record that `source` was fully re-indexed just now and should be
+    /// again within `sla`.
+    pub async fn mark_indexed(&self, source: &str, sla: Duration) -> Result<()> {
+        if self.client.get_collection(&self.control_collection).await.is_err() {
+            self.client.create_collection(&self.control_collection).await?;
+        }
+
+        let metadata = HashMap::from([
+            (LAST_INDEXED_AT_FIELD.to_string(), Utc::now().to_rfc3339()),
+            (SLA_SECS_FIELD.to_string(), sla.as_secs().to_string()),
+        ]);
+        let record = Document {
+            id: source.to_string(),
+            content: source.to_string(),
+            metadata,
+            uri: None,
+        };
+        self.client
+            .upsert_documents(&self.control_collection, vec![record], vec![PLACEHOLDER_EMBEDDING.to_vec()])
+            .await
+    }
+
+    /// Every tracked source with its age, stale ones flagged. Empty if
+    /// nothing was ever marked.
+    pub async fn report(&self) -> Result<FreshnessReport> {
+        if self.client.get_collection(&self.control_collection).await.is_err() {
+            return Ok(FreshnessReport { checked_at: Utc::now(), sources: Vec::new() });
+        }
+        let response = self.client.get_documents(&self.control_collection, None, None, None).await?;
+        Ok(FreshnessReport::from_response(&response, Utc::now()))
+    }
+
+    /// Stop tracking `source`, e.g. after it was removed from the corpus.
+    pub async fn forget(&self, source: &str) -> Result<()> {
+        self.client.delete_documents(&self.control_collection, vec![source.to_string()]).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_freshness_report_flags_sources_past_their_sla() {
+        let now = chrono::Utc::now();
+        let indexed = |hours: i64| (now - chrono::Duration::hours(hours)).to_rfc3339();
+        let response: QueryResponse = serde_json::from_value(serde_json::json!({
+            "ids": [["wiki/hr", "wiki/eng", "feeds/status"]],
+            "metadatas": [[
+                {"last_indexed_at": indexed(30), "sla_secs": "86400"},
+                {"last_indexed_at": indexed(2), "sla_secs": "86400"},
+                {"sla_secs": "3600"}
+            ]]
+        }))
+        .unwrap();
+
+        let report = FreshnessReport::from_response(&response, now);
+        assert!(!report.is_fresh());
+        let stale: Vec<&str> = report.stale().map(|s| s.source.as_str()).collect();
+        assert_eq!(stale, vec!["feeds/status", "wiki/hr"]);
+        let eng = report.sources.iter().find(|s| s.source == "wiki/eng").unwrap();
+        assert_eq!(eng.age_secs, Some(2 * 3600));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::models::QueryHit;
+use crate::score::{min_max_normalize, z_score_normalize};
+use std::collections::HashMap;
+
+/// The usual RRF constant; larger values flatten the gap between ranks.
+pub const DEFAULT_RRF_K: f32 = 60.0;
+
+/// How each list's scores are rescaled before a weighted sum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Normalization {
+    /// Use scores as they are; only sound when every list uses the same
+    /// metric.
+    #[default]
+    None,
+    /// Rescale each list to `[0, 1]`.
+    MinMax,
+    /// Standardize each list to mean 0, standard deviation 1, so lists with
+    /// a narrow score spread don't get drowned out.
+    ZScore,
+}
+
+/// Reciprocal rank fusion: a hit scores `Σ 1 / (k + rank)` over the lists it
+/// appears in (ranks start at 1, in each list's given order). Only ranks
+/// matter, so lists with unrelated score scales (vector and keyword search,
+/// different metrics) merge safely.
+pub fn reciprocal_rank(lists: Vec<Vec<QueryHit>>, k: f32) -> Vec<QueryHit> {
+    fuse(lists.into_iter().flat_map(|hits| {
+        hits.into_iter()
+            .enumerate()
+            .map(|(rank, hit)| (1.0 / (k + rank as f32 + 1.0), hit))
+            .collect::<Vec<_>>()
+    }))
+}
+
+/// Weighted score sum: a hit scores `Σ weight × score` over the lists it
+/// appears in, after rescaling each list per `normalization`. Records found
+/// by several lists rank above those found by one.
+pub fn weighted_sum(lists: Vec<(f32, Vec<QueryHit>)>, normalization: Normalization) -> Vec<QueryHit> {
+    fuse(lists.into_iter().flat_map(|(weight, hits)| {
+        let mut scores: Vec<f32> = hits.iter().map(|hit| hit.score).collect();
+        match normalization {
+            Normalization::None => {}
+            Normalization::MinMax => min_max_normalize(&mut scores),
+            Normalization::ZScore => z_score_normalize(&mut scores),
+        }
+        scores
+            .into_iter()
+            .zip(hits)
+            .map(|(score, hit)| (weight * score, hit))
+            .collect::<Vec<_>>()
+    }))
+}
+
+/// Merge `(contribution, hit)` pairs by id. The fused hit's score is the sum
+/// of its contributions; its document, metadata and distance come from the
+/// strongest one. Returned in rank order.
+fn fuse(contributions: impl Iterator<Item = (f32, QueryHit)>) -> Vec<QueryHit> {
+    let mut fused: HashMap<String, (QueryHit, f32)> = HashMap::new();
+    for (contribution, hit) in contributions {
+        match fused.get_mut(&hit.id) {
+            Some((best, strongest)) => {
+                best.score += contribution;
+                if contribution > *strongest {
+                    *strongest = contribution;
+                    *best = QueryHit { score: best.score, ..hit };
+                }
+            }
+            None => {
+                fused.insert(hit.id.clone(), (QueryHit { score: contribution, ..hit }, contribution));
+            }
+        }
+    }
+
+    let mut hits: Vec<QueryHit> = fused.into_values().map(|(hit, _)| hit).collect();
+    hits.sort_by(QueryHit::rank_cmp);
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_fusion_merges_lists_consistently() {
+        let hit = |id: &str, score: f32| QueryHit {
+            id: id.to_string(),
+            document: Some(format!("{} @ {}", id, score)),
+            metadata: None,
+            distance: 1.0 - score,
+            score,
+            uri: None,
+        };
+        let vector = vec![hit("a", 0.9), hit("b", 0.8), hit("c", 0.7)];
+        let keyword = vec![hit("c", 12.0), hit("d", 3.0), hit("a", 1.0)];
+
+        let rrf = reciprocal_rank(vec![vector.clone(), keyword.clone()], DEFAULT_RRF_K);
+        let ids: Vec<&str> = rrf.iter().map(|h| h.id.as_str()).collect();
+        // a: 1/61 + 1/63, c: 1/63 + 1/61 tie and break by id; b and d tie too.
+        assert_eq!(ids, vec!["a", "c", "b", "d"]);
+        assert!((rrf[0].score - (1.0 / 61.0 + 1.0 / 63.0)).abs() < 1e-6);
+
+        // Unnormalized keyword scores swamp the vector list...
+        let raw = weighted_sum(vec![(1.0, vector.clone()), (1.0, keyword.clone())], Normalization::None);
+        assert_eq!(raw[0].id, "c");
+        assert_eq!(raw[0].document.as_deref(), Some("c @ 12"));
+        // ...min-max puts both on [0, 1].
+        let min_max = weighted_sum(vec![(1.0, vector.clone()), (1.0, keyword.clone())], Normalization::MinMax);
+        let scores: HashMap<&str, f32> = min_max.iter().map(|h| (h.id.as_str(), h.score)).collect();
+        assert!((scores["a"] - 1.0).abs() < 1e-6);
+        assert!((scores["c"] - 1.0).abs() < 1e-6);
+        assert!((scores["b"] - 0.5).abs() < 1e-6);
+
+        let z = weighted_sum(vec![(2.0, vector), (1.0, keyword)], Normalization::ZScore);
+        assert_eq!(z.len(), 4);
+        assert!(z.windows(2).all(|w| w[0].rank_cmp(&w[1]).is_lt()));
+        let mut flat = [5.0, 5.0];
+        z_score_normalize(&mut flat);
+        assert_eq!(flat, [0.0, 0.0]);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::error::{ChromaError, Result};
+use reqwest::header::{HeaderName, HeaderValue, USER_AGENT};
+use reqwest::{Client, Request};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+static SHARED_CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// `User-Agent` sent by both clients, e.g. `chromadb-demo/0.1.0`.
+pub const DEFAULT_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Header naming the deploying application, set from `CLIENT_APP_ID` or the
+/// clients' `with_app_id`.
+pub const APP_ID_HEADER: &str = "x-client-app";
+
+/// Builds the `reqwest::Client` used by `ChromaClient` and `EmbeddingClient`.
+///
+/// By default both clients share one process-wide client, and with it one
+/// connection pool. Apps that already manage a client can pass it to the
+/// clients' `with_http_client` instead.
+///
+/// Pooled connections belong to the Tokio runtime that opened them, so
+/// programs running several runtimes should `build` a client per runtime.
+pub struct HttpClientFactory;
+
+impl HttpClientFactory {
+    /// The process-wide client, built on first use from the environment
+    /// (`CONNECTION_TIMEOUT_MS`, `REQUEST_TIMEOUT_MS`).
+    pub fn shared() -> Result<Client> {
+        if let Some(client) = SHARED_CLIENT.get() {
+            return Ok(client.clone());
+        }
+        let client = Self::build()?;
+        Ok(SHARED_CLIENT.get_or_init(|| client).clone())
+    }
+
+    /// A new client with its own connection pool, configured from the
+    /// environment.
+    pub fn build() -> Result<Client> {
+        let connection_timeout = Duration::from_millis(
+            std::env::var("CONNECTION_TIMEOUT_MS")
+                .unwrap_or_else(|_| "30000".to_string())
+                .parse()
+                .unwrap_or(30000)
+        );
+
+        let request_timeout = Duration::from_millis(
+            std::env::var("REQUEST_TIMEOUT_MS")
+                .unwrap_or_else(|_| "60000".to_string())
+                .parse()
+                .unwrap_or(60000)
+        );
+
+        Client::builder()
+            .user_agent(DEFAULT_USER_AGENT)
+            .connect_timeout(connection_timeout)
+            .timeout(request_timeout)
+            .pool_max_idle_per_host(10)
+            .pool_idle_timeout(Duration::from_secs(90))
+            .tcp_keepalive(Duration::from_secs(60))
+            .build()
+            .map_err(|e| ChromaError::ConfigError(format!("Failed to create HTTP client: {}", e)))
+    }
+}
+
+/// Identification headers stamped on every request before middleware runs,
+/// so they reach custom transports too. With an app id the user agent
+/// becomes `chromadb-demo/0.1.0 <app id>`.
+#[derive(Debug, Clone)]
+pub(crate) struct ClientIdentity {
+    user_agent: HeaderValue,
+    app_id: Option<HeaderValue>,
+}
+
+impl ClientIdentity {
+    /// Reads the app id from `CLIENT_APP_ID`.
+    pub(crate) fn from_env() -> Result<Self> {
+        let identity = Self {
+            user_agent: HeaderValue::from_static(DEFAULT_USER_AGENT),
+            app_id: None,
+        };
+        match std::env::var("CLIENT_APP_ID") {
+            Ok(app_id) if !app_id.trim().is_empty() => identity.with_app_id(&app_id),
+            _ => Ok(identity),
+        }
+    }
+
+    pub(crate) fn with_app_id(self, app_id: &str) -> Result<Self> {
+        let app_id = app_id.trim();
+        let invalid = |_| ChromaError::ConfigError(format!("Invalid app id for HTTP headers: {:?}", app_id));
+        Ok(Self {
+            user_agent: HeaderValue::from_str(&format!("{} {}", DEFAULT_USER_AGENT, app_id)).map_err(invalid)?,
+            app_id: Some(HeaderValue::from_str(app_id).map_err(invalid)?),
+        })
+    }
+
+    /// Add the identification headers a request doesn't already carry.
+    pub(crate) fn apply(&self, request: &mut Request) {
+        let headers = request.headers_mut();
+        if !headers.contains_key(USER_AGENT) {
+            headers.insert(USER_AGENT, self.user_agent.clone());
+        }
+        if let Some(app_id) = &self.app_id {
+            headers
+                .entry(HeaderName::from_static(APP_ID_HEADER))
+                .or_insert_with(|| app_id.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chroma_client::ChromaClient;
+    use crate::test_support::{MOCK_URL, mock_chroma, mock_transport};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_requests_carry_user_agent_and_app_id() {
+        use std::sync::Mutex;
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorded = seen.clone();
+        let client = mock_chroma(move |request| {
+            let header = |name: &str| request.headers().get(name).map(|v| v.to_str().unwrap().to_string());
+            recorded.lock().unwrap().push((header("user-agent"), header(APP_ID_HEADER)));
+            r#"{"id": "c0ffee", "name": "docs"}"#
+        });
+        client.health_check().await.unwrap();
+        let client = client.with_app_id("search-api").unwrap();
+        client.health_check().await.unwrap();
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen[0].0.as_deref(), Some(DEFAULT_USER_AGENT));
+        assert_eq!(seen[0].1, None);
+        assert_eq!(seen[1].0, Some(format!("{} search-api", DEFAULT_USER_AGENT)));
+        assert_eq!(seen[1].1.as_deref(), Some("search-api"));
+        assert!(ChromaClient::try_new(MOCK_URL.to_string()).unwrap().with_app_id("bad\napp").is_err());
+    }
+
+    /// A keep-alive HTTP/1.1 server answering every request with `body`,
+    /// and the number of connections it has accepted.
+    async fn counting_server(body: &'static str) -> (String, Arc<std::sync::atomic::AtomicUsize>) {
+        use std::sync::atomic::Ordering;
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let connections = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let accepted = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut stream = BufReader::new(stream);
+                    loop {
+                        let mut content_length = 0;
+                        let mut line = String::new();
+                        loop {
+                            line.clear();
+                            if stream.read_line(&mut line).await.unwrap_or(0) == 0 {
+                                return;
+                            }
+                            if line == "\r\n" {
+                                break;
+                            }
+                            if let Some((name, value)) = line.split_once(':')
+                                && name.eq_ignore_ascii_case("content-length")
+                            {
+                                content_length = value.trim().parse().unwrap();
+                            }
+                        }
+                        let mut request_body = vec![0; content_length];
+                        stream.read_exact(&mut request_body).await.unwrap();
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                            body.len(),
+                            body
+                        );
+                        stream.get_mut().write_all(response.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+        (url, connections)
+    }
+
+    #[tokio::test]
+    async fn test_clients_share_the_pooled_http_client_until_given_their_own() {
+        use crate::embeddings::EmbeddingClient;
+        use std::sync::atomic::Ordering;
+
+        let (url, connections) = counting_server(r#"{"embedding": {"values": [0.1, 0.2]}}"#).await;
+        let chroma = ChromaClient::try_new(url.clone()).unwrap();
+        let embeddings = EmbeddingClient::try_new("key".to_string())
+            .unwrap()
+            .with_base_url(&url)
+            .unwrap()
+            .with_expected_dimension(2);
+
+        // Both go through `HttpClientFactory::shared`, so the embedding call
+        // reuses the connection the health check left in the pool.
+        assert!(chroma.health_check().await.unwrap());
+        assert_eq!(embeddings.embed_text("hi").await.unwrap(), vec![0.1, 0.2]);
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+
+        // An app-managed client brings its own pool, and replaces a custom
+        // transport rather than sitting behind it.
+        let unavailable = mock_transport(|_| http::Response::builder().status(503).body("down".into()).unwrap());
+        let own = ChromaClient::try_new(url)
+            .unwrap()
+            .with_transport(unavailable)
+            .with_http_client(HttpClientFactory::build().unwrap());
+        assert!(own.health_check().await.unwrap());
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,378 @@
+use crate::atomic_file::AtomicFile;
+use crate::chroma_client::ChromaClient;
+use crate::compression;
+use crate::error::{ChromaError, Result};
+use crate::export::{ExportRecord, RecordParser};
+use crate::models::Document;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{info, warn};
+
+const DEFAULT_BATCH_SIZE: usize = 256;
+const DEFAULT_CONCURRENCY: usize = 4;
+/// Checkpoint of a sharded import, kept in the export directory.
+const SHARDED_CHECKPOINT_FILE: &str = "import-checkpoint.json";
+/// Appended to an export file's name for its import checkpoint.
+const CHECKPOINT_SUFFIX: &str = ".import-checkpoint.json";
+
+/// Settings for `ChromaClient::import_file` and `import_sharded`.
+#[derive(Debug, Clone)]
+pub struct ImportConfig {
+    /// Records upserted per request; progress is checkpointed after each.
+    pub batch_size: usize,
+    /// Shards of a sharded export imported at once.
+    pub concurrency: usize,
+    /// Continue from the checkpoint an interrupted import left, instead of
+    /// starting over.
+    pub resume: bool,
+}
+
+impl Default for ImportConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_BATCH_SIZE,
+            concurrency: DEFAULT_CONCURRENCY,
+            resume: false,
+        }
+    }
+}
+
+impl ImportConfig {
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub records_imported: usize,
+    /// Records a resumed import skipped because an earlier run had
+    /// confirmed them.
+    pub records_resumed: usize,
+    pub batches: usize,
+}
+
+impl ImportReport {
+    /// Records of the export now in the collection, this run's and earlier
+    /// runs'.
+    pub fn records(&self) -> usize {
+        self.records_imported + self.records_resumed
+    }
+
+    pub(crate) fn merge(&mut self, other: &ImportReport) {
+        self.records_imported += other.records_imported;
+        self.records_resumed += other.records_resumed;
+        self.batches += other.batches;
+    }
+}
+
+/// Upsert the JSON Lines written by `export` (compressed or not) into
+/// `collection_name`, in batches. Records must carry their embeddings.
+pub(crate) async fn import<R: BufRead + Send>(
+    client: &ChromaClient,
+    collection_name: &str,
+    reader: R,
+) -> Result<ImportReport> {
+    let report = import_from(client, collection_name, reader, 0, DEFAULT_BATCH_SIZE, |_| Ok(())).await?;
+    info!("Imported {} records into {}", report.records_imported, collection_name);
+    Ok(report)
+}
+
+/// `import` from a file, checkpointing each confirmed batch next to it so
+/// an interrupted run can be resumed with `ImportConfig::with_resume`. The
+/// checkpoint is removed once the collection is verified to hold at least
+/// as many records as were imported.
+pub(crate) async fn import_file(
+    client: &ChromaClient,
+    collection_name: &str,
+    path: &Path,
+    config: &ImportConfig,
+) -> Result<ImportReport> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| ChromaError::ValidationError(format!("{} is not a file path", path.display())))?;
+    let checkpoint_path = path.with_file_name(format!("{}{}", name, CHECKPOINT_SUFFIX));
+    let checkpoint = Checkpoint::open(checkpoint_path, collection_name, config)?;
+
+    let report = import_checkpointed(client, collection_name, path, &name, &checkpoint, config).await;
+    let report = checkpoint.finish(report)?;
+    checkpoint.finish(verify_count(client, collection_name, report.records()).await)?;
+    checkpoint.remove()?;
+    info!(
+        "Imported {} records into {} ({} resumed)",
+        report.records_imported, collection_name, report.records_resumed
+    );
+    Ok(report)
+}
+
+/// Import one file under `key` in `checkpoint`, skipping the records it
+/// already confirmed.
+pub(crate) async fn import_checkpointed(
+    client: &ChromaClient,
+    collection_name: &str,
+    path: &Path,
+    key: &str,
+    checkpoint: &Checkpoint,
+    config: &ImportConfig,
+) -> Result<ImportReport> {
+    let done = checkpoint.progress(key);
+    if done.complete {
+        return Ok(ImportReport { records_resumed: done.records, ..ImportReport::default() });
+    }
+    if done.records > 0 {
+        info!("Resuming {} after {} confirmed records", key, done.records);
+    }
+
+    let reader = BufReader::new(File::open(path)?);
+    let confirmed = |records| checkpoint.record(key, records, false);
+    let report = import_from(client, collection_name, reader, done.records, config.batch_size, confirmed).await?;
+    checkpoint.record(key, done.records + report.records_imported, true)?;
+    Ok(ImportReport { records_resumed: done.records, ..report })
+}
+
+/// Import the records of `reader` after the first `skip`, calling
+/// `confirmed` with the number of records done (skipped ones included)
+/// after each batch is written.
+async fn import_from<R: BufRead + Send>(
+    client: &ChromaClient,
+    collection_name: &str,
+    reader: R,
+    skip: usize,
+    batch_size: usize,
+    mut confirmed: impl FnMut(usize) -> Result<()>,
+) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut parser = RecordParser::default();
+    let mut records = BufReader::new(compression::decoder(reader)?)
+        .lines()
+        .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+        .skip(skip);
+    loop {
+        let line = records.next().transpose()?;
+        if let Some(line) = &line {
+            let record = parser.parse(line)??;
+            if !record.checksum_matches()? {
+                return Err(ChromaError::ValidationError(format!(
+                    "Record {} doesn't match its checksum; the export is damaged",
+                    record.id
+                )));
+            }
+            batch.push(record);
+        }
+        if batch.len() == batch_size || (line.is_none() && !batch.is_empty()) {
+            write_batch(client, collection_name, std::mem::take(&mut batch), &mut report).await?;
+            confirmed(skip + report.records_imported)?;
+        }
+        if line.is_none() {
+            return Ok(report);
+        }
+    }
+}
+
+/// Fail unless `collection_name` holds at least `expected` records.
+pub(crate) async fn verify_count(client: &ChromaClient, collection_name: &str, expected: usize) -> Result<()> {
+    let count = client.count(collection_name).await?;
+    if count < expected {
+        return Err(ChromaError::ValidationError(format!(
+            "{} holds {} records after importing {}",
+            collection_name, count, expected
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub(crate) struct FileProgress {
+    /// Records confirmed written, in file order.
+    pub records: usize,
+    pub complete: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CheckpointState {
+    collection: String,
+    files: BTreeMap<String, FileProgress>,
+}
+
+/// Import progress per file, rewritten atomically after every confirmed
+/// batch so a crash loses at most the batch in flight.
+pub(crate) struct Checkpoint {
+    path: PathBuf,
+    state: Mutex<CheckpointState>,
+}
+
+impl Checkpoint {
+    /// The checkpoint of a sharded import of `dir`.
+    pub(crate) fn open_in(dir: &Path, collection_name: &str, config: &ImportConfig) -> Result<Self> {
+        Self::open(dir.join(SHARDED_CHECKPOINT_FILE), collection_name, config)
+    }
+
+    /// Pick up the checkpoint at `path` when resuming, or start a fresh one.
+    fn open(path: PathBuf, collection_name: &str, config: &ImportConfig) -> Result<Self> {
+        let state = if config.resume && path.exists() {
+            let state: CheckpointState = serde_json::from_reader(BufReader::new(File::open(&path)?))?;
+            if state.collection != collection_name {
+                return Err(ChromaError::ValidationError(format!(
+                    "The checkpoint at {} is for an import into {}, not {}",
+                    path.display(),
+                    state.collection,
+                    collection_name
+                )));
+            }
+            state
+        } else {
+            CheckpointState { collection: collection_name.to_string(), files: BTreeMap::new() }
+        };
+        Ok(Self { path, state: Mutex::new(state) })
+    }
+
+    pub(crate) fn progress(&self, key: &str) -> FileProgress {
+        self.lock().files.get(key).copied().unwrap_or_default()
+    }
+
+    pub(crate) fn record(&self, key: &str, records: usize, complete: bool) -> Result<()> {
+        let mut state = self.lock();
+        state.files.insert(key.to_string(), FileProgress { records, complete });
+        let mut out = AtomicFile::create(&self.path)?;
+        serde_json::to_writer(&mut out, &*state)?;
+        out.commit()
+    }
+
+    /// Pass `result` through, pointing at the checkpoint when it failed.
+    pub(crate) fn finish<T>(&self, result: Result<T>) -> Result<T> {
+        if let Err(e) = &result {
+            warn!("Import stopped ({}); resume it to continue from {}", e, self.path.display());
+        }
+        result
+    }
+
+    /// Drop the checkpoint of a verified import.
+    pub(crate) fn remove(&self) -> Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CheckpointState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+async fn write_batch(
+    client: &ChromaClient,
+    collection_name: &str,
+    records: Vec<ExportRecord>,
+    report: &mut ImportReport,
+) -> Result<()> {
+    let count = records.len();
+    let (documents, embeddings) = records.into_iter().map(into_document).collect::<Result<(Vec<_>, Vec<_>)>>()?;
+    client.upsert_documents(collection_name, documents, embeddings).await?;
+    report.records_imported += count;
+    report.batches += 1;
+    Ok(())
+}
+
+/// The document and embedding to write back for an exported record.
+fn into_document(record: ExportRecord) -> Result<(Document, Vec<f32>)> {
+    let embedding = record.embedding.ok_or_else(|| {
+        ChromaError::ValidationError(format!(
+            "Record {} has no embedding; only exports that include embeddings can be imported",
+            record.id
+        ))
+    })?;
+    let metadata = match record.metadata {
+        Some(Value::Object(fields)) => fields
+            .into_iter()
+            .map(|(key, value)| match value {
+                Value::String(s) => (key, s),
+                other => (key, other.to_string()),
+            })
+            .collect(),
+        _ => Default::default(),
+    };
+    let document = Document {
+        id: record.id,
+        content: record.document.unwrap_or_default(),
+        metadata,
+        uri: record.uri,
+    };
+    Ok((document, embedding))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_import_resumes_from_the_last_confirmed_batch() {
+        use std::sync::Mutex;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use crate::transport::HttpResponse;
+
+        let upserted: Arc<Mutex<Vec<String>>> = Arc::default();
+        let recorded = upserted.clone();
+        let failing = Arc::new(AtomicBool::new(true));
+        let fail = failing.clone();
+        let client = mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap_or_default();
+            if path.ends_with("/upsert") {
+                let ids: Vec<String> =
+                    body["ids"].as_array().unwrap().iter().map(|id| id.as_str().unwrap().into()).collect();
+                if fail.load(Ordering::SeqCst) && ids.contains(&"r2".to_string()) {
+                    http::Response::builder().status(400).body("rejected".into()).unwrap()
+                } else {
+                    recorded.lock().unwrap().extend(ids);
+                    HttpResponse::new("true".into())
+                }
+            } else if path.ends_with("/count") {
+                HttpResponse::new("5".into())
+            } else {
+                HttpResponse::new(r#"{"id": "c0ffee", "name": "docs"}"#.into())
+            }
+        });
+
+        let dir = std::env::temp_dir().join(format!("import-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("docs.jsonl");
+        let lines: Vec<String> = (0..5)
+            .map(|i| format!(r#"{{"id": "r{}", "document": "text {}", "embedding": [0.5, 0.5]}}"#, i, i))
+            .collect();
+        std::fs::write(&file, lines.join("\n") + "\n").unwrap();
+        let checkpoint = dir.join("docs.jsonl.import-checkpoint.json");
+
+        let config = ImportConfig::default().with_batch_size(2);
+        assert!(client.import_file("docs", &file, &config).await.is_err());
+        assert!(checkpoint.exists());
+        assert_eq!(*upserted.lock().unwrap(), vec!["r0", "r1"]);
+
+        failing.store(false, Ordering::SeqCst);
+        let report = client.import_file("docs", &file, &config.with_resume(true)).await.unwrap();
+        assert_eq!((report.records_imported, report.records_resumed, report.batches), (3, 2, 2));
+        assert_eq!(*upserted.lock().unwrap(), vec!["r0", "r1", "r2", "r3", "r4"]);
+        assert!(!checkpoint.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,248 @@
+use crate::error::{ChromaError, Result};
+use crate::models::Document;
+use crate::pipeline::{Pipeline, QueryResult};
+use crate::query::QueryOptions;
+use crate::rate_limit::RateLimiter;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+use tracing::{debug, error};
+
+/// Batching and rate limits for an `IndexerHandle`.
+#[derive(Debug, Clone)]
+pub struct IndexerConfig {
+    /// Documents per embed + upsert call.
+    pub batch_size: usize,
+    /// How long a partial batch may wait for more documents before it is
+    /// written anyway.
+    pub max_batch_delay: Duration,
+    /// Ceiling on embedding requests per second, shared by writes and
+    /// queries. `None` means unlimited.
+    pub max_requests_per_second: Option<f64>,
+    /// Submitted documents buffered before `submit` starts waiting.
+    pub queue_capacity: usize,
+}
+
+impl Default for IndexerConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 32,
+            max_batch_delay: Duration::from_millis(500),
+            max_requests_per_second: None,
+            queue_capacity: 1024,
+        }
+    }
+}
+
+impl IndexerConfig {
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn with_max_batch_delay(mut self, max_batch_delay: Duration) -> Self {
+        self.max_batch_delay = max_batch_delay;
+        self
+    }
+
+    pub fn with_max_requests_per_second(mut self, per_second: f64) -> Self {
+        self.max_requests_per_second = Some(per_second);
+        self
+    }
+
+    pub fn with_queue_capacity(mut self, queue_capacity: usize) -> Self {
+        self.queue_capacity = queue_capacity.max(1);
+        self
+    }
+}
+
+enum Command {
+    Submit(Document),
+    Flush(oneshot::Sender<Result<usize>>),
+}
+
+/// Cheap-to-clone handle to a background task that indexes documents
+/// through a `Pipeline`.
+///
+/// Request handlers call `submit` and return; the task groups documents
+/// into batches, respects the configured request rate, and writes a batch
+/// once it is full or has waited `max_batch_delay`. Write failures are
+/// logged and reported by the next `flush`. The task stops once every
+/// handle has been dropped, writing whatever is still pending.
+///
+/// ```no_run
+/// # async fn example(pipeline: std::sync::Arc<chromadb_demo::Pipeline>, doc: chromadb_demo::Document) -> chromadb_demo::Result<()> {
+/// use chromadb_demo::indexer::{IndexerConfig, IndexerHandle};
+///
+/// let indexer = IndexerHandle::spawn(pipeline, IndexerConfig::default().with_max_requests_per_second(5.0));
+/// indexer.submit(doc).await?;
+/// indexer.flush().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct IndexerHandle {
+    commands: mpsc::Sender<Command>,
+    pipeline: Arc<Pipeline>,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl IndexerHandle {
+    /// Start the indexing task on the current Tokio runtime.
+    pub fn spawn(pipeline: Arc<Pipeline>, config: IndexerConfig) -> Self {
+        let (commands, receiver) = mpsc::channel(config.queue_capacity.max(1));
+        let rate_limiter = Arc::new(RateLimiter::per_second(config.max_requests_per_second.unwrap_or(0.0)));
+
+        let worker = Worker {
+            pipeline: pipeline.clone(),
+            rate_limiter: rate_limiter.clone(),
+            batch_size: config.batch_size.max(1),
+            max_batch_delay: config.max_batch_delay,
+            pending: Vec::new(),
+            oldest_pending: None,
+            written: 0,
+            last_error: None,
+        };
+        tokio::spawn(worker.run(receiver));
+
+        Self { commands, pipeline, rate_limiter }
+    }
+
+    /// Queue a document for indexing. Waits only if the queue is full.
+    pub async fn submit(&self, document: Document) -> Result<()> {
+        self.commands
+            .send(Command::Submit(document))
+            .await
+            .map_err(|_| stopped())
+    }
+
+    /// Write everything submitted so far and return how many documents were
+    /// written since the previous flush, or the first write error since then.
+    pub async fn flush(&self) -> Result<usize> {
+        let (reply, response) = oneshot::channel();
+        self.commands.send(Command::Flush(reply)).await.map_err(|_| stopped())?;
+        response.await.map_err(|_| stopped())?
+    }
+
+    /// Run a query under the same rate limit as indexing. Documents still
+    /// queued are not visible until they have been written.
+    pub async fn query(&self, text: &str, options: &QueryOptions) -> Result<QueryResult> {
+        self.rate_limiter.acquire().await;
+        self.pipeline.query(text, options).await
+    }
+}
+
+fn stopped() -> ChromaError {
+    ChromaError::IndexerError("Indexer task has stopped".to_string())
+}
+
+struct Worker {
+    pipeline: Arc<Pipeline>,
+    rate_limiter: Arc<RateLimiter>,
+    batch_size: usize,
+    max_batch_delay: Duration,
+    pending: Vec<Document>,
+    oldest_pending: Option<Instant>,
+    written: usize,
+    last_error: Option<ChromaError>,
+}
+
+impl Worker {
+    async fn run(mut self, mut commands: mpsc::Receiver<Command>) {
+        loop {
+            let deadline = self.oldest_pending.map(|t| t + self.max_batch_delay);
+            let command = tokio::select! {
+                command = commands.recv() => command,
+                _ = sleep_until(deadline) => {
+                    self.write_pending().await;
+                    continue;
+                }
+            };
+
+            match command {
+                Some(Command::Submit(document)) => {
+                    self.oldest_pending.get_or_insert_with(Instant::now);
+                    self.pending.push(document);
+                    if self.pending.len() >= self.batch_size {
+                        self.write_pending().await;
+                    }
+                }
+                Some(Command::Flush(reply)) => {
+                    self.write_pending().await;
+                    let result = match self.last_error.take() {
+                        Some(e) => Err(e),
+                        None => Ok(self.written),
+                    };
+                    self.written = 0;
+                    let _ = reply.send(result);
+                }
+                None => {
+                    self.write_pending().await;
+                    debug!("Indexer stopped");
+                    return;
+                }
+            }
+        }
+    }
+
+    async fn write_pending(&mut self) {
+        self.oldest_pending = None;
+        while !self.pending.is_empty() {
+            let rest = self.pending.split_off(self.pending.len().min(self.batch_size));
+            let batch = std::mem::replace(&mut self.pending, rest);
+            let count = batch.len();
+
+            self.rate_limiter.acquire().await;
+            match self.pipeline.ingest(batch).await {
+                Ok(written) => self.written += written,
+                Err(e) => {
+                    error!("Indexer dropped a batch of {} documents: {}", count, e);
+                    self.last_error.get_or_insert(e);
+                }
+            }
+        }
+    }
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{FixedEmbeddings, mock_chroma};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_indexer_batches_submitted_documents() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let upserts = Arc::new(AtomicUsize::new(0));
+        let counter = upserts.clone();
+        let chroma = mock_chroma(move |request| {
+            if request.uri().path().ends_with("/upsert") {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+            r#"{"id": "c0ffee", "name": "docs"}"#
+        });
+        let pipeline = Arc::new(Pipeline::new(Arc::new(chroma), Arc::new(FixedEmbeddings), "docs"));
+        let indexer = IndexerHandle::spawn(
+            pipeline,
+            IndexerConfig::default().with_batch_size(2).with_max_batch_delay(std::time::Duration::from_secs(60)),
+        );
+
+        for i in 0..5 {
+            let document = Document { id: format!("doc{}", i), content: "text".to_string(), metadata: HashMap::new(), uri: None };
+            indexer.submit(document).await.unwrap();
+        }
+        assert_eq!(indexer.flush().await.unwrap(), 5);
+        assert_eq!(upserts.load(Ordering::SeqCst), 3);
+        assert_eq!(indexer.flush().await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,553 @@
+use crate::chroma_client::validate_url;
+use crate::error::{ChromaError, Result};
+use crate::http_client::{ClientIdentity, HttpClientFactory};
+use crate::middleware::{Middleware, Next, with_attempt};
+use crate::prompt::estimate_tokens;
+use crate::transport::Transport;
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream, StreamExt};
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+const DEFAULT_GEMINI_API_BASE: &str = "https://generativelanguage.googleapis.com";
+const DEFAULT_GEMINI_API_VERSION: &str = "v1beta";
+const DEFAULT_GEMINI_MODEL: &str = "gemini-2.0-flash";
+const DEFAULT_OPENAI_API_BASE: &str = "https://api.openai.com/v1";
+const DEFAULT_OPENAI_MODEL: &str = "gpt-4o-mini";
+
+/// Text chunks of a streamed generation, in order.
+pub type TextStream = BoxStream<'static, Result<String>>;
+
+/// One prompt to complete.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GenerationRequest {
+    pub prompt: String,
+    pub system: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    /// Ask for a JSON response (Gemini's `responseMimeType`, OpenAI's
+    /// `response_format`). The content still needs validating.
+    pub json: bool,
+}
+
+impl GenerationRequest {
+    pub fn new(prompt: impl Into<String>) -> Self {
+        Self {
+            prompt: prompt.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn with_json(mut self) -> Self {
+        self.json = true;
+        self
+    }
+}
+
+/// A completed generation, with token usage when the API reports it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Generation {
+    pub text: String,
+    pub model: String,
+    pub prompt_tokens: Option<usize>,
+    pub completion_tokens: Option<usize>,
+}
+
+/// Anything that completes prompts, so the answer step can swap Gemini for
+/// an OpenAI-compatible endpoint (or a stub in tests), the way
+/// `EmbeddingProvider` does for embeddings.
+pub trait LlmProvider: Send + Sync {
+    fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, Result<Generation>>;
+
+    /// The generation as text chunks. Defaults to one chunk holding the
+    /// whole `generate` output.
+    fn generate_stream<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, Result<TextStream>> {
+        Box::pin(async move {
+            let generation = self.generate(request).await?;
+            Ok(stream::once(async move { Ok(generation.text) }).boxed())
+        })
+    }
+
+    /// Tokens `text` takes up in the model's context. Defaults to
+    /// `estimate_tokens`.
+    fn count_tokens<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(async move { Ok(estimate_tokens(text)) })
+    }
+
+    fn model_name(&self) -> &str;
+
+    fn provider_name(&self) -> &str {
+        "custom"
+    }
+}
+
+/// The provider `LLM_PROVIDER` names: `gemini` (the default, keyed by
+/// `GOOGLE_API_KEY`) or `openai` for any OpenAI-compatible endpoint
+/// (see `OpenAiCompatibleLlm::from_env`).
+pub fn from_env() -> Result<Arc<dyn LlmProvider>> {
+    match std::env::var("LLM_PROVIDER").as_deref().map(str::trim) {
+        Err(_) | Ok("" | "gemini") => {
+            let api_key = std::env::var("GOOGLE_API_KEY")
+                .map_err(|_| ChromaError::ConfigError("GOOGLE_API_KEY is not set".to_string()))?;
+            Ok(Arc::new(GeminiLlm::try_new(api_key)?))
+        }
+        Ok("openai") => Ok(Arc::new(OpenAiCompatibleLlm::from_env()?)),
+        Ok(other) => Err(ChromaError::ConfigError(format!(
+            "Unknown LLM_PROVIDER '{}', expected gemini or openai",
+            other
+        ))),
+    }
+}
+
+/// HTTP plumbing shared by the LLM clients: the same transport, middleware,
+/// identity headers and retry policy as the other clients.
+#[derive(Clone)]
+struct LlmHttp {
+    client: Client,
+    transport: Transport,
+    middleware: Vec<Arc<dyn Middleware>>,
+    identity: ClientIdentity,
+    bearer_token: Option<String>,
+    max_retries: u32,
+    retry_delay: Duration,
+}
+
+impl LlmHttp {
+    fn from_env() -> Result<Self> {
+        let client = HttpClientFactory::shared()?;
+        Ok(Self {
+            transport: Transport::reqwest(client.clone()),
+            client,
+            middleware: crate::middleware::from_env(),
+            identity: ClientIdentity::from_env()?,
+            bearer_token: None,
+            max_retries: std::env::var("MAX_RETRIES").ok().and_then(|v| v.parse().ok()).unwrap_or(3),
+            retry_delay: Duration::from_millis(
+                std::env::var("RETRY_DELAY_MS").ok().and_then(|v| v.parse().ok()).unwrap_or(1000),
+            ),
+        })
+    }
+
+    /// POST `body` and return the response body, retrying rate limits and
+    /// server errors. `service` names the API in error messages.
+    async fn post(&self, service: &str, url: &str, body: &Value) -> Result<Bytes> {
+        let mut retries = 0;
+        loop {
+            match with_attempt(retries, self.post_once(service, url, body)).await {
+                Ok(body) => return Ok(body),
+                Err((e, true)) if retries < self.max_retries => {
+                    retries += 1;
+                    warn!(
+                        "{} request failed (attempt {}/{}): {}. Retrying in {:?}",
+                        service,
+                        retries,
+                        self.max_retries + 1,
+                        e,
+                        self.retry_delay
+                    );
+                    tokio::time::sleep(self.retry_delay * retries).await;
+                }
+                Err((e, _)) => return Err(e),
+            }
+        }
+    }
+
+    /// One attempt; errors say whether they are worth retrying.
+    async fn post_once(&self, service: &str, url: &str, body: &Value) -> std::result::Result<Bytes, (ChromaError, bool)> {
+        let mut builder = self.client.post(url).json(body);
+        if let Some(token) = &self.bearer_token {
+            builder = builder.bearer_auth(token);
+        }
+        let mut request = builder.build().map_err(|e| (ChromaError::from(e), false))?;
+        self.identity.apply(&mut request);
+
+        let response = Next::new(&self.transport, &self.middleware)
+            .run(request)
+            .await
+            .map_err(|e| (e, true))?;
+        let status = response.status();
+        let bytes = response.bytes().await.map_err(|e| (ChromaError::from(e), true))?;
+        if status.is_success() {
+            return Ok(bytes);
+        }
+        let retryable = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+        Err((
+            ChromaError::GenerationError(format!("{} API error {}: {}", service, status, String::from_utf8_lossy(&bytes))),
+            retryable,
+        ))
+    }
+}
+
+/// Text of each `data:` event in a server-sent events body, skipping the
+/// OpenAI-style `[DONE]` terminator. Multi-line data is joined with `\n`.
+fn sse_events(body: &[u8]) -> Vec<String> {
+    let body = String::from_utf8_lossy(body);
+    let mut events = Vec::new();
+    let mut data: Vec<&str> = Vec::new();
+    for line in body.lines().chain(std::iter::once("")) {
+        if line.is_empty() {
+            if !data.is_empty() {
+                let event = data.join("\n");
+                if event != "[DONE]" {
+                    events.push(event);
+                }
+                data.clear();
+            }
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push(value.strip_prefix(' ').unwrap_or(value));
+        }
+    }
+    events
+}
+
+/// Turn a server-sent events body into a stream of the text each event
+/// carries, as extracted by `text`.
+fn sse_stream(body: &[u8], text: fn(&Value) -> Result<String>) -> TextStream {
+    let chunks: Vec<Result<String>> = sse_events(body)
+        .into_iter()
+        .map(|event| text(&serde_json::from_str(&event)?))
+        .filter(|chunk| !matches!(chunk, Ok(chunk) if chunk.is_empty()))
+        .collect();
+    stream::iter(chunks).boxed()
+}
+
+/// Gemini `generateContent` client.
+#[derive(Clone)]
+pub struct GeminiLlm {
+    http: LlmHttp,
+    base_url: String,
+    api_version: String,
+    model: String,
+    api_key: String,
+}
+
+impl GeminiLlm {
+    /// Configured like `EmbeddingClient` (`GEMINI_API_BASE`,
+    /// `GEMINI_API_VERSION`), with the model from `GEMINI_GENERATION_MODEL`.
+    pub fn try_new(api_key: String) -> Result<Self> {
+        if api_key.trim().is_empty() {
+            return Err(ChromaError::ConfigError("Gemini API key is empty".to_string()));
+        }
+        Ok(Self {
+            http: LlmHttp::from_env()?,
+            base_url: validate_url(
+                &std::env::var("GEMINI_API_BASE").unwrap_or_else(|_| DEFAULT_GEMINI_API_BASE.to_string()),
+            )?,
+            api_version: std::env::var("GEMINI_API_VERSION")
+                .unwrap_or_else(|_| DEFAULT_GEMINI_API_VERSION.to_string())
+                .trim_matches('/')
+                .to_string(),
+            model: std::env::var("GEMINI_GENERATION_MODEL").unwrap_or_else(|_| DEFAULT_GEMINI_MODEL.to_string()),
+            api_key,
+        })
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> Result<Self> {
+        self.base_url = validate_url(base_url)?;
+        Ok(self)
+    }
+
+    /// Model, with or without the `models/` prefix.
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+
+    pub fn with_middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.http.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.http.transport = transport;
+        self
+    }
+
+    fn endpoint(&self, method: &str, query: &str) -> String {
+        let model = self.model.trim_start_matches("models/");
+        let version = if self.api_version.is_empty() { String::new() } else { format!("/{}", self.api_version) };
+        format!("{}{}/models/{}:{}?{}key={}", self.base_url, version, model, method, query, self.api_key)
+    }
+
+    fn body(request: &GenerationRequest) -> Value {
+        let mut body = json!({ "contents": [{ "role": "user", "parts": [{ "text": request.prompt }] }] });
+        if let Some(system) = &request.system {
+            body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+        }
+        let mut config = serde_json::Map::new();
+        if let Some(temperature) = request.temperature {
+            config.insert("temperature".to_string(), json!(temperature));
+        }
+        if let Some(max_tokens) = request.max_tokens {
+            config.insert("maxOutputTokens".to_string(), json!(max_tokens));
+        }
+        if request.json {
+            config.insert("responseMimeType".to_string(), json!("application/json"));
+        }
+        if !config.is_empty() {
+            body["generationConfig"] = Value::Object(config);
+        }
+        body
+    }
+
+    /// Text of the first candidate. A response without candidates means the
+    /// prompt was blocked.
+    fn text(response: &Value) -> Result<String> {
+        let Some(parts) = response["candidates"][0]["content"]["parts"].as_array() else {
+            if response["candidates"].is_array() {
+                return Ok(String::new());
+            }
+            let reason = response["promptFeedback"]["blockReason"].as_str().unwrap_or("no candidates returned");
+            return Err(ChromaError::GenerationError(format!("Gemini refused the prompt: {}", reason)));
+        };
+        Ok(parts.iter().filter_map(|part| part["text"].as_str()).collect())
+    }
+}
+
+impl LlmProvider for GeminiLlm {
+    fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, Result<Generation>> {
+        Box::pin(async move {
+            let body = self.http.post("Gemini", &self.endpoint("generateContent", ""), &Self::body(request)).await?;
+            let response: Value = serde_json::from_slice(&body)?;
+            let usage = |key: &str| response["usageMetadata"][key].as_u64().map(|n| n as usize);
+            Ok(Generation {
+                text: Self::text(&response)?,
+                model: self.model.clone(),
+                prompt_tokens: usage("promptTokenCount"),
+                completion_tokens: usage("candidatesTokenCount"),
+            })
+        })
+    }
+
+    fn generate_stream<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, Result<TextStream>> {
+        Box::pin(async move {
+            let url = self.endpoint("streamGenerateContent", "alt=sse&");
+            let body = self.http.post("Gemini", &url, &Self::body(request)).await?;
+            Ok(sse_stream(&body, Self::text))
+        })
+    }
+
+    fn count_tokens<'a>(&'a self, text: &'a str) -> BoxFuture<'a, Result<usize>> {
+        Box::pin(async move {
+            let request = json!({ "contents": [{ "role": "user", "parts": [{ "text": text }] }] });
+            let body = self.http.post("Gemini", &self.endpoint("countTokens", ""), &request).await?;
+            let response: Value = serde_json::from_slice(&body)?;
+            response["totalTokens"]
+                .as_u64()
+                .map(|n| n as usize)
+                .ok_or_else(|| ChromaError::GenerationError("Gemini countTokens returned no totalTokens".to_string()))
+        })
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    fn provider_name(&self) -> &str {
+        "gemini"
+    }
+}
+
+/// Client for OpenAI's `/chat/completions` API and the servers that mimic
+/// it: OpenAI itself, vLLM, Ollama, LiteLLM and llama.cpp's `llama-server`.
+/// Token counts use `estimate_tokens`, since there is no standard
+/// tokenizer endpoint.
+#[derive(Clone)]
+pub struct OpenAiCompatibleLlm {
+    http: LlmHttp,
+    base_url: String,
+    model: String,
+}
+
+impl OpenAiCompatibleLlm {
+    /// `base_url` includes the API version, e.g. `http://localhost:8080/v1`
+    /// for a local llama.cpp server.
+    pub fn new(base_url: &str, model: &str) -> Result<Self> {
+        Ok(Self {
+            http: LlmHttp::from_env()?,
+            base_url: validate_url(base_url)?,
+            model: model.to_string(),
+        })
+    }
+
+    /// Configured by `OPENAI_API_BASE`, `OPENAI_MODEL` and, for hosted
+    /// APIs, `OPENAI_API_KEY`.
+    pub fn from_env() -> Result<Self> {
+        let base_url = std::env::var("OPENAI_API_BASE").unwrap_or_else(|_| DEFAULT_OPENAI_API_BASE.to_string());
+        let model = std::env::var("OPENAI_MODEL").unwrap_or_else(|_| DEFAULT_OPENAI_MODEL.to_string());
+        let llm = Self::new(&base_url, &model)?;
+        match std::env::var("OPENAI_API_KEY") {
+            Ok(key) if !key.trim().is_empty() => Ok(llm.with_api_key(key.trim())),
+            _ => Ok(llm),
+        }
+    }
+
+    /// Sent as a bearer token; local servers usually need none.
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.http.bearer_token = Some(api_key.to_string());
+        self
+    }
+
+    pub fn with_middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.http.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    pub fn with_transport(mut self, transport: Transport) -> Self {
+        self.http.transport = transport;
+        self
+    }
+
+    fn body(&self, request: &GenerationRequest, stream: bool) -> Value {
+        let mut messages = Vec::new();
+        if let Some(system) = &request.system {
+            messages.push(json!({ "role": "system", "content": system }));
+        }
+        messages.push(json!({ "role": "user", "content": request.prompt }));
+
+        let mut body = json!({ "model": self.model, "messages": messages, "stream": stream });
+        if let Some(temperature) = request.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(max_tokens) = request.max_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        if request.json {
+            body["response_format"] = json!({ "type": "json_object" });
+        }
+        body
+    }
+
+    fn url(&self) -> String {
+        format!("{}/chat/completions", self.base_url)
+    }
+
+    fn delta(event: &Value) -> Result<String> {
+        Ok(event["choices"][0]["delta"]["content"].as_str().unwrap_or_default().to_string())
+    }
+}
+
+impl LlmProvider for OpenAiCompatibleLlm {
+    fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, Result<Generation>> {
+        Box::pin(async move {
+            let body = self.http.post("OpenAI-compatible", &self.url(), &self.body(request, false)).await?;
+            let response: Value = serde_json::from_slice(&body)?;
+            let text = response["choices"][0]["message"]["content"].as_str().ok_or_else(|| {
+                ChromaError::GenerationError("OpenAI-compatible response has no message content".to_string())
+            })?;
+            let usage = |key: &str| response["usage"][key].as_u64().map(|n| n as usize);
+            Ok(Generation {
+                text: text.to_string(),
+                model: response["model"].as_str().unwrap_or(&self.model).to_string(),
+                prompt_tokens: usage("prompt_tokens"),
+                completion_tokens: usage("completion_tokens"),
+            })
+        })
+    }
+
+    fn generate_stream<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, Result<TextStream>> {
+        Box::pin(async move {
+            let body = self.http.post("OpenAI-compatible", &self.url(), &self.body(request, true)).await?;
+            Ok(sse_stream(&body, Self::delta))
+        })
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    fn provider_name(&self) -> &str {
+        "openai"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::answer_cache::AnswerCache;
+    use crate::chroma_client::ChromaClient;
+    use crate::pipeline::Pipeline;
+    use crate::query::QueryOptions;
+    use crate::test_support::{FixedEmbeddings, MOCK_URL, mock_transport};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_pipeline_answers_with_a_pluggable_llm() {
+        use futures::StreamExt;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let chroma_service = mock_transport(|request| {
+            if request.uri().path().ends_with("/query") {
+                r#"{"ids": [["a", "b"]], "distances": [[0.1, 0.2]], "documents": [["Rust is safe.", "Rust is fast."]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+        let chroma = Arc::new(ChromaClient::try_new(MOCK_URL.to_string()).unwrap().with_transport(chroma_service));
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let llm_calls = calls.clone();
+        let llm_service = mock_transport(move |request| {
+            llm_calls.fetch_add(1, Ordering::SeqCst);
+            let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+            let prompt = body["messages"][0]["content"].as_str().unwrap_or_default();
+            let answer = if prompt.contains("[a] Rust is safe.\n\n[b] Rust is fast.") { "Safe and fast [a][b]." } else { "no context" };
+            if body["stream"] == true {
+                format!(
+                    "data: {}\n\ndata: {}\n\ndata: [DONE]\n\n",
+                    serde_json::json!({ "choices": [{ "delta": { "content": "Safe and " } }] }),
+                    serde_json::json!({ "choices": [{ "delta": { "content": "fast." } }] })
+                )
+            } else {
+                serde_json::json!({ "model": "llama-3", "choices": [{ "message": { "content": answer } }] }).to_string()
+            }
+        });
+        let llm = OpenAiCompatibleLlm::new("http://127.0.0.1:8080/v1", "llama-3").unwrap().with_transport(llm_service);
+
+        let stream: Vec<String> = llm
+            .generate_stream(&GenerationRequest::new("hi"))
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(stream, vec!["Safe and ", "fast."]);
+        calls.store(0, Ordering::SeqCst);
+
+        let without_llm = Pipeline::new(chroma.clone(), Arc::new(FixedEmbeddings), "docs");
+        assert!(matches!(without_llm.answer("Why Rust?", &QueryOptions::new(2)).await, Err(ChromaError::ConfigError(_))));
+
+        let pipeline = Pipeline::new(chroma, Arc::new(FixedEmbeddings), "docs")
+            .with_llm(Arc::new(llm))
+            .with_answer_cache(Arc::new(AnswerCache::new(10)));
+        let answer = pipeline.answer("Why Rust?", &QueryOptions::new(2)).await.unwrap();
+        assert_eq!(answer.text, "Safe and fast [a][b].");
+        assert_eq!(answer.context_ids, vec!["a", "b"]);
+        assert!(!answer.cached);
+
+        let again = pipeline.answer("Why Rust?", &QueryOptions::new(2)).await.unwrap();
+        assert!(again.cached);
+        assert_eq!(again.text, answer.text);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
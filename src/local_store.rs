@@ -0,0 +1,417 @@
+use crate::atomic_file;
+use crate::compression::{self, Compression};
+use crate::encryption::StoreCipher;
+use crate::error::{ChromaError, Result};
+use crate::filter::Filter;
+use crate::models::{DistanceSpace, rank_order};
+use crate::score::Score;
+use crate::vector_ops::{check_vector, similarity};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::warn;
+
+/// Stores smaller than this are scored on the calling thread even with the
+/// `parallel` feature; splitting the work costs more than it saves.
+#[cfg(feature = "parallel")]
+const PARALLEL_THRESHOLD: usize = 4096;
+
+/// The `k` best of `scored` in rank order (descending score, then id),
+/// selected in O(n) rather than by sorting everything.
+pub(crate) fn top_k<T>(mut scored: Vec<(f32, T)>, k: usize, id: impl Fn(&T) -> &str) -> Vec<(f32, T)> {
+    let order = |a: &(f32, T), b: &(f32, T)| rank_order(a.0, id(&a.1), b.0, id(&b.1));
+    if k == 0 {
+        return Vec::new();
+    }
+    if k < scored.len() {
+        scored.select_nth_unstable_by(k - 1, &order);
+        scored.truncate(k);
+    }
+    scored.sort_by(&order);
+    scored
+}
+
+/// Similarity of `doc` to the query, or `None` if `filter` excludes it.
+fn score_document<'a>(
+    doc: &'a StoredDocument,
+    query_embedding: &[f32],
+    space: DistanceSpace,
+    filter: Option<&Filter>,
+) -> Option<Result<(f32, &'a StoredDocument)>> {
+    match filter.map(|filter| filter.matches(&doc.metadata)) {
+        Some(Ok(false)) => None,
+        Some(Err(e)) => Some(Err(e)),
+        _ => Some(Ok((similarity(space, query_embedding, &doc.embedding), doc))),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredDocument {
+    pub id: String,
+    pub content: String,
+    pub embedding: Vec<f32>,
+    pub metadata: HashMap<String, String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// In-memory vector store persisted to a single JSON file, for deployments
+/// that don't run a Chroma server.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VectorStore {
+    pub documents: Vec<StoredDocument>,
+    pub dimension: usize,
+    pub model: String,
+    /// Documents loaded with unusable embeddings (NaN, infinite, zero).
+    /// They are kept so they can be re-embedded, but never searched.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quarantined: Vec<StoredDocument>,
+    /// Metric `search` ranks by. Match the `hnsw:space` of the collection
+    /// the store mirrors so scores line up with `QueryHit::score`.
+    #[serde(default = "default_space")]
+    pub space: DistanceSpace,
+}
+
+/// Stores saved before the metric was configurable used cosine.
+fn default_space() -> DistanceSpace {
+    DistanceSpace::Cosine
+}
+
+impl Default for VectorStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VectorStore {
+    pub fn new() -> Self {
+        Self {
+            documents: Vec::new(),
+            dimension: 3072, // Gemini embedding dimension
+            model: "gemini-embedding-exp-03-07".to_string(),
+            quarantined: Vec::new(),
+            space: default_space(),
+        }
+    }
+
+    pub fn with_space(mut self, space: DistanceSpace) -> Self {
+        self.space = space;
+        self
+    }
+
+    /// A similarity returned by `search` as a `Score` in this store's space.
+    pub fn score(&self, similarity: f32) -> Score {
+        Score::similarity(self.space, similarity)
+    }
+
+    /// Add a document, rejecting embeddings that are empty, all zeros or
+    /// contain NaN/infinite values.
+    pub fn add_document(&mut self, doc: StoredDocument) -> Result<()> {
+        Self::check_document(&doc)?;
+        self.documents.push(doc);
+        Ok(())
+    }
+
+    pub(crate) fn check_document(doc: &StoredDocument) -> Result<()> {
+        match check_vector(&doc.embedding) {
+            Some(defect) => Err(ChromaError::ValidationError(format!(
+                "Embedding of document '{}' is unusable: {}", doc.id, defect
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Remove every document with `id`, returning the first one removed.
+    pub fn remove_document(&mut self, id: &str) -> Option<StoredDocument> {
+        let position = self.documents.iter().position(|doc| doc.id == id)?;
+        let removed = self.documents.remove(position);
+        self.documents.retain(|doc| doc.id != id);
+        Some(removed)
+    }
+
+    /// The `k` documents most similar to the query under `space`, scored
+    /// `1 - distance` (cosine similarity for the default cosine space). See
+    /// `score` for comparing them with Chroma results.
+    pub fn search(&self, query_embedding: &[f32], k: usize) -> Result<Vec<(f32, &StoredDocument)>> {
+        self.search_filtered(query_embedding, k, None)
+    }
+
+    /// Like `search`, scoring only documents whose metadata matches `filter`
+    /// (the same `Filter` used for Chroma `where` clauses).
+    pub fn search_filtered(
+        &self,
+        query_embedding: &[f32],
+        k: usize,
+        filter: Option<&Filter>,
+    ) -> Result<Vec<(f32, &StoredDocument)>> {
+        if let Some(defect) = check_vector(query_embedding) {
+            return Err(ChromaError::ValidationError(format!("Query embedding is unusable: {}", defect)));
+        }
+        let score = |doc| score_document(doc, query_embedding, self.space, filter);
+
+        #[cfg(feature = "parallel")]
+        let similarities = if self.documents.len() >= PARALLEL_THRESHOLD {
+            use rayon::prelude::*;
+            self.documents.par_iter().filter_map(score).collect::<Result<Vec<_>>>()?
+        } else {
+            self.documents.iter().filter_map(score).collect::<Result<Vec<_>>>()?
+        };
+        #[cfg(not(feature = "parallel"))]
+        let similarities = self.documents.iter().filter_map(score).collect::<Result<Vec<_>>>()?;
+
+        Ok(top_k(similarities, k, |doc| &doc.id))
+    }
+
+    /// Move documents with unusable embeddings out of `documents`, so a
+    /// store written before they were rejected (or corrupted on disk) can
+    /// still be searched.
+    fn quarantine_defective(mut self) -> Self {
+        let (usable, defective): (Vec<_>, Vec<_>) =
+            self.documents.into_iter().partition(|doc| check_vector(&doc.embedding).is_none());
+        for doc in &defective {
+            if let Some(defect) = check_vector(&doc.embedding) {
+                warn!("Quarantined local document '{}': {}", doc.id, defect);
+            }
+        }
+        self.documents = usable;
+        self.quarantined.extend(defective);
+        self
+    }
+
+    /// Save as plaintext JSON, or encrypted when a store key is configured
+    /// (`LOCAL_STORE_KEY` or the OS keyring, see `StoreCipher::load`),
+    /// compressed as configured by `LOCAL_STORE_COMPRESSION`.
+    pub fn save_to_file(&self, path: &str) -> Result<()> {
+        self.save_with_compression(path, Compression::from_env()?)
+    }
+
+    /// Like `save_to_file` with an explicit compression setting. Data is
+    /// compressed before it is encrypted, since ciphertext doesn't compress.
+    /// The file is replaced atomically, with a checksum footer that loading
+    /// verifies, so a crash mid-save leaves the previous version in place.
+    pub fn save_with_compression(&self, path: &str, compression: Compression) -> Result<()> {
+        self.save_with(path, compression, StoreCipher::load()?.as_ref())
+    }
+
+    /// `save_with_compression` with an explicit cipher (`None` for
+    /// plaintext) instead of the configured store key.
+    pub(crate) fn save_with(&self, path: &str, compression: Compression, cipher: Option<&StoreCipher>) -> Result<()> {
+        let data = match compression {
+            Compression::None => serde_json::to_vec_pretty(self)?,
+            compression => compression.compress(&serde_json::to_vec(self)?)?,
+        };
+        match cipher {
+            Some(cipher) => atomic_file::write(path, &cipher.encrypt(&data)?),
+            None => atomic_file::write(path, &data),
+        }
+    }
+
+    /// Load a store written by `save_to_file`, decrypting with the configured
+    /// store key if the file is encrypted and decompressing if it is compressed.
+    /// Documents with unusable embeddings end up in `quarantined`.
+    pub fn load_from_file(path: &str) -> Result<Self> {
+        let data = atomic_file::read(path)?;
+
+        if StoreCipher::is_encrypted(&data) {
+            let cipher = StoreCipher::load()?.ok_or_else(|| {
+                ChromaError::EncryptionError(format!(
+                    "{} is encrypted but no store key is configured",
+                    path
+                ))
+            })?;
+            return Self::from_encrypted_bytes(&data, &cipher);
+        }
+
+        let store: Self = serde_json::from_slice(&compression::decompress(data)?)?;
+        Ok(store.quarantine_defective())
+    }
+
+    pub fn save_encrypted(&self, path: &str, cipher: &StoreCipher) -> Result<()> {
+        let json = serde_json::to_vec(self)?;
+        atomic_file::write(path, &cipher.encrypt(&json)?)
+    }
+
+    pub fn load_encrypted(path: &str, cipher: &StoreCipher) -> Result<Self> {
+        Self::from_encrypted_bytes(&atomic_file::read(path)?, cipher)
+    }
+
+    fn from_encrypted_bytes(data: &[u8], cipher: &StoreCipher) -> Result<Self> {
+        let store: Self = serde_json::from_slice(&compression::decompress(cipher.decrypt(data)?)?)?;
+        Ok(store.quarantine_defective())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mmap_store::MappedStore;
+    use crate::vector_ops;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_local_store_rejects_and_quarantines_defective_vectors() {
+        use vector_ops::{check_vector, VectorDefect};
+
+        assert_eq!(check_vector(&[]), Some(VectorDefect::Empty));
+        assert_eq!(check_vector(&[0.1, f32::INFINITY]), Some(VectorDefect::NonFinite));
+        assert_eq!(check_vector(&[0.0, 1e-9]), Some(VectorDefect::Zero));
+        assert_eq!(check_vector(&[0.0, 1.0]), None);
+        assert_eq!(vector_ops::cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert!(vector_ops::cosine_similarity(&[1.0, 1.0], &[1.0, 1.0]) <= 1.0);
+
+        let doc = |id: &str, embedding: Vec<f32>| StoredDocument {
+            id: id.to_string(),
+            content: String::new(),
+            embedding,
+            metadata: HashMap::new(),
+            created_at: chrono::Utc::now(),
+        };
+        let mut store = VectorStore::new();
+        store.add_document(doc("good", vec![1.0, 0.0])).unwrap();
+        let error = store.add_document(doc("nan", vec![f32::NAN, 0.0])).unwrap_err().to_string();
+        assert!(error.contains("'nan'") && error.contains("NaN"), "{}", error);
+        assert!(store.search(&[0.0, 0.0], 1).is_err());
+
+        // A file written before the checks existed, with a corrupt vector.
+        store.documents.push(doc("zero", vec![0.0, 0.0]));
+        let path = std::env::temp_dir().join(format!("store-{}.json", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        // Pinned rather than read from LOCAL_STORE_KEY / LOCAL_STORE_COMPRESSION.
+        store.save_with(path, Compression::None, None).unwrap();
+        let loaded = VectorStore::load_from_file(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.documents.len(), 1);
+        assert_eq!(loaded.quarantined[0].id, "zero");
+        assert_eq!(loaded.search(&[1.0, 0.0], 5).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_compressed_store_round_trip() {
+        use std::io::Read;
+
+        assert_eq!("zstd:9".parse::<Compression>().unwrap(), Compression::Zstd { level: 9 });
+        assert_eq!("none".parse::<Compression>().unwrap(), Compression::None);
+        assert!("gzip".parse::<Compression>().is_err());
+
+        let mut store = VectorStore::new();
+        for i in 0..20 {
+            store
+                .add_document(StoredDocument {
+                    id: format!("doc-{}", i),
+                    content: "the same sentence, over and over".to_string(),
+                    embedding: vec![0.25; 256],
+                    metadata: HashMap::new(),
+                    created_at: chrono::Utc::now(),
+                })
+                .unwrap();
+        }
+
+        let dir = std::env::temp_dir();
+        let plain = dir.join(format!("store-{}.json", Uuid::new_v4()));
+        let packed = dir.join(format!("store-{}.json.zst", Uuid::new_v4()));
+        store.save_with(plain.to_str().unwrap(), Compression::None, None).unwrap();
+        store.save_with(packed.to_str().unwrap(), Compression::zstd(), None).unwrap();
+
+        let packed_bytes = atomic_file::read(&packed).unwrap();
+        assert!(compression::is_compressed(&packed_bytes));
+        assert!(packed_bytes.len() * 4 < std::fs::metadata(&plain).unwrap().len() as usize);
+
+        let loaded = VectorStore::load_from_file(packed.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&plain).unwrap();
+        std::fs::remove_file(&packed).unwrap();
+        assert_eq!(loaded.documents.len(), 20);
+        assert_eq!(loaded.documents[3].embedding, vec![0.25; 256]);
+
+        let mut lines = String::new();
+        compression::decoder(&packed_bytes[..]).unwrap().read_to_string(&mut lines).unwrap();
+        assert!(lines.contains("doc-19"));
+    }
+
+    #[test]
+    fn test_local_search_applies_where_filters() {
+        let mut store = VectorStore::new();
+        for (id, category, year) in [("a", "rust", "2019"), ("b", "rust", "2022"), ("c", "go", "2023")] {
+            store
+                .add_document(StoredDocument {
+                    id: id.to_string(),
+                    content: String::new(),
+                    embedding: vec![1.0, 0.0],
+                    metadata: HashMap::from([
+                        ("category".to_string(), category.to_string()),
+                        ("year".to_string(), year.to_string()),
+                    ]),
+                    created_at: chrono::Utc::now(),
+                })
+                .unwrap();
+        }
+        let ids = |filter: &Filter| -> Vec<String> {
+            store
+                .search_filtered(&[1.0, 0.0], 10, Some(filter))
+                .unwrap()
+                .into_iter()
+                .map(|(_, doc)| doc.id.clone())
+                .collect()
+        };
+
+        assert_eq!(ids(&Filter::eq("category", "rust").and(Filter::gte("year", 2020_i64))), vec!["b"]);
+        assert_eq!(ids(&Filter::not_in("category", ["rust"]).or(Filter::lt("year", 2020.5))), vec!["a", "c"]);
+        // Missing keys match nothing, as in Chroma.
+        assert!(ids(&Filter::ne("author", "someone")).is_empty());
+        let parsed = Filter::from_json(&serde_json::json!({"category": "go"})).unwrap();
+        assert_eq!(ids(&parsed), vec!["c"]);
+        assert!(store.search_filtered(&[1.0, 0.0], 10, Some(&Filter::gt("category", "a"))).is_err());
+    }
+
+    #[test]
+    fn test_top_k_selection_matches_full_sort() {
+        let scores = [0.5, 0.9, f32::NAN, 0.5, -0.0, 0.0, 0.9, 0.1, 0.7, 0.5];
+        let scored: Vec<(f32, String)> =
+            scores.iter().enumerate().map(|(i, s)| (*s, format!("doc-{}", i))).collect();
+        let mut sorted = scored.clone();
+        sorted.sort_by(|a, b| rank_order(a.0, &a.1, b.0, &b.1));
+
+        for k in 0..=scores.len() + 1 {
+            let selected = top_k(scored.clone(), k, |id| id);
+            let ids: Vec<&String> = selected.iter().map(|(_, id)| id).collect();
+            let expected: Vec<&String> = sorted.iter().take(k).map(|(_, id)| id).collect();
+            assert_eq!(ids, expected, "k = {}", k);
+        }
+    }
+
+    #[test]
+    fn test_local_store_distance_metrics_match_chroma() {
+        let (a, b) = ([1.0, 2.0], [3.0, 0.5]);
+        assert_eq!(vector_ops::distance(DistanceSpace::L2, &a, &b), 6.25);
+        assert_eq!(vector_ops::distance(DistanceSpace::Ip, &a, &b), -3.0);
+        assert!((vector_ops::similarity(DistanceSpace::Cosine, &a, &a) - 1.0).abs() < 1e-6);
+
+        let store_in = |space: DistanceSpace| {
+            let mut store = VectorStore::new().with_space(space);
+            for (id, embedding) in [("near", vec![0.9, 0.1]), ("long", vec![4.0, 4.0])] {
+                store
+                    .add_document(StoredDocument {
+                        id: id.to_string(),
+                        content: String::new(),
+                        embedding,
+                        metadata: HashMap::new(),
+                        created_at: chrono::Utc::now(),
+                    })
+                    .unwrap();
+            }
+            store
+        };
+        let best = |store: &VectorStore| store.search(&[1.0, 0.0], 1).unwrap()[0].1.id.clone();
+        assert_eq!(best(&store_in(DistanceSpace::Cosine)), "near");
+        assert_eq!(best(&store_in(DistanceSpace::L2)), "near");
+        // Inner product favours magnitude.
+        assert_eq!(best(&store_in(DistanceSpace::Ip)), "long");
+        let l2 = store_in(DistanceSpace::L2);
+        assert!((l2.search(&[1.0, 0.0], 1).unwrap()[0].0 - 0.98).abs() < 1e-6);
+
+        let path = std::env::temp_dir().join(format!("store-{}.cdbv", Uuid::new_v4()));
+        MappedStore::build(&store_in(DistanceSpace::Ip), &path).unwrap();
+        let mapped = MappedStore::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(mapped.space(), DistanceSpace::Ip);
+        assert_eq!(mapped.search(&[1.0, 0.0], 1).unwrap()[0].1.id, "long");
+    }
+}
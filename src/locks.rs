@@ -0,0 +1,235 @@
+use crate::chroma_client::ChromaClient;
+use crate::error::{ChromaError, Result};
+use crate::models::{Document, QueryResponse};
+use crate::spaces::space_collection;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+use uuid::Uuid;
+
+/// Space name of the lease collection, e.g. `docs__locks`.
+pub const LOCKS_SPACE: &str = "locks";
+const LEASE_ID: &str = "write-lease";
+const HOLDER_FIELD: &str = "holder";
+const EXPIRES_AT_FIELD: &str = "expires_at";
+/// Lease records carry no meaningful vector, but Chroma requires one.
+const PLACEHOLDER_EMBEDDING: [f32; 1] = [1.0];
+
+/// In-process write locks, one per collection name. Shared by every clone of
+/// a `ChromaClient`.
+#[derive(Debug, Default)]
+pub(crate) struct LockRegistry {
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl LockRegistry {
+    pub(crate) fn lock_for(&self, collection_name: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        locks.entry(collection_name.to_string()).or_default().clone()
+    }
+}
+
+/// Cross-process write leases for `ChromaClient::with_collection_lock`.
+///
+/// The lease is a record in a sibling collection (`{collection}__locks`)
+/// naming its holder and expiry. Chroma has no compare-and-swap, so this is
+/// advisory: workers that all go through `with_collection_lock` serialize
+/// their critical sections, anything else can still write. The TTL must
+/// outlast the critical section; a crashed holder's lease is taken over once
+/// it expires.
+#[derive(Debug, Clone)]
+pub struct LeaseConfig {
+    pub ttl: Duration,
+    /// How long to wait for another holder's lease before giving up.
+    pub wait: Duration,
+    pub poll_interval: Duration,
+    holder: String,
+}
+
+impl LeaseConfig {
+    /// Leases held for `ttl`, with a random holder id for this client.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            wait: Duration::from_secs(30),
+            poll_interval: Duration::from_millis(500),
+            holder: Uuid::new_v4().to_string(),
+        }
+    }
+
+    pub fn with_wait(mut self, wait: Duration) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Identify this worker in lease records (default: a random UUID).
+    pub fn with_holder(mut self, holder: &str) -> Self {
+        self.holder = holder.to_string();
+        self
+    }
+
+    pub fn holder(&self) -> &str {
+        &self.holder
+    }
+}
+
+/// Current holder of a lease record and when it runs out.
+fn read_lease(response: &QueryResponse) -> Option<(String, DateTime<Utc>)> {
+    let metadata = response.metadata(0, 0)?;
+    let holder = metadata.get(HOLDER_FIELD)?.as_str()?.to_string();
+    let expires_at = metadata
+        .get(EXPIRES_AT_FIELD)
+        .and_then(Value::as_str)
+        .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())?
+        .with_timezone(&Utc);
+    Some((holder, expires_at))
+}
+
+async fn current_lease(client: &ChromaClient, lease_collection: &str) -> Result<Option<(String, DateTime<Utc>)>> {
+    let response = client
+        .get_documents(lease_collection, Some(vec![LEASE_ID.to_string()]), None, None)
+        .await?;
+    Ok(read_lease(&response))
+}
+
+/// Take the lease on `collection_name`, waiting up to `config.wait` for
+/// another holder's lease to be released or expire.
+pub(crate) async fn acquire_lease(client: &ChromaClient, collection_name: &str, config: &LeaseConfig) -> Result<()> {
+    let lease_collection = space_collection(collection_name, LOCKS_SPACE);
+    if client.get_collection(&lease_collection).await.is_err() {
+        client.create_collection(&lease_collection).await?;
+    }
+
+    let started = Instant::now();
+    loop {
+        let held_by_other = current_lease(client, &lease_collection)
+            .await?
+            .filter(|(holder, expires_at)| *holder != config.holder && *expires_at > Utc::now());
+
+        match held_by_other {
+            None => {
+                let ttl = chrono::Duration::from_std(config.ttl).unwrap_or_else(|_| chrono::Duration::days(365));
+                let expires_at = Utc::now() + ttl;
+                let lease = Document {
+                    id: LEASE_ID.to_string(),
+                    content: config.holder.clone(),
+                    metadata: HashMap::from([
+                        (HOLDER_FIELD.to_string(), config.holder.clone()),
+                        (EXPIRES_AT_FIELD.to_string(), expires_at.to_rfc3339()),
+                    ]),
+                    uri: None,
+                };
+                client
+                    .upsert_documents(&lease_collection, vec![lease], vec![PLACEHOLDER_EMBEDDING.to_vec()])
+                    .await?;
+                // Another worker may have written between our read and
+                // write; whoever's record survived holds the lease.
+                if current_lease(client, &lease_collection).await?.is_some_and(|(holder, _)| holder == config.holder) {
+                    debug!("Acquired write lease on '{}' as {}", collection_name, config.holder);
+                    return Ok(());
+                }
+            }
+            Some((holder, expires_at)) if started.elapsed() >= config.wait => {
+                return Err(ChromaError::CollectionError(format!(
+                    "Collection '{}' is locked by {} until {}",
+                    collection_name, holder, expires_at.to_rfc3339()
+                )));
+            }
+            Some(_) => {}
+        }
+        tokio::time::sleep(config.poll_interval).await;
+    }
+}
+
+/// Give up the lease on `collection_name` if this worker still holds it.
+pub(crate) async fn release_lease(client: &ChromaClient, collection_name: &str, config: &LeaseConfig) -> Result<()> {
+    let lease_collection = space_collection(collection_name, LOCKS_SPACE);
+    if current_lease(client, &lease_collection).await?.is_some_and(|(holder, _)| holder == config.holder) {
+        client.delete_documents(&lease_collection, vec![LEASE_ID.to_string()]).await?;
+        debug!("Released write lease on '{}'", collection_name);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_collection_lock_serializes_workers_and_leases() {
+        use std::sync::Mutex;
+
+        let lease: Arc<Mutex<Option<serde_json::Value>>> = Arc::new(Mutex::new(None));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (stored, requests) = (lease.clone(), log.clone());
+        let client = mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            requests.lock().unwrap().push(path.rsplit('/').next().unwrap_or_default().to_string());
+            if path.ends_with("/upsert") {
+                let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                *stored.lock().unwrap() = Some(body["metadatas"][0].clone());
+                "true".to_string()
+            } else if path.ends_with("/delete") {
+                *stored.lock().unwrap() = None;
+                "[]".to_string()
+            } else if path.ends_with("/get") {
+                match stored.lock().unwrap().clone() {
+                    Some(metadata) => serde_json::json!({"ids": ["write-lease"], "metadatas": [metadata]}).to_string(),
+                    None => r#"{"ids": [], "metadatas": []}"#.to_string(),
+                }
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+
+        let inside = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let workers = (0..4).map(|_| {
+            let (client, inside) = (client.clone(), inside.clone());
+            tokio::spawn(async move {
+                client
+                    .with_collection_lock("docs", || async {
+                        assert_eq!(inside.fetch_add(1, std::sync::atomic::Ordering::SeqCst), 0);
+                        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                        inside.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+                        Ok(())
+                    })
+                    .await
+            })
+        });
+        for worker in futures::future::join_all(workers).await {
+            worker.unwrap().unwrap();
+        }
+        assert!(log.lock().unwrap().is_empty(), "local locks need no round trips");
+
+        let leased = client.with_write_leases(LeaseConfig::new(std::time::Duration::from_secs(60)).with_holder("worker-1"));
+        let holder = leased
+            .with_collection_lock("docs", || async { Ok(lease.lock().unwrap().clone().unwrap()["holder"].clone()) })
+            .await
+            .unwrap();
+        assert_eq!(holder, "worker-1");
+        assert!(lease.lock().unwrap().is_none(), "lease is released afterwards");
+
+        // Another live holder's lease blocks until `wait` runs out.
+        *lease.lock().unwrap() = Some(serde_json::json!({
+            "holder": "worker-2",
+            "expires_at": (chrono::Utc::now() + chrono::Duration::minutes(1)).to_rfc3339()
+        }));
+        let impatient = leased.with_write_leases(
+            LeaseConfig::new(std::time::Duration::from_secs(60))
+                .with_wait(std::time::Duration::ZERO)
+                .with_poll_interval(std::time::Duration::from_millis(1)),
+        );
+        let error = impatient.with_collection_lock("docs", || async { Ok(()) }).await.unwrap_err();
+        assert!(error.to_string().contains("locked by worker-2"), "{}", error);
+    }
+}
@@ -1,13 +1,4 @@
-mod chroma_client;
-mod embeddings;
-mod error;
-mod models;
-
-use chroma_client::ChromaClient;
-use embeddings::EmbeddingClient;
-use models::Document;
-use std::collections::HashMap;
-use uuid::Uuid;
+use chromadb_demo::{ChromaClient, Document, EmbeddingClient};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -29,8 +20,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .unwrap_or_else(|_| "documents".to_string());
 
     // Initialize clients
-    let chroma = ChromaClient::new(chroma_host);
-    let embeddings = EmbeddingClient::new(google_api_key);
+    let chroma = ChromaClient::try_new(chroma_host)?;
+    let embeddings = EmbeddingClient::try_new(google_api_key)?;
 
     // Health check
     println!("✓ Checking ChromaDB health...");
@@ -50,36 +41,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Prepare documents
     let docs = vec![
-        Document {
-            id: Uuid::new_v4().to_string(),
-            content: "Rust is a systems programming language".to_string(),
-            metadata: {
-                let mut m = HashMap::new();
-                m.insert("source".to_string(), "documentation".to_string());
-                m.insert("language".to_string(), "rust".to_string());
-                m
-            },
-        },
-        Document {
-            id: Uuid::new_v4().to_string(),
-            content: "ChromaDB is a vector database for AI applications".to_string(),
-            metadata: {
-                let mut m = HashMap::new();
-                m.insert("source".to_string(), "documentation".to_string());
-                m.insert("database".to_string(), "chromadb".to_string());
-                m
-            },
-        },
-        Document {
-            id: Uuid::new_v4().to_string(),
-            content: "Gemini AI models provide powerful embeddings".to_string(),
-            metadata: {
-                let mut m = HashMap::new();
-                m.insert("source".to_string(), "documentation".to_string());
-                m.insert("model".to_string(), "gemini".to_string());
-                m
-            },
-        },
+        Document::builder()
+            .content("Rust is a systems programming language")
+            .meta("source", "documentation")
+            .meta("language", "rust")
+            .build(),
+        Document::builder()
+            .content("ChromaDB is a vector database for AI applications")
+            .meta("source", "documentation")
+            .meta("database", "chromadb")
+            .build(),
+        Document::builder()
+            .content("Gemini AI models provide powerful embeddings")
+            .meta("source", "documentation")
+            .meta("model", "gemini")
+            .build(),
     ];
 
     // Generate embeddings
@@ -115,14 +91,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     .await?;
 
     println!("✓ Query: '{}'", query_text);
-    println!("✓ Top {} results:", results.ids[0].len());
+    println!("✓ Top {} results:", results.ids_for(0).len());
     
-    for (i, ((id, doc), distance)) in results.ids[0]
-        .iter()
-        .zip(results.documents[0].iter())
-        .zip(results.distances[0].iter())
-        .enumerate()
-    {
+    for i in 0..results.ids_for(0).len() {
+        let distance = results.distance(0, i).unwrap_or_default();
+        let doc = results.document(0, i).unwrap_or_default();
         println!("  {}. [distance: {:.4}] {}", i + 1, distance, doc);
     }
 
@@ -0,0 +1,158 @@
+use crate::error::Result;
+use crate::transport::Transport;
+use crate::wire_log::WireLog;
+use futures::future::BoxFuture;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Request, Response};
+use std::future::Future;
+use std::sync::Arc;
+
+tokio::task_local! {
+    static ATTEMPT: u32;
+}
+
+/// Retry attempt of the request a middleware is handling: `0` for the first
+/// try, `1` for the first retry, and so on.
+pub fn current_attempt() -> u32 {
+    ATTEMPT.try_with(|attempt| *attempt).unwrap_or(0)
+}
+
+/// Run one attempt of a client's retry loop, making the attempt number
+/// visible to middleware through `current_attempt`.
+pub(crate) async fn with_attempt<F: Future>(attempt: u32, attempt_future: F) -> F::Output {
+    ATTEMPT.scope(attempt, attempt_future).await
+}
+
+/// Middleware enabled through the environment (currently `WIRE_LOG`).
+pub(crate) fn from_env() -> Vec<Arc<dyn Middleware>> {
+    let mut middleware: Vec<Arc<dyn Middleware>> = Vec::new();
+    if let Some(wire_log) = WireLog::from_env() {
+        middleware.push(Arc::new(wire_log));
+    }
+    middleware
+}
+
+/// Hook around every HTTP request sent by `ChromaClient` and `EmbeddingClient`.
+///
+/// An implementation can mutate the request (headers, signing), pass it on
+/// with `next.run(request)`, look at the response that comes back (logging,
+/// metrics), or return a response of its own without calling `next` at all
+/// (caching, canned responses in tests). Middleware runs in the order it was
+/// added, inside the clients' retry loops.
+///
+/// ```
+/// use chromadb_demo::middleware::{Middleware, Next};
+/// use chromadb_demo::Result;
+/// use futures::future::BoxFuture;
+/// use reqwest::{Request, Response};
+///
+/// struct LogStatus;
+///
+/// impl Middleware for LogStatus {
+///     fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+///         Box::pin(async move {
+///             let url = request.url().clone();
+///             let response = next.run(request).await?;
+///             println!("{} -> {}", url, response.status());
+///             Ok(response)
+///         })
+///     }
+/// }
+/// ```
+pub trait Middleware: Send + Sync {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>>;
+}
+
+/// The rest of the middleware chain, ending in the client's `Transport`.
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    transport: &'a Transport,
+    middleware: &'a [Arc<dyn Middleware>],
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(transport: &'a Transport, middleware: &'a [Arc<dyn Middleware>]) -> Self {
+        Self { transport, middleware }
+    }
+
+    pub fn run(self, request: Request) -> BoxFuture<'a, Result<Response>> {
+        match self.middleware.split_first() {
+            Some((first, rest)) => first.handle(request, Next::new(self.transport, rest)),
+            None => Box::pin(self.transport.execute(request)),
+        }
+    }
+}
+
+/// Build a response for a middleware to return instead of calling `next`.
+pub fn canned_response(status: u16, body: impl Into<reqwest::Body>) -> Response {
+    let response = http::Response::builder()
+        .status(status)
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(body.into())
+        .unwrap_or_else(|_| http::Response::new(reqwest::Body::from("")));
+    Response::from(response)
+}
+
+/// Adds fixed headers (API gateway keys, tracing ids, ...) to every request
+/// that doesn't already set them.
+#[derive(Debug, Clone, Default)]
+pub struct DefaultHeaders {
+    headers: HeaderMap,
+}
+
+impl DefaultHeaders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+}
+
+impl Middleware for DefaultHeaders {
+    fn handle<'a>(&'a self, mut request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        for (name, value) in &self.headers {
+            if !request.headers().contains_key(name) {
+                request.headers_mut().insert(name.clone(), value.clone());
+            }
+        }
+        next.run(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chroma_client::ChromaClient;
+    use crate::test_support::MOCK_URL;
+
+    #[tokio::test]
+    async fn test_middleware_can_mutate_and_short_circuit() {
+        use futures::future::BoxFuture;
+        use reqwest::header::{HeaderName, HeaderValue};
+
+        struct Heartbeat;
+
+        impl Middleware for Heartbeat {
+            fn handle<'a>(&'a self, request: reqwest::Request, _next: Next<'a>) -> BoxFuture<'a, Result<reqwest::Response>> {
+                Box::pin(async move {
+                    let status = if request.headers().contains_key("x-api-key") { 200 } else { 401 };
+                    Ok(canned_response(status, "{}"))
+                })
+            }
+        }
+
+        // Nothing listens on port 9; a real request would fail to connect.
+        let client = ChromaClient::try_new(MOCK_URL.to_string()).unwrap()
+            .with_retries(0, std::time::Duration::from_millis(1))
+            .with_middleware(
+                DefaultHeaders::new()
+                    .with_header(HeaderName::from_static("x-api-key"), HeaderValue::from_static("secret")),
+            )
+            .with_middleware(Heartbeat);
+
+        assert!(client.health_check().await.unwrap());
+    }
+}
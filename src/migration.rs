@@ -0,0 +1,171 @@
+use crate::embeddings::EmbeddingProvider;
+use crate::error::Result;
+use crate::models::*;
+use crate::pipeline::Pipeline;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use tracing::info;
+
+const MIGRATION_PAGE_SIZE: u32 = 100;
+/// Collection metadata key recording which model produced the vectors.
+pub const EMBEDDING_MODEL_KEY: &str = "embedding_model";
+
+/// Outcome of `Pipeline::migrate_to`.
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationReport {
+    pub source_collection: String,
+    pub target_collection: String,
+    pub model: String,
+    pub documents_migrated: usize,
+    /// Records without stored text, which can't be re-embedded.
+    pub documents_skipped: usize,
+}
+
+/// Copy every record of the pipeline's collection into `target_collection`,
+/// re-embedding its text with `embedder`.
+///
+/// Vectors from different models can't share a collection (dimensions and
+/// geometry differ), so the copy goes to a new collection, tagged with the
+/// new model's name. Ids, metadata and uris are preserved. The source is left
+/// untouched; switch readers over and delete it once the target is verified.
+pub(crate) async fn migrate(
+    pipeline: &Pipeline,
+    embedder: &dyn EmbeddingProvider,
+    target_collection: &str,
+) -> Result<MigrationReport> {
+    let chroma = pipeline.chroma();
+    if chroma.get_collection(target_collection).await.is_err() {
+        let source = chroma.get_collection(pipeline.collection()).await?;
+        let mut metadata = source.metadata.unwrap_or_else(|| CollectionMetadata::new(DistanceSpace::Cosine));
+        metadata.dimension = None;
+        metadata.extra.insert(EMBEDDING_MODEL_KEY.to_string(), Value::String(embedder.model_name().to_string()));
+        chroma.create_collection_with_metadata(target_collection, &metadata).await?;
+    }
+
+    let mut report = MigrationReport {
+        source_collection: pipeline.collection().to_string(),
+        target_collection: target_collection.to_string(),
+        model: embedder.model_name().to_string(),
+        documents_migrated: 0,
+        documents_skipped: 0,
+    };
+
+    let mut offset = 0;
+    loop {
+        let request = GetRequest {
+            limit: Some(MIGRATION_PAGE_SIZE),
+            offset: Some(offset),
+            include: Some(vec![Include::Documents, Include::Metadatas, Include::Uris]),
+            ..GetRequest::default()
+        };
+        let response = chroma.send_get(pipeline.collection(), &request).await?;
+        let count = response.ids_for(0).len() as u32;
+
+        let documents: Vec<Document> = (0..count as usize)
+            .filter_map(|i| {
+                Some(Document {
+                    id: response.ids_for(0)[i].clone(),
+                    content: response.document(0, i)?.to_string(),
+                    metadata: response.metadata(0, i).map(string_metadata).unwrap_or_default(),
+                    uri: response.uri(0, i).map(str::to_string),
+                })
+            })
+            .collect();
+        report.documents_skipped += count as usize - documents.len();
+
+        if !documents.is_empty() {
+            let texts: Vec<&str> = documents.iter().map(|d| d.content.as_str()).collect();
+            let embeddings = embedder.embed(&texts).await?;
+            report.documents_migrated += documents.len();
+            chroma.upsert_documents(target_collection, documents, embeddings).await?;
+        }
+
+        if count < MIGRATION_PAGE_SIZE {
+            break;
+        }
+        offset += count;
+    }
+
+    info!(
+        "Migrated {} documents from {} to {} with {} ({} skipped)",
+        report.documents_migrated, report.source_collection, report.target_collection, report.model,
+        report.documents_skipped
+    );
+    Ok(report)
+}
+
+/// Stored metadata back in the string form `Document` carries.
+fn string_metadata(metadata: &Value) -> HashMap<String, String> {
+    metadata
+        .as_object()
+        .map(|map| {
+            map.iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    (key.clone(), value)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chroma_client::ChromaClient;
+    use crate::embeddings::EmbeddingClient;
+    use crate::error::ChromaError;
+    use crate::test_support::{FixedEmbeddings, MOCK_URL, mock_transport};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_retired_model_surfaces_deprecation_and_migrates() {
+        use std::sync::Mutex;
+
+        let gemini = mock_transport(|_| {
+            http::Response::builder()
+                .status(404)
+                .body(r#"{"error": {"message": "models/gemini-embedding-exp-03-07 is not found"}}"#.into())
+                .unwrap()
+        });
+        let embedder = EmbeddingClient::try_new("key".to_string())
+            .unwrap()
+            .with_model("gemini-embedding-exp-03-07")
+            .with_transport(gemini);
+        match embedder.embed_text("hello").await {
+            Err(ChromaError::ModelDeprecated { model, replacement }) => {
+                assert_eq!(model, "models/gemini-embedding-exp-03-07");
+                assert_eq!(replacement.as_deref(), Some("models/gemini-embedding-001"));
+            }
+            other => panic!("expected ModelDeprecated, got {:?}", other),
+        }
+
+        let upserts = Arc::new(Mutex::new(Vec::new()));
+        let recorded = upserts.clone();
+        let chroma = mock_transport(move |request| {
+            let path = request.uri().path().to_string();
+            if path.ends_with("/get") {
+                r#"{"ids": ["a", "b"], "documents": ["alpha", null], "metadatas": [{"lang": "en", "rank": 2}, null], "uris": ["s3://a", null]}"#.to_string()
+            } else if path.ends_with("/upsert") {
+                recorded.lock().unwrap().push(serde_json::from_slice::<serde_json::Value>(request.body()).unwrap());
+                "true".to_string()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+        let client = ChromaClient::try_new(MOCK_URL.to_string()).unwrap().with_transport(chroma);
+        let pipeline = Pipeline::new(Arc::new(client), Arc::new(FixedEmbeddings), "docs");
+
+        let report = pipeline.migrate_to(Arc::new(FixedEmbeddings), "docs_v2").await.unwrap();
+        assert_eq!((report.documents_migrated, report.documents_skipped), (1, 1));
+        let upserts = upserts.lock().unwrap();
+        assert_eq!(upserts[0]["ids"], serde_json::json!(["a"]));
+        assert_eq!(upserts[0]["metadatas"][0]["rank"], "2");
+        assert_eq!(upserts[0]["uris"], serde_json::json!(["s3://a"]));
+        assert_eq!(upserts[0]["embeddings"], serde_json::json!([[5.0]]));
+    }
+}
@@ -0,0 +1,311 @@
+use crate::atomic_file::AtomicFile;
+use crate::error::{ChromaError, Result};
+use crate::local_store::{StoredDocument, VectorStore, top_k};
+use crate::models::DistanceSpace;
+use crate::vector_ops::{check_vector, similarity};
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"CDBV";
+const FORMAT_VERSION: u32 = 1;
+const HEADER_LEN: usize = 32;
+/// Per-record entry: blob offset (u64), id length (u32), body length (u32).
+const ENTRY_LEN: usize = 16;
+
+/// Everything about a record except its id and vector, stored as JSON.
+#[derive(Serialize, Deserialize)]
+struct RecordBody<'a> {
+    content: Cow<'a, str>,
+    metadata: Cow<'a, HashMap<String, String>>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A read-only local store served straight from a memory-mapped file.
+///
+/// For serving deployments where the index is built offline: opening only
+/// maps the file and checks its header, so startup is instant whatever the
+/// store size, and pages are read in (and can be evicted) by the OS rather
+/// than held on the heap. Build the file with `MappedStore::build`.
+///
+/// Layout, little-endian:
+///
+/// ```text
+/// header   MAGIC | version: u32 | dimension: u32 | space: u32 | count: u64 | entries offset: u64
+/// vectors  count × dimension × f32, fixed width, starting at byte 32
+/// entries  count × (blob offset: u64 | id length: u32 | body length: u32)
+/// blobs    per record: id bytes, then the rest as JSON
+/// ```
+///
+/// Mapped files are neither encrypted nor compressed.
+pub struct MappedStore {
+    map: Mmap,
+    dimension: usize,
+    space: DistanceSpace,
+    count: usize,
+    entries_offset: usize,
+}
+
+fn space_code(space: DistanceSpace) -> u32 {
+    match space {
+        DistanceSpace::Cosine => 0,
+        DistanceSpace::L2 => 1,
+        DistanceSpace::Ip => 2,
+    }
+}
+
+impl MappedStore {
+    /// Write `store`'s searchable documents to `path` in the mapped layout.
+    /// Every embedding must have the same dimension.
+    pub fn build(store: &VectorStore, path: impl AsRef<Path>) -> Result<()> {
+        let dimension = store.documents.first().map_or(store.dimension, |doc| doc.embedding.len());
+        if let Some(doc) = store.documents.iter().find(|doc| doc.embedding.len() != dimension) {
+            return Err(ChromaError::ValidationError(format!(
+                "Document '{}' has {} dimensions, expected {}",
+                doc.id,
+                doc.embedding.len(),
+                dimension
+            )));
+        }
+
+        let bodies = store
+            .documents
+            .iter()
+            .map(|doc| {
+                serde_json::to_vec(&RecordBody {
+                    content: Cow::Borrowed(&doc.content),
+                    metadata: Cow::Borrowed(&doc.metadata),
+                    created_at: doc.created_at,
+                })
+            })
+            .collect::<serde_json::Result<Vec<_>>>()?;
+
+        let count = store.documents.len();
+        let entries_offset = HEADER_LEN + count * dimension * 4;
+        let mut out = AtomicFile::create(path)?;
+        out.write_all(MAGIC)?;
+        out.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        out.write_all(&(dimension as u32).to_le_bytes())?;
+        out.write_all(&space_code(store.space).to_le_bytes())?;
+        out.write_all(&(count as u64).to_le_bytes())?;
+        out.write_all(&(entries_offset as u64).to_le_bytes())?;
+
+        for doc in &store.documents {
+            for x in &doc.embedding {
+                out.write_all(&x.to_le_bytes())?;
+            }
+        }
+
+        let mut blob_offset = entries_offset + count * ENTRY_LEN;
+        for (doc, body) in store.documents.iter().zip(&bodies) {
+            out.write_all(&(blob_offset as u64).to_le_bytes())?;
+            out.write_all(&(doc.id.len() as u32).to_le_bytes())?;
+            out.write_all(&(body.len() as u32).to_le_bytes())?;
+            blob_offset += doc.id.len() + body.len();
+        }
+        for (doc, body) in store.documents.iter().zip(&bodies) {
+            out.write_all(doc.id.as_bytes())?;
+            out.write_all(body)?;
+        }
+        out.commit()
+    }
+
+    /// Map the file at `path`, checking its header and size.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if cfg!(target_endian = "big") {
+            return Err(ChromaError::ConfigError("Mapped stores require a little-endian target".to_string()));
+        }
+        let invalid = |reason: &str| ChromaError::ValidationError(format!("{} is not a mapped store: {}", path.display(), reason));
+
+        let file = File::open(path)?;
+        // SAFETY: the map is only read. Stores are replaced by renaming a new
+        // file over the old one (see `AtomicFile`), which leaves this mapping
+        // on the old inode rather than changing the bytes under it.
+        let map = unsafe { Mmap::map(&file)? };
+        if map.len() < HEADER_LEN || &map[..4] != MAGIC {
+            return Err(invalid("bad header"));
+        }
+        let version = read_u32(&map, 4);
+        if version != FORMAT_VERSION {
+            return Err(invalid(&format!("unsupported version {}", version)));
+        }
+        let dimension = read_u32(&map, 8) as usize;
+        let space = match read_u32(&map, 12) {
+            0 => DistanceSpace::Cosine,
+            1 => DistanceSpace::L2,
+            2 => DistanceSpace::Ip,
+            other => return Err(invalid(&format!("unknown distance space {}", other))),
+        };
+        let count = read_u64(&map, 16) as usize;
+        let entries_offset = read_u64(&map, 24) as usize;
+
+        let vectors_end = count
+            .checked_mul(dimension)
+            .and_then(|n| n.checked_mul(4))
+            .and_then(|n| n.checked_add(HEADER_LEN));
+        let entries_end = count.checked_mul(ENTRY_LEN).and_then(|n| n.checked_add(entries_offset));
+        if vectors_end != Some(entries_offset) || entries_end.is_none_or(|end| end > map.len()) {
+            return Err(invalid("truncated"));
+        }
+
+        Ok(Self { map, dimension, space, count, entries_offset })
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    pub fn space(&self) -> DistanceSpace {
+        self.space
+    }
+
+    /// Embedding of record `index`, borrowed from the map.
+    pub fn vector(&self, index: usize) -> &[f32] {
+        assert!(index < self.count, "record {} out of range", index);
+        let start = HEADER_LEN + index * self.dimension * 4;
+        let bytes = &self.map[start..start + self.dimension * 4];
+        // SAFETY: f32 has no invalid bit patterns, the target is little-endian
+        // (checked in `open`), and the section starts 32 bytes into a
+        // page-aligned map, so alignment holds; `align_to` re-checks it.
+        let (prefix, floats, _) = unsafe { bytes.align_to::<f32>() };
+        assert!(prefix.is_empty(), "mapped vectors are misaligned");
+        floats
+    }
+
+    pub fn id(&self, index: usize) -> Result<&str> {
+        let (offset, id_len, _) = self.entry(index)?;
+        std::str::from_utf8(self.blob(offset, id_len)?)
+            .map_err(|_| ChromaError::ValidationError(format!("Record {} has a non-UTF-8 id", index)))
+    }
+
+    /// Record `index` as an owned document, embedding included.
+    pub fn document(&self, index: usize) -> Result<StoredDocument> {
+        let (offset, id_len, body_len) = self.entry(index)?;
+        let body: RecordBody = serde_json::from_slice(self.blob(offset + id_len, body_len)?)?;
+        Ok(StoredDocument {
+            id: self.id(index)?.to_string(),
+            content: body.content.into_owned(),
+            embedding: self.vector(index).to_vec(),
+            metadata: body.metadata.into_owned(),
+            created_at: body.created_at,
+        })
+    }
+
+    /// Same ranking as `VectorStore::search`; only the `k` results are
+    /// decoded.
+    pub fn search(&self, query_embedding: &[f32], k: usize) -> Result<Vec<(f32, StoredDocument)>> {
+        if let Some(defect) = check_vector(query_embedding) {
+            return Err(ChromaError::ValidationError(format!("Query embedding is unusable: {}", defect)));
+        }
+        if query_embedding.len() != self.dimension {
+            return Err(ChromaError::ValidationError(format!(
+                "Query embedding has {} dimensions, store has {}",
+                query_embedding.len(),
+                self.dimension
+            )));
+        }
+
+        let scored = (0..self.count)
+            .map(|i| Ok((similarity(self.space, query_embedding, self.vector(i)), (self.id(i)?, i))))
+            .collect::<Result<Vec<_>>>()?;
+
+        top_k(scored, k, |&(id, _)| id)
+            .into_iter()
+            .map(|(score, (_, i))| Ok((score, self.document(i)?)))
+            .collect()
+    }
+
+    fn entry(&self, index: usize) -> Result<(usize, usize, usize)> {
+        if index >= self.count {
+            return Err(ChromaError::ValidationError(format!("Record {} out of range", index)));
+        }
+        let at = self.entries_offset + index * ENTRY_LEN;
+        Ok((
+            read_u64(&self.map, at) as usize,
+            read_u32(&self.map, at + 8) as usize,
+            read_u32(&self.map, at + 12) as usize,
+        ))
+    }
+
+    fn blob(&self, offset: usize, len: usize) -> Result<&[u8]> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.map.get(offset..end))
+            .ok_or_else(|| ChromaError::ValidationError(format!("Record data at {} is truncated", offset)))
+    }
+}
+
+impl std::fmt::Debug for MappedStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MappedStore")
+            .field("dimension", &self.dimension)
+            .field("space", &self.space)
+            .field("count", &self.count)
+            .field("bytes", &self.map.len())
+            .finish()
+    }
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().expect("4 bytes"))
+}
+
+fn read_u64(data: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(data[at..at + 8].try_into().expect("8 bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_mapped_store_matches_in_memory_search() {
+        let mut store = VectorStore::new();
+        for (id, embedding) in [("b", vec![1.0, 0.0, 0.0]), ("a", vec![1.0, 0.0, 0.0]), ("c", vec![0.0, 1.0, 0.5])] {
+            store
+                .add_document(StoredDocument {
+                    id: id.to_string(),
+                    content: format!("content {}", id),
+                    embedding,
+                    metadata: HashMap::from([("source".to_string(), format!("{}.md", id))]),
+                    created_at: chrono::Utc::now(),
+                })
+                .unwrap();
+        }
+        let path = std::env::temp_dir().join(format!("store-{}.cdbv", Uuid::new_v4()));
+        MappedStore::build(&store, &path).unwrap();
+
+        let mapped = MappedStore::open(&path).unwrap();
+        assert_eq!((mapped.len(), mapped.dimension()), (3, 3));
+        assert_eq!(mapped.vector(2), &[0.0, 1.0, 0.5]);
+
+        let query = [0.9, 0.1, 0.0];
+        let expected: Vec<(f32, String)> =
+            store.search(&query, 3).unwrap().into_iter().map(|(s, d)| (s, d.id.clone())).collect();
+        let results = mapped.search(&query, 3).unwrap();
+        let actual: Vec<(f32, String)> = results.iter().map(|(s, d)| (*s, d.id.clone())).collect();
+        assert_eq!(actual, expected);
+        assert_eq!(results[0].1.metadata["source"], "a.md");
+        assert_eq!(results[0].1.content, "content a");
+        assert!(mapped.search(&[1.0, 0.0], 1).is_err());
+
+        std::fs::write(&path, b"CDBV").unwrap();
+        assert!(MappedStore::open(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,5 +1,27 @@
+use crate::error::{ChromaError, Result};
+use crate::filter::MetadataValue;
+use crate::provenance::Provenance;
+use crate::schema::KnownFields;
+use crate::score::Score;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use uuid::Uuid;
+
+/// Metadata key linking a stored chunk to the logical document it came from.
+pub const PARENT_ID_FIELD: &str = "parent_id";
+/// Metadata key holding a chunk's position within its parent document.
+pub const CHUNK_INDEX_FIELD: &str = "chunk_index";
+/// Metadata key naming where a document came from (file, URL, feed), used
+/// by `QueryOptions::with_max_per_source`.
+pub const SOURCE_FIELD: &str = "source";
+/// Metadata keys holding the character range `[start, end)` a chunk covers
+/// in its parent document.
+pub const CHUNK_START_FIELD: &str = "chunk_start";
+pub const CHUNK_END_FIELD: &str = "chunk_end";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbedResult {
@@ -11,6 +33,105 @@ pub struct Document {
     pub id: String,
     pub content: String,
     pub metadata: HashMap<String, String>,
+    /// Where the content lives (file path, URL, object key); sent as Chroma's
+    /// `uris` field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+}
+
+impl Document {
+    /// Start building a document; see `DocumentBuilder`.
+    pub fn builder() -> DocumentBuilder {
+        DocumentBuilder::default()
+    }
+}
+
+/// A document with no metadata and a random id.
+impl From<&str> for Document {
+    fn from(content: &str) -> Self {
+        Document::builder().content(content).build()
+    }
+}
+
+impl From<String> for Document {
+    fn from(content: String) -> Self {
+        Document::builder().content(content).build()
+    }
+}
+
+/// Builder for `Document`, so ingestion code doesn't assemble metadata maps by
+/// hand:
+///
+/// ```
+/// use chromadb_demo::Document;
+///
+/// let doc = Document::builder()
+///     .content("Rust is a systems programming language")
+///     .meta("language", "rust")
+///     .meta_num("year", 2023)
+///     .id_from_content()
+///     .build();
+/// assert_eq!(doc.metadata["year"], "2023");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DocumentBuilder {
+    id: Option<String>,
+    id_from_content: bool,
+    content: String,
+    metadata: HashMap<String, String>,
+    uri: Option<String>,
+}
+
+impl DocumentBuilder {
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Derive the id from the content (a UUIDv5 of the text), so ingesting
+    /// the same text twice overwrites instead of duplicating. Ignored if an
+    /// explicit `id` is set.
+    pub fn id_from_content(mut self) -> Self {
+        self.id_from_content = true;
+        self
+    }
+
+    pub fn content(mut self, content: impl Into<String>) -> Self {
+        self.content = content.into();
+        self
+    }
+
+    pub fn meta(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Numeric metadata, stored in its shortest decimal form (`2023`, `0.5`).
+    pub fn meta_num(self, key: impl Into<String>, value: impl Into<f64>) -> Self {
+        let value = value.into().to_string();
+        self.meta(key, value)
+    }
+
+    pub fn uri(mut self, uri: impl Into<String>) -> Self {
+        self.uri = Some(uri.into());
+        self
+    }
+
+    /// Finish the document. Without `id` or `id_from_content` it gets a
+    /// random UUIDv4.
+    pub fn build(self) -> Document {
+        let id = match self.id {
+            Some(id) => id,
+            None if self.id_from_content => Uuid::new_v5(&Uuid::NAMESPACE_OID, self.content.as_bytes()).to_string(),
+            None => Uuid::new_v4().to_string(),
+        };
+        Document {
+            id,
+            content: self.content,
+            metadata: self.metadata,
+            uri: self.uri,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -19,27 +140,647 @@ pub struct AddRequest {
     pub embeddings: Vec<Vec<f32>>,
     pub metadatas: Vec<HashMap<String, String>>,
     pub documents: Vec<String>,
+    /// One entry per record, omitted entirely when no record has a URI.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uris: Option<Vec<Option<String>>>,
+}
+
+impl AddRequest {
+    /// Build the request body for writing `documents` with `embeddings`.
+    pub fn from_documents(documents: &[Document], embeddings: Vec<Vec<f32>>) -> Self {
+        let uris = documents
+            .iter()
+            .any(|d| d.uri.is_some())
+            .then(|| documents.iter().map(|d| d.uri.clone()).collect());
+        AddRequest {
+            ids: documents.iter().map(|d| d.id.clone()).collect(),
+            embeddings,
+            metadatas: documents.iter().map(|d| d.metadata.clone()).collect(),
+            documents: documents.iter().map(|d| d.content.clone()).collect(),
+            uris,
+        }
+    }
+
+    /// Copy out the records in `range` as a standalone request.
+    pub fn slice(&self, range: Range<usize>) -> AddRequest {
+        AddRequest {
+            ids: self.ids[range.clone()].to_vec(),
+            embeddings: self.embeddings.get(range.clone()).map(<[_]>::to_vec).unwrap_or_default(),
+            metadatas: self.metadatas.get(range.clone()).map(<[_]>::to_vec).unwrap_or_default(),
+            documents: self.documents.get(range.clone()).map(<[_]>::to_vec).unwrap_or_default(),
+            uris: self.uris.as_ref().map(|u| u.get(range).map(<[_]>::to_vec).unwrap_or_default()),
+        }
+    }
+
+    /// Copy out the records whose id is in `ids`, keeping their order.
+    /// Fails on a misaligned request (see `check_alignment`), where the
+    /// copied fields would no longer line up.
+    pub fn select(&self, ids: &HashSet<String>) -> Result<AddRequest> {
+        self.check_alignment()?;
+        let keep: Vec<usize> = (0..self.ids.len()).filter(|&i| ids.contains(&self.ids[i])).collect();
+        Ok(AddRequest {
+            ids: keep.iter().map(|&i| self.ids[i].clone()).collect(),
+            embeddings: keep.iter().map(|&i| self.embeddings[i].clone()).collect(),
+            metadatas: keep.iter().map(|&i| self.metadatas[i].clone()).collect(),
+            documents: keep.iter().map(|&i| self.documents[i].clone()).collect(),
+            uris: self.uris.as_ref().map(|u| keep.iter().map(|&i| u[i].clone()).collect()),
+        })
+    }
+}
+
+/// Fields Chroma should return for each result. Omitted fields come back as
+/// `null` (or not at all), hence the `Option`s on `QueryResponse`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Include {
+    Documents,
+    Metadatas,
+    Distances,
+    Embeddings,
+    Uris,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct QueryRequest {
     pub query_embeddings: Vec<Vec<f32>>,
     pub n_results: u32,
+    #[serde(rename = "where", skip_serializing_if = "Option::is_none")]
     pub where_filter: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<Include>>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Body of a Chroma `get`. Without `ids` or `where` it pages through the
+/// whole collection via `limit`/`offset`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GetRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ids: Option<Vec<String>>,
+    #[serde(rename = "where", default, skip_serializing_if = "Option::is_none")]
+    pub where_filter: Option<serde_json::Value>,
+    /// Filter on document text, e.g. `{"$contains": "rust"}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub where_document: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<Include>>,
+}
+
+/// Results of a query, one row per query embedding. Everything except `ids`
+/// depends on the request's `include` set and may be missing; use the
+/// accessors to read single values without unwrapping each level.
+#[derive(Debug, Clone, Default, Deserialize)]
 pub struct QueryResponse {
     pub ids: Vec<Vec<String>>,
+    #[serde(default)]
     pub embeddings: Option<Vec<Vec<Vec<f32>>>>,
-    pub documents: Vec<Vec<String>>,
-    pub metadatas: Vec<Vec<serde_json::Value>>,
-    pub distances: Vec<Vec<f32>>,
+    #[serde(default)]
+    pub documents: Option<Vec<Vec<Option<String>>>>,
+    #[serde(default)]
+    pub metadatas: Option<Vec<Vec<Option<serde_json::Value>>>>,
+    #[serde(default)]
+    pub distances: Option<Vec<Vec<f32>>>,
+    #[serde(default)]
+    pub uris: Option<Vec<Vec<Option<String>>>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl KnownFields for QueryResponse {
+    const NAME: &'static str = "Chroma query";
+    const FIELDS: &'static [&'static str] =
+        &["ids", "embeddings", "documents", "metadatas", "distances", "uris", "include"];
+}
+
+/// Flat response of the `get` endpoint. Chroma returns `null` for documents
+/// and metadata that were never set.
+#[derive(Debug, Deserialize)]
+pub struct GetResponse {
+    pub ids: Vec<String>,
+    pub embeddings: Option<Vec<Vec<f32>>>,
+    pub documents: Option<Vec<Option<String>>>,
+    pub metadatas: Option<Vec<Option<serde_json::Value>>>,
+    #[serde(default)]
+    pub uris: Option<Vec<Option<String>>>,
+}
+
+impl KnownFields for GetResponse {
+    const NAME: &'static str = "Chroma get";
+    const FIELDS: &'static [&'static str] =
+        &["ids", "embeddings", "documents", "metadatas", "uris", "include"];
+}
+
+impl From<GetResponse> for QueryResponse {
+    /// Wrap a get result as a single query row so callers can treat both alike.
+    fn from(response: GetResponse) -> Self {
+        QueryResponse {
+            ids: vec![response.ids],
+            embeddings: response.embeddings.map(|e| vec![e]),
+            documents: response.documents.map(|d| vec![d]),
+            metadatas: response.metadatas.map(|m| vec![m]),
+            distances: None,
+            uris: response.uris.map(|u| vec![u]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CollectionResponse {
     pub name: String,
     pub id: String,
+    pub metadata: Option<CollectionMetadata>,
+    /// Embedding dimension, set by Chroma once the first record is written.
+    #[serde(default)]
+    pub dimension: Option<usize>,
+}
+
+/// Distance function of a collection's HNSW index (`hnsw:space`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DistanceSpace {
+    L2,
+    Cosine,
+    Ip,
+}
+
+/// Collection metadata: the keys this crate knows about, plus any others in
+/// `extra`.
+///
+/// The `hnsw:*` keys configure the index and can only be set at creation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollectionMetadata {
+    #[serde(rename = "hnsw:space", default, skip_serializing_if = "Option::is_none")]
+    pub space: Option<DistanceSpace>,
+    #[serde(rename = "hnsw:construction_ef", default, skip_serializing_if = "Option::is_none")]
+    pub construction_ef: Option<u32>,
+    #[serde(rename = "hnsw:search_ef", default, skip_serializing_if = "Option::is_none")]
+    pub search_ef: Option<u32>,
+    /// Expected embedding dimension. Informational; Chroma infers the real one
+    /// from the first add.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimension: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    /// Provider and model the vectors were embedded with; see
+    /// `EmbeddingBinding`.
+    #[serde(rename = "embedding:provider", default, skip_serializing_if = "Option::is_none")]
+    pub embedding_provider: Option<String>,
+    #[serde(rename = "embedding:model", default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl CollectionMetadata {
+    pub fn new(space: DistanceSpace) -> Self {
+        Self {
+            space: Some(space),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_construction_ef(mut self, ef: u32) -> Self {
+        self.construction_ef = Some(ef);
+        self
+    }
+
+    pub fn with_search_ef(mut self, ef: u32) -> Self {
+        self.search_ef = Some(ef);
+        self
+    }
+
+    pub fn with_dimension(mut self, dimension: usize) -> Self {
+        self.dimension = Some(dimension);
+        self
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn with_created_by(mut self, created_by: &str) -> Self {
+        self.created_by = Some(created_by.to_string());
+        self
+    }
+
+    pub fn with_extra(mut self, key: &str, value: impl Into<MetadataValue>) -> Result<Self> {
+        self.extra.insert(key.to_string(), value.into().to_json()?);
+        Ok(self)
+    }
+
+    /// Check values Chroma would reject, or that would build a useless index.
+    pub fn validate(&self) -> Result<()> {
+        let positive = [
+            ("hnsw:construction_ef", self.construction_ef.map(|v| v as usize)),
+            ("hnsw:search_ef", self.search_ef.map(|v| v as usize)),
+            ("dimension", self.dimension),
+        ];
+        if let Some((key, _)) = positive.iter().find(|(_, value)| *value == Some(0)) {
+            return Err(ChromaError::ValidationError(format!("Collection metadata '{}' must be positive", key)));
+        }
+
+        for (key, value) in &self.extra {
+            if key.starts_with("hnsw:") {
+                return Err(ChromaError::ValidationError(format!("Unsupported index setting '{}'", key)));
+            }
+            MetadataValue::from_json(value).map_err(|_| {
+                ChromaError::ValidationError(format!(
+                    "Collection metadata '{}' must be a string, number or bool, got {}",
+                    key, value
+                ))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn has_index_settings(&self) -> bool {
+        self.space.is_some() || self.construction_ef.is_some() || self.search_ef.is_some()
+    }
+
+    /// Validate metadata for a modify call, where index settings are fixed.
+    pub(crate) fn validate_update(&self) -> Result<()> {
+        if self.has_index_settings() {
+            return Err(ChromaError::ValidationError(
+                "hnsw:* settings can only be set when the collection is created".to_string(),
+            ));
+        }
+        self.validate()
+    }
+}
+
+impl KnownFields for CollectionResponse {
+    const NAME: &'static str = "Chroma collection";
+    const FIELDS: &'static [&'static str] = &[
+        "id",
+        "name",
+        "metadata",
+        "configuration_json",
+        "dimension",
+        "tenant",
+        "database",
+        "log_position",
+        "version",
+    ];
+}
+
+/// A single flattened query result.
+///
+/// `score` is higher-is-better and starts out as `1 - distance` (cosine
+/// similarity for collections created with `hnsw:space = cosine`); client-side
+/// re-ranking adjusts it without touching the raw `distance`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct QueryHit {
+    pub id: String,
+    pub document: Option<String>,
     pub metadata: Option<serde_json::Value>,
+    pub distance: f32,
+    pub score: f32,
+    #[serde(default)]
+    pub uri: Option<String>,
+}
+
+impl QueryHit {
+    /// Deserialize the metadata value under `key`, e.g. one decoded by a
+    /// `Collection` codec. `None` if the key is absent.
+    pub fn metadata_as<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        match self.metadata.as_ref().and_then(|m| m.get(key)) {
+            Some(value) => Ok(Some(T::deserialize(value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Id of the logical document this hit belongs to: the `parent_id`
+    /// metadata for chunks, the hit's own id otherwise.
+    pub fn parent_id(&self) -> &str {
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.get(PARENT_ID_FIELD))
+            .and_then(Value::as_str)
+            .unwrap_or(&self.id)
+    }
+
+    /// Where this record came from and how it was ingested, if it was
+    /// written with provenance metadata.
+    pub fn provenance(&self) -> Option<Provenance> {
+        Provenance::from_metadata(self.metadata.as_ref()?)
+    }
+
+    /// What this hit counts against for `QueryOptions::with_max_per_source`:
+    /// its `source` metadata, or its parent id when it has none.
+    pub fn source(&self) -> &str {
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.get(SOURCE_FIELD))
+            .and_then(Value::as_str)
+            .unwrap_or_else(|| self.parent_id())
+    }
+
+    /// The character range this chunk covers in its parent, from the
+    /// `chunk_start`/`chunk_end` metadata `Chunker` records.
+    pub fn chunk_span(&self) -> Option<(usize, usize)> {
+        let field = |key: &str| {
+            let value = self.metadata.as_ref()?.get(key)?;
+            match value {
+                Value::Number(n) => n.as_u64().map(|n| n as usize),
+                Value::String(s) => s.parse().ok(),
+                _ => None,
+            }
+        };
+        Some((field(CHUNK_START_FIELD)?, field(CHUNK_END_FIELD)?))
+    }
+
+    /// Whether this hit and `other` are chunks of the same parent covering
+    /// overlapping text. Without recorded spans, chunks next to each other
+    /// (by `chunk_index`) are assumed to overlap.
+    pub fn overlaps(&self, other: &QueryHit) -> bool {
+        if self.parent_id() != other.parent_id() {
+            return false;
+        }
+        if let (Some(a), Some(b)) = (self.chunk_span(), other.chunk_span()) {
+            return a.0 < b.1 && b.0 < a.1;
+        }
+        let index = |hit: &QueryHit| match hit.metadata.as_ref()?.get(CHUNK_INDEX_FIELD)? {
+            Value::Number(n) => n.as_u64(),
+            Value::String(s) => s.parse().ok(),
+            _ => None,
+        };
+        match (index(self), index(other)) {
+            (Some(a), Some(b)) => a.abs_diff(b) <= 1,
+            _ => false,
+        }
+    }
+
+    /// The raw Chroma distance as a `Score` in the collection's `space`,
+    /// unaffected by re-ranking.
+    pub fn distance_score(&self, space: DistanceSpace) -> Score {
+        Score::distance(space, self.distance)
+    }
+
+    /// Result order: higher score first, equal scores by id. Use with
+    /// `sort_by` wherever hits are re-ranked or merged.
+    pub fn rank_cmp(&self, other: &Self) -> Ordering {
+        rank_order(self.score, &self.id, other.score, &other.id)
+    }
+}
+
+/// Order two ranked results by descending score, breaking ties by ascending
+/// id.
+///
+/// Equal scores are common (duplicates, quantized vectors, fused ranks), and
+/// leaving them in whatever order a hash map or backend produced makes
+/// evaluation metrics and snapshots differ between runs. NaN scores rank
+/// last.
+pub fn rank_order(a_score: f32, a_id: &str, b_score: f32, b_id: &str) -> Ordering {
+    // `+ 0.0` folds -0.0 into 0.0, which `total_cmp` would otherwise split.
+    let key = |score: f32| if score.is_nan() { f32::NEG_INFINITY } else { score + 0.0 };
+    key(b_score).total_cmp(&key(a_score)).then_with(|| a_id.cmp(b_id))
+}
+
+impl QueryResponse {
+    /// Result ids for query `query`, empty if there is no such row.
+    pub fn ids_for(&self, query: usize) -> &[String] {
+        self.ids.get(query).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn document(&self, query: usize, index: usize) -> Option<&str> {
+        self.documents.as_ref()?.get(query)?.get(index)?.as_deref()
+    }
+
+    pub fn metadata(&self, query: usize, index: usize) -> Option<&serde_json::Value> {
+        self.metadatas
+            .as_ref()?
+            .get(query)?
+            .get(index)?
+            .as_ref()
+            .filter(|m| !m.is_null())
+    }
+
+    pub fn distance(&self, query: usize, index: usize) -> Option<f32> {
+        self.distances.as_ref()?.get(query)?.get(index).copied()
+    }
+
+    pub fn uri(&self, query: usize, index: usize) -> Option<&str> {
+        self.uris.as_ref()?.get(query)?.get(index)?.as_deref()
+    }
+
+    pub fn embedding(&self, query: usize, index: usize) -> Option<&[f32]> {
+        self.embeddings.as_ref()?.get(query)?.get(index).map(Vec::as_slice)
+    }
+
+    /// Flatten the results of the first query embedding into hits. Hits
+    /// without a distance (not included, or a `get`) sort last.
+    pub fn into_hits(self) -> Vec<QueryHit> {
+        fn first_row<T>(rows: Option<Vec<Vec<T>>>) -> std::vec::IntoIter<T> {
+            rows.and_then(|r| r.into_iter().next()).unwrap_or_default().into_iter()
+        }
+
+        let ids = self.ids.into_iter().next().unwrap_or_default();
+        let mut documents = first_row(self.documents);
+        let mut metadatas = first_row(self.metadatas);
+        let mut distances = first_row(self.distances);
+        let mut uris = first_row(self.uris);
+
+        let mut hits = ids
+            .into_iter()
+            .map(|id| {
+                let distance = distances.next().unwrap_or(f32::MAX);
+                QueryHit {
+                    id,
+                    document: documents.next().flatten(),
+                    metadata: metadatas.next().flatten().filter(|m| !m.is_null()),
+                    distance,
+                    score: 1.0 - distance,
+                    uri: uris.next().flatten(),
+                }
+            })
+            .collect::<Vec<_>>();
+        // Chroma orders by distance but leaves ties in index order, which
+        // differs between servers and rebuilds.
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance).then_with(|| a.id.cmp(&b.id)));
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local_store::{StoredDocument, VectorStore};
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_document_creation() {
+        let doc = Document {
+            id: Uuid::new_v4().to_string(),
+            content: "Test document content".to_string(),
+            metadata: {
+                let mut m = HashMap::new();
+                m.insert("source".to_string(), "test".to_string());
+                m
+            },
+            uri: None,
+        };
+        
+        assert!(!doc.id.is_empty());
+        assert_eq!(doc.content, "Test document content");
+        assert_eq!(doc.metadata.get("source").unwrap(), "test");
+    }
+
+    #[test]
+    fn test_document_builder() {
+        let doc = Document::builder()
+            .content("Test document content")
+            .meta("source", "test")
+            .meta_num("year", 2023)
+            .meta_num("score", 0.5)
+            .id_from_content()
+            .build();
+        assert_eq!(doc.metadata["year"], "2023");
+        assert_eq!(doc.metadata["score"], "0.5");
+        assert_eq!(doc.id, Document::builder().content("Test document content").id_from_content().build().id);
+        assert_ne!(doc.id, Document::builder().content("Other content").id_from_content().build().id);
+
+        let explicit = Document::builder().id("doc-1").content("text").id_from_content().build();
+        assert_eq!(explicit.id, "doc-1");
+
+        let plain = Document::from("text");
+        assert_eq!(plain.content, "text");
+        assert!(plain.metadata.is_empty() && plain.uri.is_none());
+        assert_ne!(plain.id, Document::from("text").id);
+    }
+
+    #[test]
+    fn test_get_response_becomes_one_query_row() {
+        let response: GetResponse = serde_json::from_str(
+            r#"{"ids": ["a", "b"], "embeddings": null, "documents": ["text", null], "metadatas": [null, {"k": 1}]}"#,
+        )
+        .unwrap();
+        let row = QueryResponse::from(response);
+
+        assert_eq!(row.ids, vec![vec!["a".to_string(), "b".to_string()]]);
+        assert_eq!(row.documents, Some(vec![vec![Some("text".to_string()), None]]));
+        assert_eq!(row.metadatas, Some(vec![vec![None, Some(serde_json::json!({"k": 1}))]]));
+        assert!(row.embeddings.is_none() && row.distances.is_none());
+    }
+
+    #[test]
+    fn test_ties_are_broken_by_id() {
+        let response = QueryResponse {
+            ids: vec![vec!["c".to_string(), "a".to_string(), "b".to_string(), "d".to_string()]],
+            distances: Some(vec![vec![0.2, 0.2, 0.1, f32::NAN]]),
+            documents: None,
+            metadatas: None,
+            embeddings: None,
+            uris: None,
+        };
+        let hits = response.into_hits();
+        let ids: Vec<&str> = hits.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "a", "c", "d"]);
+
+        let mut reversed: Vec<QueryHit> = hits.iter().rev().cloned().collect();
+        reversed.sort_by(QueryHit::rank_cmp);
+        assert_eq!(reversed.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), ids);
+
+        let mut store = VectorStore::new();
+        for id in ["z", "m", "a"] {
+            store.add_document(StoredDocument {
+                id: id.to_string(),
+                content: String::new(),
+                embedding: vec![1.0, 0.0],
+                metadata: HashMap::new(),
+                created_at: chrono::Utc::now(),
+            }).unwrap();
+        }
+        let found: Vec<&str> = store.search(&[1.0, 0.0], 2).unwrap().into_iter().map(|(_, d)| d.id.as_str()).collect();
+        assert_eq!(found, vec!["a", "m"]);
+    }
+
+    #[test]
+    fn test_query_response_with_partial_include() {
+        let json = serde_json::json!({
+            "ids": [["a", "b"]],
+            "documents": [["first", null]],
+            "metadatas": null,
+            "distances": [[0.2, 0.4]],
+            "include": ["documents", "distances"]
+        });
+        let response: QueryResponse = serde_json::from_value(json).unwrap();
+
+        assert_eq!(response.document(0, 0), Some("first"));
+        assert_eq!(response.document(0, 1), None);
+        assert_eq!(response.metadata(0, 0), None);
+        assert_eq!(response.distance(0, 1), Some(0.4));
+
+        let hits = response.into_hits();
+        assert_eq!(hits[1].id, "b");
+        assert!(hits[1].document.is_none());
+
+        let ids_only: QueryResponse = serde_json::from_value(serde_json::json!({"ids": [["a"]]})).unwrap();
+        assert_eq!(ids_only.ids_for(0), ["a".to_string()]);
+        assert_eq!(ids_only.distance(0, 0), None);
+    }
+
+    #[test]
+    fn test_uris_round_trip_through_requests_and_hits() {
+        let document = |id: &str, uri: Option<&str>| Document {
+            id: id.to_string(),
+            content: "text".to_string(),
+            metadata: HashMap::new(),
+            uri: uri.map(str::to_string),
+        };
+
+        let plain = AddRequest::from_documents(&[document("a", None)], vec![vec![0.1]]);
+        assert!(serde_json::to_value(&plain).unwrap().get("uris").is_none());
+
+        let request = AddRequest::from_documents(
+            &[document("a", Some("file:///a.pdf")), document("b", None)],
+            vec![vec![0.1], vec![0.2]],
+        );
+        assert_eq!(serde_json::to_value(&request).unwrap()["uris"], serde_json::json!(["file:///a.pdf", null]));
+        assert_eq!(request.slice(1..2).uris, Some(vec![None]));
+
+        let misaligned = AddRequest { uris: Some(vec![None]), ..request };
+        assert!(misaligned.check_alignment().is_err());
+
+        let response: QueryResponse = serde_json::from_value(serde_json::json!({
+            "ids": [["a", "b"]],
+            "distances": [[0.1, 0.2]],
+            "uris": [["file:///a.pdf", null]]
+        }))
+        .unwrap();
+        assert_eq!(response.uri(0, 0), Some("file:///a.pdf"));
+        let hits = response.into_hits();
+        assert_eq!(hits[0].uri.as_deref(), Some("file:///a.pdf"));
+        assert!(hits[1].uri.is_none());
+    }
+
+    #[test]
+    fn test_collection_metadata_round_trip_and_validation() {
+        let json = serde_json::json!({
+            "hnsw:space": "ip",
+            "hnsw:search_ef": 64,
+            "description": "support articles",
+            "team": "docs"
+        });
+        let metadata: CollectionMetadata = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(metadata.space, Some(DistanceSpace::Ip));
+        assert_eq!(metadata.search_ef, Some(64));
+        assert_eq!(metadata.extra["team"], "docs");
+        assert_eq!(serde_json::to_value(&metadata).unwrap(), json);
+        assert!(metadata.validate().is_ok());
+        assert!(metadata.validate_update().is_err());
+
+        let zero_ef = CollectionMetadata::new(DistanceSpace::Cosine).with_construction_ef(0);
+        assert!(matches!(zero_ef.validate(), Err(ChromaError::ValidationError(_))));
+
+        let mut nested = CollectionMetadata::default();
+        nested.extra.insert("owner".to_string(), serde_json::json!({"name": "x"}));
+        assert!(matches!(nested.validate(), Err(ChromaError::ValidationError(_))));
+    }
 }
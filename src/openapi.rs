@@ -0,0 +1,79 @@
+use crate::server::ApiDoc;
+use serde_json::Value;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, Ref, ResponseBuilder};
+use utoipa::{Modify, OpenApi};
+
+/// Swagger UI page for `GET /docs`, rendering `/openapi.json`. The UI itself
+/// loads from a CDN, so the browser needs internet access.
+pub const SWAGGER_UI_HTML: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>chromadb-demo API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>
+"##;
+
+/// OpenAPI 3.1 description of the server's client-facing endpoints
+/// (`/health`, `/query`, `/documents`, `/usage`, `/answer`,
+/// `/answer/stream`), served at `GET /openapi.json` for generating clients.
+/// The `/admin` endpoints are left out; they are for operators, not
+/// frontends.
+///
+/// Generated from the handlers' `#[utoipa::path]` attributes and the
+/// request and response types, so it changes with them.
+pub fn spec() -> Value {
+    serde_json::to_value(ApiDoc::openapi()).expect("OpenAPI documents serialize to JSON")
+}
+
+/// Statuses any endpoint behind an API key can fail with, all with the
+/// server's `{"error", "code"}` body.
+const ERROR_STATUSES: [&str; 9] = ["400", "401", "403", "404", "413", "415", "422", "429", "502"];
+
+/// What the path attributes can't say: the API key security schemes, and
+/// the error responses shared by every endpoint that needs a key.
+pub(crate) struct ServerConventions;
+
+impl Modify for ServerConventions {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        // Filled in from Cargo.toml, which names no license.
+        openapi.info.license = None;
+        let components = openapi.components.get_or_insert_with(Default::default);
+        let bearer = HttpBuilder::new()
+            .scheme(HttpAuthScheme::Bearer)
+            .description(Some("An API key from API_KEYS_FILE"))
+            .build();
+        components.add_security_scheme("bearer", SecurityScheme::Http(bearer));
+        components.add_security_scheme("apiKey", SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))));
+        let error = ResponseBuilder::new()
+            .description("Request failed")
+            .content("application/json", ContentBuilder::new().schema(Some(Ref::from_schema_name("ErrorBody"))).build())
+            .build();
+        components.responses.insert("Error".to_string(), error.into());
+
+        // Open endpoints override the document's security requirement.
+        let keyed = openapi
+            .paths
+            .paths
+            .values_mut()
+            .flat_map(|item| [item.get.as_mut(), item.post.as_mut()])
+            .flatten()
+            .filter(|operation| operation.security.is_none());
+        for operation in keyed {
+            for status in ERROR_STATUSES {
+                operation.responses.responses.insert(status.to_string(), Ref::from_response_name("Error").into());
+            }
+        }
+    }
+}
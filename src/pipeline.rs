@@ -0,0 +1,1259 @@
+use crate::answer_cache::AnswerCache;
+use crate::binding::{self, EmbeddingBinding, ProviderRegistry};
+use crate::canary::{self, CanaryQuery, CanaryReport};
+use crate::chroma_client::ChromaClient;
+use crate::chunk_gc::{self, ChunkManifest};
+use crate::chunking::Chunker;
+use crate::conversation::{self, ChatTurn, Conversation, RewriteConfig, Role};
+use crate::degraded::{self, DegradedMode, IngestOutcome, Outbox};
+use crate::doctor::{self, DiagnosticBundle};
+use crate::drift::{self, DriftConfig, DriftReport};
+use crate::embeddings::EmbeddingProvider;
+use crate::error::{ChromaError, Result};
+use crate::extract::{self, DEFAULT_EXTRACTION_ATTEMPTS, Extraction};
+use crate::freshness::FreshnessReport;
+use crate::llm::{GenerationRequest, LlmProvider, TextStream};
+use crate::migration::{self, MigrationReport};
+use crate::models::{
+    CollectionMetadata, CollectionResponse, DistanceSpace, Document, GetRequest, Include, QueryHit,
+};
+use crate::preflight::{self, PreflightReport};
+use crate::prompt::{ANSWER_TEMPLATE, EXTRACT_TEMPLATE, PromptLibrary, RenderedPrompt, estimate_tokens};
+use crate::provenance::{self, Provenance};
+use crate::query::{QueryExplain, QueryOptions};
+use crate::sync_plan::{self, PlanOutcome, SyncPlan};
+use crate::versioning::{self, OutdatedReport, PIPELINE_VERSION, RecordVersion};
+use crate::workers::WorkerPool;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+const DEFAULT_CACHE_CAPACITY: usize = 1024;
+const DEFAULT_BATCH_CONCURRENCY: usize = 8;
+const SYNC_BATCH_SIZE: usize = 32;
+const SYNC_EXTENSIONS: &[&str] = &["txt", "md"];
+/// Ids per delete request when applying a `SyncPlan`.
+const PLAN_DELETE_BATCH_SIZE: usize = 500;
+
+/// Embeds and stores documents in one collection and answers text queries
+/// against it, caching query embeddings.
+///
+/// Long-lived processes (the server, indexer handles) build one `Pipeline`
+/// and share it, so connection pools and caches stay warm across requests.
+pub struct Pipeline {
+    chroma: Arc<ChromaClient>,
+    embedder: Arc<dyn EmbeddingProvider>,
+    collection: String,
+    cache: EmbeddingCache,
+    sync_sla: Option<Duration>,
+    batch_concurrency: usize,
+    workers: WorkerPool,
+    providers: ProviderRegistry,
+    llm: Option<Arc<dyn LlmProvider>>,
+    prompts: PromptLibrary,
+    answer_cache: Option<Arc<AnswerCache>>,
+    extraction_attempts: u32,
+    rewrite: Option<RewriteConfig>,
+    degraded: Option<DegradedMode>,
+    outbox: Outbox,
+    pipeline_version: String,
+}
+
+/// Hits of a `Pipeline::query` with how long each stage took.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct QueryResult {
+    pub hits: Vec<QueryHit>,
+    pub timings: QueryTimings,
+    /// The embedding provider was down, so these are keyword (BM25) matches
+    /// rather than semantic ones (see `DegradedMode`).
+    pub degraded: bool,
+    /// Set when the query options ask for it (`QueryOptions::with_explain`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "server", schema(value_type = Option<Object>))]
+    pub explain: Option<QueryExplain>,
+}
+
+/// Wall-clock milliseconds spent per query stage.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct QueryTimings {
+    pub embed_ms: f64,
+    pub search_ms: f64,
+    pub rerank_ms: f64,
+    pub total_ms: f64,
+    /// The query embedding came from the cache, so `embed_ms` is near zero.
+    pub embedding_cached: bool,
+}
+
+/// A generated answer and the retrieved chunks it was grounded on.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Answer {
+    pub text: String,
+    pub model: String,
+    /// Ids of the chunks packed into the prompt, best-ranked first.
+    pub context_ids: Vec<String>,
+    /// Where each chunk in `context_ids` came from, for chunks written with
+    /// provenance.
+    pub provenance: BTreeMap<String, Provenance>,
+    /// Retrieved chunks left out to fit the prompt's token budget.
+    pub dropped_ids: Vec<String>,
+    /// Served from the answer cache without calling the LLM.
+    pub cached: bool,
+    /// What was searched for, when a follow-up question was rewritten to
+    /// stand on its own (see `RewriteConfig`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub standalone_question: Option<String>,
+    /// Timings of the retrieval step.
+    pub timings: QueryTimings,
+    /// The context came from keyword search (see `QueryResult::degraded`).
+    pub degraded: bool,
+}
+
+/// An answer whose text is still being generated: everything but the text
+/// is known once retrieval is done, so it can be shown alongside or after
+/// the streamed tokens.
+pub struct AnswerStream {
+    /// The answer text, chunk by chunk.
+    pub tokens: TextStream,
+    pub model: String,
+    /// The chunks packed into the prompt, best-ranked first.
+    pub citations: Vec<QueryHit>,
+    /// Retrieved chunks left out to fit the prompt's token budget.
+    pub dropped_ids: Vec<String>,
+    /// The text is coming from the answer cache, as a single chunk.
+    pub cached: bool,
+    pub timings: QueryTimings,
+    pub degraded: bool,
+}
+
+/// Outcome of `Pipeline::sync_directory`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncReport {
+    pub files_seen: usize,
+    pub documents_upserted: usize,
+}
+
+impl Pipeline {
+    pub fn new(chroma: Arc<ChromaClient>, embedder: Arc<dyn EmbeddingProvider>, collection: &str) -> Self {
+        Self {
+            chroma,
+            embedder,
+            collection: collection.to_string(),
+            cache: EmbeddingCache::new(DEFAULT_CACHE_CAPACITY),
+            sync_sla: None,
+            batch_concurrency: DEFAULT_BATCH_CONCURRENCY,
+            workers: WorkerPool::from_env(),
+            providers: ProviderRegistry::new(),
+            llm: None,
+            prompts: PromptLibrary::new(),
+            answer_cache: None,
+            extraction_attempts: DEFAULT_EXTRACTION_ATTEMPTS,
+            rewrite: None,
+            degraded: None,
+            outbox: Outbox::new(0),
+            pipeline_version: PIPELINE_VERSION.to_string(),
+        }
+    }
+
+    /// Number of query embeddings to keep; `0` disables the cache.
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache = EmbeddingCache::new(capacity);
+        self
+    }
+
+    /// Record each `sync_directory` run in the freshness control collection,
+    /// expecting the directory to be re-synced within `sla`.
+    pub fn with_sync_sla(mut self, sla: Duration) -> Self {
+        self.sync_sla = Some(sla);
+        self
+    }
+
+    /// Searches `query_batch` keeps in flight at once.
+    pub fn with_batch_concurrency(mut self, concurrency: usize) -> Self {
+        self.batch_concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Pool for chunking and reading source files; share one pool between
+    /// pipelines to cap CPU use across them.
+    pub fn with_worker_pool(mut self, workers: WorkerPool) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Providers to embed with when the collection is bound to a model other
+    /// than the default embedder's (see `EmbeddingBinding`).
+    pub fn with_providers(mut self, providers: ProviderRegistry) -> Self {
+        self.providers = providers;
+        self
+    }
+
+    /// Model that writes `answer`s from the retrieved context.
+    pub fn with_llm(mut self, llm: Arc<dyn LlmProvider>) -> Self {
+        self.llm = Some(llm);
+        self
+    }
+
+    /// Templates to prompt with; `answer` uses the one named `answer`.
+    pub fn with_prompts(mut self, prompts: PromptLibrary) -> Self {
+        self.prompts = prompts;
+        self
+    }
+
+    /// Reuse answers for a question whose retrieved context hasn't changed.
+    /// Register `cache.invalidator()` on the Chroma client so edits to a
+    /// cited chunk drop the answers built on it.
+    pub fn with_answer_cache(mut self, cache: Arc<AnswerCache>) -> Self {
+        self.answer_cache = Some(cache);
+        self
+    }
+
+    /// Generations `extract` may spend on getting JSON that fits the
+    /// schema (default 3).
+    pub fn with_extraction_attempts(mut self, attempts: u32) -> Self {
+        self.extraction_attempts = attempts.max(1);
+        self
+    }
+
+    /// Rewrite follow-up questions in `answer_in_conversation` into
+    /// standalone queries before retrieving.
+    pub fn with_query_rewriting(mut self, config: RewriteConfig) -> Self {
+        self.rewrite = Some(config);
+        self
+    }
+
+    /// Keep serving when the embedding provider is down: keyword search for
+    /// queries, an outbox for `ingest_or_queue`.
+    pub fn with_degraded_mode(mut self, mode: DegradedMode) -> Self {
+        self.outbox = Outbox::new(mode.outbox_capacity);
+        self.degraded = Some(mode);
+        self
+    }
+
+    /// Version stamped into the `pipeline_version` metadata of every record
+    /// this pipeline writes (default: the crate version). Bump it when
+    /// ingestion changes in a way stored records should be rebuilt for, then
+    /// find them with `outdated_records`.
+    pub fn with_pipeline_version(mut self, version: &str) -> Self {
+        self.pipeline_version = version.to_string();
+        self
+    }
+
+    /// The same pipeline serving another collection: shares the clients,
+    /// providers and settings, with an embedding cache of its own. The
+    /// answer cache is left out, since chunk ids are only unique within a
+    /// collection.
+    pub fn for_collection(&self, collection: &str) -> Self {
+        Self {
+            chroma: self.chroma.clone(),
+            embedder: self.embedder.clone(),
+            collection: collection.to_string(),
+            cache: EmbeddingCache::new(self.cache.capacity),
+            sync_sla: self.sync_sla,
+            batch_concurrency: self.batch_concurrency,
+            workers: self.workers.clone(),
+            providers: self.providers.clone(),
+            llm: self.llm.clone(),
+            prompts: self.prompts.clone(),
+            answer_cache: None,
+            extraction_attempts: self.extraction_attempts,
+            rewrite: self.rewrite.clone(),
+            degraded: self.degraded.clone(),
+            outbox: Outbox::new(self.outbox.capacity()),
+            pipeline_version: self.pipeline_version.clone(),
+        }
+    }
+
+    pub fn collection(&self) -> &str {
+        &self.collection
+    }
+
+    pub fn chroma(&self) -> &ChromaClient {
+        &self.chroma
+    }
+
+    pub fn embedder(&self) -> &dyn EmbeddingProvider {
+        self.embedder.as_ref()
+    }
+
+    /// Create the collection if it doesn't exist yet, bound to the default
+    /// embedder's model. Doubles as a warm-up: it opens a pooled connection
+    /// to Chroma.
+    pub async fn ensure_collection(&self) -> Result<()> {
+        if self.chroma.get_collection(&self.collection).await.is_err() {
+            info!("Creating collection {}", self.collection);
+            let binding = EmbeddingBinding::of(self.embedder.as_ref());
+            let metadata = CollectionMetadata::new(DistanceSpace::Cosine).with_binding(&binding);
+            self.chroma.create_collection_with_metadata(&self.collection, &metadata).await?;
+        }
+        Ok(())
+    }
+
+    /// The provider to embed with for this collection: the default embedder
+    /// unless the collection is bound to another model, in which case a
+    /// registered provider for that model. Collections without a binding use
+    /// the default embedder.
+    ///
+    /// Fails with `ChromaError::ModelMismatch` when no provider serves the
+    /// bound model.
+    pub async fn bound_embedder(&self) -> Result<Arc<dyn EmbeddingProvider>> {
+        Ok(self.resolve_embedder().await?.0)
+    }
+
+    async fn resolve_embedder(&self) -> Result<(Arc<dyn EmbeddingProvider>, CollectionResponse)> {
+        let collection = self.chroma.get_collection(&self.collection).await?;
+        let Some(binding) = collection.metadata.as_ref().and_then(CollectionMetadata::binding) else {
+            return Ok((self.embedder.clone(), collection));
+        };
+        if binding.matches(self.embedder.as_ref()) {
+            return Ok((self.embedder.clone(), collection));
+        }
+        match self.providers.resolve(&binding) {
+            Some(provider) => Ok((provider, collection)),
+            None => Err(ChromaError::ModelMismatch {
+                collection: self.collection.clone(),
+                expected: binding.to_string(),
+                actual: EmbeddingBinding::of(self.embedder.as_ref()).to_string(),
+            }),
+        }
+    }
+
+    /// Check the whole setup end to end: Chroma reachable (through any
+    /// proxy/TLS), credentials accepted, collection present, a test embedding
+    /// succeeds and its dimension matches the collection. Never fails; look
+    /// at `PreflightReport::is_ok` and the individual checks.
+    pub async fn preflight(&self) -> PreflightReport {
+        preflight::run(self).await
+    }
+
+    /// Everything `preflight` checks, plus the Chroma version and an
+    /// ingest/query/delete round trip in a scratch collection, bundled with
+    /// versions and the redacted configuration for a support ticket. Never
+    /// fails; look at `DiagnosticBundle::is_ok` and the individual checks.
+    pub async fn doctor(&self) -> DiagnosticBundle {
+        doctor::run(self).await
+    }
+
+    /// Re-embed a sample of stored documents and compare them with their
+    /// stored vectors, to catch an upstream model that changed behind the
+    /// same name.
+    pub async fn check_drift(&self, config: &DriftConfig) -> Result<DriftReport> {
+        drift::check(self, config).await
+    }
+
+    /// Re-embed the whole collection with `embedder` into
+    /// `target_collection`, e.g. after queries fail with
+    /// `ChromaError::ModelDeprecated`:
+    ///
+    /// ```no_run
+    /// # async fn example(pipeline: chromadb_demo::Pipeline, api_key: String) -> chromadb_demo::Result<()> {
+    /// use chromadb_demo::{ChromaError, EmbeddingClient};
+    /// use std::sync::Arc;
+    ///
+    /// if let Err(ChromaError::ModelDeprecated { replacement: Some(model), .. }) = pipeline.embed_query("probe").await {
+    ///     let embedder = Arc::new(EmbeddingClient::try_new(api_key)?.with_model(&model));
+    ///     pipeline.migrate_to(embedder, "docs_v2").await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn migrate_to(&self, embedder: Arc<dyn EmbeddingProvider>, target_collection: &str) -> Result<MigrationReport> {
+        migration::migrate(self, embedder.as_ref(), target_collection).await
+    }
+
+    /// Run each canary query once and check its expected top results. See
+    /// `CanaryMonitor` to do this on a schedule.
+    pub async fn run_canaries(&self, canaries: &[CanaryQuery]) -> CanaryReport {
+        canary::run(self, canaries).await
+    }
+
+    /// Embed `documents` and upsert them, returning how many were written.
+    pub async fn ingest(&self, documents: Vec<Document>) -> Result<usize> {
+        if documents.is_empty() {
+            return Ok(0);
+        }
+
+        let embeddings = self.embed_documents(&documents).await?;
+        self.upsert(documents, embeddings).await
+    }
+
+    /// `ingest`, except that with degraded mode on, documents are queued in
+    /// the outbox when the embedding provider is down (see `flush_outbox`).
+    pub async fn ingest_or_queue(&self, documents: Vec<Document>) -> Result<IngestOutcome> {
+        if documents.is_empty() {
+            return Ok(IngestOutcome::default());
+        }
+
+        match self.embed_documents(&documents).await {
+            Ok(embeddings) => Ok(IngestOutcome {
+                upserted: self.upsert(documents, embeddings).await?,
+                ..IngestOutcome::default()
+            }),
+            Err(e) if self.degraded.is_some() && degraded::is_provider_outage(&e) => {
+                let queued = documents.len();
+                self.outbox.push(documents, &e)?;
+                warn!("Embedding provider unavailable, queued {} documents: {}", queued, e);
+                Ok(IngestOutcome { upserted: 0, queued, degraded: true })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Ingest queued documents, oldest first, until the outbox is empty.
+    /// Returns how many were written; on failure the rest stay queued.
+    pub async fn flush_outbox(&self) -> Result<usize> {
+        let mut flushed = 0;
+        loop {
+            let batch = self.outbox.take(SYNC_BATCH_SIZE);
+            if batch.is_empty() {
+                break;
+            }
+            let embeddings = match self.embed_documents(&batch).await {
+                Ok(embeddings) => embeddings,
+                Err(e) => {
+                    self.outbox.restore(batch);
+                    return Err(e);
+                }
+            };
+            let documents = self.stamped(batch.clone());
+            if let Err(e) = self.chroma.upsert_documents(&self.collection, documents, embeddings).await {
+                self.outbox.restore(batch);
+                return Err(e);
+            }
+            flushed += batch.len();
+        }
+        if flushed > 0 {
+            info!("Flushed {} queued documents", flushed);
+        }
+        Ok(flushed)
+    }
+
+    /// Documents waiting in the outbox.
+    pub fn outbox_len(&self) -> usize {
+        self.outbox.len()
+    }
+
+    async fn embed_documents(&self, documents: &[Document]) -> Result<Vec<Vec<f32>>> {
+        let embedder = self.bound_embedder().await?;
+        let texts: Vec<&str> = documents.iter().map(|d| d.content.as_str()).collect();
+        embedder.embed(&texts).await
+    }
+
+    async fn upsert(&self, documents: Vec<Document>, embeddings: Vec<Vec<f32>>) -> Result<usize> {
+        let count = documents.len();
+        self.chroma.upsert_documents(&self.collection, self.stamped(documents), embeddings).await?;
+        Ok(count)
+    }
+
+    /// Stamp version and provenance metadata. Documents not assigned to a
+    /// job by the caller form one job per write.
+    fn stamped(&self, mut documents: Vec<Document>) -> Vec<Document> {
+        let job_id = provenance::new_job_id();
+        let now = chrono::Utc::now();
+        for document in &mut documents {
+            versioning::stamp(document, &self.pipeline_version);
+            provenance::stamp(document, &job_id, now);
+        }
+        documents
+    }
+
+    /// Records not written by this pipeline version with `chunker` (`None`
+    /// for collections ingested without chunking), e.g. to re-chunk only
+    /// those after changing the chunk size: re-ingest
+    /// `OutdatedReport::document_ids`.
+    pub async fn outdated_records(&self, chunker: Option<&Chunker>) -> Result<OutdatedReport> {
+        let expected = RecordVersion::new(&self.pipeline_version, chunker);
+        self.chroma.find_outdated(&self.collection, &expected).await
+    }
+
+    /// Split each document into chunks and upsert them, replacing any chunks
+    /// stored for it before. Returns the number of chunks written.
+    pub async fn ingest_chunked(&self, documents: Vec<Document>, chunker: &Chunker) -> Result<usize> {
+        if documents.is_empty() {
+            return Ok(0);
+        }
+
+        let chunker = *chunker;
+        let split = self.workers.map(documents, move |d| (d.id.clone(), chunker.split(&d))).await?;
+        let manifest: ChunkManifest = split.iter().map(|(id, chunks)| (id.clone(), chunks.len())).collect();
+        let mut chunks: Vec<Document> = split.into_iter().flat_map(|(_, chunks)| chunks).collect();
+        let transform = format!("chunk:{}", chunker.config_hash());
+        for chunk in &mut chunks {
+            provenance::add_transform(chunk, &transform);
+        }
+        let written = self.ingest(chunks).await?;
+
+        // Only once the new chunks are in: a shorter new version would
+        // otherwise leave its old tail chunks behind.
+        let stale = chunk_gc::find_stale(&self.chroma, &manifest, &self.collection).await?;
+        chunk_gc::collect(&self.chroma, &stale, &self.collection).await?;
+        Ok(written)
+    }
+
+    pub async fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        let embedder = self.bound_embedder().await?;
+        Ok(self.embed_query_cached(embedder.as_ref(), text).await?.0)
+    }
+
+    /// The query embedding from the collection's bound provider, and
+    /// whether it came from the cache.
+    async fn embed_query_cached(&self, embedder: &dyn EmbeddingProvider, text: &str) -> Result<(Vec<f32>, bool)> {
+        let key = cache_key(embedder, text);
+        if let Some(embedding) = self.cache.get(&key) {
+            debug!("Query embedding cache hit");
+            return Ok((embedding, true));
+        }
+
+        let embedding = embedder.embed(&[text]).await?.into_iter().next().ok_or_else(|| {
+            ChromaError::EmbeddingError(format!("{} returned no embedding for the query", embedder.model_name()))
+        })?;
+        self.cache.insert(&key, embedding.clone());
+        Ok((embedding, false))
+    }
+
+    /// Embed `text`, search and re-rank, timing each stage.
+    pub async fn query(&self, text: &str, options: &QueryOptions) -> Result<QueryResult> {
+        let started = Instant::now();
+        let (embedder, collection) = self.resolve_embedder().await?;
+        let (embedding, embedding_cached) = match self.embed_query_cached(embedder.as_ref(), text).await {
+            Ok(embedded) => embedded,
+            Err(e) if self.degraded.is_some() && degraded::is_provider_outage(&e) => {
+                // `$contains` can't match ciphertext, so there is nothing to fall back to.
+                if self.chroma.encrypts_documents() {
+                    warn!("Embedding provider unavailable, and keyword search can't read encrypted documents: {}", e);
+                    return Err(e);
+                }
+                warn!("Embedding provider unavailable, falling back to keyword search: {}", e);
+                return self.keyword_query(text, options, started).await;
+            }
+            Err(e) => return Err(e),
+        };
+        let embedded = Instant::now();
+        binding::check_query_dimension(&collection, embedding.len(), || {
+            EmbeddingBinding { dimension: Some(embedding.len()), ..EmbeddingBinding::of(embedder.as_ref()) }.to_string()
+        })?;
+
+        let request = options.to_request(embedding);
+        let response = self.chroma.send_query(&self.collection, &request).await?;
+        let searched = Instant::now();
+
+        let candidates = response.into_hits();
+        let candidates_returned = candidates.len();
+        let hits = options.rerank(candidates);
+        let reranked = Instant::now();
+
+        let timings = QueryTimings {
+            embed_ms: millis(embedded - started),
+            search_ms: millis(searched - embedded),
+            rerank_ms: millis(reranked - searched),
+            total_ms: millis(reranked - started),
+            embedding_cached,
+        };
+        debug!("Query timings: {:?}", timings);
+        let explain = options.explain.then(|| QueryExplain {
+            embedding_model: Some(embedder.model_name().to_string()),
+            embedding_task_type: embedder.task_type().map(str::to_string),
+            ..QueryExplain::new(options, &request, candidates_returned, hits.len())
+        });
+        Ok(QueryResult { hits, timings, degraded: false, explain })
+    }
+
+    /// Degraded-mode search: fetch documents containing a query term and
+    /// rank them with BM25. Options that depend on vector distances
+    /// (`min_score`, `score_fn`) are ignored.
+    async fn keyword_query(&self, text: &str, options: &QueryOptions, started: Instant) -> Result<QueryResult> {
+        let Some(mode) = &self.degraded else {
+            return Err(ChromaError::ConfigError("Degraded mode is off".to_string()));
+        };
+        let failed = Instant::now();
+        let request = GetRequest {
+            where_filter: options.request_filter(),
+            where_document: degraded::keyword_filter(text),
+            limit: Some(mode.keyword_candidates),
+            include: Some(vec![Include::Documents, Include::Metadatas, Include::Uris]),
+            ..GetRequest::default()
+        };
+        let candidates = match request.where_document {
+            Some(_) => self.chroma.send_get(&self.collection, &request).await?.into_hits(),
+            None => Vec::new(),
+        };
+        let searched = Instant::now();
+
+        let candidates_returned = candidates.len();
+        let mut keyword_options = options.clone();
+        keyword_options.min_score = None;
+        keyword_options.score_fn = None;
+        let hits = keyword_options.rerank(degraded::bm25_rank(text, candidates));
+        let reranked = Instant::now();
+
+        let timings = QueryTimings {
+            embed_ms: millis(failed - started),
+            search_ms: millis(searched - failed),
+            rerank_ms: millis(reranked - searched),
+            total_ms: millis(reranked - started),
+            embedding_cached: false,
+        };
+        let explain = options.explain.then(|| QueryExplain {
+            candidates_requested: mode.keyword_candidates,
+            ..QueryExplain::new(options, &options.to_request(Vec::new()), candidates_returned, hits.len())
+        });
+        Ok(QueryResult { hits, timings, degraded: true, explain })
+    }
+
+    /// Retrieve context for `question` and have the LLM answer from it,
+    /// using the `answer` prompt template. Chunks that don't fit the
+    /// template's token budget are dropped lowest-ranked first.
+    pub async fn answer(&self, question: &str, options: &QueryOptions) -> Result<Answer> {
+        let llm = self.llm()?;
+        let (prompt, retrieved) = self.retrieve_prompt(ANSWER_TEMPLATE, question, &[], options).await?;
+
+        let model = llm.model_name().to_string();
+        let cached = self.answer_cache.as_ref().and_then(|cache| cache.get(question, &prompt.included_ids, &model));
+        let (text, cached) = match cached {
+            Some(text) => (text, true),
+            None => {
+                let generation = llm.generate(&GenerationRequest::new(prompt.text)).await?;
+                if let Some(cache) = &self.answer_cache {
+                    cache.insert(question, &prompt.included_ids, &model, &generation.text);
+                }
+                (generation.text, false)
+            }
+        };
+
+        let provenance = retrieved
+            .hits
+            .iter()
+            .filter(|hit| prompt.included_ids.contains(&hit.id))
+            .filter_map(|hit| Some((hit.id.clone(), hit.provenance()?)))
+            .collect();
+        Ok(Answer {
+            text,
+            model,
+            context_ids: prompt.included_ids,
+            provenance,
+            dropped_ids: prompt.dropped_ids,
+            cached,
+            standalone_question: None,
+            timings: retrieved.timings,
+            degraded: retrieved.degraded,
+        })
+    }
+
+    /// `answer`, with the text streamed as the LLM generates it. Retrieval
+    /// errors are returned up front; generation errors arrive in the stream.
+    /// A fully streamed answer is added to the answer cache.
+    pub async fn answer_stream(&self, question: &str, options: &QueryOptions) -> Result<AnswerStream> {
+        let llm = self.llm()?;
+        let (prompt, retrieved) = self.retrieve_prompt(ANSWER_TEMPLATE, question, &[], options).await?;
+
+        let model = llm.model_name().to_string();
+        let cached = self.answer_cache.as_ref().and_then(|cache| cache.get(question, &prompt.included_ids, &model));
+        let (tokens, cached) = match cached {
+            Some(text) => (stream::once(async move { Ok(text) }).boxed(), true),
+            None => {
+                let tokens = llm.generate_stream(&GenerationRequest::new(prompt.text)).await?;
+                let tokens = match &self.answer_cache {
+                    Some(cache) => {
+                        let key = (question.to_string(), prompt.included_ids.clone(), model.clone());
+                        cache_when_complete(tokens, cache.clone(), key)
+                    }
+                    None => tokens,
+                };
+                (tokens, false)
+            }
+        };
+
+        let citations = retrieved
+            .hits
+            .into_iter()
+            .filter(|hit| prompt.included_ids.contains(&hit.id))
+            .collect();
+        Ok(AnswerStream {
+            tokens,
+            model,
+            citations,
+            dropped_ids: prompt.dropped_ids,
+            cached,
+            timings: retrieved.timings,
+            degraded: retrieved.degraded,
+        })
+    }
+
+    /// Answer the next `question` of `conversation`, then record both in it.
+    /// With query rewriting on, a follow-up is first rewritten into a
+    /// standalone question, which is what gets searched for and answered.
+    pub async fn answer_in_conversation(
+        &self,
+        conversation: &mut Conversation,
+        question: &str,
+        options: &QueryOptions,
+    ) -> Result<Answer> {
+        let standalone = self.standalone_question(question, &conversation.turns).await?;
+        let mut answer = self.answer(&standalone, options).await?;
+        if standalone != question {
+            answer.standalone_question = Some(standalone);
+        }
+        conversation.push(Role::User, question);
+        conversation.push(Role::Assistant, answer.text.clone());
+        Ok(answer)
+    }
+
+    /// `question` rewritten to be understood without `history`, or as it is
+    /// when rewriting is off, there is no history or the model's reply is
+    /// empty.
+    pub async fn standalone_question(&self, question: &str, history: &[ChatTurn]) -> Result<String> {
+        let Some(config) = &self.rewrite else {
+            return Ok(question.to_string());
+        };
+        if history.is_empty() {
+            return Ok(question.to_string());
+        }
+
+        let recent = &history[history.len().saturating_sub(config.max_turns)..];
+        let history = conversation::format_history(recent);
+        let variables = HashMap::from([("history", history.as_str()), ("question", question)]);
+        let prompt = self.prompts.get(&config.template)?.render(&variables)?;
+        let reply = self.llm()?.generate(&GenerationRequest::new(prompt).with_temperature(0.0)).await?;
+        let standalone = conversation::clean_rewrite(&reply.text).unwrap_or_else(|| question.to_string());
+        debug!("Rewrote follow-up {:?} as {:?}", question, standalone);
+        Ok(standalone)
+    }
+
+    /// Retrieve context for `question` and have the LLM extract a `T` from
+    /// it as JSON conforming to `schema` (see `ExtractionSchema` for
+    /// deriving one from `T`), using the `extract` prompt template. Output
+    /// that isn't valid JSON, breaks the schema or doesn't deserialize is
+    /// sent back to the model with the reason, up to
+    /// `with_extraction_attempts` times.
+    pub async fn extract<T: DeserializeOwned>(
+        &self,
+        question: &str,
+        schema: &Value,
+        options: &QueryOptions,
+    ) -> Result<Extraction<T>> {
+        let llm = self.llm()?;
+        let schema_text = serde_json::to_string_pretty(schema)?;
+        let (prompt, _) = self
+            .retrieve_prompt(EXTRACT_TEMPLATE, question, &[("schema", &schema_text)], options)
+            .await?;
+
+        let mut request = GenerationRequest::new(prompt.text.clone()).with_json().with_temperature(0.0);
+        let mut rejection = String::new();
+        for attempt in 1..=self.extraction_attempts {
+            let generation = llm.generate(&request).await?;
+            match extract::parse_response::<T>(&generation.text, schema) {
+                Ok(value) => {
+                    return Ok(Extraction {
+                        value,
+                        model: llm.model_name().to_string(),
+                        context_ids: prompt.included_ids,
+                        attempts: attempt,
+                    });
+                }
+                Err(e) => {
+                    warn!("Extraction attempt {}/{} rejected: {}", attempt, self.extraction_attempts, e);
+                    rejection = e.to_string();
+                    request.prompt = format!(
+                        "{}\n\nYour previous response was rejected: {}\nRespond again with only JSON that conforms to the schema.",
+                        prompt.text, rejection
+                    );
+                }
+            }
+        }
+        Err(ChromaError::GenerationError(format!(
+            "No usable JSON after {} attempts; last rejection: {}",
+            self.extraction_attempts, rejection
+        )))
+    }
+
+    /// Query for `question` and render `template` with the hits packed into
+    /// its context, plus `question` and any `variables`.
+    async fn retrieve_prompt(
+        &self,
+        template: &str,
+        question: &str,
+        variables: &[(&str, &str)],
+        options: &QueryOptions,
+    ) -> Result<(RenderedPrompt, QueryResult)> {
+        let retrieved = self.query(question, options).await?;
+        let mut values = HashMap::from([("question", question)]);
+        values.extend(variables.iter().copied());
+        let prompt = self
+            .prompts
+            .get(template)?
+            .render_with_context(&values, &retrieved.hits, estimate_tokens)?;
+        Ok((prompt, retrieved))
+    }
+
+    fn llm(&self) -> Result<&dyn LlmProvider> {
+        self.llm
+            .as_deref()
+            .ok_or_else(|| ChromaError::ConfigError("No LLM configured for answers; see Pipeline::with_llm".to_string()))
+    }
+
+    /// Answer many queries at once, e.g. for an evaluation run: texts that
+    /// aren't cached are embedded in batched calls, then the searches run
+    /// with at most `with_batch_concurrency` in flight. Results are keyed by
+    /// query text; duplicate texts are searched once.
+    pub async fn query_batch(&self, texts: &[String], k: u32) -> Result<HashMap<String, Vec<QueryHit>>> {
+        let embedder = self.bound_embedder().await?;
+        let mut embeddings: HashMap<&str, Vec<f32>> = HashMap::new();
+        let mut uncached: Vec<&str> = Vec::new();
+        let mut seen = HashSet::new();
+        for text in texts.iter().filter(|text| seen.insert(text.as_str())) {
+            match self.cache.get(&cache_key(embedder.as_ref(), text)) {
+                Some(embedding) => {
+                    embeddings.insert(text, embedding);
+                }
+                None => uncached.push(text),
+            }
+        }
+
+        if !uncached.is_empty() {
+            debug!("Embedding {} of {} batch queries", uncached.len(), texts.len());
+            let fresh = embedder.embed(&uncached).await?;
+            if fresh.len() != uncached.len() {
+                return Err(ChromaError::EmbeddingError(format!(
+                    "{} returned {} embeddings for {} queries",
+                    embedder.model_name(),
+                    fresh.len(),
+                    uncached.len()
+                )));
+            }
+            for (text, embedding) in uncached.into_iter().zip(fresh) {
+                self.cache.insert(&cache_key(embedder.as_ref(), text), embedding.clone());
+                embeddings.insert(text, embedding);
+            }
+        }
+
+        let options = QueryOptions::new(k);
+        let searches = embeddings.into_iter().map(|(text, embedding)| {
+            let options = &options;
+            async move {
+                let hits = self.chroma.query_with_options(&self.collection, embedding, options).await?;
+                Ok::<_, ChromaError>((text.to_string(), hits))
+            }
+        });
+        stream::iter(searches)
+            .buffer_unordered(self.batch_concurrency)
+            .try_collect()
+            .await
+    }
+
+    pub fn cache_len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn flush_cache(&self) {
+        self.cache.clear();
+    }
+
+    /// Upsert every `.txt`/`.md` file directly inside `dir`, keyed by file
+    /// name, so re-running picks up edits without duplicating documents.
+    pub async fn sync_directory(&self, dir: &Path) -> Result<SyncReport> {
+        let source = dir.to_path_buf();
+        let (mut documents, files_seen) = self.workers.run(move || load_directory(&source)).await??;
+        let mut report = SyncReport { files_seen, ..SyncReport::default() };
+        let job_id = provenance::new_job_id();
+        provenance::assign_job(&mut documents, &job_id);
+
+        while !documents.is_empty() {
+            let rest = documents.split_off(documents.len().min(SYNC_BATCH_SIZE));
+            report.documents_upserted += self.ingest(std::mem::replace(&mut documents, rest)).await?;
+        }
+
+        info!(
+            "Synced {} into {} (job {}): {} files, {} documents",
+            dir.display(), self.collection, job_id, report.files_seen, report.documents_upserted
+        );
+        if let Some(sla) = self.sync_sla {
+            self.chroma.freshness(&self.collection).mark_indexed(&dir.display().to_string(), sla).await?;
+        }
+        Ok(report)
+    }
+
+    /// Carry out a plan from `ChromaClient::diff`: embed and upsert the
+    /// documents it adds or updates, taken from `documents`, then delete the
+    /// ids it drops. Writes go first, so a failure part-way leaves stale
+    /// documents behind rather than missing ones; diff again and re-apply to
+    /// finish.
+    pub async fn apply_plan(&self, plan: &SyncPlan, documents: Vec<Document>) -> Result<PlanOutcome> {
+        let writes = plan.writes();
+        let mut documents: Vec<Document> = documents.into_iter().filter(|d| writes.contains(d.id.as_str())).collect();
+        if documents.len() != writes.len() {
+            return Err(ChromaError::ValidationError(format!(
+                "The plan writes {} documents, but only {} of them were given",
+                writes.len(),
+                documents.len()
+            )));
+        }
+        provenance::assign_job(&mut documents, &provenance::new_job_id());
+
+        while !documents.is_empty() {
+            let rest = documents.split_off(documents.len().min(SYNC_BATCH_SIZE));
+            self.ingest(std::mem::replace(&mut documents, rest)).await?;
+        }
+        for ids in plan.delete.chunks(PLAN_DELETE_BATCH_SIZE) {
+            self.chroma.delete_documents(&self.collection, ids.to_vec()).await?;
+        }
+
+        Ok(PlanOutcome {
+            added: plan.add.len(),
+            updated: plan.update.len(),
+            deleted: plan.delete.len(),
+        })
+    }
+
+    /// Make the collection hold exactly the `.txt`/`.md` files in `dir`
+    /// (see `load_directory`): unchanged files aren't re-embedded, and
+    /// documents whose file is gone are deleted. Returns the plan that was
+    /// applied.
+    pub async fn mirror_directory(&self, dir: &Path) -> Result<SyncPlan> {
+        let source = dir.to_path_buf();
+        let (documents, _) = self.workers.run(move || load_directory(&source)).await??;
+        let manifest = sync_plan::manifest_of(&documents, &self.chroma.content_hasher()?);
+        let plan = self.chroma.diff(&manifest, &self.collection).await?;
+        let outcome = self.apply_plan(&plan, documents).await?;
+        info!(
+            "Mirrored {} into {}: {} added, {} updated, {} deleted, {} unchanged",
+            dir.display(), self.collection, outcome.added, outcome.updated, outcome.deleted, plan.unchanged
+        );
+        Ok(plan)
+    }
+
+    /// Sources of this collection that haven't been re-indexed within their
+    /// SLA. See `Freshness`.
+    pub async fn freshness_report(&self) -> Result<FreshnessReport> {
+        self.chroma.freshness(&self.collection).report().await
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Read the `.txt`/`.md` files directly inside `dir` as documents with the
+/// file name as id, sorted by id. Also returns how many such files there
+/// were, including empty ones that produced no document.
+pub fn load_directory(dir: &Path) -> Result<(Vec<Document>, usize)> {
+    let mut documents = Vec::new();
+    let mut files_seen = 0;
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_source = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| SYNC_EXTENSIONS.contains(&e));
+        if !path.is_file() || !is_source {
+            continue;
+        }
+
+        files_seen += 1;
+        let content = std::fs::read_to_string(&path)?;
+        if content.trim().is_empty() {
+            continue;
+        }
+
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let metadata = HashMap::from([("source".to_string(), name.clone())]);
+        let mut document = Document { id: name, content, metadata, uri: None };
+        provenance::set_origin(&mut document, "directory", &path.to_string_lossy());
+        documents.push(document);
+    }
+
+    documents.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok((documents, files_seen))
+}
+
+/// Pass `tokens` through, then cache the full text under `key` (question,
+/// cited chunk ids, model) once the stream ends. Answers cut short by an
+/// error aren't cached.
+fn cache_when_complete(tokens: TextStream, cache: Arc<AnswerCache>, key: (String, Vec<String>, String)) -> TextStream {
+    stream::unfold((tokens, String::new(), Some(key)), move |(mut tokens, mut text, mut key)| {
+        let cache = cache.clone();
+        async move {
+            match tokens.next().await {
+                Some(Ok(chunk)) => {
+                    text.push_str(&chunk);
+                    Some((Ok(chunk), (tokens, text, key)))
+                }
+                Some(Err(e)) => Some((Err(e), (tokens, text, None))),
+                None => {
+                    if let Some((question, ids, model)) = key.take() {
+                        cache.insert(&question, &ids, &model, &text);
+                    }
+                    None
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+/// Bounded text → embedding cache with first-in-first-out eviction.
+/// Query embeddings are cached per model, so switching providers never
+/// serves a vector from the wrong space.
+fn cache_key(embedder: &dyn EmbeddingProvider, text: &str) -> String {
+    format!("{}\n{}", embedder.model_name(), text)
+}
+
+struct EmbeddingCache {
+    capacity: usize,
+    inner: Mutex<CacheEntries>,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    map: HashMap<String, Vec<f32>>,
+    order: VecDeque<String>,
+}
+
+impl EmbeddingCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(CacheEntries::default()),
+        }
+    }
+
+    fn get(&self, text: &str) -> Option<Vec<f32>> {
+        self.inner.lock().unwrap().map.get(text).cloned()
+    }
+
+    fn insert(&self, text: &str, embedding: Vec<f32>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        if inner.map.insert(text.to_string(), embedding).is_none() {
+            inner.order.push_back(text.to_string());
+        }
+        while inner.order.len() > self.capacity {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.map.remove(&oldest);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.inner.lock().unwrap().map.len()
+    }
+
+    fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.map.clear();
+        inner.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_queries_of_the_wrong_dimension_are_refused() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let queries = Arc::new(AtomicUsize::new(0));
+        let counter = queries.clone();
+        let chroma = Arc::new(mock_chroma(move |request| {
+            let counter = counter.clone();
+            if request.uri().path().ends_with("/query") {
+                counter.fetch_add(1, Ordering::SeqCst);
+                r#"{"ids": [["a"]], "distances": [[0.1]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs", "dimension": 3, "metadata": {"embedding:provider": "gemini", "embedding:model": "fixed"}}"#
+            }
+        }));
+
+        let error = chroma.query("docs", vec![vec![1.0, 0.0]], 1).await.unwrap_err().to_string();
+        assert!(error.contains("gemini/fixed (3 dimensions)") && error.contains("2-dimensional"), "{}", error);
+
+        // One-dimensional embeddings, under the bound model's name.
+        struct GeminiNamed;
+        impl EmbeddingProvider for GeminiNamed {
+            fn embed<'a>(&'a self, texts: &'a [&'a str]) -> futures::future::BoxFuture<'a, Result<Vec<Vec<f32>>>> {
+                Box::pin(async move { Ok(texts.iter().map(|t| vec![t.len() as f32]).collect()) })
+            }
+            fn model_name(&self) -> &str {
+                "fixed"
+            }
+            fn provider_name(&self) -> &str {
+                "gemini"
+            }
+        }
+        let pipeline = Pipeline::new(chroma, Arc::new(GeminiNamed), "docs");
+        let error = pipeline.query("rust", &QueryOptions::new(1)).await.unwrap_err().to_string();
+        assert!(error.contains("the query is gemini/fixed (1 dimensions)"), "{}", error);
+        assert_eq!(queries.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_missing_query_embeddings_are_errors() {
+        struct NoEmbeddings;
+
+        impl EmbeddingProvider for NoEmbeddings {
+            fn embed<'a>(&'a self, _: &'a [&'a str]) -> futures::future::BoxFuture<'a, Result<Vec<Vec<f32>>>> {
+                Box::pin(async { Ok(Vec::new()) })
+            }
+
+            fn model_name(&self) -> &str {
+                "empty"
+            }
+        }
+
+        let chroma = Arc::new(mock_chroma(|_| r#"{"id": "c0ffee", "name": "docs"}"#));
+        let pipeline = Pipeline::new(chroma, Arc::new(NoEmbeddings), "docs");
+        let error = pipeline.query("rust", &QueryOptions::new(1)).await.unwrap_err();
+        assert!(matches!(error, ChromaError::EmbeddingError(ref m) if m.contains("empty")), "{}", error);
+        let texts = vec!["rust".to_string(), "go".to_string()];
+        let error = pipeline.query_batch(&texts, 1).await.unwrap_err();
+        assert!(matches!(error, ChromaError::EmbeddingError(ref m) if m.contains("0 embeddings for 2")), "{}", error);
+        assert_eq!(pipeline.cache_len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_query_batch_embeds_once_and_keys_results_by_query() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        struct CountingEmbeddings(AtomicUsize);
+
+        impl EmbeddingProvider for CountingEmbeddings {
+            fn embed<'a>(&'a self, texts: &'a [&'a str]) -> futures::future::BoxFuture<'a, Result<Vec<Vec<f32>>>> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async move { Ok(texts.iter().map(|t| vec![t.len() as f32]).collect()) })
+            }
+
+            fn model_name(&self) -> &str {
+                "counting"
+            }
+        }
+
+        // Echo the query vector back as the hit id so results can be matched to queries.
+        let chroma = mock_chroma(|request| {
+            if request.uri().path().ends_with("/query") {
+                let query: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                serde_json::json!({"ids": [[query["query_embeddings"][0][0].to_string()]], "distances": [[0.1]]}).to_string()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+        let embedder = Arc::new(CountingEmbeddings(AtomicUsize::new(0)));
+        let pipeline = Pipeline::new(Arc::new(chroma), embedder.clone(), "docs").with_batch_concurrency(2);
+
+        let texts: Vec<String> = ["a", "bb", "ccc", "a"].iter().map(|t| t.to_string()).collect();
+        let results = pipeline.query_batch(&texts, 1).await.unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results["ccc"][0].id, "3.0");
+        assert_eq!(embedder.0.load(Ordering::SeqCst), 1);
+
+        pipeline.query_batch(&texts, 1).await.unwrap();
+        assert_eq!(embedder.0.load(Ordering::SeqCst), 1, "second run is served from the cache");
+    }
+
+    #[tokio::test]
+    async fn test_ingest_chunked_keeps_old_chunks_until_the_new_ones_are_written() {
+        use crate::test_support::FixedEmbeddings;
+
+        struct DownEmbeddings;
+
+        impl EmbeddingProvider for DownEmbeddings {
+            fn embed<'a>(&'a self, _: &'a [&'a str]) -> futures::future::BoxFuture<'a, Result<Vec<Vec<f32>>>> {
+                Box::pin(async { Err(ChromaError::EmbeddingError("provider down".to_string())) })
+            }
+
+            fn model_name(&self) -> &str {
+                "down"
+            }
+        }
+
+        let writes: Arc<Mutex<Vec<(String, Value)>>> = Arc::default();
+        let recorded = writes.clone();
+        let chroma = Arc::new(mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            if path.ends_with("/get") {
+                serde_json::json!({
+                    "ids": ["a#0", "a#1", "a#2"],
+                    "metadatas": [
+                        {"parent_id": "a", "chunk_index": "0"},
+                        {"parent_id": "a", "chunk_index": "1"},
+                        {"parent_id": "a", "chunk_index": "2"},
+                    ],
+                })
+                .to_string()
+            } else if path.ends_with("/upsert") || path.ends_with("/delete") {
+                let body: Value = serde_json::from_slice(request.body()).unwrap();
+                let operation = path.rsplit('/').next().unwrap().to_string();
+                recorded.lock().unwrap().push((operation, body["ids"].clone()));
+                "true".to_string()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        }));
+        let shorter = vec![Document::builder().id("a").content("short now").build()];
+
+        let failing = Pipeline::new(chroma.clone(), Arc::new(DownEmbeddings), "docs");
+        assert!(failing.ingest_chunked(shorter.clone(), &Chunker::default()).await.is_err());
+        assert!(writes.lock().unwrap().is_empty(), "{:?}", writes.lock().unwrap());
+
+        let pipeline = Pipeline::new(chroma, Arc::new(FixedEmbeddings), "docs");
+        assert_eq!(pipeline.ingest_chunked(shorter, &Chunker::default()).await.unwrap(), 1);
+        assert_eq!(
+            *writes.lock().unwrap(),
+            vec![
+                ("upsert".to_string(), serde_json::json!(["a#0"])),
+                ("delete".to_string(), serde_json::json!(["a#1", "a#2"])),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_timings_cover_each_stage_and_flag_cached_embeddings() {
+        struct SlowEmbeddings;
+
+        impl EmbeddingProvider for SlowEmbeddings {
+            fn embed<'a>(&'a self, texts: &'a [&'a str]) -> futures::future::BoxFuture<'a, Result<Vec<Vec<f32>>>> {
+                Box::pin(async move {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    Ok(texts.iter().map(|t| vec![t.len() as f32]).collect())
+                })
+            }
+
+            fn model_name(&self) -> &str {
+                "slow"
+            }
+        }
+
+        let chroma = mock_chroma(|request| {
+            if request.uri().path().ends_with("/query") {
+                std::thread::sleep(Duration::from_millis(10));
+                r#"{"ids": [["a"]], "distances": [[0.1]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+        let pipeline = Pipeline::new(Arc::new(chroma), Arc::new(SlowEmbeddings), "docs");
+
+        let first = pipeline.query("rust", &QueryOptions::new(1)).await.unwrap().timings;
+        assert!(!first.embedding_cached);
+        assert!(first.embed_ms >= 20.0 && first.search_ms >= 10.0, "{:?}", first);
+        assert!(first.rerank_ms >= 0.0);
+        assert!(first.total_ms >= first.embed_ms + first.search_ms + first.rerank_ms - 0.001, "{:?}", first);
+
+        let second = pipeline.query("rust", &QueryOptions::new(1)).await.unwrap().timings;
+        assert!(second.embedding_cached);
+        assert!(second.embed_ms < 20.0 && second.search_ms >= 10.0, "{:?}", second);
+    }
+}
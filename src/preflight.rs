@@ -0,0 +1,235 @@
+use crate::error::ChromaError;
+use crate::models::{GetRequest, Include};
+use crate::pipeline::Pipeline;
+use serde::Serialize;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// Not run because an earlier check it depends on failed, or there was
+    /// nothing to compare against.
+    Skip,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreflightCheck {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+    pub elapsed_ms: u64,
+}
+
+/// Result of `Pipeline::preflight`, one entry per check in the order run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PreflightReport {
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    /// True when no check failed. Skipped checks don't count as failures.
+    pub fn is_ok(&self) -> bool {
+        self.checks.iter().all(|c| c.status != CheckStatus::Fail)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &PreflightCheck> {
+        self.checks.iter().filter(|c| c.status == CheckStatus::Fail)
+    }
+
+    pub(crate) fn record(&mut self, name: &'static str, started: Instant, status: CheckStatus, detail: impl Into<String>) {
+        self.checks.push(PreflightCheck {
+            name,
+            status,
+            detail: detail.into(),
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        });
+    }
+
+    pub(crate) fn skip(&mut self, name: &'static str, detail: &str) {
+        self.record(name, Instant::now(), CheckStatus::Skip, detail);
+    }
+}
+
+pub(crate) async fn run(pipeline: &Pipeline) -> PreflightReport {
+    let mut report = PreflightReport::default();
+    let chroma = pipeline.chroma();
+
+    let started = Instant::now();
+    let reachable = match chroma.health_check().await {
+        Ok(_) => {
+            report.record("chroma_reachable", started, CheckStatus::Pass, via_proxy("heartbeat ok"));
+            true
+        }
+        Err(e) => {
+            report.record("chroma_reachable", started, CheckStatus::Fail, via_proxy(&describe_connection_error(&e)));
+            false
+        }
+    };
+
+    let started = Instant::now();
+    let authorized = if !reachable {
+        report.skip("chroma_auth", "Chroma is unreachable");
+        false
+    } else {
+        match chroma.list_collections().await {
+            Ok(collections) => {
+                let detail = format!("listed {} collection(s)", collections.len());
+                report.record("chroma_auth", started, CheckStatus::Pass, detail);
+                true
+            }
+            Err(e) => {
+                let message = e.to_string();
+                let detail = if message.contains("401") || message.contains("403") {
+                    format!("credentials rejected: {}", message)
+                } else {
+                    message
+                };
+                report.record("chroma_auth", started, CheckStatus::Fail, detail);
+                false
+            }
+        }
+    };
+
+    let started = Instant::now();
+    let collection = if !authorized {
+        report.skip("collection", "Chroma access failed");
+        None
+    } else {
+        match chroma.get_collection(pipeline.collection()).await {
+            Ok(collection) => {
+                report.record("collection", started, CheckStatus::Pass, format!("'{}' exists", collection.name));
+                Some(collection)
+            }
+            Err(e) => {
+                let detail = format!("'{}' not found: {}", pipeline.collection(), e);
+                report.record("collection", started, CheckStatus::Fail, detail);
+                None
+            }
+        }
+    };
+
+    let started = Instant::now();
+    let embedding_dimension = match pipeline.embedder().embed(&["preflight"]).await {
+        Ok(vectors) => match vectors.first().map(Vec::len) {
+            Some(dimension) if dimension > 0 => {
+                let detail = format!("{} returned {} dimensions", pipeline.embedder().model_name(), dimension);
+                report.record("embedding", started, CheckStatus::Pass, detail);
+                Some(dimension)
+            }
+            _ => {
+                report.record("embedding", started, CheckStatus::Fail, "provider returned an empty embedding");
+                None
+            }
+        },
+        Err(e) => {
+            report.record("embedding", started, CheckStatus::Fail, describe_connection_error(&e));
+            None
+        }
+    };
+
+    let started = Instant::now();
+    match (collection, embedding_dimension) {
+        (Some(collection), Some(dimension)) => {
+            let stored = match collection.dimension {
+                Some(stored) => Some(stored),
+                None => stored_dimension(pipeline).await,
+            };
+            match stored {
+                Some(stored) if stored == dimension => {
+                    report.record("dimension", started, CheckStatus::Pass, format!("{} matches collection", dimension));
+                }
+                Some(stored) => {
+                    let detail = format!("embedder produces {} dimensions but collection holds {}", dimension, stored);
+                    report.record("dimension", started, CheckStatus::Fail, detail);
+                }
+                None => report.skip("dimension", "collection is empty; the first write sets its dimension"),
+            }
+        }
+        _ => report.skip("dimension", "needs both the collection and a test embedding"),
+    }
+
+    report
+}
+
+/// Dimension of one stored embedding, for servers that don't report it.
+async fn stored_dimension(pipeline: &Pipeline) -> Option<usize> {
+    let request = GetRequest {
+        limit: Some(1),
+        include: Some(vec![Include::Embeddings]),
+        ..GetRequest::default()
+    };
+    let response = pipeline.chroma().send_get(pipeline.collection(), &request).await.ok()?;
+    response.embedding(0, 0).map(<[f32]>::len)
+}
+
+/// Point at the usual culprits for connection-level failures.
+pub(crate) fn describe_connection_error(error: &ChromaError) -> String {
+    match error {
+        ChromaError::RequestError(e) if e.is_connect() => {
+            format!("connection failed (check host, proxy and TLS settings): {}", e)
+        }
+        ChromaError::RequestError(e) if e.is_timeout() => format!("timed out: {}", e),
+        other => other.to_string(),
+    }
+}
+
+fn via_proxy(detail: &str) -> String {
+    let proxy = ["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy"]
+        .iter()
+        .find_map(|key| std::env::var(key).ok().filter(|v| !v.is_empty()))
+        .map(|proxy| without_credentials(&proxy));
+    match proxy {
+        Some(proxy) => format!("{} (via proxy {})", detail, proxy),
+        None => detail.to_string(),
+    }
+}
+
+fn without_credentials(proxy: &str) -> String {
+    match url::Url::parse(proxy) {
+        Ok(mut url) => {
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+            url.to_string()
+        }
+        Err(_) => "<unparseable proxy URL>".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{FixedEmbeddings, mock_chroma};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_preflight_flags_dimension_mismatch() {
+        let chroma = mock_chroma(|request| {
+            let path = request.uri().path().to_string();
+            if path.ends_with("/heartbeat") {
+                r#"{"nanosecond heartbeat": 1}"#
+            } else if path.ends_with("/collections") {
+                "[]"
+            } else {
+                r#"{"id": "c0ffee", "name": "docs", "dimension": 768}"#
+            }
+        });
+        let pipeline = Pipeline::new(Arc::new(chroma), Arc::new(FixedEmbeddings), "docs");
+
+        let report = pipeline.preflight().await;
+        let statuses: Vec<(&str, CheckStatus)> = report.checks.iter().map(|c| (c.name, c.status)).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("chroma_reachable", CheckStatus::Pass),
+                ("chroma_auth", CheckStatus::Pass),
+                ("collection", CheckStatus::Pass),
+                ("embedding", CheckStatus::Pass),
+                ("dimension", CheckStatus::Fail),
+            ]
+        );
+        assert!(!report.is_ok());
+        assert!(report.failures().next().unwrap().detail.contains("768"));
+    }
+}
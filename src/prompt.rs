@@ -0,0 +1,361 @@
+use crate::error::{ChromaError, Result};
+use crate::models::QueryHit;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Directory `PromptLibrary::from_env` loads `*.prompt` files from.
+const PROMPT_DIR_ENV_VAR: &str = "PROMPT_TEMPLATES_DIR";
+const PROMPT_EXTENSION: &str = "prompt";
+/// The variable retrieved chunks are packed into.
+pub const CONTEXT_VARIABLE: &str = "context";
+/// Name of the built-in answer template.
+pub const ANSWER_TEMPLATE: &str = "answer";
+/// Name of the built-in structured extraction template.
+pub const EXTRACT_TEMPLATE: &str = "extract";
+/// Name of the built-in follow-up question rewriting template.
+pub const REWRITE_TEMPLATE: &str = "rewrite";
+
+const DEFAULT_ANSWER_TEMPLATE: &str = "\
+Answer the question using only the context below. Cite the ids of the \
+passages you used in square brackets. If the context doesn't contain the \
+answer, say so.
+
+Context:
+{{context}}
+
+Question: {{question}}
+Answer:";
+
+const DEFAULT_EXTRACT_TEMPLATE: &str = "\
+Extract what the request asks for from the context below. Respond with only \
+a JSON value that conforms to this JSON Schema, with no other text:
+{{schema}}
+
+Context:
+{{context}}
+
+Request: {{question}}";
+
+const DEFAULT_REWRITE_TEMPLATE: &str = "\
+Rewrite the last question of this conversation so it can be understood \
+without the conversation: replace pronouns and references with what they \
+refer to. Reply with only the rewritten question. If it already stands on its \
+own, repeat it unchanged.
+
+Conversation:
+{{history}}
+
+Question: {{question}}
+Standalone question:";
+
+/// Rough token count for budgeting when the model's tokenizer isn't
+/// available: about four characters per token for English text.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// A prompt with `{{variable}}` placeholders.
+///
+/// `{{context}}` is special: `render_with_context` fills it with retrieved
+/// chunks, one `[id] text` passage each, and drops the lowest-ranked chunks
+/// until the whole prompt fits the token budget.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub text: String,
+    /// Upper bound on the rendered prompt's tokens; `None` packs every chunk.
+    pub token_budget: Option<usize>,
+}
+
+/// A rendered prompt and which chunks made it into the context.
+#[derive(Debug, Clone)]
+pub struct RenderedPrompt {
+    pub text: String,
+    pub included_ids: Vec<String>,
+    /// Chunks left out to stay within the token budget, best-ranked first.
+    pub dropped_ids: Vec<String>,
+    pub tokens: usize,
+}
+
+impl PromptTemplate {
+    pub fn new(name: &str, text: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            text: text.to_string(),
+            token_budget: None,
+        }
+    }
+
+    /// The built-in question-answering template; expects `question`.
+    pub fn answer() -> Self {
+        Self::new(ANSWER_TEMPLATE, DEFAULT_ANSWER_TEMPLATE)
+    }
+
+    /// The built-in structured extraction template; expects `question` and
+    /// `schema`.
+    pub fn extract() -> Self {
+        Self::new(EXTRACT_TEMPLATE, DEFAULT_EXTRACT_TEMPLATE)
+    }
+
+    /// The built-in template turning a follow-up into a standalone
+    /// question; expects `history` and `question`.
+    pub fn rewrite() -> Self {
+        Self::new(REWRITE_TEMPLATE, DEFAULT_REWRITE_TEMPLATE)
+    }
+
+    /// Load a template from a file, named after the file's stem.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| ChromaError::ConfigError(format!("{} is not a valid template name", path.display())))?;
+        let template = Self::new(name, &std::fs::read_to_string(path)?);
+        template.variables()?;
+        Ok(template)
+    }
+
+    pub fn with_token_budget(mut self, tokens: usize) -> Self {
+        self.token_budget = Some(tokens);
+        self
+    }
+
+    /// The distinct variables the template uses, in order of appearance.
+    pub fn variables(&self) -> Result<Vec<&str>> {
+        let mut variables = Vec::new();
+        for segment in self.segments()? {
+            if let Segment::Variable(name) = segment
+                && !variables.contains(&name)
+            {
+                variables.push(name);
+            }
+        }
+        Ok(variables)
+    }
+
+    /// Substitute `variables`; every variable the template uses must be
+    /// given.
+    pub fn render(&self, variables: &HashMap<&str, &str>) -> Result<String> {
+        let mut out = String::with_capacity(self.text.len());
+        for segment in self.segments()? {
+            match segment {
+                Segment::Text(text) => out.push_str(text),
+                Segment::Variable(name) => match variables.get(name) {
+                    Some(value) => out.push_str(value),
+                    None => {
+                        return Err(ChromaError::ValidationError(format!(
+                            "Prompt template '{}' needs a value for '{}'",
+                            self.name, name
+                        )));
+                    }
+                },
+            }
+        }
+        Ok(out)
+    }
+
+    /// Render with `hits` (in rank order) packed into `{{context}}`, counting
+    /// tokens with `count_tokens` (e.g. `estimate_tokens`). Chunks are
+    /// dropped from the bottom of the ranking until the prompt fits; it is an
+    /// error if even the prompt without context doesn't.
+    pub fn render_with_context(
+        &self,
+        variables: &HashMap<&str, &str>,
+        hits: &[QueryHit],
+        count_tokens: impl Fn(&str) -> usize,
+    ) -> Result<RenderedPrompt> {
+        let mut included = hits.len();
+        loop {
+            let context = pack_context(&hits[..included]);
+            let mut with_context = variables.clone();
+            with_context.insert(CONTEXT_VARIABLE, &context);
+            let text = self.render(&with_context)?;
+            let tokens = count_tokens(&text);
+
+            let fits = self.token_budget.is_none_or(|budget| tokens <= budget);
+            if fits || included == 0 {
+                if !fits {
+                    return Err(ChromaError::ValidationError(format!(
+                        "Prompt template '{}' needs {} tokens without any context, over the budget of {}",
+                        self.name,
+                        tokens,
+                        self.token_budget.unwrap_or_default()
+                    )));
+                }
+                let ids = |hits: &[QueryHit]| hits.iter().map(|hit| hit.id.clone()).collect();
+                return Ok(RenderedPrompt {
+                    text,
+                    included_ids: ids(&hits[..included]),
+                    dropped_ids: ids(&hits[included..]),
+                    tokens,
+                });
+            }
+            included -= 1;
+        }
+    }
+
+    fn segments(&self) -> Result<Vec<Segment<'_>>> {
+        let mut segments = Vec::new();
+        let mut rest = self.text.as_str();
+        while let Some(open) = rest.find("{{") {
+            segments.push(Segment::Text(&rest[..open]));
+            let after = &rest[open + 2..];
+            let close = after.find("}}").ok_or_else(|| {
+                ChromaError::ValidationError(format!("Prompt template '{}' has an unclosed '{{{{'", self.name))
+            })?;
+            let name = after[..close].trim();
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(ChromaError::ValidationError(format!(
+                    "Prompt template '{}' has an invalid variable '{}'",
+                    self.name,
+                    &after[..close]
+                )));
+            }
+            segments.push(Segment::Variable(name));
+            rest = &after[close + 2..];
+        }
+        segments.push(Segment::Text(rest));
+        Ok(segments)
+    }
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Variable(&'a str),
+}
+
+/// One `[id] text` passage per hit, separated by blank lines.
+fn pack_context(hits: &[QueryHit]) -> String {
+    let mut context = String::new();
+    for hit in hits {
+        if !context.is_empty() {
+            context.push_str("\n\n");
+        }
+        let _ = write!(context, "[{}] {}", hit.id, hit.document.as_deref().unwrap_or_default().trim());
+    }
+    context
+}
+
+/// Named prompt templates, so prompts can change without recompiling.
+/// Always holds the built-in `answer`, `extract` and `rewrite` templates
+/// unless loaded ones replace them.
+#[derive(Debug, Clone)]
+pub struct PromptLibrary {
+    templates: HashMap<String, PromptTemplate>,
+}
+
+impl Default for PromptLibrary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PromptLibrary {
+    pub fn new() -> Self {
+        Self {
+            templates: HashMap::from([
+                (ANSWER_TEMPLATE.to_string(), PromptTemplate::answer()),
+                (EXTRACT_TEMPLATE.to_string(), PromptTemplate::extract()),
+                (REWRITE_TEMPLATE.to_string(), PromptTemplate::rewrite()),
+            ]),
+        }
+    }
+
+    /// The built-in templates plus any in `PROMPT_TEMPLATES_DIR`.
+    pub fn from_env() -> Result<Self> {
+        match std::env::var(PROMPT_DIR_ENV_VAR) {
+            Ok(dir) => Self::new().load_dir(dir),
+            Err(_) => Ok(Self::new()),
+        }
+    }
+
+    /// Add every `*.prompt` file in `dir`, named after its file stem.
+    pub fn load_dir(mut self, dir: impl AsRef<Path>) -> Result<Self> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == PROMPT_EXTENSION) {
+                self = self.with_template(PromptTemplate::from_file(&path)?);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Add templates from a JSON object of `name: text` pairs, e.g. a config
+    /// file section.
+    pub fn load_json(mut self, json: &str) -> Result<Self> {
+        let templates: HashMap<String, String> = serde_json::from_str(json)?;
+        for (name, text) in templates {
+            let template = PromptTemplate::new(&name, &text);
+            template.variables()?;
+            self = self.with_template(template);
+        }
+        Ok(self)
+    }
+
+    pub fn with_template(mut self, template: PromptTemplate) -> Self {
+        self.templates.insert(template.name.clone(), template);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Result<&PromptTemplate> {
+        self.templates
+            .get(name)
+            .ok_or_else(|| ChromaError::ConfigError(format!("No prompt template named '{}'", name)))
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.templates.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_prompt_templates_pack_context_within_budget() {
+        let hit = |id: &str, text: &str| QueryHit {
+            id: id.to_string(),
+            document: Some(text.to_string()),
+            metadata: None,
+            distance: 0.1,
+            score: 0.9,
+            uri: None,
+        };
+        let hits = vec![hit("a", "Rust is memory safe."), hit("b", "Rust has no GC."), hit("c", "Cargo builds it.")];
+        let template = PromptTemplate::new("qa", "Context:\n{{ context }}\nQ: {{question}}");
+        assert_eq!(template.variables().unwrap(), vec!["context", "question"]);
+        assert!(matches!(template.render(&HashMap::new()), Err(ChromaError::ValidationError(_))));
+
+        let variables = HashMap::from([("question", "Is Rust safe?")]);
+        let words = |text: &str| text.split_whitespace().count();
+        let full = template.render_with_context(&variables, &hits, words).unwrap();
+        assert_eq!(full.included_ids, vec!["a", "b", "c"]);
+        assert!(full.text.contains("[b] Rust has no GC.\n\n[c] Cargo builds it."));
+
+        let packed = template.clone().with_token_budget(full.tokens - 1).render_with_context(&variables, &hits, words).unwrap();
+        assert_eq!(packed.included_ids, vec!["a", "b"]);
+        assert_eq!(packed.dropped_ids, vec!["c"]);
+        assert!(packed.tokens < full.tokens);
+        assert!(template.clone().with_token_budget(1).render_with_context(&variables, &hits, words).is_err());
+
+        let dir = std::env::temp_dir().join(format!("prompts-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("summary.prompt"), "Summarize:\n{{context}}").unwrap();
+        std::fs::write(dir.join("notes.txt"), "ignored").unwrap();
+        let library = PromptLibrary::new()
+            .load_dir(&dir)
+            .unwrap()
+            .load_json(r#"{"answer": "{{context}} -> {{question}}"}"#)
+            .unwrap();
+        assert_eq!(library.names(), vec!["answer", "extract", "rewrite", "summary"]);
+        assert_eq!(library.get("answer").unwrap().text, "{{context}} -> {{question}}");
+        assert!(PromptLibrary::new().load_json(r#"{"bad": "{{unclosed"}"#).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
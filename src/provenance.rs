@@ -0,0 +1,166 @@
+use crate::models::{Document, SOURCE_FIELD};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Metadata key naming what read a document in (`directory`, or `api` for
+/// documents handed to the pipeline directly).
+pub const LOADER_FIELD: &str = "provenance_loader";
+/// Metadata key holding where the document was read from.
+pub const SOURCE_URI_FIELD: &str = "provenance_source_uri";
+/// Metadata key holding the id of the ingestion run that wrote the record.
+pub const JOB_ID_FIELD: &str = "provenance_job_id";
+/// Metadata key holding when the record was written, as RFC 3339.
+pub const INGESTED_AT_FIELD: &str = "provenance_ingested_at";
+/// Metadata key holding the comma-separated transforms applied between
+/// loading and storing, in order.
+pub const TRANSFORMS_FIELD: &str = "provenance_transforms";
+
+const DEFAULT_LOADER: &str = "api";
+
+/// Where a stored record came from and how it got there, written by the
+/// pipeline into the `provenance_*` metadata of every record and read back
+/// with `QueryHit::provenance`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Provenance {
+    pub loader: String,
+    pub source_uri: Option<String>,
+    pub job_id: String,
+    pub ingested_at: Option<DateTime<Utc>>,
+    pub transforms: Vec<String>,
+}
+
+impl Provenance {
+    /// The provenance recorded in a hit's metadata, if it was written by a
+    /// pipeline that records it.
+    pub fn from_metadata(metadata: &Value) -> Option<Self> {
+        let field = |key: &str| metadata.get(key).and_then(Value::as_str);
+        Some(Self {
+            loader: field(LOADER_FIELD)?.to_string(),
+            source_uri: field(SOURCE_URI_FIELD).map(str::to_string),
+            job_id: field(JOB_ID_FIELD)?.to_string(),
+            ingested_at: field(INGESTED_AT_FIELD)
+                .and_then(|ts| DateTime::parse_from_rfc3339(ts).ok())
+                .map(|ts| ts.with_timezone(&Utc)),
+            transforms: field(TRANSFORMS_FIELD)
+                .map(|t| t.split(',').filter(|t| !t.is_empty()).map(str::to_string).collect())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// Id for one ingestion run; every record it writes carries it.
+pub fn new_job_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// Record which loader read `document` in and from where, for loaders
+/// outside this crate.
+pub fn set_origin(document: &mut Document, loader: &str, source_uri: &str) {
+    document.metadata.insert(LOADER_FIELD.to_string(), loader.to_string());
+    document.metadata.insert(SOURCE_URI_FIELD.to_string(), source_uri.to_string());
+}
+
+/// Append `transform` to the transforms applied to `document`. Commas are
+/// the separator, so they're replaced in `transform`.
+pub fn add_transform(document: &mut Document, transform: &str) {
+    let transform = transform.replace(',', ";");
+    document
+        .metadata
+        .entry(TRANSFORMS_FIELD.to_string())
+        .and_modify(|transforms| {
+            if !transforms.is_empty() {
+                transforms.push(',');
+            }
+            transforms.push_str(&transform);
+        })
+        .or_insert(transform);
+}
+
+/// Assign `job_id` to documents not already part of a job.
+pub(crate) fn assign_job(documents: &mut [Document], job_id: &str) {
+    for document in documents {
+        document.metadata.entry(JOB_ID_FIELD.to_string()).or_insert_with(|| job_id.to_string());
+    }
+}
+
+/// Fill in whatever provenance `document` doesn't carry yet and stamp the
+/// write time: loaders that didn't `set_origin` count as `api`, with the
+/// document's URI or `source` metadata as the source.
+pub(crate) fn stamp(document: &mut Document, job_id: &str, now: DateTime<Utc>) {
+    let source_uri = document.uri.clone().or_else(|| document.metadata.get(SOURCE_FIELD).cloned());
+    let metadata = &mut document.metadata;
+    metadata.entry(LOADER_FIELD.to_string()).or_insert_with(|| DEFAULT_LOADER.to_string());
+    if let Some(source_uri) = source_uri {
+        metadata.entry(SOURCE_URI_FIELD.to_string()).or_insert(source_uri);
+    }
+    metadata.entry(JOB_ID_FIELD.to_string()).or_insert_with(|| job_id.to_string());
+    metadata.entry(TRANSFORMS_FIELD.to_string()).or_default();
+    metadata.insert(INGESTED_AT_FIELD.to_string(), now.to_rfc3339());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunking::Chunker;
+    use crate::models::QueryHit;
+    use crate::pipeline::Pipeline;
+    use crate::test_support::{FixedEmbeddings, mock_chroma};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_pipeline_writes_provenance_surfaced_on_hits() {
+        use std::sync::Mutex;
+
+        let upserts: Arc<Mutex<Vec<serde_json::Value>>> = Arc::default();
+        let recorded = upserts.clone();
+        let chroma = mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            if path.ends_with("/upsert") {
+                let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                recorded.lock().unwrap().push(body["metadatas"].clone());
+                "true".to_string()
+            } else if path.ends_with("/get") {
+                r#"{"ids": []}"#.to_string()
+            } else if path.ends_with("/delete") {
+                "true".to_string()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+        let pipeline = Pipeline::new(Arc::new(chroma), Arc::new(FixedEmbeddings), "docs");
+
+        let mut loaded = Document::builder().id("faq").content("alpha beta gamma delta epsilon zeta eta theta").build();
+        set_origin(&mut loaded, "crawler", "https://example.com/faq");
+        add_transform(&mut loaded, "strip_html");
+        let chunker = Chunker::new(20, 6);
+        pipeline.ingest_chunked(vec![loaded], &chunker).await.unwrap();
+        let note = Document::builder().id("note").content("plain").meta("source", "notes.md").build();
+        pipeline.ingest(vec![note]).await.unwrap();
+
+        let upserts = upserts.lock().unwrap().clone();
+        let hit = |metadata: &serde_json::Value| QueryHit {
+            id: "hit".to_string(),
+            document: None,
+            metadata: Some(metadata.clone()),
+            distance: 0.1,
+            score: 0.9,
+            uri: None,
+        };
+        let chunks: Vec<Provenance> =
+            upserts[0].as_array().unwrap().iter().map(|m| hit(m).provenance().unwrap()).collect();
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|p| p.job_id == chunks[0].job_id && p.ingested_at.is_some()));
+        assert_eq!(chunks[0].loader, "crawler");
+        assert_eq!(chunks[0].source_uri.as_deref(), Some("https://example.com/faq"));
+        assert_eq!(chunks[0].transforms, vec!["strip_html".to_string(), format!("chunk:{}", chunker.config_hash())]);
+
+        let note = hit(&upserts[1][0]).provenance().unwrap();
+        assert_eq!((note.loader.as_str(), note.source_uri.as_deref()), ("api", Some("notes.md")));
+        assert!(note.transforms.is_empty());
+        assert_ne!(note.job_id, chunks[0].job_id);
+        assert!(hit(&serde_json::json!({ "source": "old.md" })).provenance().is_none());
+    }
+}
@@ -0,0 +1,637 @@
+use crate::acl;
+use crate::models::{DistanceSpace, Include, QueryHit, QueryRequest};
+use crate::score::Score;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+const DEFAULT_OVER_FETCH: u32 = 3;
+const DEFAULT_TIMESTAMP_FIELD: &str = "timestamp";
+const DEFAULT_RECENCY_WEIGHT: f32 = 0.3;
+
+/// Callback computing an adjusted, higher-is-better score from a candidate's
+/// `(distance, metadata, document)`.
+pub type ScoreFn = Arc<dyn Fn(f32, Option<&Value>, Option<&str>) -> f32 + Send + Sync>;
+
+/// Options for `ChromaClient::query_with_options`.
+///
+/// When client-side re-ranking is enabled, `n_results * over_fetch` candidates
+/// are requested from Chroma, re-scored locally and truncated back to `n_results`.
+/// A custom `score_fn` replaces the default `1 - distance` score and runs before
+/// any recency boost is blended in.
+///
+/// Chroma's `where` clause only matches metadata, so `not_ids` are removed
+/// client-side; the candidate window grows by `not_ids.len()` to compensate.
+///
+/// With `group_by_parent`, chunks of the same logical document collapse into
+/// their best-scoring hit, and candidates are over-fetched so `n_results`
+/// distinct documents usually remain. `collapse_overlapping` is the gentler
+/// version: only chunks whose text overlaps a better hit from the same parent
+/// are dropped, so distinct passages of one document can still appear.
+/// `max_per_source` caps hits per `source` (see `QueryHit::source`) so
+/// answers draw on several documents; the best hits of each source are kept.
+///
+/// `acl_groups` restricts results to documents labelled for one of the
+/// caller's groups (see `acl::set_acl`), both in the `where` clause and
+/// again on the returned hits.
+#[derive(Clone)]
+pub struct QueryOptions {
+    pub n_results: u32,
+    pub where_filter: Option<Value>,
+    pub not_ids: HashSet<String>,
+    pub recency: Option<RecencyBoost>,
+    pub score_fn: Option<ScoreFn>,
+    pub over_fetch: u32,
+    /// Fields to request; `None` uses Chroma's default set.
+    pub include: Option<Vec<Include>>,
+    pub group_by_parent: bool,
+    pub collapse_overlapping: bool,
+    pub max_per_source: Option<u32>,
+    /// Report how the query was built and narrowed down (see `QueryExplain`).
+    pub explain: bool,
+    /// The collection's `hnsw:space`, for interpreting `min_score`.
+    pub space: DistanceSpace,
+    /// Drop candidates whose raw distance is worse than this, before any
+    /// re-scoring. May be given in another metric; see `Score`.
+    pub min_score: Option<Score>,
+    /// The caller's groups; `None` skips access control.
+    pub acl_groups: Option<Vec<String>>,
+}
+
+impl fmt::Debug for QueryOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueryOptions")
+            .field("n_results", &self.n_results)
+            .field("where_filter", &self.where_filter)
+            .field("not_ids", &self.not_ids)
+            .field("recency", &self.recency)
+            .field("score_fn", &self.score_fn.as_ref().map(|_| "<fn>"))
+            .field("over_fetch", &self.over_fetch)
+            .field("include", &self.include)
+            .field("group_by_parent", &self.group_by_parent)
+            .field("collapse_overlapping", &self.collapse_overlapping)
+            .field("max_per_source", &self.max_per_source)
+            .field("explain", &self.explain)
+            .field("space", &self.space)
+            .field("min_score", &self.min_score)
+            .field("acl_groups", &self.acl_groups)
+            .finish()
+    }
+}
+
+impl QueryOptions {
+    pub fn new(n_results: u32) -> Self {
+        Self {
+            n_results,
+            where_filter: None,
+            not_ids: HashSet::new(),
+            recency: None,
+            score_fn: None,
+            over_fetch: DEFAULT_OVER_FETCH,
+            include: None,
+            group_by_parent: false,
+            collapse_overlapping: false,
+            max_per_source: None,
+            explain: false,
+            space: DistanceSpace::Cosine,
+            min_score: None,
+            acl_groups: None,
+        }
+    }
+
+    pub fn with_filter(mut self, where_filter: Value) -> Self {
+        self.where_filter = Some(where_filter);
+        self
+    }
+
+    pub fn with_not_ids<I, S>(mut self, ids: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.not_ids.extend(ids.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_recency(mut self, recency: RecencyBoost) -> Self {
+        self.recency = Some(recency);
+        self
+    }
+
+    pub fn with_score_fn<F>(mut self, score_fn: F) -> Self
+    where
+        F: Fn(f32, Option<&Value>, Option<&str>) -> f32 + Send + Sync + 'static,
+    {
+        self.score_fn = Some(Arc::new(score_fn));
+        self
+    }
+
+    pub fn with_over_fetch(mut self, over_fetch: u32) -> Self {
+        self.over_fetch = over_fetch.max(1);
+        self
+    }
+
+    pub fn with_include(mut self, include: Vec<Include>) -> Self {
+        self.include = Some(include);
+        self
+    }
+
+    /// Return at most one hit (the best chunk) per logical document.
+    pub fn with_group_by_parent(mut self) -> Self {
+        self.group_by_parent = true;
+        self
+    }
+
+    /// Drop chunks overlapping a better-scoring chunk of the same parent
+    /// (see `QueryHit::overlaps`), freeing their slots for other sources.
+    pub fn with_collapse_overlapping(mut self) -> Self {
+        self.collapse_overlapping = true;
+        self
+    }
+
+    /// Return at most `limit` hits from any one source.
+    pub fn with_max_per_source(mut self, limit: u32) -> Self {
+        self.max_per_source = Some(limit.max(1));
+        self
+    }
+
+    /// Return a `QueryExplain` with the results of `Pipeline::query`.
+    pub fn with_explain(mut self) -> Self {
+        self.explain = true;
+        self
+    }
+
+    pub fn with_space(mut self, space: DistanceSpace) -> Self {
+        self.space = space;
+        self
+    }
+
+    /// Only return hits at least as good as `threshold`, e.g.
+    /// `Score::similarity(DistanceSpace::Cosine, 0.75)`.
+    pub fn with_min_score(mut self, threshold: Score) -> Self {
+        self.min_score = Some(threshold);
+        self
+    }
+
+    /// Only return documents whose `acl` lists one of `groups`.
+    pub fn with_acl_groups<I, S>(mut self, groups: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.acl_groups = Some(groups.into_iter().map(Into::into).collect());
+        self
+    }
+
+    fn needs_rerank(&self) -> bool {
+        self.recency.is_some()
+            || self.score_fn.is_some()
+            || self.group_by_parent
+            || self.collapse_overlapping
+            || self.max_per_source.is_some()
+    }
+
+    /// Number of candidates to request from Chroma.
+    pub(crate) fn candidate_count(&self) -> u32 {
+        let base = if self.needs_rerank() {
+            self.n_results.saturating_mul(self.over_fetch)
+        } else {
+            self.n_results
+        };
+        base.saturating_add(self.not_ids.len() as u32)
+    }
+
+    /// `include` with metadatas added, for hits that are checked against
+    /// their metadata after retrieval. `None` stays `None`: Chroma's
+    /// default set already has them.
+    pub(crate) fn include_with_metadatas(&self) -> Option<Vec<Include>> {
+        let mut include = self.include.clone()?;
+        if !include.contains(&Include::Metadatas) {
+            include.push(Include::Metadatas);
+        }
+        Some(include)
+    }
+
+    /// The `where` clause sent to Chroma: `where_filter` plus the ACL
+    /// restriction, if any.
+    pub(crate) fn request_filter(&self) -> Option<Value> {
+        match &self.acl_groups {
+            Some(groups) => Some(acl::acl_filter(groups, self.where_filter.clone())),
+            None => self.where_filter.clone(),
+        }
+    }
+
+    /// The Chroma request fetching the candidate set for `query_embedding`.
+    pub(crate) fn to_request(&self, query_embedding: Vec<f32>) -> QueryRequest {
+        QueryRequest {
+            query_embeddings: vec![query_embedding],
+            n_results: self.candidate_count(),
+            where_filter: self.request_filter(),
+            // `rerank` checks hits against their `acl` metadata.
+            include: match &self.acl_groups {
+                Some(_) => self.include_with_metadatas(),
+                None => self.include.clone(),
+            },
+        }
+    }
+
+    /// Re-score the candidate set and truncate it to `n_results`.
+    pub(crate) fn rerank(&self, mut hits: Vec<QueryHit>) -> Vec<QueryHit> {
+        if let Some(groups) = &self.acl_groups {
+            let before = hits.len();
+            hits.retain(|hit| acl::is_allowed(hit.metadata.as_ref(), groups));
+            if hits.len() != before {
+                warn!("Dropped {} query hit(s) not readable by groups {:?}", before - hits.len(), groups);
+            }
+        }
+        if !self.not_ids.is_empty() {
+            hits.retain(|hit| !self.not_ids.contains(&hit.id));
+        }
+        if let Some(threshold) = &self.min_score {
+            hits.retain(|hit| hit.distance_score(self.space).meets(threshold));
+        }
+
+        if let Some(score_fn) = &self.score_fn {
+            for hit in hits.iter_mut() {
+                hit.score = score_fn(hit.distance, hit.metadata.as_ref(), hit.document.as_deref());
+            }
+        }
+
+        if let Some(recency) = &self.recency {
+            recency.apply(&mut hits, Utc::now());
+        }
+
+        hits.sort_by(QueryHit::rank_cmp);
+        if self.group_by_parent {
+            let mut parents = HashSet::new();
+            hits.retain(|hit| parents.insert(hit.parent_id().to_string()));
+        }
+        if self.collapse_overlapping {
+            let mut kept: Vec<QueryHit> = Vec::with_capacity(hits.len());
+            for hit in hits {
+                if !kept.iter().any(|better| better.overlaps(&hit)) {
+                    kept.push(hit);
+                }
+            }
+            hits = kept;
+        }
+        if let Some(limit) = self.max_per_source {
+            let mut per_source: HashMap<String, u32> = HashMap::new();
+            hits.retain(|hit| {
+                let count = per_source.entry(hit.source().to_string()).or_default();
+                *count += 1;
+                *count <= limit
+            });
+        }
+        hits.truncate(self.n_results as usize);
+        hits
+    }
+}
+
+/// How a query was executed, for debugging why a document did or didn't
+/// match: what was sent to Chroma, how many candidates came back and how
+/// many survived client-side re-ranking.
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryExplain {
+    /// The `where` filter exactly as sent, after any scoping was applied.
+    pub where_filter: Option<Value>,
+    pub include: Option<Vec<Include>>,
+    pub embedding_model: Option<String>,
+    pub embedding_task_type: Option<String>,
+    pub n_results: u32,
+    /// `n_results` widened by over-fetching and excluded ids.
+    pub candidates_requested: u32,
+    pub candidates_returned: usize,
+    pub results_returned: usize,
+    pub over_fetch: u32,
+    pub excluded_ids: usize,
+    pub recency: Option<RecencyExplain>,
+    pub custom_score_fn: bool,
+    pub group_by_parent: bool,
+    pub collapse_overlapping: bool,
+    pub max_per_source: Option<u32>,
+}
+
+/// The recency boost settings a query ran with.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecencyExplain {
+    pub field: String,
+    pub half_life_secs: f64,
+    pub weight: f32,
+}
+
+impl QueryExplain {
+    /// Explain a query that sent `request` and got `candidates_returned`
+    /// hits back, of which `results_returned` were kept.
+    pub fn new(options: &QueryOptions, request: &QueryRequest, candidates_returned: usize, results_returned: usize) -> Self {
+        Self {
+            where_filter: request.where_filter.clone(),
+            include: request.include.clone(),
+            embedding_model: None,
+            embedding_task_type: None,
+            n_results: options.n_results,
+            candidates_requested: request.n_results,
+            candidates_returned,
+            results_returned,
+            over_fetch: if options.needs_rerank() { options.over_fetch } else { 1 },
+            excluded_ids: options.not_ids.len(),
+            recency: options.recency.as_ref().map(|r| RecencyExplain {
+                field: r.field.clone(),
+                half_life_secs: r.half_life.as_secs_f64(),
+                weight: r.weight,
+            }),
+            custom_score_fn: options.score_fn.is_some(),
+            group_by_parent: options.group_by_parent,
+            collapse_overlapping: options.collapse_overlapping,
+            max_per_source: options.max_per_source,
+        }
+    }
+}
+
+/// Opaque "next page" token for `ChromaClient::query_paginated`.
+///
+/// Chroma has no offset for similarity queries, so each page re-queries with a
+/// larger window and drops the ids already handed out. Excluding by id rather
+/// than by position keeps pages stable when documents are inserted between calls.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueryCursor {
+    pub seen_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryPage {
+    pub hits: Vec<QueryHit>,
+    pub next_cursor: Option<QueryCursor>,
+}
+
+impl QueryCursor {
+    /// Window size to request so that a full page plus one look-ahead hit
+    /// remains after removing already-seen ids.
+    pub(crate) fn window(&self, page_size: u32) -> u32 {
+        page_size
+            .saturating_add(self.seen_ids.len() as u32)
+            .saturating_add(1)
+    }
+
+    /// Cut the next page out of a ranked window of hits.
+    pub(crate) fn next_page(&self, window: Vec<QueryHit>, page_size: u32) -> QueryPage {
+        let seen: HashSet<&str> = self.seen_ids.iter().map(String::as_str).collect();
+        let mut unseen = window.into_iter().filter(|hit| !seen.contains(hit.id.as_str()));
+
+        let hits: Vec<QueryHit> = unseen.by_ref().take(page_size as usize).collect();
+        let has_more = unseen.next().is_some();
+
+        let next_cursor = if has_more && !hits.is_empty() {
+            let mut seen_ids = self.seen_ids.clone();
+            seen_ids.extend(hits.iter().map(|hit| hit.id.clone()));
+            Some(QueryCursor { seen_ids })
+        } else {
+            None
+        };
+
+        QueryPage { hits, next_cursor }
+    }
+}
+
+/// Exponential time-decay boost over a timestamp metadata field.
+///
+/// The final score is `(1 - weight) * similarity + weight * 0.5^(age / half_life)`.
+/// Timestamps may be stored as unix seconds (number or numeric string) or RFC 3339
+/// strings; hits without a parseable timestamp get no boost.
+#[derive(Debug, Clone)]
+pub struct RecencyBoost {
+    pub field: String,
+    pub half_life: Duration,
+    pub weight: f32,
+}
+
+impl RecencyBoost {
+    pub fn new(half_life: Duration) -> Self {
+        Self {
+            field: DEFAULT_TIMESTAMP_FIELD.to_string(),
+            half_life,
+            weight: DEFAULT_RECENCY_WEIGHT,
+        }
+    }
+
+    pub fn with_field(mut self, field: &str) -> Self {
+        self.field = field.to_string();
+        self
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Decay factor in `[0, 1]` for a document timestamped `timestamp`.
+    pub fn decay(&self, timestamp: DateTime<Utc>, now: DateTime<Utc>) -> f32 {
+        let half_life = self.half_life.as_secs_f64();
+        if half_life <= 0.0 {
+            return 0.0;
+        }
+
+        // Documents from the future are treated as brand new
+        let age = (now - timestamp).num_milliseconds().max(0) as f64 / 1000.0;
+        0.5_f64.powf(age / half_life) as f32
+    }
+
+    pub(crate) fn apply(&self, hits: &mut [QueryHit], now: DateTime<Utc>) {
+        for hit in hits.iter_mut() {
+            let decay = hit
+                .metadata
+                .as_ref()
+                .and_then(|m| m.get(&self.field))
+                .and_then(parse_timestamp)
+                .map(|ts| self.decay(ts, now))
+                .unwrap_or(0.0);
+
+            hit.score = (1.0 - self.weight) * hit.score + self.weight * decay;
+        }
+    }
+}
+
+fn parse_timestamp(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::Number(n) => n.as_f64().and_then(from_unix_seconds),
+        Value::String(s) => s
+            .parse::<f64>()
+            .ok()
+            .and_then(from_unix_seconds)
+            .or_else(|| {
+                DateTime::parse_from_rfc3339(s)
+                    .ok()
+                    .map(|dt| dt.with_timezone(&Utc))
+            }),
+        _ => None,
+    }
+}
+
+fn from_unix_seconds(secs: f64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp_millis((secs * 1000.0) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::Pipeline;
+    use crate::test_support::{FixedEmbeddings, mock_chroma};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_recency_boost_prefers_fresh_documents() {
+        let now = chrono::Utc::now();
+        let hit = |id: &str, distance: f32, age_days: i64| QueryHit {
+            id: id.to_string(),
+            document: None,
+            metadata: Some(serde_json::json!({
+                "timestamp": (now - chrono::Duration::days(age_days)).to_rfc3339()
+            })),
+            distance,
+            score: 1.0 - distance,
+            uri: None,
+        };
+
+        let options = QueryOptions::new(1).with_recency(
+            RecencyBoost::new(std::time::Duration::from_secs(7 * 24 * 3600)).with_weight(0.5),
+        );
+        assert_eq!(options.candidate_count(), 3);
+
+        let hits = options.rerank(vec![hit("stale", 0.10, 365), hit("fresh", 0.20, 0)]);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, "fresh");
+    }
+
+    #[test]
+    fn test_score_fn_applies_business_rules() {
+        let hit = |id: &str, distance: f32, source: &str| QueryHit {
+            id: id.to_string(),
+            document: None,
+            metadata: Some(serde_json::json!({ "source": source })),
+            distance,
+            score: 1.0 - distance,
+            uri: None,
+        };
+
+        let options = QueryOptions::new(2).with_score_fn(|distance, metadata, _document| {
+            let penalty = match metadata.and_then(|m| m["source"].as_str()) {
+                Some("forum") => 0.5,
+                _ => 0.0,
+            };
+            1.0 - distance - penalty
+        });
+
+        let hits = options.rerank(vec![
+            hit("forum-post", 0.05, "forum"),
+            hit("manual", 0.30, "docs"),
+            hit("faq", 0.40, "docs"),
+        ]);
+        let ids: Vec<&str> = hits.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, vec!["manual", "faq"]);
+    }
+
+    #[test]
+    fn test_query_cursor_pages_skip_seen_ids() {
+        let window = |ids: &[&str]| -> Vec<QueryHit> {
+            ids.iter()
+                .map(|id| QueryHit {
+                    id: id.to_string(),
+                    document: None,
+                    metadata: None,
+                    distance: 0.0,
+                    score: 1.0,
+                    uri: None,
+                })
+                .collect()
+        };
+
+        let first = QueryCursor::default().next_page(window(&["a", "b", "c"]), 2);
+        assert_eq!(first.hits.len(), 2);
+        let cursor = first.next_cursor.expect("more results available");
+        assert_eq!(cursor.window(2), 5);
+
+        // "new" was inserted between calls and must not shift the page
+        let second = cursor.next_page(window(&["a", "new", "b", "c"]), 2);
+        let ids: Vec<&str> = second.hits.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, vec!["new", "c"]);
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_not_ids_are_excluded_from_results() {
+        let hits: Vec<QueryHit> = ["rejected", "kept", "also-kept"]
+            .iter()
+            .enumerate()
+            .map(|(i, id)| QueryHit {
+                id: id.to_string(),
+                document: None,
+                metadata: None,
+                distance: i as f32 * 0.1,
+                score: 1.0 - i as f32 * 0.1,
+                uri: None,
+            })
+            .collect();
+
+        let options = QueryOptions::new(2).with_not_ids(["rejected"]);
+        assert_eq!(options.candidate_count(), 3);
+
+        let ids: Vec<String> = options.rerank(hits).into_iter().map(|h| h.id).collect();
+        assert_eq!(ids, vec!["kept", "also-kept"]);
+    }
+
+    #[tokio::test]
+    async fn test_query_explain_reports_request_and_candidate_counts() {
+        let chroma = mock_chroma(|request| {
+            if request.uri().path().ends_with("/query") {
+                r#"{"ids": [["a", "b", "c"]], "distances": [[0.1, 0.2, 0.3]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+        let pipeline = Pipeline::new(Arc::new(chroma), Arc::new(FixedEmbeddings), "docs");
+
+        let options = QueryOptions::new(1).with_filter(serde_json::json!({"lang": "en"})).with_not_ids(["a"]);
+        assert!(pipeline.query("hello", &options).await.unwrap().explain.is_none());
+
+        let result = pipeline.query("hello", &options.with_explain()).await.unwrap();
+        let explain = result.explain.unwrap();
+        assert_eq!(explain.where_filter, Some(serde_json::json!({"lang": "en"})));
+        assert_eq!(explain.embedding_model.as_deref(), Some("fixed"));
+        assert_eq!((explain.candidates_requested, explain.candidates_returned, explain.results_returned), (2, 3, 1));
+        assert_eq!(explain.excluded_ids, 1);
+        assert_eq!(result.hits[0].id, "b");
+    }
+
+    #[test]
+    fn test_max_per_source_spreads_results_across_documents() {
+        let hit = |id: &str, metadata: serde_json::Value, distance: f32| QueryHit {
+            id: id.to_string(),
+            document: None,
+            metadata: Some(metadata),
+            distance,
+            score: 1.0 - distance,
+            uri: None,
+        };
+        let hits = vec![
+            hit("a1", serde_json::json!({ "source": "a.md" }), 0.1),
+            hit("a2", serde_json::json!({ "source": "a.md" }), 0.2),
+            hit("a3", serde_json::json!({ "source": "a.md" }), 0.3),
+            hit("b#0", serde_json::json!({ "parent_id": "b" }), 0.4),
+            hit("b#1", serde_json::json!({ "parent_id": "b" }), 0.5),
+            hit("c", serde_json::json!({}), 0.6),
+        ];
+
+        let options = QueryOptions::new(4).with_max_per_source(1);
+        assert_eq!(options.candidate_count(), 12);
+        let ids: Vec<String> = options.rerank(hits.clone()).into_iter().map(|h| h.id).collect();
+        assert_eq!(ids, vec!["a1", "b#0", "c"]);
+
+        let ids: Vec<String> = QueryOptions::new(4).with_max_per_source(2).rerank(hits).into_iter().map(|h| h.id).collect();
+        assert_eq!(ids, vec!["a1", "a2", "b#0", "b#1"]);
+    }
+}
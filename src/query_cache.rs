@@ -0,0 +1,192 @@
+use crate::models::QueryResponse;
+use bytes::Bytes;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long `ChromaClient` serves a cached query response (see
+/// `ChromaClient::with_query_cache`).
+///
+/// For `ttl` after it was fetched a response is served as is. For the
+/// following `stale_while_revalidate` it is still served immediately, but
+/// the first such hit refreshes it in the background, so callers don't wait
+/// for Chroma while results are only slightly out of date. After both have
+/// passed, the next query waits for a fresh response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryCachePolicy {
+    pub ttl: Duration,
+    pub stale_while_revalidate: Duration,
+}
+
+impl QueryCachePolicy {
+    /// Serve responses for `ttl`, with no stale window.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            stale_while_revalidate: Duration::ZERO,
+        }
+    }
+
+    pub fn with_stale_while_revalidate(mut self, window: Duration) -> Self {
+        self.stale_while_revalidate = window;
+        self
+    }
+}
+
+/// Cache key: the collection's query URL and the exact request body.
+pub(crate) type QueryKey = (String, Bytes);
+
+/// Query responses cached by `ChromaClient`, evicting the oldest beyond
+/// `capacity`. Responses are stored as Chroma sent them, before field
+/// decryption.
+#[derive(Debug)]
+pub(crate) struct QueryCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    responses: HashMap<QueryKey, CachedQuery>,
+    order: VecDeque<QueryKey>,
+    /// Bumped by every invalidation, so a response fetched before a write
+    /// isn't stored after it.
+    generations: HashMap<String, u64>,
+}
+
+#[derive(Debug)]
+struct CachedQuery {
+    collection: String,
+    response: QueryResponse,
+    fetched_at: Instant,
+    refreshing: bool,
+}
+
+/// What `send_query` should do for a key.
+pub(crate) enum Cached {
+    Fresh(QueryResponse),
+    /// Serve this, and refresh it in the background if `refresh` is set;
+    /// only the first stale hit is asked to.
+    Stale { response: QueryResponse, refresh: bool },
+    Miss,
+}
+
+impl QueryCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    pub(crate) fn lookup(&self, key: &QueryKey, policy: &QueryCachePolicy) -> Cached {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(entry) = entries.responses.get_mut(key) else {
+            return Cached::Miss;
+        };
+        let age = entry.fetched_at.elapsed();
+        if age < policy.ttl {
+            Cached::Fresh(entry.response.clone())
+        } else if age < policy.ttl + policy.stale_while_revalidate {
+            let refresh = !entry.refreshing;
+            entry.refreshing = true;
+            Cached::Stale { response: entry.response.clone(), refresh }
+        } else {
+            Cached::Miss
+        }
+    }
+
+    /// Pass to `store` along with the response fetched after calling this.
+    pub(crate) fn generation(&self, collection: &str) -> u64 {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.generations.get(collection).copied().unwrap_or_default()
+    }
+
+    pub(crate) fn store(&self, collection: &str, generation: u64, key: QueryKey, response: QueryResponse) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.generations.get(collection).copied().unwrap_or_default() != generation {
+            return;
+        }
+        let entry = CachedQuery {
+            collection: collection.to_string(),
+            response,
+            fetched_at: Instant::now(),
+            refreshing: false,
+        };
+        if entries.responses.insert(key.clone(), entry).is_none() {
+            entries.order.push_back(key);
+        }
+        while entries.order.len() > self.capacity {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.responses.remove(&oldest);
+            }
+        }
+    }
+
+    /// Let the next stale hit on `key` try again after a failed refresh.
+    pub(crate) fn refresh_failed(&self, key: &QueryKey) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = entries.responses.get_mut(key) {
+            entry.refreshing = false;
+        }
+    }
+
+    /// Drop every response from `collection`, after this client wrote to it.
+    pub(crate) fn invalidate(&self, collection: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        *entries.generations.entry(collection.to_string()).or_default() += 1;
+        entries.responses.retain(|_, entry| entry.collection != collection);
+        let Entries { responses, order, .. } = &mut *entries;
+        order.retain(|key| responses.contains_key(key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_query_cache_serves_stale_results_while_refreshing() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let queries = Arc::new(AtomicUsize::new(0));
+        let counter = queries.clone();
+        // Everything is stale at once, but usable for a minute.
+        let policy = QueryCachePolicy::new(std::time::Duration::ZERO)
+            .with_stale_while_revalidate(std::time::Duration::from_secs(60));
+        let chroma = mock_chroma(move |request| {
+            if request.uri().path().ends_with("/query") {
+                let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                format!(r#"{{"ids": [["r{}"]], "distances": [[0.1]]}}"#, n)
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        })
+        .with_query_cache(100, policy);
+        let query = || chroma.query("docs", vec![vec![0.1]], 1);
+
+        assert_eq!(query().await.unwrap().ids[0], vec!["r1"]);
+        // Stale hit: served from the cache, refreshed in the background.
+        assert_eq!(query().await.unwrap().ids[0], vec!["r1"]);
+        for _ in 0..100 {
+            if queries.load(Ordering::SeqCst) == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert_eq!(query().await.unwrap().ids[0], vec!["r2"]);
+
+        // A write through this client drops the collection's entries.
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        chroma.delete_documents("docs", vec!["r2".to_string()]).await.unwrap();
+        let before = queries.load(Ordering::SeqCst);
+        assert_eq!(query().await.unwrap().ids[0], vec![format!("r{}", before + 1)]);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::models::{AddRequest, QueryResponse};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Chroma stores each dimension as an `f32`.
+const BYTES_PER_DIMENSION: u64 = 4;
+
+/// Documents sampled to estimate the size of a collection this client
+/// hasn't written to yet.
+pub(crate) const USAGE_SAMPLE: u32 = 100;
+
+/// Per-collection limits checked by `ChromaClient` before each add, upsert
+/// or update, so one tenant can't fill up a shared Chroma instance. `None`
+/// is unlimited.
+///
+/// Quotas are soft: usage is estimated from document text and vector sizes,
+/// concurrent writers may overshoot a limit by one batch, and writes by
+/// other processes only count once the usage is re-read (after a delete, or
+/// in a new process).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quota {
+    pub max_documents: Option<u64>,
+    pub max_bytes: Option<u64>,
+}
+
+impl Quota {
+    pub fn with_max_documents(mut self, max_documents: u64) -> Self {
+        self.max_documents = Some(max_documents);
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_documents.is_none() && self.max_bytes.is_none()
+    }
+
+    /// Why adding `added` to `usage` would break this quota, if it would.
+    pub(crate) fn violation(&self, usage: Usage, added: Usage) -> Option<String> {
+        let documents = usage.documents + added.documents;
+        let bytes = usage.bytes + added.bytes;
+        if let Some(max) = self.max_documents
+            && documents > max
+        {
+            return Some(format!("{} documents, over the limit of {}", documents, max));
+        }
+        if let Some(max) = self.max_bytes
+            && bytes > max
+        {
+            return Some(format!("~{} bytes, over the limit of {}", bytes, max));
+        }
+        None
+    }
+}
+
+/// Estimated contents of a collection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    pub documents: u64,
+    pub bytes: u64,
+}
+
+impl Usage {
+    /// Estimated stored size of the records in `request`.
+    pub(crate) fn of_request(request: &AddRequest) -> Self {
+        let bytes = (0..request.ids.len())
+            .map(|i| {
+                let text = request.documents.get(i).map_or(0, String::len) as u64;
+                let vector = request.embeddings.get(i).map_or(0, Vec::len) as u64 * BYTES_PER_DIMENSION;
+                text + vector
+            })
+            .sum();
+        Self {
+            documents: request.ids.len() as u64,
+            bytes,
+        }
+    }
+
+    /// `documents` records whose size is extrapolated from `sample`, a get
+    /// response including documents and embeddings.
+    pub(crate) fn estimate(documents: u64, sample: &QueryResponse) -> Self {
+        let texts = sample.documents.as_ref().and_then(|d| d.first());
+        let vectors = sample.embeddings.as_ref().and_then(|e| e.first());
+        let sampled = sample.ids.first().map_or(0, Vec::len) as u64;
+        if sampled == 0 {
+            return Self { documents, bytes: 0 };
+        }
+        let text_bytes: u64 = texts.map_or(0, |t| t.iter().flatten().map(|t| t.len() as u64).sum());
+        let vector_bytes: u64 = vectors.map_or(0, |v| v.iter().map(|v| v.len() as u64 * BYTES_PER_DIMENSION).sum());
+        Self {
+            documents,
+            bytes: (text_bytes + vector_bytes) * documents / sampled,
+        }
+    }
+}
+
+/// A collection's quota and estimated usage, as reported by
+/// `ChromaClient::quota_usage`.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct QuotaUsage {
+    pub collection: String,
+    pub documents: u64,
+    pub bytes: u64,
+    pub max_documents: Option<u64>,
+    pub max_bytes: Option<u64>,
+}
+
+/// Usage of every collection this client has checked a quota for.
+#[derive(Debug, Default)]
+pub(crate) struct QuotaTracker {
+    usage: Mutex<HashMap<String, Usage>>,
+}
+
+impl QuotaTracker {
+    pub(crate) fn get(&self, collection: &str) -> Option<Usage> {
+        self.usage.lock().unwrap_or_else(|e| e.into_inner()).get(collection).copied()
+    }
+
+    pub(crate) fn set(&self, collection: &str, usage: Usage) {
+        self.usage.lock().unwrap_or_else(|e| e.into_inner()).insert(collection.to_string(), usage);
+    }
+
+    pub(crate) fn add(&self, collection: &str, added: Usage) {
+        let mut usage = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(current) = usage.get_mut(collection) {
+            current.documents += added.documents;
+            current.bytes += added.bytes;
+        }
+    }
+
+    /// Re-read `collection`'s usage from Chroma next time it's needed.
+    pub(crate) fn forget(&self, collection: &str) {
+        self.usage.lock().unwrap_or_else(|e| e.into_inner()).remove(collection);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ChromaError;
+    use crate::models::Document;
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_quota_rejects_writes_over_the_limit() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let adds = Arc::new(AtomicUsize::new(0));
+        let counter = adds.clone();
+        let chroma = mock_chroma(move |request| {
+            let path = request.uri().path();
+            if path.ends_with("/count") {
+                "2"
+            } else if path.ends_with("/get") {
+                r#"{"ids": ["a", "b"], "documents": ["abcd", "efgh"], "embeddings": [[0.1, 0.2], [0.3, 0.4]]}"#
+            } else if path.ends_with("/add") {
+                counter.fetch_add(1, Ordering::SeqCst);
+                "true"
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        })
+        .with_quota(Quota::default().with_max_bytes(1_000))
+        .with_collection_quota("docs", Quota::default().with_max_documents(3));
+        let doc = |id: &str| Document::builder().id(id).content("wxyz").build();
+
+        // Seeded from Chroma: 2 documents of 4 text bytes and 2 dimensions.
+        let usage = chroma.quota_usage("docs").await.unwrap();
+        assert_eq!((usage.documents, usage.bytes, usage.max_documents), (2, 24, Some(3)));
+
+        chroma.add_documents("docs", vec![doc("c")], vec![vec![0.5, 0.6]]).await.unwrap();
+        assert_eq!(chroma.quota_usage("docs").await.unwrap().documents, 3);
+
+        let error = chroma.add_documents("docs", vec![doc("d")], vec![vec![0.7, 0.8]]).await.unwrap_err();
+        assert!(matches!(error, ChromaError::QuotaExceeded(_)), "{}", error);
+        assert!(error.to_string().contains("4 documents, over the limit of 3"), "{}", error);
+        assert_eq!(adds.load(Ordering::SeqCst), 1);
+
+        // Other collections get the client-wide quota.
+        let error = chroma.add_documents("other", vec![doc("e")], vec![vec![0.5; 300]]).await.unwrap_err();
+        assert!(error.to_string().contains("bytes, over the limit of 1000"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_updates_go_through_payload_limits_and_quota() {
+        use crate::validation::PayloadLimits;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let updates = Arc::new(AtomicUsize::new(0));
+        let counter = updates.clone();
+        let chroma = mock_chroma(move |request| {
+            let path = request.uri().path();
+            if path.ends_with("/count") {
+                "2"
+            } else if path.ends_with("/get") {
+                r#"{"ids": ["a", "b"], "documents": ["abcd", "efgh"], "embeddings": [[0.1, 0.2], [0.3, 0.4]]}"#
+            } else if path.ends_with("/update") {
+                counter.fetch_add(1, Ordering::SeqCst);
+                "true"
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        })
+        .with_collection_quota("docs", Quota::default().with_max_bytes(100))
+        .with_payload_limits(PayloadLimits::default().with_max_document_len(10));
+        let doc = |content: &str| Document::builder().id("a").content(content).build();
+
+        chroma.update_documents("docs", vec![doc("wxyz")], vec![vec![0.5, 0.6]]).await.unwrap();
+        assert_eq!(chroma.quota_usage("docs").await.unwrap().documents, 2);
+
+        let error = chroma.update_documents("docs", vec![doc("wxyz")], vec![vec![0.5; 30]]).await.unwrap_err();
+        assert!(error.to_string().contains("bytes, over the limit of 100"), "{}", error);
+        let error = chroma.update_documents("docs", vec![doc("far too long")], vec![vec![0.5]]).await.unwrap_err();
+        assert!(matches!(error, ChromaError::ValidationError(_)), "{}", error);
+        assert_eq!(updates.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Spaces calls evenly so they never exceed a fixed rate. Shared between
+/// tasks behind an `Arc`; each caller waits for its own slot.
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    burst: u32,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// Allow at most `per_second` calls per second. Non-positive rates are
+    /// treated as unlimited.
+    pub fn per_second(per_second: f64) -> Self {
+        let interval = if per_second > 0.0 {
+            Duration::from_secs_f64(1.0 / per_second)
+        } else {
+            Duration::ZERO
+        };
+        Self {
+            interval,
+            burst: 1,
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Let up to `burst` calls through back to back after an idle period;
+    /// the long-run rate stays the same.
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    /// Wait until the next call is allowed.
+    pub async fn acquire(&self) {
+        self.acquire_many(1).await;
+    }
+
+    /// Wait until `permits` units (e.g. documents in a batch) are allowed,
+    /// spending them all at once.
+    pub async fn acquire_many(&self, permits: u32) {
+        if self.interval.is_zero() || permits == 0 {
+            return;
+        }
+
+        let slot = {
+            let mut next_slot = self.next_slot.lock().await;
+            let now = Instant::now();
+            let slot = (*next_slot).max(now);
+            *next_slot = slot + self.interval * permits;
+            slot.checked_sub(self.tolerance()).map_or(now, |start| start.max(now))
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+
+    /// Take a slot if one is free right now, without waiting. Otherwise
+    /// returns how long until one frees up, e.g. for a `Retry-After` header.
+    pub async fn try_acquire(&self) -> std::result::Result<(), Duration> {
+        if self.interval.is_zero() {
+            return Ok(());
+        }
+
+        let mut next_slot = self.next_slot.lock().await;
+        let now = Instant::now();
+        let slot = (*next_slot).max(now);
+        let wait = slot.saturating_duration_since(now + self.tolerance());
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+        *next_slot = slot + self.interval;
+        Ok(())
+    }
+
+    /// How far ahead of now the schedule may run before callers wait.
+    fn tolerance(&self) -> Duration {
+        self.interval * (self.burst - 1)
+    }
+}
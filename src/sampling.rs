@@ -0,0 +1,170 @@
+use crate::chroma_client::ChromaClient;
+use crate::error::Result;
+use crate::models::{GetRequest, Include, QueryResponse};
+use serde_json::Value;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Ids listed, and sampled records fetched, per request.
+const PAGE_SIZE: usize = 1000;
+
+/// Up to `n` records of `collection_name` matching `where_filter`, chosen
+/// uniformly at random and returned in random order as one `get` row. The
+/// ids are paged through once, keeping a reservoir of `n`, so memory stays
+/// bounded by the sample; only the chosen records' documents and metadata
+/// are fetched. The same `seed` over an unchanged collection picks the same
+/// sample.
+pub(crate) async fn sample(
+    client: &ChromaClient,
+    collection_name: &str,
+    n: usize,
+    where_filter: Option<Value>,
+    seed: Option<u64>,
+) -> Result<QueryResponse> {
+    if n == 0 {
+        return fetch_in_order(client, collection_name, Vec::new()).await;
+    }
+    let mut rng = SplitMix64::new(seed.unwrap_or_else(|| Uuid::new_v4().as_u64_pair().0));
+    let mut reservoir: Vec<String> = Vec::with_capacity(n.min(PAGE_SIZE));
+    let mut seen = 0;
+    let mut offset = 0;
+    loop {
+        let request = GetRequest {
+            where_filter: where_filter.clone(),
+            limit: Some(PAGE_SIZE as u32),
+            offset: Some(offset),
+            include: Some(Vec::new()),
+            ..GetRequest::default()
+        };
+        let ids = client.send_get(collection_name, &request).await?.ids.into_iter().next().unwrap_or_default();
+        let count = ids.len();
+        for id in ids {
+            if reservoir.len() < n {
+                reservoir.push(id);
+            } else if let Some(slot) = reservoir.get_mut(rng.below(seen + 1)) {
+                *slot = id;
+            }
+            seen += 1;
+        }
+        if count < PAGE_SIZE {
+            break;
+        }
+        offset += count as u32;
+    }
+    rng.shuffle(&mut reservoir);
+
+    fetch_in_order(client, collection_name, reservoir).await
+}
+
+/// The documents, metadata and URIs of `ids`, in the order given. Ids
+/// deleted since they were listed are left out.
+async fn fetch_in_order(client: &ChromaClient, collection_name: &str, ids: Vec<String>) -> Result<QueryResponse> {
+    let mut records = HashMap::with_capacity(ids.len());
+    for page in ids.chunks(PAGE_SIZE) {
+        let request = GetRequest {
+            ids: Some(page.to_vec()),
+            include: Some(vec![Include::Documents, Include::Metadatas, Include::Uris]),
+            ..GetRequest::default()
+        };
+        let response = client.send_get(collection_name, &request).await?;
+        for (index, id) in response.ids_for(0).iter().enumerate() {
+            let record = (
+                response.document(0, index).map(str::to_string),
+                response.metadata(0, index).cloned(),
+                response.uri(0, index).map(str::to_string),
+            );
+            records.insert(id.clone(), record);
+        }
+    }
+
+    let (mut documents, mut metadatas, mut uris) = (Vec::new(), Vec::new(), Vec::new());
+    let ids: Vec<String> = ids
+        .into_iter()
+        .filter_map(|id| {
+            let (document, metadata, uri) = records.remove(&id)?;
+            documents.push(document);
+            metadatas.push(metadata);
+            uris.push(uri);
+            Some(id)
+        })
+        .collect();
+    Ok(QueryResponse {
+        ids: vec![ids],
+        documents: Some(vec![documents]),
+        metadatas: Some(vec![metadatas]),
+        uris: Some(vec![uris]),
+        ..QueryResponse::default()
+    })
+}
+
+/// Small, seedable generator; sampling needs speed and reproducibility, not
+/// cryptographic strength.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..bound`; `bound` must be positive.
+    fn below(&mut self, bound: usize) -> usize {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::mock_chroma;
+
+    #[tokio::test]
+    async fn test_sample_draws_distinct_records_reproducibly() {
+        use std::collections::HashSet;
+
+        let chroma = mock_chroma(|request| {
+            if request.uri().path().ends_with("/get") {
+                let get: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                if let Some(ids) = get["ids"].as_array() {
+                    let documents: Vec<String> =
+                        ids.iter().map(|id| format!("text of {}", id.as_str().unwrap())).collect();
+                    serde_json::json!({ "ids": ids, "documents": documents }).to_string()
+                } else {
+                    assert_eq!(get["where"], serde_json::json!({ "lang": "en" }));
+                    let offset = get["offset"].as_u64().unwrap() as usize;
+                    let limit = get["limit"].as_u64().unwrap() as usize;
+                    let ids: Vec<String> = (offset..2500.min(offset + limit)).map(|i| format!("id{}", i)).collect();
+                    serde_json::json!({ "ids": ids }).to_string()
+                }
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+        let filter = || Some(serde_json::json!({ "lang": "en" }));
+
+        let sample = chroma.sample_with_seed("docs", 10, filter(), 7).await.unwrap();
+        let ids = sample.ids_for(0).to_vec();
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 10);
+        assert!(ids.iter().all(|id| id[2..].parse::<usize>().unwrap() < 2500));
+        assert_eq!(sample.document(0, 3), Some(format!("text of {}", ids[3]).as_str()));
+        assert_eq!(chroma.sample_with_seed("docs", 10, filter(), 7).await.unwrap().ids_for(0), ids.as_slice());
+        assert_ne!(chroma.sample_with_seed("docs", 10, filter(), 8).await.unwrap().ids_for(0), ids.as_slice());
+
+        // Asking for more than there is returns everything.
+        let everything = chroma.sample("docs", 3000, filter()).await.unwrap();
+        assert_eq!(everything.ids_for(0).iter().collect::<HashSet<_>>().len(), 2500);
+        assert!(chroma.sample("docs", 0, filter()).await.unwrap().ids_for(0).is_empty());
+    }
+}
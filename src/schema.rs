@@ -0,0 +1,70 @@
+use crate::error::{ChromaError, Result};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tracing::warn;
+
+/// How to treat response fields the crate's models don't know about.
+///
+/// Unknown fields are usually harmless additions, but they are also the first
+/// sign of an upstream API change, so by default they are logged. `Strict`
+/// turns them into errors, which is what contract tests run with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchemaMode {
+    Off,
+    #[default]
+    Warn,
+    Strict,
+}
+
+impl SchemaMode {
+    /// Read `SCHEMA_MODE` (`off`, `warn` or `strict`), defaulting to `warn`.
+    pub fn from_env() -> Self {
+        match std::env::var("SCHEMA_MODE").as_deref().map(str::to_ascii_lowercase).as_deref() {
+            Ok("off") => SchemaMode::Off,
+            Ok("strict") => SchemaMode::Strict,
+            _ => SchemaMode::Warn,
+        }
+    }
+}
+
+/// Top-level fields a response model expects from the server.
+pub trait KnownFields {
+    const NAME: &'static str;
+    const FIELDS: &'static [&'static str];
+}
+
+/// Fields of `value` that `T` doesn't declare.
+pub fn unknown_fields<T: KnownFields>(value: &Value) -> Vec<String> {
+    value
+        .as_object()
+        .map(|map| {
+            map.keys()
+                .filter(|key| !T::FIELDS.contains(&key.as_str()))
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Check `value` for schema drift according to `mode`, then deserialize it,
+/// naming the model in any error.
+pub fn decode<T: DeserializeOwned + KnownFields>(value: Value, mode: SchemaMode) -> Result<T> {
+    if mode != SchemaMode::Off {
+        let unknown = unknown_fields::<T>(&value);
+        if !unknown.is_empty() {
+            let message = format!(
+                "{} response has unexpected field(s): {}",
+                T::NAME,
+                unknown.join(", ")
+            );
+            match mode {
+                SchemaMode::Strict => return Err(ChromaError::SchemaError(message)),
+                _ => warn!("{}; the upstream API may have changed", message),
+            }
+        }
+    }
+
+    serde_json::from_value(value).map_err(|e| {
+        ChromaError::SchemaError(format!("{} response does not match the expected schema: {}", T::NAME, e))
+    })
+}
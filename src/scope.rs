@@ -0,0 +1,218 @@
+use crate::chroma_client::ChromaClient;
+use crate::error::{ChromaError, Result};
+use crate::models::*;
+use crate::query::QueryOptions;
+use serde_json::{json, Value};
+use tracing::warn;
+
+const DEFAULT_OWNER_FIELD: &str = "owner_id";
+
+/// A collection handle bound to a single owner.
+///
+/// Every write stamps the owner into document metadata and every read or
+/// delete is restricted with an owner filter, so callers in multi-user apps
+/// cannot forget to scope a request. Query hits are also checked after
+/// retrieval in case a filter is dropped on the way to the server.
+pub struct ScopedCollection<'a> {
+    client: &'a ChromaClient,
+    collection_name: String,
+    owner_field: String,
+    owner_id: String,
+}
+
+impl<'a> ScopedCollection<'a> {
+    pub(crate) fn new(client: &'a ChromaClient, collection_name: &str, owner_id: &str) -> Self {
+        Self {
+            client,
+            collection_name: collection_name.to_string(),
+            owner_field: DEFAULT_OWNER_FIELD.to_string(),
+            owner_id: owner_id.to_string(),
+        }
+    }
+
+    /// Use a metadata key other than `owner_id` to store the owner.
+    pub fn with_owner_field(mut self, owner_field: &str) -> Self {
+        self.owner_field = owner_field.to_string();
+        self
+    }
+
+    pub fn owner_id(&self) -> &str {
+        &self.owner_id
+    }
+
+    pub async fn add_documents(
+        &self,
+        documents: Vec<Document>,
+        embeddings: Vec<Vec<f32>>,
+    ) -> Result<()> {
+        let documents = self.stamp_owner(documents);
+        self.client.add_documents(&self.collection_name, documents, embeddings).await
+    }
+
+    /// Update documents, refusing if any id belongs to a different owner.
+    pub async fn update_documents(
+        &self,
+        documents: Vec<Document>,
+        embeddings: Vec<Vec<f32>>,
+    ) -> Result<()> {
+        let ids: Vec<String> = documents.iter().map(|d| d.id.clone()).collect();
+        let owned = self.get_documents(Some(ids.clone()), None, None).await?;
+        let owned_count = owned.ids.iter().map(Vec::len).sum::<usize>();
+
+        if owned_count != ids.len() {
+            return Err(ChromaError::CollectionError(format!(
+                "Refusing to update {} document(s) not owned by '{}'",
+                ids.len() - owned_count.min(ids.len()),
+                self.owner_id
+            )));
+        }
+
+        let documents = self.stamp_owner(documents);
+        self.client.update_documents(&self.collection_name, documents, embeddings).await
+    }
+
+    pub async fn query_with_options(
+        &self,
+        query_embedding: Vec<f32>,
+        options: &QueryOptions,
+    ) -> Result<Vec<QueryHit>> {
+        let mut scoped = options.clone();
+        scoped.where_filter = Some(self.scoped_filter(options.where_filter.clone()));
+        // Hits are checked against their owner below, which needs metadata.
+        scoped.include = options.include_with_metadatas();
+
+        let mut hits = self.client
+            .query_with_options(&self.collection_name, query_embedding, &scoped)
+            .await?;
+
+        let before = hits.len();
+        hits.retain(|hit| self.is_owned(hit.metadata.as_ref()));
+        if hits.len() != before {
+            warn!(
+                "Dropped {} query hit(s) not owned by '{}' from collection '{}'",
+                before - hits.len(), self.owner_id, self.collection_name
+            );
+        }
+
+        Ok(hits)
+    }
+
+    pub async fn get_documents(
+        &self,
+        ids: Option<Vec<String>>,
+        where_filter: Option<Value>,
+        limit: Option<u32>,
+    ) -> Result<QueryResponse> {
+        self.client
+            .get_documents(&self.collection_name, ids, Some(self.scoped_filter(where_filter)), limit)
+            .await
+    }
+
+    pub async fn delete_documents(&self, ids: Vec<String>) -> Result<()> {
+        self.client
+            .delete_documents_with_filter(&self.collection_name, Some(ids), Some(self.scoped_filter(None)))
+            .await
+    }
+
+    fn stamp_owner(&self, mut documents: Vec<Document>) -> Vec<Document> {
+        for doc in documents.iter_mut() {
+            doc.metadata.insert(self.owner_field.clone(), self.owner_id.clone());
+        }
+        documents
+    }
+
+    fn scoped_filter(&self, where_filter: Option<Value>) -> Value {
+        let owner_filter = json!({ self.owner_field.as_str(): { "$eq": self.owner_id } });
+
+        match where_filter {
+            Some(filter) => json!({ "$and": [filter, owner_filter] }),
+            None => owner_filter,
+        }
+    }
+
+    fn is_owned(&self, metadata: Option<&Value>) -> bool {
+        metadata
+            .and_then(|m| m.get(&self.owner_field))
+            .and_then(Value::as_str)
+            == Some(self.owner_id.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_chroma;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn test_scoped_requests_are_filtered_and_stamped_by_owner() {
+        let requests: Arc<Mutex<Vec<(String, Value)>>> = Arc::default();
+        let recorded = requests.clone();
+        let chroma = mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            let operation = path.rsplit('/').next().unwrap().to_string();
+            if ["add", "get", "query", "delete"].contains(&operation.as_str()) {
+                let body: Value = serde_json::from_slice(request.body()).unwrap();
+                recorded.lock().unwrap().push((operation.clone(), body));
+            }
+            match operation.as_str() {
+                "get" => r#"{"ids": ["mine"]}"#.to_string(),
+                "query" => json!({
+                    "ids": [["mine", "theirs"]],
+                    "distances": [[0.1, 0.2]],
+                    "metadatas": [[{"owner_id": "alice"}, {"owner_id": "bob"}]],
+                })
+                .to_string(),
+                "add" | "delete" => "true".to_string(),
+                _ => r#"{"id": "c0ffee", "name": "docs"}"#.to_string(),
+            }
+        });
+        let scoped = chroma.scoped_to("docs", "alice");
+        let owner_filter = json!({"owner_id": {"$eq": "alice"}});
+
+        let doc = Document::builder().id("mine").content("notes").meta("owner_id", "bob").build();
+        scoped.add_documents(vec![doc], vec![vec![0.1]]).await.unwrap();
+
+        let options = QueryOptions::new(2).with_filter(json!({"lang": "en"})).with_include(vec![Include::Documents]);
+        let hits = scoped.query_with_options(vec![0.1], &options).await.unwrap();
+        assert_eq!(hits.iter().map(|hit| hit.id.as_str()).collect::<Vec<_>>(), vec!["mine"]);
+
+        scoped.get_documents(None, None, None).await.unwrap();
+        scoped.delete_documents(vec!["mine".to_string()]).await.unwrap();
+
+        let requests = requests.lock().unwrap().clone();
+        let operations: Vec<&str> = requests.iter().map(|(operation, _)| operation.as_str()).collect();
+        assert_eq!(operations, vec!["add", "query", "get", "delete"]);
+        assert_eq!(requests[0].1["metadatas"][0]["owner_id"], "alice");
+        assert_eq!(requests[1].1["where"], json!({"$and": [{"lang": "en"}, owner_filter]}));
+        assert_eq!(requests[1].1["include"], json!(["documents", "metadatas"]));
+        assert_eq!(requests[2].1["where"], owner_filter);
+        assert_eq!(requests[3].1["where"], owner_filter);
+    }
+
+    #[tokio::test]
+    async fn test_scoped_updates_refuse_ids_owned_by_someone_else() {
+        let updates: Arc<Mutex<usize>> = Arc::default();
+        let counted = updates.clone();
+        let chroma = mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            if path.ends_with("/get") {
+                r#"{"ids": ["mine"]}"#.to_string()
+            } else if path.ends_with("/update") {
+                *counted.lock().unwrap() += 1;
+                "true".to_string()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+        let scoped = chroma.scoped_to("docs", "alice");
+        let doc = |id: &str| Document::builder().id(id).content("edited").build();
+
+        let error = scoped.update_documents(vec![doc("mine"), doc("theirs")], vec![vec![0.1], vec![0.2]]).await;
+        assert!(matches!(error, Err(ChromaError::CollectionError(ref m)) if m.contains("1 document(s)")), "{:?}", error);
+        assert_eq!(*updates.lock().unwrap(), 0);
+
+        scoped.update_documents(vec![doc("mine")], vec![vec![0.1]]).await.unwrap();
+        assert_eq!(*updates.lock().unwrap(), 1);
+    }
+}
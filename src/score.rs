@@ -0,0 +1,150 @@
+use crate::models::DistanceSpace;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Whether a raw value is a distance (lower is better) or a similarity
+/// (higher is better).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoreKind {
+    Distance,
+    Similarity,
+}
+
+/// A raw score together with the metric and direction it was produced in.
+///
+/// Chroma returns distances and the local store returns similarities, and
+/// the ranges differ per `hnsw:space`, so a bare `0.3` means nothing on its
+/// own. All backends convert the same way: similarity is `1 - distance`
+/// (what `QueryHit::score` and `VectorStore::search` report), and
+/// `normalized` maps any score onto `[0, 1]` so thresholds can be shared
+/// across metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Score {
+    pub value: f32,
+    pub space: DistanceSpace,
+    pub kind: ScoreKind,
+}
+
+impl Score {
+    pub fn distance(space: DistanceSpace, value: f32) -> Self {
+        Self { value, space, kind: ScoreKind::Distance }
+    }
+
+    pub fn similarity(space: DistanceSpace, value: f32) -> Self {
+        Self { value, space, kind: ScoreKind::Similarity }
+    }
+
+    pub fn to_distance(&self) -> f32 {
+        match self.kind {
+            ScoreKind::Distance => self.value,
+            ScoreKind::Similarity => 1.0 - self.value,
+        }
+    }
+
+    pub fn to_similarity(&self) -> f32 {
+        match self.kind {
+            ScoreKind::Distance => 1.0 - self.value,
+            ScoreKind::Similarity => self.value,
+        }
+    }
+
+    /// Similarity on `[0, 1]`, higher is better: cosine maps `[-1, 1]`
+    /// linearly, squared L2 distance goes through `1 / (1 + d)` and inner
+    /// product through a logistic curve. Monotonic, so rankings within a
+    /// metric are preserved. NaN stays NaN.
+    pub fn normalized(&self) -> f32 {
+        match self.space {
+            DistanceSpace::Cosine => ((self.to_similarity() + 1.0) / 2.0).clamp(0.0, 1.0),
+            DistanceSpace::L2 => 1.0 / (1.0 + self.to_distance().max(0.0)),
+            DistanceSpace::Ip => 1.0 / (1.0 + (-self.to_similarity()).exp()),
+        }
+    }
+
+    /// Compare by quality: a smaller distance or a larger similarity is
+    /// `Greater`, NaN is worst. Scores from different metrics are compared
+    /// on their normalized value.
+    pub fn quality_cmp(&self, other: &Score) -> Ordering {
+        let key = |s: f32| if s.is_nan() { f32::NEG_INFINITY } else { s };
+        if self.space == other.space {
+            key(self.to_similarity()).total_cmp(&key(other.to_similarity()))
+        } else {
+            key(self.normalized()).total_cmp(&key(other.normalized()))
+        }
+    }
+
+    /// At least as good as `threshold`.
+    pub fn meets(&self, threshold: &Score) -> bool {
+        self.quality_cmp(threshold) != Ordering::Less
+    }
+}
+
+/// Rescale `scores` in place to `[0, 1]` by min-max. All-equal (or empty)
+/// input becomes all 1.0, since nothing ranks below anything else; NaN
+/// values are left as they are and ignored for the range.
+pub fn min_max_normalize(scores: &mut [f32]) {
+    let finite = scores.iter().copied().filter(|s| s.is_finite());
+    let (min, max) = finite.fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), s| (lo.min(s), hi.max(s)));
+    let range = max - min;
+    for score in scores.iter_mut().filter(|s| s.is_finite()) {
+        *score = if range > 0.0 { (*score - min) / range } else { 1.0 };
+    }
+}
+
+/// Standardize `scores` in place to mean 0 and standard deviation 1. With no
+/// spread every score becomes 0.0; NaN values are left as they are and
+/// ignored for the statistics.
+pub fn z_score_normalize(scores: &mut [f32]) {
+    let finite: Vec<f32> = scores.iter().copied().filter(|s| s.is_finite()).collect();
+    if finite.is_empty() {
+        return;
+    }
+    let n = finite.len() as f32;
+    let mean = finite.iter().sum::<f32>() / n;
+    let std_dev = (finite.iter().map(|s| (s - mean) * (s - mean)).sum::<f32>() / n).sqrt();
+    for score in scores.iter_mut().filter(|s| s.is_finite()) {
+        *score = if std_dev > 0.0 { (*score - mean) / std_dev } else { 0.0 };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::QueryHit;
+    use crate::query::QueryOptions;
+
+    #[test]
+    fn test_scores_convert_and_share_thresholds() {
+        let chroma = Score::distance(DistanceSpace::Cosine, 0.2);
+        let local = Score::similarity(DistanceSpace::Cosine, 0.8);
+        assert!((chroma.to_similarity() - local.value).abs() < 1e-6);
+        assert!((local.to_distance() - chroma.value).abs() < 1e-6);
+        assert!((chroma.normalized() - 0.9).abs() < 1e-6);
+        assert_eq!(Score::distance(DistanceSpace::L2, 0.0).normalized(), 1.0);
+        assert_eq!(Score::similarity(DistanceSpace::Ip, 0.0).normalized(), 0.5);
+
+        let threshold = Score::similarity(DistanceSpace::Cosine, 0.75);
+        assert!(chroma.meets(&threshold) && local.meets(&threshold));
+        assert!(!Score::distance(DistanceSpace::Cosine, 0.3).meets(&threshold));
+        assert!(!Score::distance(DistanceSpace::Cosine, f32::NAN).meets(&threshold));
+        // Across metrics, normalized values are compared.
+        assert!(Score::distance(DistanceSpace::L2, 0.1).meets(&Score::similarity(DistanceSpace::Cosine, 0.5)));
+
+        let hit = |id: &str, distance: f32| QueryHit {
+            id: id.to_string(),
+            document: None,
+            metadata: None,
+            distance,
+            score: 1.0 - distance,
+            uri: None,
+        };
+        let options = QueryOptions::new(10).with_min_score(threshold);
+        let ids: Vec<String> = options.rerank(vec![hit("close", 0.1), hit("far", 0.6)]).into_iter().map(|h| h.id).collect();
+        assert_eq!(ids, vec!["close"]);
+
+        let mut scores = [2.0, 4.0, f32::NAN, 3.0];
+        min_max_normalize(&mut scores);
+        assert_eq!((scores[0], scores[1], scores[3]), (0.0, 1.0, 0.5));
+        assert!(scores[2].is_nan());
+    }
+}
@@ -0,0 +1,1329 @@
+use crate::api_keys::{ApiKey, ApiKeys};
+use crate::chroma_client::ChromaClient;
+use crate::degraded::{DegradedMode, IngestOutcome};
+use crate::embeddings::EmbeddingClient;
+use crate::error::{ChromaError, Result};
+use crate::llm::{GeminiLlm, LlmProvider, OpenAiCompatibleLlm};
+use crate::models::Document;
+use crate::openapi::{self, ServerConventions};
+use crate::pipeline::{Answer, Pipeline, QueryResult, QueryTimings, SyncReport};
+use crate::provenance::Provenance;
+use crate::query::QueryOptions;
+use crate::quota::{Quota, QuotaUsage};
+use crate::validation::validate_collection_name;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{DefaultBodyLimit, FromRequest, FromRequestParts, State};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, HeaderValue, Method, Request, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::stream::{self, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+use utoipa::{OpenApi, ToSchema};
+use uuid::Uuid;
+
+/// Settings for `chroma-cli serve`, read from an env file and the process
+/// environment. Values in the file win, so editing it and sending `SIGHUP`
+/// (or `POST /admin/reload`) is enough to reconfigure a running server.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    pub chroma_host: String,
+    pub collection: String,
+    pub google_api_key: String,
+    pub embedding_model: Option<String>,
+    pub sync_dir: Option<PathBuf>,
+    pub cache_capacity: usize,
+    /// Fall back to keyword search and queue ingests while the embedding
+    /// provider is down (see `DegradedMode`).
+    pub degraded_mode: bool,
+    /// JSON file of API keys (see `ApiKeys::load`); unset leaves the server
+    /// open to anyone who can reach it.
+    pub api_keys_file: Option<PathBuf>,
+    pub cors: CorsConfig,
+    pub limits: RequestLimits,
+    /// Applied to each collection the server writes to (see `Quota`).
+    pub quota: Quota,
+    pub env_file: Option<PathBuf>,
+}
+
+/// Which browser origins may call the server. No origins (the default)
+/// sends no CORS headers, so browsers only allow same-origin pages.
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    /// Exact origins such as `https://app.example.com`, or `*` for any.
+    pub allowed_origins: Vec<String>,
+    /// Request headers a browser may send, beyond the CORS-safelisted ones.
+    pub allowed_headers: Vec<String>,
+    /// How long browsers may cache a preflight response.
+    pub max_age: Duration,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_headers: ["content-type", "authorization", "x-api-key"].map(String::from).to_vec(),
+            max_age: Duration::from_secs(600),
+        }
+    }
+}
+
+impl CorsConfig {
+    fn allows(&self, origin: &str) -> bool {
+        self.allowed_origins.iter().any(|allowed| allowed == "*" || allowed == origin)
+    }
+}
+
+/// Caps on what one request may ask of the server.
+#[derive(Debug, Clone)]
+pub struct RequestLimits {
+    /// Largest request body accepted; bigger ones get `413`.
+    pub max_body_bytes: usize,
+    /// Most documents one `POST /documents` may upsert.
+    pub max_ingest_documents: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 2 * 1024 * 1024,
+            max_ingest_documents: 256,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn load(env_file: Option<&Path>) -> Result<Self> {
+        let mut file_values = HashMap::new();
+        if let Some(path) = env_file {
+            // The only dotenv API that reads a file without exporting it into
+            // the process environment, which reload relies on.
+            #[allow(deprecated)]
+            let entries = dotenv::from_path_iter(path)
+                .map_err(|e| ChromaError::ConfigError(format!("Cannot read {}: {}", path.display(), e)))?;
+            for entry in entries {
+                let (key, value) = entry
+                    .map_err(|e| ChromaError::ConfigError(format!("Invalid line in {}: {}", path.display(), e)))?;
+                file_values.insert(key, value);
+            }
+        }
+        let lookup = |key: &str| file_values.get(key).cloned().or_else(|| std::env::var(key).ok());
+
+        let bind = lookup("SERVER_BIND").unwrap_or_else(|| "127.0.0.1:8080".to_string());
+        let bind = bind
+            .parse()
+            .map_err(|_| ChromaError::ConfigError(format!("Invalid SERVER_BIND address: {}", bind)))?;
+        let parse_usize = |key: &str, default: usize| match lookup(key) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| ChromaError::ConfigError(format!("Invalid {}: {}", key, value))),
+            None => Ok(default),
+        };
+        let list = |key: &str| {
+            lookup(key).map(|value| {
+                value
+                    .split(',')
+                    .map(|item| item.trim().to_string())
+                    .filter(|item| !item.is_empty())
+                    .collect::<Vec<_>>()
+            })
+        };
+        let cache_capacity = parse_usize("EMBEDDING_CACHE_SIZE", 1024)?;
+
+        let mut cors = CorsConfig::default();
+        if let Some(origins) = list("CORS_ALLOWED_ORIGINS") {
+            cors.allowed_origins = origins;
+        }
+        if let Some(headers) = list("CORS_ALLOWED_HEADERS") {
+            cors.allowed_headers = headers;
+        }
+        let max_age_secs = parse_usize("CORS_MAX_AGE_SECS", cors.max_age.as_secs() as usize)?;
+        cors.max_age = Duration::from_secs(max_age_secs as u64);
+        let defaults = RequestLimits::default();
+        let limits = RequestLimits {
+            max_body_bytes: parse_usize("SERVER_MAX_BODY_BYTES", defaults.max_body_bytes)?,
+            max_ingest_documents: parse_usize("SERVER_MAX_INGEST_DOCUMENTS", defaults.max_ingest_documents)?.max(1),
+        };
+
+        let parse_u64 = |key: &str| match lookup(key) {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| ChromaError::ConfigError(format!("Invalid {}: {}", key, value))),
+            None => Ok(None),
+        };
+        let quota = Quota {
+            max_documents: parse_u64("TENANT_MAX_DOCUMENTS")?,
+            max_bytes: parse_u64("TENANT_MAX_BYTES")?,
+        };
+
+        let collection = lookup("COLLECTION_NAME").unwrap_or_else(|| "documents".to_string());
+        validate_collection_name(&collection).map_err(|e| ChromaError::ConfigError(format!("COLLECTION_NAME: {}", e)))?;
+
+        Ok(Self {
+            bind,
+            chroma_host: lookup("CHROMA_HOST").unwrap_or_else(|| "http://localhost:8000".to_string()),
+            collection,
+            google_api_key: lookup("GOOGLE_API_KEY").unwrap_or_default(),
+            embedding_model: lookup("GEMINI_EMBEDDING_MODEL"),
+            sync_dir: lookup("SYNC_DIR").map(PathBuf::from),
+            cache_capacity,
+            degraded_mode: lookup("DEGRADED_MODE").is_some_and(|value| matches!(value.trim(), "1" | "true" | "yes")),
+            api_keys_file: lookup("API_KEYS_FILE").map(PathBuf::from),
+            cors,
+            limits,
+            quota,
+            env_file: env_file.map(Path::to_path_buf),
+        })
+    }
+
+    /// Build and warm up a pipeline: connects to Chroma and makes sure the
+    /// collection exists before the server starts using it.
+    pub async fn build_pipeline(&self) -> Result<Pipeline> {
+        let chroma = ChromaClient::try_new(self.chroma_host.clone())?.with_quota(self.quota);
+        let mut embeddings = EmbeddingClient::try_new(self.google_api_key.clone())?;
+        if let Some(model) = &self.embedding_model {
+            embeddings = embeddings.with_model(model);
+        }
+
+        let mut pipeline = Pipeline::new(Arc::new(chroma), Arc::new(embeddings), &self.collection)
+            .with_cache_capacity(self.cache_capacity);
+        if self.degraded_mode {
+            pipeline = pipeline.with_degraded_mode(DegradedMode::default());
+        }
+        match self.build_llm() {
+            Ok(llm) => pipeline = pipeline.with_llm(llm),
+            Err(e) => warn!("POST /answer is disabled: {}", e),
+        }
+        pipeline.ensure_collection().await?;
+        Ok(pipeline)
+    }
+
+    /// The model behind `/answer`: Gemini with `GOOGLE_API_KEY` unless
+    /// `LLM_PROVIDER=openai` (see `llm::from_env`).
+    fn build_llm(&self) -> Result<Arc<dyn LlmProvider>> {
+        match std::env::var("LLM_PROVIDER").ok().as_deref().map(str::trim) {
+            Some("openai") => Ok(Arc::new(OpenAiCompatibleLlm::from_env()?)),
+            None | Some("" | "gemini") => Ok(Arc::new(GeminiLlm::try_new(self.google_api_key.clone())?)),
+            Some(other) => Err(ChromaError::ConfigError(format!("Unknown LLM_PROVIDER '{}'", other))),
+        }
+    }
+
+    /// The keys in `API_KEYS_FILE`, or none if it isn't set.
+    pub fn load_api_keys(&self) -> Result<ApiKeys> {
+        match &self.api_keys_file {
+            Some(path) => ApiKeys::load(path),
+            None => Ok(ApiKeys::default()),
+        }
+    }
+}
+
+/// Shared state behind the server's routes. The pipeline is swapped as a
+/// whole on reload; in-flight requests finish on the one they started with.
+pub struct AppState {
+    pipeline: RwLock<Arc<Pipeline>>,
+    /// Pipelines for the collections API keys are scoped to, built on first
+    /// use and dropped on reload.
+    scoped: RwLock<HashMap<String, Arc<Pipeline>>>,
+    api_keys: RwLock<Arc<ApiKeys>>,
+    config: RwLock<ServerConfig>,
+    sync: Mutex<SyncStatus>,
+    started_at: Instant,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+struct SyncStatus {
+    running: bool,
+    last_report: Option<SyncReport>,
+    last_error: Option<String>,
+}
+
+impl AppState {
+    pub fn new(config: ServerConfig, pipeline: Pipeline) -> Arc<Self> {
+        Arc::new(Self {
+            pipeline: RwLock::new(Arc::new(pipeline)),
+            scoped: RwLock::new(HashMap::new()),
+            api_keys: RwLock::new(Arc::new(ApiKeys::default())),
+            config: RwLock::new(config),
+            sync: Mutex::new(SyncStatus::default()),
+            started_at: Instant::now(),
+        })
+    }
+
+    pub fn pipeline(&self) -> Arc<Pipeline> {
+        self.pipeline.read().unwrap().clone()
+    }
+
+    /// The pipeline for `collection`, or the default one for `None`.
+    pub fn pipeline_for(&self, collection: Option<&str>) -> Arc<Pipeline> {
+        let pipeline = self.pipeline();
+        let Some(collection) = collection.filter(|c| *c != pipeline.collection()) else {
+            return pipeline;
+        };
+        if let Some(scoped) = self.scoped.read().unwrap().get(collection) {
+            return scoped.clone();
+        }
+        self.scoped
+            .write()
+            .unwrap()
+            .entry(collection.to_string())
+            .or_insert_with(|| Arc::new(pipeline.for_collection(collection)))
+            .clone()
+    }
+
+    pub fn api_keys(&self) -> Arc<ApiKeys> {
+        self.api_keys.read().unwrap().clone()
+    }
+
+    /// Require one of `keys` on every route but `/health`; no keys turns
+    /// authentication off.
+    pub fn set_api_keys(&self, keys: ApiKeys) {
+        *self.api_keys.write().unwrap() = Arc::new(keys);
+    }
+
+    /// Re-read the env file the server was started with and swap in a fresh
+    /// pipeline. The old pipeline stays in place if anything fails.
+    pub async fn reload(&self) -> Result<()> {
+        let (env_file, old_bind, old_max_body) = {
+            let config = self.config.read().unwrap();
+            (config.env_file.clone(), config.bind, config.limits.max_body_bytes)
+        };
+        let config = ServerConfig::load(env_file.as_deref())?;
+        if config.bind != old_bind {
+            warn!("SERVER_BIND changed to {}; restart the server to apply it", config.bind);
+        }
+        if config.limits.max_body_bytes != old_max_body {
+            warn!("SERVER_MAX_BODY_BYTES changed; restart the server to apply it");
+        }
+
+        let api_keys = config.load_api_keys()?;
+        let pipeline = config.build_pipeline().await?;
+        *self.pipeline.write().unwrap() = Arc::new(pipeline);
+        self.scoped.write().unwrap().clear();
+        self.set_api_keys(api_keys);
+        *self.config.write().unwrap() = config;
+        info!("Configuration reloaded");
+        Ok(())
+    }
+}
+
+pub fn router(state: Arc<AppState>) -> Router {
+    let max_body_bytes = state.config.read().unwrap().limits.max_body_bytes;
+    Router::new()
+        .route("/query", post(query))
+        .route("/documents", post(add_documents))
+        .route("/usage", get(usage))
+        .route("/answer", post(answer))
+        .route("/answer/stream", post(answer_stream))
+        .route("/admin/reload", post(reload))
+        .route("/admin/cache/flush", post(flush_cache))
+        .route("/admin/outbox/flush", post(flush_outbox))
+        .route("/admin/sync", post(start_sync))
+        .route("/admin/status", get(status))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        // Added after the auth layer so probes and API docs don't need a key.
+        .route("/health", get(health))
+        .route("/openapi.json", get(openapi_spec))
+        .route("/docs", get(swagger_ui))
+        // Outermost, so preflights skip authentication and every response,
+        // errors included, carries the CORS headers.
+        .layer(middleware::from_fn_with_state(state.clone(), cors))
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+        .with_state(state)
+}
+
+/// The routes `openapi::spec` describes: everything a frontend calls.
+#[derive(OpenApi)]
+#[openapi(
+    info(description = "Semantic search and retrieval-augmented answers over a Chroma collection."),
+    paths(health, query, add_documents, usage, answer, answer_stream),
+    components(schemas(ErrorBody, TokenEvent, AnswerCitations)),
+    security(("bearer" = []), ("apiKey" = [])),
+    modifiers(&ServerConventions)
+)]
+pub(crate) struct ApiDoc;
+
+/// Run the server until Ctrl-C or `SIGTERM`, reloading on `SIGHUP`.
+pub async fn serve(config: ServerConfig) -> Result<()> {
+    let bind = config.bind;
+    let api_keys = config.load_api_keys()?;
+    if api_keys.is_empty() {
+        if !bind.ip().is_loopback() {
+            warn!("API_KEYS_FILE is not set; anyone who can reach {} can read and write the collection", bind);
+        }
+    } else {
+        info!("Requiring one of {} API keys", api_keys.len());
+    }
+    let pipeline = config.build_pipeline().await?;
+    for check in pipeline.preflight().await.failures() {
+        warn!("Preflight check {} failed: {}", check.name, check.detail);
+    }
+    let state = AppState::new(config, pipeline);
+    state.set_api_keys(api_keys);
+
+    #[cfg(unix)]
+    spawn_sighup_reload(state.clone())?;
+
+    info!("Listening on {}", bind);
+    axum::Server::try_bind(&bind)
+        .map_err(|e| ChromaError::ConfigError(format!("Cannot bind {}: {}", bind, e)))?
+        .serve(router(state).into_make_service())
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .map_err(|e| ChromaError::IoError(std::io::Error::other(e)))
+}
+
+#[cfg(unix)]
+fn spawn_sighup_reload(state: Arc<AppState>) -> Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            info!("SIGHUP received, reloading configuration");
+            if let Err(e) = state.reload().await {
+                error!("Reload failed, keeping previous configuration: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+
+    info!("Shutting down");
+}
+
+/// A failed request as an HTTP response: `{"error": "...", "code": "..."}`
+/// with a status that says whose fault it was and a stable code clients can
+/// match on.
+enum ServerError {
+    Chroma(ChromaError),
+    Rejected {
+        status: StatusCode,
+        code: &'static str,
+        message: String,
+    },
+}
+
+impl ServerError {
+    fn rejected(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self::Rejected {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<ChromaError> for ServerError {
+    fn from(error: ChromaError) -> Self {
+        Self::Chroma(error)
+    }
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Chroma(error) => {
+                let (status, code) = match &error {
+                    ChromaError::ValidationError(_) | ChromaError::ConfigError(_) => {
+                        (StatusCode::BAD_REQUEST, "invalid_request")
+                    }
+                    ChromaError::CollectionError(_) => (StatusCode::NOT_FOUND, "collection_not_found"),
+                    ChromaError::QuotaExceeded(_) => (StatusCode::FORBIDDEN, "quota_exceeded"),
+                    _ => (StatusCode::BAD_GATEWAY, "upstream_error"),
+                };
+                error_response(status, code, &error.to_string())
+            }
+            Self::Rejected { status, code, message } => error_response(status, code, &message),
+        }
+    }
+}
+
+/// The body of every failed request, and of `error` events on
+/// `/answer/stream`.
+#[derive(Debug, Serialize, ToSchema)]
+struct ErrorBody {
+    /// What went wrong, for humans.
+    error: String,
+    /// Stable reason to match on, e.g. `payload_too_large`.
+    code: String,
+}
+
+impl ErrorBody {
+    fn new(code: &str, message: &str) -> Self {
+        Self {
+            error: message.to_string(),
+            code: code.to_string(),
+        }
+    }
+}
+
+fn error_response(status: StatusCode, code: &str, message: &str) -> Response {
+    (status, Json(ErrorBody::new(code, message))).into_response()
+}
+
+/// `Json`, with malformed, oversized or mistyped bodies rejected in the
+/// server's error format instead of axum's plain-text one.
+struct JsonBody<T>(T);
+
+#[axum::async_trait]
+impl<S, B, T> FromRequest<S, B> for JsonBody<T>
+where
+    Json<T>: FromRequest<S, B, Rejection = JsonRejection>,
+    S: Send + Sync,
+    B: Send + 'static,
+{
+    type Rejection = ServerError;
+
+    async fn from_request(request: Request<B>, state: &S) -> std::result::Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(request, state).await {
+            Ok(Json(value)) => Ok(Self(value)),
+            Err(rejection) => {
+                let status = rejection.status();
+                let code = match status {
+                    StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+                    StatusCode::UNPROCESSABLE_ENTITY => "invalid_body",
+                    _ => "malformed_body",
+                };
+                Err(ServerError::rejected(status, code, rejection.body_text()))
+            }
+        }
+    }
+}
+
+/// Answer CORS preflights and add `Access-Control-Allow-Origin` to
+/// responses for allowed origins. Requests without an `Origin` header
+/// (anything but a browser) pass through untouched.
+async fn cors<B>(State(state): State<Arc<AppState>>, request: Request<B>, next: Next<B>) -> Response {
+    let Some(origin) = request.headers().get(header::ORIGIN).cloned() else {
+        return next.run(request).await;
+    };
+    let cors = state.config.read().unwrap().cors.clone();
+    let allowed = origin.to_str().is_ok_and(|origin| cors.allows(origin));
+    let preflight = request.method() == Method::OPTIONS
+        && request.headers().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+    let mut response = match (preflight, allowed) {
+        (true, true) => {
+            let mut response = StatusCode::NO_CONTENT.into_response();
+            let headers = response.headers_mut();
+            headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, HeaderValue::from_static("GET, POST, OPTIONS"));
+            if let Ok(allowed_headers) = HeaderValue::from_str(&cors.allowed_headers.join(", ")) {
+                headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, allowed_headers);
+            }
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(cors.max_age.as_secs()));
+            response
+        }
+        (true, false) => {
+            return error_response(StatusCode::FORBIDDEN, "origin_not_allowed", "This origin may not call the server");
+        }
+        (false, _) => next.run(request).await,
+    };
+    if allowed {
+        let headers = response.headers_mut();
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        headers.append(header::VARY, HeaderValue::from_static("origin"));
+    }
+    response
+}
+
+type HandlerResult = std::result::Result<Json<Value>, ServerError>;
+
+/// Check the request's API key (`Authorization: Bearer <key>` or
+/// `X-API-Key: <key>`) and its rate limit, then pass the key on to the
+/// handler. Only admin keys may call `/admin`.
+async fn authenticate<B>(State(state): State<Arc<AppState>>, mut request: Request<B>, next: Next<B>) -> Response {
+    let keys = state.api_keys();
+    if keys.is_empty() {
+        return next.run(request).await;
+    }
+
+    let Some(key) = presented_key(request.headers()).and_then(|presented| keys.authenticate(presented)) else {
+        let mut response = error_response(StatusCode::UNAUTHORIZED, "unauthorized", "Missing or invalid API key");
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        return response;
+    };
+    if request.uri().path().starts_with("/admin/") && !key.admin {
+        warn!("API key '{}' denied access to {}", key.name, request.uri().path());
+        return error_response(StatusCode::FORBIDDEN, "forbidden", "This API key cannot use admin endpoints");
+    }
+    if let Err(wait) = keys.check_rate(&key).await {
+        let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+        let message = format!("Rate limit exceeded; retry in {}s", retry_after);
+        let mut response = error_response(StatusCode::TOO_MANY_REQUESTS, "rate_limited", &message);
+        response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        return response;
+    }
+
+    request.extensions_mut().insert(key);
+    next.run(request).await
+}
+
+fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(key) = headers.get("x-api-key") {
+        return key.to_str().ok();
+    }
+    let authorization = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = authorization.split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+}
+
+/// The pipeline for the collection the request's API key is scoped to.
+struct ScopedPipeline(Arc<Pipeline>);
+
+#[axum::async_trait]
+impl FromRequestParts<Arc<AppState>> for ScopedPipeline {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let collection = parts.extensions.get::<Arc<ApiKey>>().and_then(|key| key.collection.as_deref());
+        Ok(Self(state.pipeline_for(collection)))
+    }
+}
+
+/// The ACL groups of the request's API key, if it has any.
+struct CallerGroups(Option<Vec<String>>);
+
+#[axum::async_trait]
+impl FromRequestParts<Arc<AppState>> for CallerGroups {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &Arc<AppState>,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        Ok(Self(parts.extensions.get::<Arc<ApiKey>>().and_then(|key| key.groups.clone())))
+    }
+}
+
+impl CallerGroups {
+    fn restrict(self, options: QueryOptions) -> QueryOptions {
+        match self.0 {
+            Some(groups) => options.with_acl_groups(groups),
+            None => options,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct Health {
+    status: &'static str,
+    /// Chroma answered its heartbeat.
+    chroma: bool,
+}
+
+/// Liveness, plus whether Chroma is reachable
+#[utoipa::path(get, path = "/health", security(()), responses((status = 200, description = "Server is up", body = Health)))]
+async fn health(State(state): State<Arc<AppState>>) -> Json<Health> {
+    let chroma = state.pipeline().chroma().health_check().await.unwrap_or(false);
+    Json(Health { status: "ok", chroma })
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct QueryBody {
+    text: String,
+    #[serde(default = "default_n_results")]
+    #[schema(default = default_n_results, minimum = 1)]
+    n_results: u32,
+    /// Chroma metadata filter, e.g. `{"lang": {"$eq": "rust"}}`.
+    #[serde(rename = "where")]
+    #[schema(value_type = Option<Object>)]
+    where_filter: Option<Value>,
+    #[serde(default)]
+    group_by_parent: bool,
+    #[serde(default)]
+    collapse_overlapping: bool,
+    #[schema(minimum = 1)]
+    max_per_source: Option<u32>,
+    #[serde(default)]
+    explain: bool,
+}
+
+fn default_n_results() -> u32 {
+    5
+}
+
+/// Search the collection
+#[utoipa::path(post, path = "/query", request_body = QueryBody, responses((status = 200, description = "Ranked hits", body = QueryResult)))]
+async fn query(
+    ScopedPipeline(pipeline): ScopedPipeline,
+    groups: CallerGroups,
+    JsonBody(body): JsonBody<QueryBody>,
+) -> HandlerResult {
+    let mut options = groups.restrict(QueryOptions::new(body.n_results));
+    if let Some(where_filter) = body.where_filter {
+        options = options.with_filter(where_filter);
+    }
+    if body.group_by_parent {
+        options = options.with_group_by_parent();
+    }
+    if body.collapse_overlapping {
+        options = options.with_collapse_overlapping();
+    }
+    if let Some(limit) = body.max_per_source {
+        options = options.with_max_per_source(limit);
+    }
+    if body.explain {
+        options = options.with_explain();
+    }
+
+    let result = pipeline.query(&body.text, &options).await?;
+    Ok(Json(serde_json::to_value(result).map_err(ChromaError::from)?))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct DocumentsBody {
+    documents: Vec<DocumentBody>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct DocumentBody {
+    /// Generated if missing.
+    id: Option<String>,
+    content: String,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+}
+
+/// Embed and upsert documents
+#[utoipa::path(
+    post,
+    path = "/documents",
+    request_body = DocumentsBody,
+    responses((status = 200, description = "Documents written or queued", body = IngestOutcome))
+)]
+async fn add_documents(
+    State(state): State<Arc<AppState>>,
+    ScopedPipeline(pipeline): ScopedPipeline,
+    JsonBody(body): JsonBody<DocumentsBody>,
+) -> HandlerResult {
+    let max_documents = state.config.read().unwrap().limits.max_ingest_documents;
+    if body.documents.len() > max_documents {
+        return Err(ServerError::rejected(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "too_many_documents",
+            format!("{} documents in one request, over the limit of {}", body.documents.len(), max_documents),
+        ));
+    }
+    let documents = body
+        .documents
+        .into_iter()
+        .map(|d| Document {
+            id: d.id.unwrap_or_else(|| Uuid::new_v4().to_string()),
+            content: d.content,
+            metadata: d.metadata,
+            uri: None,
+        })
+        .collect();
+
+    let outcome = pipeline.ingest_or_queue(documents).await?;
+    Ok(Json(serde_json::to_value(outcome).map_err(ChromaError::from)?))
+}
+
+/// The collection's quota and estimated usage
+#[utoipa::path(get, path = "/usage", responses((status = 200, description = "Quota usage", body = QuotaUsage)))]
+async fn usage(ScopedPipeline(pipeline): ScopedPipeline) -> HandlerResult {
+    let usage = pipeline.chroma().quota_usage(pipeline.collection()).await?;
+    Ok(Json(serde_json::to_value(usage).map_err(ChromaError::from)?))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+struct AnswerBody {
+    question: String,
+    #[serde(default = "default_n_results")]
+    #[schema(default = default_n_results, minimum = 1)]
+    n_results: u32,
+    /// Chroma metadata filter, as for `/query`.
+    #[serde(rename = "where")]
+    #[schema(value_type = Option<Object>)]
+    where_filter: Option<Value>,
+}
+
+/// Answer a question from retrieved context
+#[utoipa::path(post, path = "/answer", request_body = AnswerBody, responses((status = 200, description = "Generated answer", body = Answer)))]
+async fn answer(
+    ScopedPipeline(pipeline): ScopedPipeline,
+    groups: CallerGroups,
+    JsonBody(body): JsonBody<AnswerBody>,
+) -> HandlerResult {
+    let mut options = groups.restrict(QueryOptions::new(body.n_results));
+    if let Some(where_filter) = body.where_filter {
+        options = options.with_filter(where_filter);
+    }
+
+    let answer = pipeline.answer(&body.question, &options).await?;
+    Ok(Json(serde_json::to_value(answer).map_err(ChromaError::from)?))
+}
+
+/// A `token` event on `/answer/stream`.
+#[derive(Debug, Serialize, ToSchema)]
+struct TokenEvent {
+    text: String,
+}
+
+/// The `citations` event closing `/answer/stream`: the cited hits and the
+/// rest of the answer's details.
+#[derive(Debug, Serialize, ToSchema)]
+struct AnswerCitations {
+    citations: Vec<Citation>,
+    dropped_ids: Vec<String>,
+    model: String,
+    cached: bool,
+    timings: QueryTimings,
+    degraded: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+struct Citation {
+    id: String,
+    source: String,
+    score: f32,
+    #[schema(value_type = Option<Object>)]
+    metadata: Option<Value>,
+    provenance: Option<Provenance>,
+}
+
+/// Answer a question, streaming the text as server-sent events
+///
+/// A `token` event (`TokenEvent`) per generated chunk, then one `citations`
+/// event (`AnswerCitations`). A failure mid-generation sends an `error`
+/// event (`ErrorBody`) before `citations`; failures before the first token
+/// are plain error responses.
+#[utoipa::path(
+    post,
+    path = "/answer/stream",
+    request_body = AnswerBody,
+    responses((status = 200, description = "Event stream", content_type = "text/event-stream", body = String))
+)]
+async fn answer_stream(
+    ScopedPipeline(pipeline): ScopedPipeline,
+    groups: CallerGroups,
+    JsonBody(body): JsonBody<AnswerBody>,
+) -> std::result::Result<Sse<impl Stream<Item = std::result::Result<Event, axum::Error>>>, ServerError> {
+    let mut options = groups.restrict(QueryOptions::new(body.n_results));
+    if let Some(where_filter) = body.where_filter {
+        options = options.with_filter(where_filter);
+    }
+
+    let answer = pipeline.answer_stream(&body.question, &options).await?;
+    let citations = answer
+        .citations
+        .iter()
+        .map(|hit| Citation {
+            id: hit.id.clone(),
+            source: hit.source().to_string(),
+            score: hit.score,
+            metadata: hit.metadata.clone(),
+            provenance: hit.provenance(),
+        })
+        .collect();
+    let summary = AnswerCitations {
+        citations,
+        dropped_ids: answer.dropped_ids,
+        model: answer.model,
+        cached: answer.cached,
+        timings: answer.timings,
+        degraded: answer.degraded,
+    };
+
+    let events = answer
+        .tokens
+        .map(|chunk| match chunk {
+            Ok(text) => Event::default().event("token").json_data(TokenEvent { text }),
+            Err(e) => Event::default()
+                .event("error")
+                .json_data(ErrorBody::new("upstream_error", &e.to_string())),
+        })
+        .chain(stream::once(async move { Event::default().event("citations").json_data(summary) }))
+        .map(|event| event.map_err(axum::Error::new));
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+async fn openapi_spec() -> Json<Value> {
+    Json(openapi::spec())
+}
+
+async fn swagger_ui() -> Html<&'static str> {
+    Html(openapi::SWAGGER_UI_HTML)
+}
+
+async fn reload(State(state): State<Arc<AppState>>) -> HandlerResult {
+    state.reload().await?;
+    Ok(Json(json!({ "status": "reloaded", "collection": state.pipeline().collection() })))
+}
+
+async fn flush_cache(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let pipeline = state.pipeline();
+    let flushed = pipeline.cache_len();
+    pipeline.flush_cache();
+    Json(json!({ "flushed": flushed }))
+}
+
+/// Retry the documents queued while the embedding provider was down.
+async fn flush_outbox(State(state): State<Arc<AppState>>) -> HandlerResult {
+    let pipeline = state.pipeline();
+    let flushed = pipeline.flush_outbox().await?;
+    Ok(Json(json!({ "flushed": flushed, "remaining": pipeline.outbox_len() })))
+}
+
+/// Start a sync of `SYNC_DIR` in the background; poll `/admin/status` for
+/// the outcome.
+async fn start_sync(State(state): State<Arc<AppState>>) -> std::result::Result<(StatusCode, Json<Value>), ServerError> {
+    let dir = state
+        .config
+        .read()
+        .unwrap()
+        .sync_dir
+        .clone()
+        .ok_or_else(|| ChromaError::ConfigError("SYNC_DIR is not set".to_string()))?;
+
+    {
+        let mut sync = state.sync.lock().unwrap();
+        if sync.running {
+            return Err(ServerError::rejected(StatusCode::CONFLICT, "sync_running", "A sync job is already running"));
+        }
+        sync.running = true;
+    }
+
+    let pipeline = state.pipeline();
+    let job_state = state.clone();
+    tokio::spawn(async move {
+        let outcome = pipeline.sync_directory(&dir).await;
+        let mut sync = job_state.sync.lock().unwrap();
+        sync.running = false;
+        match outcome {
+            Ok(report) => {
+                sync.last_report = Some(report);
+                sync.last_error = None;
+            }
+            Err(e) => {
+                error!("Sync of {} failed: {}", dir.display(), e);
+                sync.last_error = Some(e.to_string());
+            }
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(json!({ "status": "started" }))))
+}
+
+async fn status(State(state): State<Arc<AppState>>) -> Json<Value> {
+    let pipeline = state.pipeline();
+    let sync = state.sync.lock().unwrap().clone();
+    Json(json!({
+        "collection": pipeline.collection(),
+        "embedding_model": pipeline.embedder().model_name(),
+        "cache_entries": pipeline.cache_len(),
+        "outbox": pipeline.outbox_len(),
+        "api_keys": state.api_keys().len(),
+        "uptime_secs": state.started_at.elapsed().as_secs(),
+        "sync": sync,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::answer_cache::AnswerCache;
+    use crate::extract;
+    use crate::llm::{self, Generation, GenerationRequest};
+    use crate::test_support::{FixedEmbeddings, MOCK_URL, mock_chroma};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_server_queries_through_cached_pipeline() {
+        use tower::ServiceExt;
+
+        let chroma = mock_chroma(|request| {
+            if request.uri().path().ends_with("/query") {
+                r#"{"ids": [["doc1"]], "documents": [["hello"]], "distances": [[0.25]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+        let pipeline = Pipeline::new(Arc::new(chroma), Arc::new(FixedEmbeddings), "docs");
+        let config = ServerConfig {
+            bind: "127.0.0.1:0".parse().unwrap(),
+            chroma_host: MOCK_URL.to_string(),
+            collection: "docs".to_string(),
+            google_api_key: String::new(),
+            embedding_model: None,
+            sync_dir: None,
+            cache_capacity: 16,
+            degraded_mode: false,
+            api_keys_file: None,
+            cors: CorsConfig::default(),
+            limits: RequestLimits::default(),
+            quota: Quota::default(),
+            env_file: None,
+        };
+        let state = AppState::new(config, pipeline);
+
+        let call = |path: &str, body: &str| {
+            http::Request::post(path)
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .body(hyper::Body::from(body.to_string()))
+                .unwrap()
+        };
+        let read_json = |response: http::Response<axum::body::BoxBody>| async {
+            let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+        };
+
+        let app = router(state.clone());
+        let response = app.clone().oneshot(call("/query", r#"{"text": "hello"}"#)).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let body = read_json(response).await;
+        assert_eq!(body["hits"][0]["id"], "doc1");
+        assert_eq!(body["timings"]["embedding_cached"], false);
+        assert!(body["timings"]["total_ms"].as_f64().unwrap() >= body["timings"]["search_ms"].as_f64().unwrap());
+        assert_eq!(state.pipeline().cache_len(), 1);
+
+        let response = app.clone().oneshot(call("/query", r#"{"text": "hello"}"#)).await.unwrap();
+        assert_eq!(read_json(response).await["timings"]["embedding_cached"], true);
+
+        let response = app.clone().oneshot(call("/admin/cache/flush", "")).await.unwrap();
+        assert_eq!(read_json(response).await["flushed"], 1);
+        assert_eq!(state.pipeline().cache_len(), 0);
+
+        let response = app.oneshot(call("/admin/sync", "")).await.unwrap();
+        assert_eq!(response.status(), 400);
+    }
+
+    #[tokio::test]
+    async fn test_server_cors_and_request_limits() {
+        use tower::ServiceExt;
+
+        let chroma = mock_chroma(|request| {
+            if request.uri().path().ends_with("/query") {
+                r#"{"ids": [["doc1"]], "documents": [["hello"]], "distances": [[0.25]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+        let pipeline = Pipeline::new(Arc::new(chroma), Arc::new(FixedEmbeddings), "docs");
+        let config = ServerConfig {
+            bind: "127.0.0.1:0".parse().unwrap(),
+            chroma_host: MOCK_URL.to_string(),
+            collection: "docs".to_string(),
+            google_api_key: String::new(),
+            embedding_model: None,
+            sync_dir: None,
+            cache_capacity: 16,
+            degraded_mode: false,
+            api_keys_file: None,
+            cors: CorsConfig {
+                allowed_origins: vec!["https://app.example.com".to_string()],
+                ..Default::default()
+            },
+            limits: RequestLimits { max_body_bytes: 256, max_ingest_documents: 2 },
+            quota: Quota::default(),
+            env_file: None,
+        };
+        let app = router(AppState::new(config, pipeline));
+
+        let preflight = |origin: &str| {
+            http::Request::builder()
+                .method(http::Method::OPTIONS)
+                .uri("/query")
+                .header(http::header::ORIGIN, origin)
+                .header(http::header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                .body(hyper::Body::empty())
+                .unwrap()
+        };
+        let response = app.clone().oneshot(preflight("https://app.example.com")).await.unwrap();
+        assert_eq!(response.status(), 204);
+        assert_eq!(response.headers()[http::header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        let response = app.clone().oneshot(preflight("https://evil.example.com")).await.unwrap();
+        assert_eq!(response.status(), 403);
+
+        let call = |path: &str, body: String| {
+            http::Request::post(path)
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .header(http::header::ORIGIN, "https://app.example.com")
+                .body(hyper::Body::from(body))
+                .unwrap()
+        };
+        let error_code = |response: http::Response<axum::body::BoxBody>| async {
+            let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()["code"].clone()
+        };
+
+        let response = app.clone().oneshot(call("/query", r#"{"text": "hello"}"#.to_string())).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()[http::header::ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+
+        let response = app.clone().oneshot(call("/query", format!(r#"{{"text": "{}"}}"#, "x".repeat(300)))).await.unwrap();
+        assert_eq!(response.status(), 413);
+        assert_eq!(error_code(response).await, "payload_too_large");
+
+        let response = app.clone().oneshot(call("/query", "{".to_string())).await.unwrap();
+        assert_eq!(response.status(), 400);
+        assert_eq!(error_code(response).await, "malformed_body");
+
+        let documents = r#"{"documents": [{"content": "a"}, {"content": "b"}, {"content": "c"}]}"#;
+        let response = app.oneshot(call("/documents", documents.to_string())).await.unwrap();
+        assert_eq!(response.status(), 413);
+        assert_eq!(error_code(response).await, "too_many_documents");
+    }
+
+    #[tokio::test]
+    async fn test_openapi_spec_describes_served_responses() {
+        use tower::ServiceExt;
+
+        let chroma = mock_chroma(|request| {
+            if request.uri().path().ends_with("/query") {
+                r#"{"ids": [["doc1"]], "documents": [["hello"]], "metadatas": [[{"source": "a.md"}]], "distances": [[0.25]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+        let pipeline = Pipeline::new(Arc::new(chroma), Arc::new(FixedEmbeddings), "docs");
+        let config = ServerConfig {
+            bind: "127.0.0.1:0".parse().unwrap(),
+            chroma_host: MOCK_URL.to_string(),
+            collection: "docs".to_string(),
+            google_api_key: String::new(),
+            embedding_model: None,
+            sync_dir: None,
+            cache_capacity: 16,
+            degraded_mode: false,
+            api_keys_file: None,
+            cors: CorsConfig::default(),
+            limits: RequestLimits::default(),
+            quota: Quota::default(),
+            env_file: None,
+        };
+        let app = router(AppState::new(config, pipeline));
+        let read_json = |response: http::Response<axum::body::BoxBody>| async {
+            let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+        };
+
+        let get = |path: &str| http::Request::get(path).body(hyper::Body::empty()).unwrap();
+        let spec = read_json(app.clone().oneshot(get("/openapi.json")).await.unwrap()).await;
+        for path in ["/query", "/documents", "/answer"] {
+            assert!(spec["paths"][path]["post"]["requestBody"].is_object(), "{} is undocumented", path);
+        }
+        assert_eq!(spec["paths"]["/health"]["get"]["security"], json!([{}]));
+        let response = app.clone().oneshot(get("/docs")).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let page = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(String::from_utf8_lossy(&page).contains(r#"url: "/openapi.json""#));
+
+        let request = http::Request::post("/query")
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(hyper::Body::from(r#"{"text": "hello"}"#))
+            .unwrap();
+        let body = read_json(app.oneshot(request).await.unwrap()).await;
+        let schemas = &spec["components"]["schemas"];
+        let mut schema = schemas["QueryResult"].clone();
+        schema["properties"]["hits"]["items"] = schemas["QueryHit"].clone();
+        schema["properties"]["timings"] = schemas["QueryTimings"].clone();
+        assert_eq!(extract::validate(&body, &schema), Vec::<String>::new());
+    }
+
+    #[tokio::test]
+    async fn test_server_streams_answer_tokens_then_citations() {
+        use futures::StreamExt;
+        use tower::ServiceExt;
+
+        /// Streams two chunks, or one and then a failure.
+        struct ChunkedLlm {
+            fail: bool,
+        }
+
+        impl LlmProvider for ChunkedLlm {
+            fn generate<'a>(&'a self, _: &'a GenerationRequest) -> futures::future::BoxFuture<'a, Result<Generation>> {
+                Box::pin(async { Err(ChromaError::GenerationError("use generate_stream".to_string())) })
+            }
+
+            fn generate_stream<'a>(&'a self, _: &'a GenerationRequest) -> futures::future::BoxFuture<'a, Result<llm::TextStream>> {
+                let rest = match self.fail {
+                    true => Err(ChromaError::GenerationError("connection reset".to_string())),
+                    false => Ok("and fast [a].".to_string()),
+                };
+                let chunks = vec![Ok("Safe ".to_string()), rest];
+                Box::pin(async move { Ok(futures::stream::iter(chunks).boxed()) })
+            }
+
+            fn model_name(&self) -> &str {
+                "chunked"
+            }
+        }
+
+        let chroma = mock_chroma(|request| {
+            if request.uri().path().ends_with("/query") {
+                r#"{"ids": [["a"]], "distances": [[0.1]], "documents": [["Rust is safe."]], "metadatas": [[{"source": "rust.md"}]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+        let state = |fail: bool| {
+            let pipeline = Pipeline::new(Arc::new(chroma.clone()), Arc::new(FixedEmbeddings), "docs")
+                .with_llm(Arc::new(ChunkedLlm { fail }))
+                .with_answer_cache(Arc::new(AnswerCache::new(10)));
+            let config = ServerConfig {
+                bind: "127.0.0.1:0".parse().unwrap(),
+                chroma_host: MOCK_URL.to_string(),
+                collection: "docs".to_string(),
+                google_api_key: String::new(),
+                embedding_model: None,
+                sync_dir: None,
+                cache_capacity: 16,
+                degraded_mode: false,
+                api_keys_file: None,
+                cors: CorsConfig::default(),
+                limits: RequestLimits::default(),
+                quota: Quota::default(),
+                env_file: None,
+            };
+            AppState::new(config, pipeline)
+        };
+        let events = |state: Arc<AppState>| async move {
+            let request = http::Request::post("/answer/stream")
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .body(hyper::Body::from(r#"{"question": "Why Rust?"}"#))
+                .unwrap();
+            let response = router(state).oneshot(request).await.unwrap();
+            assert_eq!(response.status(), 200);
+            assert_eq!(response.headers()[http::header::CONTENT_TYPE], "text/event-stream");
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            String::from_utf8(body.to_vec())
+                .unwrap()
+                .split("\n\n")
+                .filter_map(|event| {
+                    let name = event.lines().find_map(|line| line.strip_prefix("event:"))?;
+                    let data = event.lines().find_map(|line| line.strip_prefix("data:"))?;
+                    Some((name.trim().to_string(), serde_json::from_str::<Value>(data.trim()).unwrap()))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let streamed = state(false);
+        let events_ok = events(streamed.clone()).await;
+        let names: Vec<&str> = events_ok.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["token", "token", "citations"]);
+        assert_eq!(events_ok[0].1["text"], "Safe ");
+        assert_eq!(events_ok[2].1["citations"][0]["id"], "a");
+        assert_eq!(events_ok[2].1["citations"][0]["source"], "rust.md");
+        assert_eq!(events_ok[2].1["model"], "chunked");
+
+        // A failure mid-generation is an error event, still followed by the
+        // citations.
+        let events_failed = events(state(true)).await;
+        let names: Vec<&str> = events_failed.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["token", "error", "citations"]);
+        assert_eq!(events_failed[1].1["code"], "upstream_error");
+        assert!(events_failed[1].1["error"].as_str().unwrap().contains("connection reset"));
+
+        // The streamed answer was cached whole.
+        let answer = streamed.pipeline().answer("Why Rust?", &QueryOptions::new(5)).await.unwrap();
+        assert!(answer.cached);
+        assert_eq!(answer.text, "Safe and fast [a].");
+    }
+
+    #[tokio::test]
+    async fn test_server_api_keys_scope_and_rate_limit_requests() {
+        use std::sync::Mutex;
+        use tower::ServiceExt;
+
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        let seen = paths.clone();
+        let chroma = mock_chroma(move |request| {
+            seen.lock().unwrap().push(request.uri().path().to_string());
+            if request.uri().path().ends_with("/query") {
+                r#"{"ids": [["doc1"]], "documents": [["hello"]], "distances": [[0.25]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "team-a"}"#
+            }
+        });
+        let pipeline = Pipeline::new(Arc::new(chroma), Arc::new(FixedEmbeddings), "docs");
+        let config = ServerConfig {
+            bind: "127.0.0.1:0".parse().unwrap(),
+            chroma_host: MOCK_URL.to_string(),
+            collection: "docs".to_string(),
+            google_api_key: String::new(),
+            embedding_model: None,
+            sync_dir: None,
+            cache_capacity: 16,
+            degraded_mode: false,
+            api_keys_file: None,
+            cors: CorsConfig::default(),
+            limits: RequestLimits::default(),
+            quota: Quota::default(),
+            env_file: None,
+        };
+        let state = AppState::new(config, pipeline);
+        let keys: Vec<ApiKey> = serde_json::from_value(serde_json::json!([
+            { "name": "ops", "key": "ops-0123456789abcdef", "admin": true },
+            { "name": "team-a", "key": "team-a-0123456789abcdef", "collection": "team-a", "requests_per_minute": 1 },
+        ]))
+        .unwrap();
+        state.set_api_keys(ApiKeys::new(keys).unwrap());
+
+        let call = |path: &str, key: Option<&str>| {
+            let mut request = http::Request::post(path).header(http::header::CONTENT_TYPE, "application/json");
+            if let Some(key) = key {
+                request = request.header(http::header::AUTHORIZATION, format!("Bearer {}", key));
+            }
+            request.body(hyper::Body::from(r#"{"text": "hello"}"#)).unwrap()
+        };
+        let app = router(state);
+
+        let response = app.clone().oneshot(call("/query", None)).await.unwrap();
+        assert_eq!(response.status(), 401);
+        let response = app.clone().oneshot(call("/query", Some("team-a-0123456789abcdeX"))).await.unwrap();
+        assert_eq!(response.status(), 401);
+        let response = app.clone().oneshot(http::Request::get("/health").body(hyper::Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), 200);
+
+        let response = app.clone().oneshot(call("/query", Some("team-a-0123456789abcdef"))).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(paths.lock().unwrap().iter().any(|path| path.ends_with("/collections/team-a")));
+
+        let response = app.clone().oneshot(call("/query", Some("team-a-0123456789abcdef"))).await.unwrap();
+        assert_eq!(response.status(), 429);
+        assert!(response.headers().contains_key(http::header::RETRY_AFTER));
+
+        let response = app.clone().oneshot(call("/admin/cache/flush", Some("team-a-0123456789abcdef"))).await.unwrap();
+        assert_eq!(response.status(), 403);
+        let response = app.oneshot(call("/admin/cache/flush", Some("ops-0123456789abcdef"))).await.unwrap();
+        assert_eq!(response.status(), 200);
+    }
+}
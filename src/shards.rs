@@ -0,0 +1,271 @@
+use crate::atomic_file::AtomicFile;
+use crate::chroma_client::ChromaClient;
+use crate::compression::Compression;
+use crate::error::{ChromaError, Result};
+use crate::export::{self, ExportConfig, ID_MEMORY_SHARE, IdSpool};
+use crate::import::{self, Checkpoint, ImportConfig, ImportReport};
+use crate::verify_export as verify;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tracing::info;
+use uuid::Uuid;
+
+/// Written last, so a directory with a manifest holds a complete export.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// What `ChromaClient::export_sharded` wrote.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShardManifest {
+    pub collection: String,
+    pub created_at: DateTime<Utc>,
+    pub include_embeddings: bool,
+    /// In shard order: a record with id `id` is in `shards[shard_of(id, shards.len())]`.
+    pub shards: Vec<ShardInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShardInfo {
+    /// File name within the export directory.
+    pub file: String,
+    pub records: usize,
+    pub bytes: u64,
+    /// CRC-32 of the file, as hex; see `verify_export`.
+    #[serde(default)]
+    pub checksum: String,
+}
+
+impl ShardManifest {
+    pub fn read(dir: &Path) -> Result<Self> {
+        let file = File::open(dir.join(MANIFEST_FILE))?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    pub fn records(&self) -> usize {
+        self.shards.iter().map(|s| s.records).sum()
+    }
+}
+
+/// The shard `id` belongs in out of `shards`. Stable across runs and
+/// platforms, so re-exports put each record in the same file.
+pub fn shard_of(id: &str, shards: usize) -> usize {
+    let hash = Uuid::new_v5(&Uuid::NAMESPACE_OID, id.as_bytes()).as_u128();
+    (hash % shards.max(1) as u128) as usize
+}
+
+fn shard_file(index: usize, shards: usize, config: &ExportConfig) -> String {
+    let extension = match config.compression {
+        Compression::None => "jsonl",
+        Compression::Zstd { .. } => "jsonl.zst",
+    };
+    format!("shard-{:05}-of-{:05}.{}", index, shards, extension)
+}
+
+/// Export `collection_name` into `shards` files under `dir`, written in
+/// parallel, then the manifest. The ids are listed once and split by
+/// `shard_of`; each shard then reads and writes its records like `export`,
+/// with an equal share of the memory limit.
+pub(crate) async fn export_sharded(
+    client: &ChromaClient,
+    collection_name: &str,
+    dir: &Path,
+    shards: usize,
+    config: &ExportConfig,
+) -> Result<ShardManifest> {
+    if shards == 0 {
+        return Err(ChromaError::ValidationError("An export needs at least one shard".to_string()));
+    }
+    std::fs::create_dir_all(dir)?;
+
+    let config = config.clone().with_memory_limit(config.memory_limit / shards);
+    let mut spools: Vec<IdSpool> = (0..shards)
+        .map(|_| IdSpool::new(config.memory_limit / ID_MEMORY_SHARE, &config.spill_dir))
+        .collect();
+    export::list_ids(client, collection_name, |id| spools[shard_of(&id, shards)].push(id)).await?;
+
+    let tasks = spools.into_iter().enumerate().map(|(index, mut ids)| {
+        let client = client.clone();
+        let collection_name = collection_name.to_string();
+        let file = shard_file(index, shards, &config);
+        let path = dir.join(&file);
+        let config = config.clone();
+        join(tokio::spawn(async move {
+            // Only replace an earlier shard once this one is complete.
+            let mut out = AtomicFile::create(&path)?;
+            let report = export::write_records(&client, &collection_name, ids.drain()?, &mut out, &config).await?;
+            out.commit()?;
+            Ok(ShardInfo {
+                file,
+                records: report.records_written,
+                bytes: report.bytes_written,
+                checksum: report.checksum,
+            })
+        }))
+    });
+    let shard_infos = futures::future::try_join_all(tasks).await?;
+
+    let manifest = ShardManifest {
+        collection: collection_name.to_string(),
+        created_at: Utc::now(),
+        include_embeddings: config.include_embeddings,
+        shards: shard_infos,
+    };
+    let mut out = AtomicFile::create(dir.join(MANIFEST_FILE))?;
+    serde_json::to_writer_pretty(&mut out, &manifest)?;
+    out.commit()?;
+
+    info!(
+        "Exported {} records from {} into {} shards in {}",
+        manifest.records(), collection_name, shards, dir.display()
+    );
+    Ok(manifest)
+}
+
+/// Import every shard listed in `dir`'s manifest, `config.concurrency` at
+/// a time, checkpointing progress in the directory. Each shard's checksum
+/// is checked before it is read and its record count after, and the
+/// collection's count against the total before the checkpoint is removed.
+pub(crate) async fn import_sharded(
+    client: &ChromaClient,
+    collection_name: &str,
+    dir: &Path,
+    config: &ImportConfig,
+) -> Result<ImportReport> {
+    let manifest = ShardManifest::read(dir)?;
+    if !manifest.include_embeddings {
+        return Err(ChromaError::ValidationError(format!(
+            "The export in {} has no embeddings and can't be imported",
+            dir.display()
+        )));
+    }
+    let checkpoint = Arc::new(Checkpoint::open_in(dir, collection_name, config)?);
+
+    let reports = stream::iter(manifest.shards.clone())
+        .map(|shard| {
+            let client = client.clone();
+            let collection_name = collection_name.to_string();
+            let path = dir.join(&shard.file);
+            let checkpoint = checkpoint.clone();
+            let config = config.clone();
+            join(tokio::spawn(async move {
+                verify::check_file(&path, &shard)?;
+                let report =
+                    import::import_checkpointed(&client, &collection_name, &path, &shard.file, &checkpoint, &config)
+                        .await?;
+                if report.records() != shard.records {
+                    return Err(ChromaError::ValidationError(format!(
+                        "Shard {} holds {} records, but the manifest lists {}",
+                        shard.file,
+                        report.records(),
+                        shard.records
+                    )));
+                }
+                Ok(report)
+            }))
+        })
+        .buffer_unordered(config.concurrency.max(1))
+        .try_collect::<Vec<ImportReport>>()
+        .await;
+    let reports = checkpoint.finish(reports)?;
+    checkpoint.finish(import::verify_count(client, collection_name, manifest.records()).await)?;
+
+    let mut total = ImportReport::default();
+    for report in &reports {
+        total.merge(report);
+    }
+    checkpoint.remove()?;
+    info!(
+        "Imported {} records into {} from {} shards ({} resumed)",
+        total.records_imported, collection_name, reports.len(), total.records_resumed
+    );
+    Ok(total)
+}
+
+/// Wait for a shard task, passing on a panic as it is.
+async fn join<T>(task: JoinHandle<Result<T>>) -> Result<T> {
+    match task.await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(ChromaError::IoError(std::io::Error::other(e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression;
+    use crate::export::ExportRecord;
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_sharded_export_round_trips_through_parallel_import() {
+        use std::io::Read;
+        use std::sync::Mutex;
+
+        let all: Vec<String> = (0..10).map(|i| format!("doc-{}", i)).collect();
+        let listed = all.clone();
+        let upserted: Arc<Mutex<Vec<String>>> = Arc::default();
+        let recorded = upserted.clone();
+        let client = mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap_or_default();
+            if path.ends_with("/get") {
+                match body["ids"].as_array() {
+                    Some(ids) => {
+                        let ids: Vec<&str> = ids.iter().filter_map(|v| v.as_str()).collect();
+                        serde_json::json!({
+                            "ids": ids,
+                            "documents": ids.iter().map(|id| format!("text of {}", id)).collect::<Vec<_>>(),
+                            "metadatas": ids.iter().map(|_| serde_json::json!({ "year": 2024 })).collect::<Vec<_>>(),
+                            "embeddings": ids.iter().map(|_| vec![0.25; 4]).collect::<Vec<_>>(),
+                        })
+                    }
+                    None => {
+                        let offset = body["offset"].as_u64().unwrap() as usize;
+                        serde_json::json!({ "ids": listed.get(offset..).unwrap_or_default() })
+                    }
+                }
+                .to_string()
+            } else if path.ends_with("/upsert") {
+                let ids = body["ids"].as_array().unwrap().iter().map(|id| id.as_str().unwrap().to_string());
+                recorded.lock().unwrap().extend(ids);
+                "true".to_string()
+            } else if path.ends_with("/count") {
+                "10".to_string()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+        let dir = std::env::temp_dir().join(format!("shards-{}", Uuid::new_v4()));
+
+        let config = ExportConfig::default().with_compression(Compression::zstd());
+        let manifest = client.export_sharded("docs", &dir, 3, &config).await.unwrap();
+        assert_eq!(manifest.shards.len(), 3);
+        assert_eq!(manifest.records(), 10);
+        assert_eq!(ShardManifest::read(&dir).unwrap(), manifest);
+        for (index, shard) in manifest.shards.iter().enumerate() {
+            assert_eq!(shard.file, format!("shard-{:05}-of-00003.jsonl.zst", index));
+            let file = std::io::BufReader::new(std::fs::File::open(dir.join(&shard.file)).unwrap());
+            let mut lines = String::new();
+            compression::decoder(file).unwrap().read_to_string(&mut lines).unwrap();
+            let records: Vec<ExportRecord> = lines.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+            assert_eq!(records.len(), shard.records);
+            assert!(records.iter().all(|r| shard_of(&r.id, 3) == index));
+        }
+
+        let config = ImportConfig::default().with_concurrency(2);
+        let report = client.import_sharded("docs", &dir, &config).await.unwrap();
+        assert_eq!(report.records_imported, 10);
+        let mut imported = upserted.lock().unwrap().clone();
+        imported.sort();
+        assert_eq!(imported, all);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+use crate::chroma_client::ChromaClient;
+use crate::error::{ChromaError, Result};
+use crate::models::{GetRequest, Include, QueryHit};
+use crate::query::QueryOptions;
+use std::collections::HashMap;
+
+/// How much the mean of the negative examples is subtracted by default.
+const DEFAULT_NEGATIVE_WEIGHT: f32 = 0.5;
+
+/// Documents to find more of, and optionally less of, for
+/// `ChromaClient::recommend`. The query vector is the mean of the positive
+/// examples' stored embeddings minus `negative_weight` times the mean of the
+/// negative ones; the examples themselves are never returned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recommendation {
+    pub positive: Vec<String>,
+    pub negative: Vec<String>,
+    pub negative_weight: f32,
+}
+
+impl Recommendation {
+    pub fn new<I, S>(positive: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            positive: positive.into_iter().map(Into::into).collect(),
+            negative: Vec::new(),
+            negative_weight: DEFAULT_NEGATIVE_WEIGHT,
+        }
+    }
+
+    pub fn with_negative<I, S>(mut self, negative: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.negative.extend(negative.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn with_negative_weight(mut self, negative_weight: f32) -> Self {
+        self.negative_weight = negative_weight;
+        self
+    }
+
+    /// The query vector for these examples, given their embeddings in the
+    /// same order as `positive` and `negative`.
+    pub fn query_vector(&self, positive: &[Vec<f32>], negative: &[Vec<f32>]) -> Result<Vec<f32>> {
+        let Some(mut query) = mean(positive)? else {
+            return Err(ChromaError::ValidationError(
+                "A recommendation needs at least one positive example".to_string(),
+            ));
+        };
+        if let Some(negative) = mean(negative)? {
+            if negative.len() != query.len() {
+                return Err(dimension_error(query.len(), negative.len()));
+            }
+            for (q, n) in query.iter_mut().zip(negative) {
+                *q -= self.negative_weight * n;
+            }
+        }
+        Ok(query)
+    }
+}
+
+fn mean(embeddings: &[Vec<f32>]) -> Result<Option<Vec<f32>>> {
+    let Some(first) = embeddings.first() else {
+        return Ok(None);
+    };
+    let mut sum = vec![0.0; first.len()];
+    for embedding in embeddings {
+        if embedding.len() != sum.len() {
+            return Err(dimension_error(sum.len(), embedding.len()));
+        }
+        for (s, e) in sum.iter_mut().zip(embedding) {
+            *s += e;
+        }
+    }
+    let count = embeddings.len() as f32;
+    Ok(Some(sum.into_iter().map(|s| s / count).collect()))
+}
+
+fn dimension_error(expected: usize, actual: usize) -> ChromaError {
+    ChromaError::ValidationError(format!(
+        "Example embeddings differ in dimension ({} and {})",
+        expected, actual
+    ))
+}
+
+/// The stored embeddings of `ids`, in the same order. Fails if any of them
+/// isn't in the collection.
+pub(crate) async fn stored_embeddings(
+    client: &ChromaClient,
+    collection_name: &str,
+    ids: &[String],
+) -> Result<Vec<Vec<f32>>> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let request = GetRequest {
+        ids: Some(ids.to_vec()),
+        include: Some(vec![Include::Embeddings]),
+        ..GetRequest::default()
+    };
+    let response = client.send_get(collection_name, &request).await?;
+    let embeddings: HashMap<&str, &[f32]> = response
+        .ids_for(0)
+        .iter()
+        .enumerate()
+        .filter_map(|(index, id)| Some((id.as_str(), response.embedding(0, index)?)))
+        .collect();
+    ids.iter()
+        .map(|id| {
+            embeddings.get(id.as_str()).map(|e| e.to_vec()).ok_or_else(|| {
+                ChromaError::ValidationError(format!("{} has no document {} with an embedding", collection_name, id))
+            })
+        })
+        .collect()
+}
+
+/// Query with the combined vector of `recommendation`'s examples, leaving
+/// the examples out of the results.
+pub(crate) async fn recommend(
+    client: &ChromaClient,
+    collection_name: &str,
+    recommendation: &Recommendation,
+    options: &QueryOptions,
+) -> Result<Vec<QueryHit>> {
+    let positive = stored_embeddings(client, collection_name, &recommendation.positive).await?;
+    let negative = stored_embeddings(client, collection_name, &recommendation.negative).await?;
+    let query = recommendation.query_vector(&positive, &negative)?;
+
+    let examples = recommendation.positive.iter().chain(&recommendation.negative).cloned();
+    let options = options.clone().with_not_ids(examples);
+    client.query_with_options(collection_name, query, &options).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_chroma;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_similar_to_queries_with_the_stored_embedding() {
+        use std::sync::Mutex;
+
+        let queries = Arc::new(Mutex::new(Vec::new()));
+        let recorded = queries.clone();
+        let chroma = mock_chroma(move |request| {
+            let path = request.uri().path();
+            if path.ends_with("/get") {
+                let get: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                if get["ids"][0] == "a" {
+                    r#"{"ids": ["a"], "embeddings": [[0.6, 0.8]]}"#
+                } else {
+                    r#"{"ids": [], "embeddings": []}"#
+                }
+            } else if path.ends_with("/query") {
+                recorded.lock().unwrap().push(serde_json::from_slice::<serde_json::Value>(request.body()).unwrap());
+                r#"{"ids": [["a", "c", "d"]], "distances": [[0.0, 0.1, 0.2]]}"#
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            }
+        });
+
+        let hits = chroma.similar_to("docs", "a", 2).await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["c", "d"]);
+        let query = queries.lock().unwrap()[0].clone();
+        assert_eq!(query["n_results"], 3);
+        assert_eq!(query["query_embeddings"][0], serde_json::json!([0.6, 0.8]));
+
+        let error = chroma.similar_to("docs", "missing", 2).await.unwrap_err();
+        assert!(matches!(error, ChromaError::ValidationError(_)), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_recommend_combines_positive_and_negative_examples() {
+        use std::sync::Mutex;
+
+        let recommendation = Recommendation::new(["a", "b"]).with_negative(["c"]).with_negative_weight(1.0);
+        let stored = HashMap::from([("a", vec![1.0, 0.0]), ("b", vec![0.0, 1.0]), ("c", vec![0.5, 0.0])]);
+        let query_vector = |ids: &[String]| ids.iter().map(|id| stored[id.as_str()].clone()).collect::<Vec<_>>();
+        let vector = recommendation
+            .query_vector(&query_vector(&recommendation.positive), &query_vector(&recommendation.negative))
+            .unwrap();
+        assert_eq!(vector, vec![0.0, 0.5]);
+        assert!(Recommendation::new(Vec::<String>::new()).query_vector(&[], &[]).is_err());
+        assert!(recommendation.query_vector(&[vec![1.0]], &[vec![1.0, 2.0]]).is_err());
+
+        let queries = Arc::new(Mutex::new(Vec::new()));
+        let recorded = queries.clone();
+        let chroma = mock_chroma(move |request| {
+            let path = request.uri().path();
+            if path.ends_with("/get") {
+                let get: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                let ids: Vec<&str> = get["ids"].as_array().unwrap().iter().map(|id| id.as_str().unwrap()).collect();
+                let embeddings: Vec<_> = ids.iter().map(|id| stored[id].clone()).collect();
+                serde_json::json!({ "ids": ids, "embeddings": embeddings }).to_string()
+            } else if path.ends_with("/query") {
+                recorded.lock().unwrap().push(serde_json::from_slice::<serde_json::Value>(request.body()).unwrap());
+                r#"{"ids": [["a", "x", "b", "c", "y", "z"]], "distances": [[0.0, 0.1, 0.2, 0.3, 0.4, 0.5]]}"#.to_string()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+
+        let hits = chroma.recommend("docs", &recommendation, &QueryOptions::new(2)).await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.id.as_str()).collect::<Vec<_>>(), vec!["x", "y"]);
+        let query = queries.lock().unwrap()[0].clone();
+        assert_eq!(query["query_embeddings"][0], serde_json::json!([0.0, 0.5]));
+        assert_eq!(query["n_results"], 5);
+    }
+}
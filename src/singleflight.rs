@@ -0,0 +1,163 @@
+use crate::error::{ChromaError, Result};
+use futures::future::{BoxFuture, FutureExt, Shared};
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+type Flight<T> = Shared<BoxFuture<'static, std::result::Result<T, SharedError>>>;
+
+/// A failure handed to every caller of a flight. `ChromaError` can't be
+/// cloned, so each caller gets a copy rebuilt from this.
+#[derive(Debug, Clone)]
+struct SharedError(std::sync::Arc<ChromaError>);
+
+/// Coalesces identical concurrent calls: while a call for a key is in
+/// flight, later callers with the same key wait for its result instead of
+/// starting their own. Nothing is cached; the entry goes away as soon as
+/// the call finishes.
+///
+/// The flight runs independently of whichever caller started it, so a
+/// caller that gives up (a dropped request handler) doesn't fail the
+/// others; whoever is still waiting drives it to completion. Retries belong
+/// inside the flight, so a burst of callers shares one retry loop rather
+/// than each retrying on its own.
+pub(crate) struct SingleFlight<K, T: Clone> {
+    flights: Mutex<HashMap<K, (u64, Flight<T>)>>,
+    next_id: AtomicU64,
+}
+
+impl<K, T: Clone> Default for SingleFlight<K, T> {
+    fn default() -> Self {
+        Self {
+            flights: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+}
+
+impl<K, T> SingleFlight<K, T>
+where
+    K: Hash + Eq + Clone,
+    T: Clone + Send + Sync + 'static,
+{
+    /// The result of the in-flight call for `key`, or of `call` if there is
+    /// none. `call` is only started when this caller leads the flight.
+    pub(crate) async fn run<F, Fut>(&self, key: K, call: F) -> Result<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+    {
+        let (id, flight) = {
+            let mut flights = self.flights.lock().unwrap_or_else(|e| e.into_inner());
+            match flights.get(&key) {
+                Some((id, flight)) => (*id, flight.clone()),
+                None => {
+                    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                    let flight = call().map(|result| result.map_err(|e| SharedError(e.into()))).boxed().shared();
+                    flights.insert(key.clone(), (id, flight.clone()));
+                    (id, flight)
+                }
+            }
+        };
+
+        let result = flight.await;
+        let mut flights = self.flights.lock().unwrap_or_else(|e| e.into_inner());
+        if flights.get(&key).is_some_and(|(current, _)| *current == id) {
+            flights.remove(&key);
+        }
+        drop(flights);
+        result.map_err(|e| copy_error(&e.0))
+    }
+}
+
+/// A copy of `error` with the same variant where the payload can be cloned.
+/// A `reqwest::Error` can't be, so it comes back as `ConnectionError` if it
+/// was a timeout or connection failure, which retry logic treats the same
+/// way, and as `TransportError` otherwise.
+fn copy_error(error: &ChromaError) -> ChromaError {
+    match error {
+        ChromaError::RequestError(e) if e.is_timeout() || e.is_connect() => {
+            ChromaError::ConnectionError(e.to_string())
+        }
+        ChromaError::RequestError(e) => ChromaError::TransportError(e.to_string()),
+        ChromaError::SerializeError(e) => ChromaError::SerializeError(serde::de::Error::custom(e.to_string())),
+        ChromaError::IoError(e) => ChromaError::IoError(std::io::Error::new(e.kind(), e.to_string())),
+        ChromaError::ApiError(m) => ChromaError::ApiError(m.clone()),
+        ChromaError::EmbeddingError(m) => ChromaError::EmbeddingError(m.clone()),
+        ChromaError::CollectionError(m) => ChromaError::CollectionError(m.clone()),
+        ChromaError::EncryptionError(m) => ChromaError::EncryptionError(m.clone()),
+        ChromaError::ValidationError(m) => ChromaError::ValidationError(m.clone()),
+        ChromaError::SchemaError(m) => ChromaError::SchemaError(m.clone()),
+        ChromaError::ConfigError(m) => ChromaError::ConfigError(m.clone()),
+        ChromaError::TransportError(m) => ChromaError::TransportError(m.clone()),
+        ChromaError::ConnectionError(m) => ChromaError::ConnectionError(m.clone()),
+        ChromaError::IndexerError(m) => ChromaError::IndexerError(m.clone()),
+        ChromaError::GenerationError(m) => ChromaError::GenerationError(m.clone()),
+        ChromaError::QuotaExceeded(m) => ChromaError::QuotaExceeded(m.clone()),
+        ChromaError::ModelDeprecated { model, replacement } => ChromaError::ModelDeprecated {
+            model: model.clone(),
+            replacement: replacement.clone(),
+        },
+        ChromaError::DimensionMismatch { model, expected, actual } => ChromaError::DimensionMismatch {
+            model: model.clone(),
+            expected: *expected,
+            actual: *actual,
+        },
+        ChromaError::ModelMismatch { collection, expected, actual } => ChromaError::ModelMismatch {
+            collection: collection.clone(),
+            expected: expected.clone(),
+            actual: actual.clone(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chroma_client::ChromaClient;
+    use crate::test_support::MOCK_URL;
+    use crate::transport::{HttpRequest, HttpResponse, Transport};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_identical_concurrent_queries_share_one_request() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let queries = Arc::new(AtomicUsize::new(0));
+        let counter = queries.clone();
+        // Queries are answered slowly so that the burst overlaps.
+        let service = tower::service_fn(move |request: HttpRequest| {
+            let is_query = request.uri().path().ends_with("/query");
+            let counter = counter.clone();
+            async move {
+                let body = if is_query {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    r#"{"ids": [["a"]], "distances": [[0.1]]}"#
+                } else {
+                    r#"{"id": "c0ffee", "name": "docs"}"#
+                };
+                Ok::<_, std::convert::Infallible>(HttpResponse::new(body.into()))
+            }
+        });
+        let chroma = ChromaClient::try_new(MOCK_URL.to_string()).unwrap().with_transport(Transport::new(service));
+        chroma.get_collection("docs").await.unwrap();
+
+        let burst = |client: &ChromaClient, embedding: f32| {
+            let client = client.clone();
+            async move { client.query("docs", vec![vec![embedding]], 1).await }
+        };
+        let results = futures::future::join_all((0..5).map(|_| burst(&chroma, 0.1))).await;
+        assert!(results.iter().all(|r| r.as_ref().unwrap().ids == vec![vec!["a".to_string()]]));
+        assert_eq!(queries.load(Ordering::SeqCst), 1);
+
+        // Different bodies don't share, and nothing is cached afterwards.
+        futures::future::join_all([burst(&chroma, 0.1), burst(&chroma, 0.2)]).await;
+        assert_eq!(queries.load(Ordering::SeqCst), 3);
+
+        let uncoalesced = chroma.clone().with_query_coalescing(false);
+        futures::future::join_all((0..3).map(|_| burst(&uncoalesced, 0.1))).await;
+        assert_eq!(queries.load(Ordering::SeqCst), 6);
+    }
+}
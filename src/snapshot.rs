@@ -0,0 +1,249 @@
+use crate::chroma_client::ChromaClient;
+use crate::error::Result;
+use crate::models::{GetRequest, Include, QueryResponse};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+const DEFAULT_PAGE_SIZE: u32 = 500;
+const CONTENT: [Include; 2] = [Include::Documents, Include::Metadatas];
+
+/// A point-in-time view of a collection for exports and reports.
+///
+/// `ChromaClient::snapshot` records every id with a fingerprint of its
+/// document and metadata. Pages are then read by id, so records inserted
+/// afterwards never leak into the scan, and each record whose content no
+/// longer matches its fingerprint is flagged `changed`. `finish` summarises
+/// what moved underneath the scan, including ids that were added.
+pub struct Snapshot<'a> {
+    client: &'a ChromaClient,
+    collection_name: String,
+    captured_at: DateTime<Utc>,
+    ids: Vec<String>,
+    fingerprints: HashMap<String, u64>,
+    page_size: u32,
+    position: usize,
+    modified: Vec<String>,
+    deleted: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotRecord {
+    pub id: String,
+    pub document: Option<String>,
+    pub metadata: Option<Value>,
+    /// The record was modified after the snapshot was taken; the values are
+    /// the current ones, not those at capture time.
+    pub changed: bool,
+}
+
+/// Records that changed while a snapshot was being read.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SnapshotChanges {
+    pub modified: Vec<String>,
+    pub deleted: Vec<String>,
+    pub added: Vec<String>,
+}
+
+impl SnapshotChanges {
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_empty() && self.deleted.is_empty() && self.added.is_empty()
+    }
+}
+
+impl<'a> Snapshot<'a> {
+    pub(crate) async fn capture(client: &'a ChromaClient, collection_name: &str) -> Result<Self> {
+        let captured_at = Utc::now();
+        let mut ids = Vec::new();
+        let mut fingerprints = HashMap::new();
+
+        scan(client, collection_name, DEFAULT_PAGE_SIZE, CONTENT.to_vec(), |id, document, metadata| {
+            fingerprints.insert(id.clone(), fingerprint(document, metadata));
+            ids.push(id);
+        })
+        .await?;
+
+        Ok(Self {
+            client,
+            collection_name: collection_name.to_string(),
+            captured_at,
+            ids,
+            fingerprints,
+            page_size: DEFAULT_PAGE_SIZE,
+            position: 0,
+            modified: Vec::new(),
+            deleted: Vec::new(),
+        })
+    }
+
+    pub fn with_page_size(mut self, page_size: u32) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    pub fn captured_at(&self) -> DateTime<Utc> {
+        self.captured_at
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Read the next page of snapshot records; `None` once all have been
+    /// read. Records deleted since the capture are skipped and reported by
+    /// `finish`.
+    pub async fn next_page(&mut self) -> Result<Option<Vec<SnapshotRecord>>> {
+        if self.position >= self.ids.len() {
+            return Ok(None);
+        }
+
+        let end = (self.position + self.page_size as usize).min(self.ids.len());
+        let page_ids = self.ids[self.position..end].to_vec();
+        self.position = end;
+
+        let request = GetRequest {
+            ids: Some(page_ids.clone()),
+            include: Some(CONTENT.to_vec()),
+            ..GetRequest::default()
+        };
+        let response = self.client.send_get(&self.collection_name, &request).await?;
+        let mut current: HashMap<String, SnapshotRecord> = rows(response)
+            .map(|(id, document, metadata)| {
+                let changed = self.fingerprints.get(&id) != Some(&fingerprint(document.as_deref(), metadata.as_ref()));
+                (id.clone(), SnapshotRecord { id, document, metadata, changed })
+            })
+            .collect();
+
+        let mut records = Vec::with_capacity(page_ids.len());
+        for id in page_ids {
+            match current.remove(&id) {
+                Some(record) => {
+                    if record.changed {
+                        self.modified.push(record.id.clone());
+                    }
+                    records.push(record);
+                }
+                None => self.deleted.push(id),
+            }
+        }
+        Ok(Some(records))
+    }
+
+    /// Finish the scan and report every record that changed during it. Ids
+    /// present now but not at capture time are listed as `added`.
+    pub async fn finish(mut self) -> Result<SnapshotChanges> {
+        while self.next_page().await?.is_some() {}
+
+        let known: HashSet<&str> = self.ids.iter().map(String::as_str).collect();
+        let mut added = Vec::new();
+        scan(self.client, &self.collection_name, self.page_size, Vec::new(), |id, _, _| {
+            if !known.contains(id.as_str()) {
+                added.push(id);
+            }
+        })
+        .await?;
+
+        Ok(SnapshotChanges {
+            modified: self.modified,
+            deleted: self.deleted,
+            added,
+        })
+    }
+}
+
+/// Page through a whole collection with `limit`/`offset`.
+pub(crate) async fn scan<F>(
+    client: &ChromaClient,
+    collection_name: &str,
+    page_size: u32,
+    include: Vec<Include>,
+    mut visit: F,
+) -> Result<()>
+where
+    F: FnMut(String, Option<&str>, Option<&Value>),
+{
+    let mut offset = 0;
+    loop {
+        let request = GetRequest {
+            limit: Some(page_size),
+            offset: Some(offset),
+            include: Some(include.clone()),
+            ..GetRequest::default()
+        };
+        let response = client.send_get(collection_name, &request).await?;
+        let mut count = 0;
+        for (id, document, metadata) in rows(response) {
+            visit(id, document.as_deref(), metadata.as_ref());
+            count += 1;
+        }
+
+        if count < page_size {
+            return Ok(());
+        }
+        offset += count;
+    }
+}
+
+fn rows(response: QueryResponse) -> impl Iterator<Item = (String, Option<String>, Option<Value>)> {
+    let ids = response.ids.into_iter().next().unwrap_or_default();
+    let mut documents = response.documents.and_then(|d| d.into_iter().next()).unwrap_or_default().into_iter();
+    let mut metadatas = response.metadatas.and_then(|m| m.into_iter().next()).unwrap_or_default().into_iter();
+    ids.into_iter().map(move |id| {
+        let document = documents.next().flatten();
+        let metadata = metadatas.next().flatten().filter(|m| !m.is_null());
+        (id, document, metadata)
+    })
+}
+
+fn fingerprint(document: Option<&str>, metadata: Option<&Value>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    document.hash(&mut hasher);
+    metadata.map(Value::to_string).hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_snapshot_reports_changes_during_scan() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let scans = Arc::new(AtomicUsize::new(0));
+        let scan_count = scans.clone();
+        let client = mock_chroma(move |request| {
+            let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap_or_default();
+            if !request.uri().path().ends_with("/get") {
+                r#"{"id": "c0ffee", "name": "docs"}"#
+            } else if body.get("ids").is_some() {
+                // b was edited and c deleted after the snapshot was taken
+                r#"{"ids": ["a", "b"], "documents": ["one", "two v2"], "metadatas": [null, null]}"#
+            } else if scan_count.fetch_add(1, Ordering::SeqCst) == 0 {
+                r#"{"ids": ["a", "b", "c"], "documents": ["one", "two", "three"], "metadatas": [null, null, null]}"#
+            } else {
+                r#"{"ids": ["a", "b", "d"]}"#
+            }
+        });
+
+        let mut snapshot = client.snapshot("docs").await.unwrap();
+        assert_eq!(snapshot.len(), 3);
+        let page = snapshot.next_page().await.unwrap().unwrap();
+        let flags: Vec<(&str, bool)> = page.iter().map(|r| (r.id.as_str(), r.changed)).collect();
+        assert_eq!(flags, vec![("a", false), ("b", true)]);
+        assert!(snapshot.next_page().await.unwrap().is_none());
+
+        let changes = snapshot.finish().await.unwrap();
+        assert_eq!(changes.modified, vec!["b"]);
+        assert_eq!(changes.deleted, vec!["c"]);
+        assert_eq!(changes.added, vec!["d"]);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::chroma_client::ChromaClient;
+use crate::error::{ChromaError, Result};
+use crate::fusion::{self, Normalization};
+use crate::models::*;
+use crate::query::QueryOptions;
+use futures::future::try_join_all;
+
+/// Separator between a base collection name and a space name.
+pub const SPACE_SEPARATOR: &str = "__";
+
+/// Name of the collection holding `space` vectors for `base`, e.g.
+/// `docs__title`.
+pub fn space_collection(base: &str, space: &str) -> String {
+    format!("{}{}{}", base, SPACE_SEPARATOR, space)
+}
+
+/// Several embeddings per record, one sibling collection per named space.
+///
+/// A record keeps the same id in every space, so a title vector in
+/// `docs__title` and a body vector in `docs__body` describe the same
+/// document. Queries can target one space or fuse several, weighting each
+/// space's score (e.g. title 2.0, body 1.0 so title matches rank first).
+pub struct NamedSpaces<'a> {
+    client: &'a ChromaClient,
+    base: String,
+}
+
+impl<'a> NamedSpaces<'a> {
+    pub(crate) fn new(client: &'a ChromaClient, base: &str) -> Self {
+        Self {
+            client,
+            base: base.to_string(),
+        }
+    }
+
+    pub fn collection_name(&self, space: &str) -> String {
+        space_collection(&self.base, space)
+    }
+
+    /// Create the collections for `spaces` that don't exist yet.
+    pub async fn ensure(&self, spaces: &[&str]) -> Result<()> {
+        for space in spaces {
+            let name = self.collection_name(space);
+            if self.client.get_collection(&name).await.is_err() {
+                self.client.create_collection(&name).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Store `space` vectors for `documents`, replacing existing ones.
+    pub async fn upsert(&self, space: &str, documents: Vec<Document>, embeddings: Vec<Vec<f32>>) -> Result<()> {
+        self.client
+            .upsert_documents(&self.collection_name(space), documents, embeddings)
+            .await
+    }
+
+    pub async fn delete(&self, spaces: &[&str], ids: Vec<String>) -> Result<()> {
+        for space in spaces {
+            self.client.delete_documents(&self.collection_name(space), ids.clone()).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn query(&self, space: &str, query_embedding: Vec<f32>, options: &QueryOptions) -> Result<Vec<QueryHit>> {
+        self.client
+            .query_with_options(&self.collection_name(space), query_embedding, options)
+            .await
+    }
+
+    /// Query every space in `weights` and merge hits by id. A record's score
+    /// is the weighted sum of its per-space scores, so records matching in
+    /// several spaces rank above those matching in one; each merged hit keeps
+    /// the document and distance from its strongest space.
+    pub async fn query_fused(
+        &self,
+        weights: &[(&str, f32)],
+        query_embedding: Vec<f32>,
+        options: &QueryOptions,
+    ) -> Result<Vec<QueryHit>> {
+        if weights.is_empty() {
+            return Err(ChromaError::ValidationError("query_fused needs at least one space".to_string()));
+        }
+
+        let per_space = try_join_all(
+            weights
+                .iter()
+                .map(|(space, _)| self.query(space, query_embedding.clone(), options)),
+        )
+        .await?;
+
+        let lists = weights.iter().map(|(_, weight)| *weight).zip(per_space).collect();
+        let mut hits = fusion::weighted_sum(lists, Normalization::None);
+        hits.truncate(options.n_results as usize);
+        Ok(hits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_chroma;
+
+    #[tokio::test]
+    async fn test_named_spaces_fuse_weighted_scores() {
+        let client = mock_chroma(|request| {
+            let path = request.uri().path().to_string();
+            if path.ends_with("/id-title/query") {
+                r#"{"ids": [["a", "b"]], "distances": [[0.2, 0.6]]}"#
+            } else if path.ends_with("/id-body/query") {
+                r#"{"ids": [["b", "c"]], "distances": [[0.1, 0.3]]}"#
+            } else if path.ends_with("/docs__title") {
+                r#"{"id": "id-title", "name": "docs__title"}"#
+            } else {
+                r#"{"id": "id-body", "name": "docs__body"}"#
+            }
+        });
+        let spaces = client.named_spaces("docs");
+        assert_eq!(spaces.collection_name("title"), "docs__title");
+
+        let hits = spaces
+            .query_fused(&[("title", 2.0), ("body", 1.0)], vec![0.0], &QueryOptions::new(2))
+            .await
+            .unwrap();
+        let ranked: Vec<(&str, f32)> = hits.iter().map(|h| (h.id.as_str(), h.score)).collect();
+        // b: 2.0 * 0.4 + 0.9 = 1.7, a: 2.0 * 0.8 = 1.6, c: 0.7
+        assert_eq!(ranked.len(), 2);
+        assert_eq!(ranked[0].0, "b");
+        assert!((ranked[0].1 - 1.7).abs() < 1e-5);
+        assert_eq!(ranked[1].0, "a");
+        assert!((hits[0].distance - 0.1).abs() < 1e-6);
+    }
+}
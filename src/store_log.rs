@@ -0,0 +1,270 @@
+use crate::encryption::StoreCipher;
+use crate::error::{ChromaError, Result};
+use crate::local_store::{StoredDocument, VectorStore};
+use crate::models::DistanceSpace;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+const DEFAULT_COMPACT_AFTER: usize = 1000;
+
+/// One change to the store, as recorded in the log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum LogOp {
+    Upsert { document: StoredDocument },
+    Remove { id: String },
+}
+
+impl LogOp {
+    /// Both operations are idempotent, so replaying a log over a snapshot
+    /// that already contains some of its changes is harmless.
+    fn apply(self, store: &mut VectorStore) -> Result<()> {
+        match self {
+            LogOp::Upsert { document } => {
+                store.remove_document(&document.id);
+                store.add_document(document)
+            }
+            LogOp::Remove { id } => {
+                store.remove_document(&id);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A `VectorStore` that persists changes incrementally.
+///
+/// Every change is appended to `{path}.log` and synced before it is applied,
+/// so an update costs one line of I/O rather than rewriting the whole store.
+/// Once the log holds `compact_after` operations it is folded into a fresh
+/// snapshot at `path` (written with `save_to_file`) and truncated. Opening
+/// loads the snapshot and replays the log; a torn final line left by a crash
+/// mid-append is discarded. Log lines are encrypted when a store key is
+/// configured, like the snapshot.
+pub struct LoggedStore {
+    store: VectorStore,
+    path: PathBuf,
+    log_path: PathBuf,
+    log: File,
+    log_ops: usize,
+    compact_after: usize,
+    cipher: Option<StoreCipher>,
+}
+
+impl LoggedStore {
+    /// Open the store at `path`, starting empty if neither the snapshot nor
+    /// its log exist yet.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let log_path = log_path(&path);
+        let cipher = StoreCipher::load()?;
+
+        let mut store = if path.exists() {
+            VectorStore::load_from_file(&path.to_string_lossy())?
+        } else {
+            VectorStore::new()
+        };
+
+        let mut log_ops = 0;
+        if log_path.exists() {
+            let data = fs::read(&log_path)?;
+            let complete = data.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+            for (number, line) in data[..complete].split(|b| *b == b'\n').enumerate() {
+                if line.is_empty() {
+                    continue;
+                }
+                decode_op(line, cipher.as_ref())
+                    .map_err(|e| {
+                        ChromaError::ValidationError(format!(
+                            "{} line {} is corrupt: {}",
+                            log_path.display(),
+                            number + 1,
+                            e
+                        ))
+                    })?
+                    .apply(&mut store)?;
+                log_ops += 1;
+            }
+            if complete < data.len() {
+                warn!(
+                    "Discarding {} bytes of an incomplete write at the end of {}",
+                    data.len() - complete,
+                    log_path.display()
+                );
+                OpenOptions::new().write(true).open(&log_path)?.set_len(complete as u64)?;
+            }
+            debug!("Replayed {} operations from {}", log_ops, log_path.display());
+        }
+
+        let log = OpenOptions::new().create(true).append(true).open(&log_path)?;
+        Ok(Self {
+            store,
+            path,
+            log_path,
+            log,
+            log_ops,
+            compact_after: DEFAULT_COMPACT_AFTER,
+            cipher,
+        })
+    }
+
+    /// Compact once the log holds this many operations (default 1000).
+    pub fn with_compact_after(mut self, ops: usize) -> Self {
+        self.compact_after = ops.max(1);
+        self
+    }
+
+    /// Rank by `space`; saved with the next compaction.
+    pub fn with_space(mut self, space: DistanceSpace) -> Self {
+        self.store.space = space;
+        self
+    }
+
+    pub fn store(&self) -> &VectorStore {
+        &self.store
+    }
+
+    /// Operations in the log since the last compaction.
+    pub fn log_len(&self) -> usize {
+        self.log_ops
+    }
+
+    /// Add `doc`, replacing any document with the same id.
+    pub fn upsert(&mut self, doc: StoredDocument) -> Result<()> {
+        // Validate up front so a rejected document never reaches the log.
+        VectorStore::check_document(&doc)?;
+        self.record(LogOp::Upsert { document: doc })
+    }
+
+    /// Remove the document with `id`, returning whether it existed.
+    pub fn remove(&mut self, id: &str) -> Result<bool> {
+        if !self.store.documents.iter().any(|doc| doc.id == id) {
+            return Ok(false);
+        }
+        self.record(LogOp::Remove { id: id.to_string() })?;
+        Ok(true)
+    }
+
+    /// Fold the log into a new snapshot and truncate it.
+    pub fn compact(&mut self) -> Result<()> {
+        self.store.save_to_file(&self.path.to_string_lossy())?;
+        // A crash here leaves the old log beside the new snapshot; replaying
+        // it is a no-op because every operation is idempotent.
+        self.log.set_len(0)?;
+        self.log.sync_data()?;
+        debug!("Compacted {} operations into {}", self.log_ops, self.path.display());
+        self.log_ops = 0;
+        Ok(())
+    }
+
+    fn record(&mut self, op: LogOp) -> Result<()> {
+        let mut line = encode_op(&op, self.cipher.as_ref())?;
+        line.push(b'\n');
+        self.log.write_all(&line)?;
+        self.log.sync_data()?;
+        op.apply(&mut self.store)?;
+        self.log_ops += 1;
+
+        if self.log_ops >= self.compact_after {
+            self.compact()?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for LoggedStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoggedStore")
+            .field("path", &self.path)
+            .field("log_path", &self.log_path)
+            .field("documents", &self.store.documents.len())
+            .field("log_ops", &self.log_ops)
+            .finish()
+    }
+}
+
+fn log_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".log");
+    PathBuf::from(name)
+}
+
+/// A log line: the operation as JSON, or base64 ciphertext when encrypted.
+fn encode_op(op: &LogOp, cipher: Option<&StoreCipher>) -> Result<Vec<u8>> {
+    let json = serde_json::to_vec(op)?;
+    match cipher {
+        Some(cipher) => Ok(BASE64.encode(cipher.encrypt(&json)?).into_bytes()),
+        None => Ok(json),
+    }
+}
+
+fn decode_op(line: &[u8], cipher: Option<&StoreCipher>) -> Result<LogOp> {
+    if line.starts_with(b"{") {
+        return Ok(serde_json::from_slice(line)?);
+    }
+    let cipher = cipher.ok_or_else(|| {
+        ChromaError::EncryptionError("log is encrypted but no store key is configured".to_string())
+    })?;
+    let data = BASE64
+        .decode(line)
+        .map_err(|e| ChromaError::EncryptionError(format!("Invalid log entry: {}", e)))?;
+    Ok(serde_json::from_slice(&cipher.decrypt(&data)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_logged_store_replays_and_compacts() {
+        use std::io::Write;
+
+        let doc = |id: &str, x: f32| StoredDocument {
+            id: id.to_string(),
+            content: format!("content {}", id),
+            embedding: vec![x, 1.0],
+            metadata: HashMap::new(),
+            created_at: chrono::Utc::now(),
+        };
+        let path = std::env::temp_dir().join(format!("store-{}.json", Uuid::new_v4()));
+        let log = std::path::PathBuf::from(format!("{}.log", path.display()));
+
+        let mut store = LoggedStore::open(&path).unwrap();
+        store.upsert(doc("a", 0.0)).unwrap();
+        store.upsert(doc("b", 0.5)).unwrap();
+        store.upsert(doc("a", 1.0)).unwrap();
+        assert!(store.remove("b").unwrap());
+        assert!(!store.remove("missing").unwrap());
+        assert!(store.upsert(doc("bad", f32::NAN)).is_err());
+        drop(store);
+        assert!(!path.exists());
+
+        // A crash in the middle of an append leaves a torn final line.
+        std::fs::OpenOptions::new().append(true).open(&log).unwrap().write_all(b"{\"op\":\"ups").unwrap();
+
+        let store = LoggedStore::open(&path).unwrap();
+        assert_eq!(store.log_len(), 4);
+        assert_eq!(store.store().documents.len(), 1);
+        assert_eq!(store.store().documents[0].embedding, vec![1.0, 1.0]);
+
+        let mut store = store.with_compact_after(5);
+        store.upsert(doc("c", 0.2)).unwrap();
+        assert_eq!(store.log_len(), 0);
+        assert!(path.exists());
+        assert_eq!(std::fs::metadata(&log).unwrap().len(), 0);
+        drop(store);
+
+        let reopened = LoggedStore::open(&path).unwrap();
+        let ids: Vec<&str> = reopened.store().documents.iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "c"]);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&log).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+use crate::chroma_client::ChromaClient;
+use crate::content_hash::{self, CONTENT_HASH_FIELD, ContentHasher};
+use crate::error::Result;
+use crate::models::{Document, Include};
+use crate::snapshot;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+
+const SCAN_PAGE_SIZE: u32 = 1000;
+
+/// What a source holds: document id to content hash, as computed by the
+/// collection's `ContentHasher` over the normalized text.
+pub type Manifest = BTreeMap<String, String>;
+
+/// The manifest of documents about to be synced, hashed with
+/// `ChromaClient::content_hasher`.
+pub fn manifest_of(documents: &[Document], hasher: &ContentHasher) -> Manifest {
+    documents
+        .iter()
+        .map(|d| (d.id.clone(), hasher.hash(&content_hash::normalize_text(&d.content))))
+        .collect()
+}
+
+/// The writes that make a collection mirror a manifest, from
+/// `ChromaClient::diff`. Ids are sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SyncPlan {
+    /// In the manifest but not the collection.
+    pub add: Vec<String>,
+    /// In both, but stored with a different (or no) content hash.
+    pub update: Vec<String>,
+    /// In the collection but no longer in the manifest.
+    pub delete: Vec<String>,
+    /// In both with the same content.
+    pub unchanged: usize,
+}
+
+impl SyncPlan {
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty() && self.update.is_empty() && self.delete.is_empty()
+    }
+
+    /// Ids to (re-)embed and upsert.
+    pub fn writes(&self) -> HashSet<&str> {
+        self.add.iter().chain(&self.update).map(String::as_str).collect()
+    }
+}
+
+/// Compare `manifest` with the ids and `content_hash` metadata stored in
+/// `collection_name`, reading no document text.
+pub(crate) async fn diff(client: &ChromaClient, manifest: &Manifest, collection_name: &str) -> Result<SyncPlan> {
+    let mut plan = SyncPlan::default();
+    let mut seen = HashSet::new();
+
+    snapshot::scan(client, collection_name, SCAN_PAGE_SIZE, vec![Include::Metadatas], |id, _, metadata| {
+        let stored_hash = metadata.and_then(|m| m.get(CONTENT_HASH_FIELD)).and_then(Value::as_str);
+        match manifest.get(&id) {
+            Some(hash) if Some(hash.as_str()) == stored_hash => plan.unchanged += 1,
+            Some(_) => plan.update.push(id.clone()),
+            None => plan.delete.push(id.clone()),
+        }
+        seen.insert(id);
+    })
+    .await?;
+
+    plan.add = manifest.keys().filter(|id| !seen.contains(*id)).cloned().collect();
+    plan.update.sort();
+    plan.delete.sort();
+    Ok(plan)
+}
+
+/// What `Pipeline::apply_plan` wrote.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PlanOutcome {
+    pub added: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::Pipeline;
+    use crate::test_support::{FixedEmbeddings, mock_chroma};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_diff_plans_and_applies_a_mirror_of_the_manifest() {
+        use std::sync::Mutex;
+
+        let doc = |id: &str, content: &str| Document::builder().id(id).content(content).build();
+        let documents = vec![doc("a", "alpha"), doc("b", "beta, edited"), doc("d", "delta")];
+        let manifest = manifest_of(&documents, &ContentHasher::Plain);
+        let stored = serde_json::json!({
+            "ids": ["a", "b", "c"],
+            "metadatas": [
+                {"content_hash": manifest["a"]},
+                {"content_hash": content_hash::content_hash("beta")},
+                {"content_hash": content_hash::content_hash("gamma")},
+            ],
+        })
+        .to_string();
+
+        let writes: Arc<Mutex<Vec<(String, serde_json::Value)>>> = Arc::default();
+        let recorded = writes.clone();
+        let client = mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            if path.ends_with("/get") {
+                stored.clone()
+            } else if path.ends_with("/upsert") || path.ends_with("/delete") {
+                let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                let operation = path.rsplit('/').next().unwrap().to_string();
+                recorded.lock().unwrap().push((operation, body["ids"].clone()));
+                "true".to_string()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+
+        let plan = client.diff(&manifest, "docs").await.unwrap();
+        assert_eq!(
+            plan,
+            SyncPlan {
+                add: vec!["d".to_string()],
+                update: vec!["b".to_string()],
+                delete: vec!["c".to_string()],
+                unchanged: 1,
+            }
+        );
+
+        let pipeline = Pipeline::new(Arc::new(client), Arc::new(FixedEmbeddings), "docs");
+        assert!(pipeline.apply_plan(&plan, documents[..1].to_vec()).await.is_err());
+        let outcome = pipeline.apply_plan(&plan, documents).await.unwrap();
+        assert_eq!((outcome.added, outcome.updated, outcome.deleted), (1, 1, 1));
+        assert_eq!(
+            *writes.lock().unwrap(),
+            vec![
+                ("upsert".to_string(), serde_json::json!(["b", "d"])),
+                ("delete".to_string(), serde_json::json!(["c"])),
+            ]
+        );
+    }
+}
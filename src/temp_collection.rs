@@ -0,0 +1,126 @@
+use crate::chroma_client::ChromaClient;
+use crate::error::Result;
+use crate::models::{CollectionMetadata, CollectionResponse, DistanceSpace};
+use tracing::warn;
+
+const DEFAULT_PREFIX: &str = "tmp";
+
+/// A uniquely named collection that is deleted when the guard goes away,
+/// for tests and self-checks that run against shared servers.
+///
+/// Prefer `close().await`, which deletes the collection before returning
+/// and reports failures. Dropping the guard instead spawns the deletion
+/// on the current Tokio runtime, which may not get to run if the runtime
+/// is shutting down (e.g. at the end of a `#[tokio::test]`); outside a
+/// runtime the collection is left behind with a warning.
+///
+/// ```no_run
+/// # use chromadb_demo::{ChromaClient, TempCollection};
+/// # async fn example(client: ChromaClient) -> chromadb_demo::Result<()> {
+/// let scratch = TempCollection::create(&client).await?;
+/// client.add_documents(scratch.name(), vec![], vec![]).await?;
+/// scratch.close().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct TempCollection {
+    client: ChromaClient,
+    name: String,
+    collection: CollectionResponse,
+    closed: bool,
+}
+
+impl TempCollection {
+    /// Create a cosine-distance collection named `tmp_<random>`.
+    pub async fn create(client: &ChromaClient) -> Result<Self> {
+        Self::create_with_metadata(client, DEFAULT_PREFIX, &CollectionMetadata::new(DistanceSpace::Cosine)).await
+    }
+
+    /// Create a collection named `<prefix>_<random>` with `metadata`.
+    pub async fn create_with_metadata(
+        client: &ChromaClient,
+        prefix: &str,
+        metadata: &CollectionMetadata,
+    ) -> Result<Self> {
+        let name = format!("{}_{}", prefix, uuid::Uuid::new_v4().simple());
+        let collection = client.create_collection_with_metadata(&name, metadata).await?;
+        Ok(Self {
+            client: client.clone(),
+            name,
+            collection,
+            closed: false,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn collection(&self) -> &CollectionResponse {
+        &self.collection
+    }
+
+    /// Delete the collection now.
+    pub async fn close(mut self) -> Result<()> {
+        self.closed = true;
+        self.client.delete_collection(&self.name).await
+    }
+}
+
+impl Drop for TempCollection {
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        let name = self.name.clone();
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                let client = self.client.clone();
+                runtime.spawn(async move {
+                    if let Err(e) = client.delete_collection(&name).await {
+                        warn!("Failed to delete temporary collection {}: {}", name, e);
+                    }
+                });
+            }
+            Err(_) => warn!("Temporary collection {} dropped outside a Tokio runtime; not deleted", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_temp_collection_is_deleted_on_close_and_on_drop() {
+        use std::sync::Mutex;
+
+        let deleted = Arc::new(Mutex::new(Vec::new()));
+        let seen = deleted.clone();
+        let chroma = mock_chroma(move |request| {
+            if request.method() == http::Method::DELETE {
+                seen.lock().unwrap().push(request.uri().path().rsplit('/').next().unwrap().to_string());
+            }
+            r#"{"id": "c0ffee", "name": "x"}"#
+        });
+
+        let closed = TempCollection::create(&chroma).await.unwrap();
+        let closed_name = closed.name().to_string();
+        assert!(closed_name.starts_with("tmp_"));
+        closed.close().await.unwrap();
+        assert_eq!(*deleted.lock().unwrap(), vec![closed_name.clone()]);
+
+        let dropped = TempCollection::create(&chroma).await.unwrap();
+        let dropped_name = dropped.name().to_string();
+        drop(dropped);
+        for _ in 0..100 {
+            if deleted.lock().unwrap().len() == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+        assert_eq!(*deleted.lock().unwrap(), vec![closed_name, dropped_name]);
+    }
+}
@@ -0,0 +1,76 @@
+//! Fixtures shared by the unit tests.
+
+use crate::chroma_client::ChromaClient;
+use crate::embeddings::EmbeddingProvider;
+use crate::error::Result;
+use crate::transport::{BoxError, HttpRequest, HttpResponse, Transport};
+use futures::future::BoxFuture;
+use std::sync::Arc;
+
+/// Base URL of mocked clients; nothing listens there.
+pub(crate) const MOCK_URL: &str = "http://127.0.0.1:9";
+
+/// What a mocked route answers with: a body, a full response, or a
+/// transport failure.
+pub(crate) trait Reply {
+    fn into_reply(self) -> std::result::Result<HttpResponse, BoxError>;
+}
+
+impl Reply for &'static str {
+    fn into_reply(self) -> std::result::Result<HttpResponse, BoxError> {
+        Ok(HttpResponse::new(self.into()))
+    }
+}
+
+impl Reply for String {
+    fn into_reply(self) -> std::result::Result<HttpResponse, BoxError> {
+        Ok(HttpResponse::new(self.into()))
+    }
+}
+
+impl Reply for HttpResponse {
+    fn into_reply(self) -> std::result::Result<HttpResponse, BoxError> {
+        Ok(self)
+    }
+}
+
+impl Reply for std::result::Result<HttpResponse, BoxError> {
+    fn into_reply(self) -> std::result::Result<HttpResponse, BoxError> {
+        self
+    }
+}
+
+/// A transport answering every request with `routes(request)`.
+pub(crate) fn mock_transport<F, R>(routes: F) -> Transport
+where
+    F: Fn(HttpRequest) -> R + Send + Sync + 'static,
+    R: Reply,
+{
+    let routes = Arc::new(routes);
+    Transport::new(tower::service_fn(move |request: HttpRequest| {
+        let reply = routes(request).into_reply();
+        async move { reply }
+    }))
+}
+
+/// A client for `MOCK_URL` whose requests are answered by `routes`.
+pub(crate) fn mock_chroma<F, R>(routes: F) -> ChromaClient
+where
+    F: Fn(HttpRequest) -> R + Send + Sync + 'static,
+    R: Reply,
+{
+    ChromaClient::try_new(MOCK_URL.to_string()).unwrap().with_transport(mock_transport(routes))
+}
+
+/// Embeds each text as its length, so vectors are predictable and one-dimensional.
+pub(crate) struct FixedEmbeddings;
+
+impl EmbeddingProvider for FixedEmbeddings {
+    fn embed<'a>(&'a self, texts: &'a [&'a str]) -> BoxFuture<'a, Result<Vec<Vec<f32>>>> {
+        Box::pin(async move { Ok(texts.iter().map(|t| vec![t.len() as f32]).collect()) })
+    }
+
+    fn model_name(&self) -> &str {
+        "fixed"
+    }
+}
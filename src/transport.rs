@@ -0,0 +1,177 @@
+use crate::error::{ChromaError, Result};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use std::fmt;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Service, ServiceExt};
+
+pub type HttpRequest = http::Request<Bytes>;
+pub type HttpResponse = http::Response<Bytes>;
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+type CallFn = dyn Fn(HttpRequest) -> BoxFuture<'static, std::result::Result<HttpResponse, BoxError>> + Send + Sync;
+
+/// The HTTP transport underneath a client's middleware chain.
+///
+/// Any `tower::Service<http::Request<Bytes>>` can be used, so the same stack
+/// (timeouts, rate limits, retries, a hyper client, a test double) can be
+/// shared by `ChromaClient` and `EmbeddingClient`. Cloning is cheap.
+///
+/// ```
+/// use chromadb_demo::transport::{HttpRequest, HttpResponse, Transport};
+/// use chromadb_demo::ChromaClient;
+///
+/// let stub = tower::service_fn(|_request: HttpRequest| async {
+///     Ok::<_, std::convert::Infallible>(HttpResponse::new("{}".into()))
+/// });
+/// let chroma = ChromaClient::try_new("http://localhost:8000".to_string())?
+///     .with_transport(Transport::new(stub));
+/// # Ok::<(), chromadb_demo::ChromaError>(())
+/// ```
+#[derive(Clone)]
+pub struct Transport {
+    call: Arc<CallFn>,
+}
+
+impl fmt::Debug for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transport").finish_non_exhaustive()
+    }
+}
+
+impl Transport {
+    pub fn new<S>(service: S) -> Self
+    where
+        S: Service<HttpRequest, Response = HttpResponse> + Clone + Send + Sync + 'static,
+        S::Error: Into<BoxError>,
+        S::Future: Send + 'static,
+    {
+        let call = move |request: HttpRequest| -> BoxFuture<'static, std::result::Result<HttpResponse, BoxError>> {
+            let service = service.clone();
+            Box::pin(async move { service.oneshot(request).await.map_err(Into::into) })
+        };
+        Self { call: Arc::new(call) }
+    }
+
+    /// The default transport: send requests with `client`.
+    pub fn reqwest(client: reqwest::Client) -> Self {
+        Self::new(ReqwestService { client })
+    }
+
+    pub(crate) async fn execute(&self, request: reqwest::Request) -> Result<reqwest::Response> {
+        let response = (self.call)(into_http_request(request)?).await.map_err(into_chroma_error)?;
+        Ok(reqwest::Response::from(response))
+    }
+}
+
+/// `tower::Service` adapter for a `reqwest::Client`.
+#[derive(Debug, Clone)]
+pub struct ReqwestService {
+    client: reqwest::Client,
+}
+
+impl ReqwestService {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+impl Service<HttpRequest> for ReqwestService {
+    type Response = HttpResponse;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, std::result::Result<HttpResponse, BoxError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: HttpRequest) -> Self::Future {
+        let client = self.client.clone();
+        Box::pin(async move {
+            let response = client.execute(reqwest::Request::try_from(request)?).await?;
+
+            let mut builder = http::Response::builder().status(response.status());
+            if let Some(headers) = builder.headers_mut() {
+                *headers = response.headers().clone();
+            }
+            let body = response.bytes().await?;
+            let response = builder
+                .body(body)
+                .map_err(|e| ChromaError::TransportError(format!("Invalid response: {}", e)))?;
+            Ok(response)
+        })
+    }
+}
+
+fn into_http_request(request: reqwest::Request) -> Result<HttpRequest> {
+    let body = request
+        .body()
+        .map(|body| {
+            body.as_bytes().map(Bytes::copy_from_slice).ok_or_else(|| {
+                ChromaError::TransportError("Streaming request bodies are not supported".to_string())
+            })
+        })
+        .transpose()?
+        .unwrap_or_default();
+
+    let mut builder = http::Request::builder()
+        .method(request.method().clone())
+        .uri(request.url().as_str());
+    if let Some(headers) = builder.headers_mut() {
+        *headers = request.headers().clone();
+    }
+
+    builder
+        .body(body)
+        .map_err(|e| ChromaError::TransportError(format!("Invalid request: {}", e)))
+}
+
+/// Recover the original error type where possible so retry classification
+/// (timeouts, connection failures) still works through custom stacks. Any
+/// other error from the service means no response arrived, so it is a
+/// `ConnectionError`.
+fn into_chroma_error(error: BoxError) -> ChromaError {
+    let error = match error.downcast::<reqwest::Error>() {
+        Ok(e) => return ChromaError::RequestError(*e),
+        Err(e) => e,
+    };
+    match error.downcast::<ChromaError>() {
+        Ok(e) => *e,
+        Err(e) => ChromaError::ConnectionError(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chroma_client::ChromaClient;
+    use crate::embeddings::EmbeddingClient;
+    use crate::test_support::{MOCK_URL, mock_transport};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_clients_share_a_tower_transport() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let transport = mock_transport(move |request| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            if request.uri().path().ends_with(":embedContent") {
+                r#"{"embedding": {"values": [0.5]}}"#
+            } else {
+                "{}"
+            }
+        });
+
+        let chroma = ChromaClient::try_new(MOCK_URL.to_string()).unwrap().with_transport(transport.clone());
+        let embeddings = EmbeddingClient::try_new("key".to_string())
+            .unwrap()
+            .with_transport(transport)
+            .with_expected_dimension(1);
+
+        assert!(chroma.health_check().await.unwrap());
+        assert_eq!(embeddings.embed_text("hi").await.unwrap(), vec![0.5]);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,438 @@
+use crate::error::{ChromaError, Result};
+use crate::models::AddRequest;
+use crate::vector_ops::{check_vector, VectorDefect};
+use std::ops::Range;
+
+const DEFAULT_MAX_REQUEST_BYTES: usize = 8 * 1024 * 1024;
+const DEFAULT_MAX_BATCH_ITEMS: usize = 1000;
+
+/// Serialized floats are at most ~16 bytes in JSON (`-0.012345678,`).
+const EST_BYTES_PER_FLOAT: usize = 16;
+/// Quotes, commas and brackets around each field of an item.
+const EST_ITEM_OVERHEAD: usize = 32;
+
+/// Offending records listed in an error before the rest are summarised.
+const MAX_LISTED_RECORDS: usize = 10;
+
+/// Client-side limits checked before add requests are sent, so oversized
+/// payloads fail with a precise error instead of an opaque 413/422 from Chroma.
+#[derive(Debug, Clone)]
+pub struct PayloadLimits {
+    /// Estimated JSON body size per request.
+    pub max_request_bytes: usize,
+    /// Records per request.
+    pub max_batch_items: usize,
+    /// Maximum document length in characters, if any.
+    pub max_document_len: Option<usize>,
+    /// Split over-limit requests into several batches instead of rejecting them.
+    pub split_batches: bool,
+}
+
+impl Default for PayloadLimits {
+    fn default() -> Self {
+        Self {
+            max_request_bytes: DEFAULT_MAX_REQUEST_BYTES,
+            max_batch_items: DEFAULT_MAX_BATCH_ITEMS,
+            max_document_len: None,
+            split_batches: true,
+        }
+    }
+}
+
+impl PayloadLimits {
+    pub fn with_max_request_bytes(mut self, max_request_bytes: usize) -> Self {
+        self.max_request_bytes = max_request_bytes;
+        self
+    }
+
+    pub fn with_max_batch_items(mut self, max_batch_items: usize) -> Self {
+        self.max_batch_items = max_batch_items.max(1);
+        self
+    }
+
+    pub fn with_max_document_len(mut self, max_document_len: usize) -> Self {
+        self.max_document_len = Some(max_document_len);
+        self
+    }
+
+    pub fn with_split_batches(mut self, split_batches: bool) -> Self {
+        self.split_batches = split_batches;
+        self
+    }
+
+    /// Check that `request` is aligned and its documents are within
+    /// `max_document_len`. Run it on the plaintext: field encryption makes
+    /// documents longer.
+    pub fn validate(&self, request: &AddRequest) -> Result<()> {
+        request.check_alignment()?;
+
+        if let Some(max_len) = self.max_document_len {
+            let too_long: Vec<String> = request
+                .documents
+                .iter()
+                .enumerate()
+                .filter_map(|(i, doc)| {
+                    let len = doc.chars().count();
+                    let id = request.ids.get(i).map_or("<missing id>", String::as_str);
+                    (len > max_len).then(|| format!("{} ({} chars)", id, len))
+                })
+                .collect();
+
+            if !too_long.is_empty() {
+                return Err(ChromaError::ValidationError(format!(
+                    "{} document(s) exceed the maximum length of {} chars: {}",
+                    too_long.len(), max_len, too_long.join(", ")
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The index ranges to send `request` as, within the per-request byte
+    /// and item limits. Sizes are estimated from the request as given, so
+    /// plan after encrypting.
+    pub fn plan_batches(&self, request: &AddRequest) -> Result<Vec<Range<usize>>> {
+        request.check_alignment()?;
+
+        let mut batches = Vec::new();
+        let mut start = 0;
+        let mut batch_bytes = 0;
+
+        for i in 0..request.ids.len() {
+            let item_bytes = estimate_item_bytes(request, i);
+            if item_bytes > self.max_request_bytes {
+                return Err(ChromaError::ValidationError(format!(
+                    "Record '{}' is ~{} bytes, above the {} byte request limit on its own",
+                    request.ids[i], item_bytes, self.max_request_bytes
+                )));
+            }
+
+            let batch_full = i - start >= self.max_batch_items
+                || batch_bytes + item_bytes > self.max_request_bytes;
+            if batch_full {
+                batches.push(start..i);
+                start = i;
+                batch_bytes = 0;
+            }
+            batch_bytes += item_bytes;
+        }
+
+        if start < request.ids.len() {
+            batches.push(start..request.ids.len());
+        }
+
+        if batches.len() > 1 && !self.split_batches {
+            return Err(ChromaError::ValidationError(format!(
+                "Add request with {} records (~{} bytes) exceeds limits of {} records / {} bytes",
+                request.ids.len(),
+                estimate_request_bytes(request),
+                self.max_batch_items,
+                self.max_request_bytes
+            )));
+        }
+
+        Ok(batches)
+    }
+}
+
+/// Estimated size of the JSON body for `request`.
+pub fn estimate_request_bytes(request: &AddRequest) -> usize {
+    (0..request.ids.len()).map(|i| estimate_item_bytes(request, i)).sum()
+}
+
+fn estimate_item_bytes(request: &AddRequest, index: usize) -> usize {
+    let id = request.ids.get(index).map_or(0, String::len);
+    let document = request.documents.get(index).map_or(0, String::len);
+    let embedding = request.embeddings.get(index).map_or(0, Vec::len) * EST_BYTES_PER_FLOAT;
+    let metadata = request.metadatas.get(index).map_or(0, |m| {
+        m.iter().map(|(k, v)| k.len() + v.len() + 6).sum::<usize>()
+    });
+    let uri = request
+        .uris
+        .as_ref()
+        .and_then(|u| u.get(index))
+        .map_or(0, |u| u.as_deref().map_or(0, str::len));
+
+    id + document + embedding + metadata + uri + EST_ITEM_OVERHEAD
+}
+
+impl AddRequest {
+    /// Check that ids, documents, metadatas, embeddings and any uris line up
+    /// one to one and that every embedding has the same dimension, only
+    /// finite values and a non-zero norm. Errors name the offending indices
+    /// and ids.
+    pub fn check_alignment(&self) -> Result<()> {
+        let count = self.ids.len();
+        let lengths = [
+            ("documents", self.documents.len()),
+            ("metadatas", self.metadatas.len()),
+            ("embeddings", self.embeddings.len()),
+            ("uris", self.uris.as_ref().map_or(count, Vec::len)),
+        ];
+        let misaligned: Vec<String> = lengths
+            .iter()
+            .filter(|(_, len)| *len != count)
+            .map(|(field, len)| format!("{} {}", len, field))
+            .collect();
+        if !misaligned.is_empty() {
+            let longest = lengths.iter().map(|(_, len)| *len).max().unwrap_or(0).max(count);
+            let incomplete: Vec<usize> = (0..longest)
+                .filter(|&i| i >= count || lengths.iter().any(|(_, len)| i >= *len))
+                .collect();
+            return Err(ChromaError::ValidationError(format!(
+                "Misaligned request: {} ids but {}; incomplete records at {}",
+                count,
+                misaligned.join(", "),
+                self.describe_indices(&incomplete)
+            )));
+        }
+
+        let Some(expected) = self.embeddings.first().map(Vec::len) else {
+            return Ok(());
+        };
+        if expected == 0 {
+            return Err(ChromaError::ValidationError(format!(
+                "Empty embedding for record '{}'", self.ids[0]
+            )));
+        }
+        let wrong_dimension: Vec<usize> = (0..count).filter(|&i| self.embeddings[i].len() != expected).collect();
+        if !wrong_dimension.is_empty() {
+            return Err(ChromaError::ValidationError(format!(
+                "Embedding dimension mismatch: expected {} (from record '{}'), got other dimensions at {}",
+                expected,
+                self.ids[0],
+                self.describe_indices(&wrong_dimension)
+            )));
+        }
+
+        for (defect, description) in [
+            (VectorDefect::NonFinite, "contain NaN or infinite values"),
+            (VectorDefect::Zero, "are all zeros"),
+        ] {
+            let defective: Vec<usize> =
+                (0..count).filter(|&i| check_vector(&self.embeddings[i]) == Some(defect)).collect();
+            if !defective.is_empty() {
+                return Err(ChromaError::ValidationError(format!(
+                    "Embeddings {} at {}",
+                    description,
+                    self.describe_indices(&defective)
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `indices [2 ('c'), 5 ('f')]`, truncated after a few entries.
+    fn describe_indices(&self, indices: &[usize]) -> String {
+        let mut listed: Vec<String> = indices
+            .iter()
+            .take(MAX_LISTED_RECORDS)
+            .map(|&i| match self.ids.get(i) {
+                Some(id) => format!("{} ('{}')", i, id),
+                None => i.to_string(),
+            })
+            .collect();
+        if indices.len() > MAX_LISTED_RECORDS {
+            listed.push(format!("and {} more", indices.len() - MAX_LISTED_RECORDS));
+        }
+        format!("indices [{}]", listed.join(", "))
+    }
+}
+
+/// Shortest collection name Chroma accepts.
+pub const MIN_COLLECTION_NAME_LEN: usize = 3;
+/// Longest collection name Chroma accepts.
+pub const MAX_COLLECTION_NAME_LEN: usize = 512;
+
+/// Check `name` against Chroma's collection naming rules, so a bad name
+/// fails with the rule it breaks instead of a bare 422:
+///
+/// - 3 to 512 characters of `[a-zA-Z0-9._-]`;
+/// - starts and ends with a letter or digit;
+/// - no two consecutive periods;
+/// - not an IPv4 address.
+pub fn validate_collection_name(name: &str) -> Result<()> {
+    let invalid = |reason: String| -> Result<()> {
+        Err(ChromaError::ValidationError(format!("Invalid collection name '{}': {}", name, reason)))
+    };
+
+    let len = name.chars().count();
+    if !(MIN_COLLECTION_NAME_LEN..=MAX_COLLECTION_NAME_LEN).contains(&len) {
+        return invalid(format!(
+            "must be {} to {} characters long, got {}",
+            MIN_COLLECTION_NAME_LEN, MAX_COLLECTION_NAME_LEN, len
+        ));
+    }
+    let mut bad: Vec<char> = name.chars().filter(|c| !is_name_char(*c)).collect();
+    if !bad.is_empty() {
+        bad.dedup();
+        let listed: Vec<String> = bad.iter().map(|c| format!("{:?}", c)).collect();
+        return invalid(format!("only letters, digits, '.', '_' and '-' are allowed, found {}", listed.join(", ")));
+    }
+    if !name.starts_with(|c: char| c.is_ascii_alphanumeric()) || !name.ends_with(|c: char| c.is_ascii_alphanumeric()) {
+        return invalid("must start and end with a letter or digit".to_string());
+    }
+    if name.contains("..") {
+        return invalid("must not contain two consecutive periods".to_string());
+    }
+    if name.parse::<std::net::Ipv4Addr>().is_ok() {
+        return invalid("must not be an IPv4 address".to_string());
+    }
+    Ok(())
+}
+
+/// Turn an arbitrary label (a tenant name, a file name) into a valid
+/// collection name: disallowed characters become `_` (runs collapse to
+/// one), repeated periods collapse, leading and trailing separators are
+/// trimmed and the result is cut to the maximum length. Fails if nothing
+/// usable is left, rather than inventing a name.
+pub fn normalize_collection_name(name: &str) -> Result<String> {
+    let mut normalized = String::with_capacity(name.len());
+    for c in name.trim().chars() {
+        let c = if is_name_char(c) { c } else { '_' };
+        let previous = normalized.chars().last();
+        if (c == '_' || c == '.') && previous == Some(c) {
+            continue;
+        }
+        normalized.push(c);
+    }
+
+    let trimmed: String = normalized
+        .trim_matches(|c: char| !c.is_ascii_alphanumeric())
+        .chars()
+        .take(MAX_COLLECTION_NAME_LEN)
+        .collect();
+    let normalized = trimmed.trim_end_matches(|c: char| !c.is_ascii_alphanumeric()).to_string();
+    validate_collection_name(&normalized).map_err(|e| {
+        ChromaError::ValidationError(format!("Cannot make a collection name out of '{}': {}", name, e))
+    })?;
+    Ok(normalized)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chroma_client::ChromaClient;
+    use crate::test_support::{MOCK_URL, mock_chroma};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_payload_limits_split_and_reject() {
+        let request = AddRequest {
+            ids: (0..5).map(|i| format!("doc-{}", i)).collect(),
+            embeddings: vec![vec![0.1; 8]; 5],
+            metadatas: vec![HashMap::new(); 5],
+            documents: vec!["short".to_string(); 5],
+            uris: None,
+        };
+
+        let limits = PayloadLimits::default().with_max_batch_items(2);
+        assert_eq!(limits.plan_batches(&request).unwrap(), vec![0..2, 2..4, 4..5]);
+
+        let strict = limits.with_split_batches(false);
+        assert!(matches!(strict.plan_batches(&request), Err(ChromaError::ValidationError(_))));
+
+        let max_len = PayloadLimits::default().with_max_document_len(3);
+        assert!(matches!(max_len.validate(&request), Err(ChromaError::ValidationError(_))));
+        assert_eq!(max_len.plan_batches(&request).unwrap(), vec![0..5]);
+    }
+
+    #[tokio::test]
+    async fn test_document_length_is_checked_before_encryption() {
+        use crate::encryption::{FieldEncryption, StoreCipher};
+        use crate::models::Document;
+        use std::sync::{Arc, Mutex};
+
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let recorded = sent.clone();
+        let encryption = FieldEncryption::new(StoreCipher::from_base64(&StoreCipher::generate_key()).unwrap());
+        let chroma = mock_chroma(move |request| {
+            if request.uri().path().ends_with("/add") {
+                let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                recorded.lock().unwrap().push(body["documents"][0].as_str().unwrap().to_string());
+            }
+            r#"{"id": "c0ffee", "name": "docs"}"#
+        })
+        .with_field_encryption(encryption)
+        .with_payload_limits(PayloadLimits::default().with_max_document_len(17));
+
+        let document = |content: &str| Document {
+            id: "doc-1".to_string(),
+            content: content.to_string(),
+            metadata: HashMap::new(),
+            uri: None,
+        };
+        chroma.add_documents("docs", vec![document("confidential text")], vec![vec![0.1]]).await.unwrap();
+        let sent = sent.lock().unwrap().clone();
+        assert!(sent[0].len() > 17 && !sent[0].contains("confidential"), "{:?}", sent);
+
+        let error = chroma.add_documents("docs", vec![document("confidential texts")], vec![vec![0.1]]).await;
+        assert!(matches!(error, Err(ChromaError::ValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_collection_names_are_validated_and_normalized() {
+        for valid in ["docs", "docs_v2", "a.b-c", "docs__title", "3rd-party"] {
+            assert!(validate_collection_name(valid).is_ok(), "{}", valid);
+        }
+        for (invalid, reason) in [
+            ("ab", "3 to 512"),
+            ("my docs", "' '"),
+            ("_docs", "start and end"),
+            ("docs.", "start and end"),
+            ("a..b", "consecutive periods"),
+            ("10.0.0.1", "IPv4"),
+        ] {
+            let error = validate_collection_name(invalid).unwrap_err().to_string();
+            assert!(error.contains(reason), "{}: {}", invalid, error);
+        }
+
+        assert_eq!(normalize_collection_name(" Q3 reports / EU ").unwrap(), "Q3_reports_EU");
+        assert_eq!(normalize_collection_name("..notes...v2--").unwrap(), "notes.v2");
+        assert_eq!(normalize_collection_name(&"x".repeat(600)).unwrap().len(), 512);
+        assert!(normalize_collection_name("é!").is_err());
+
+        // Rejected before any request is sent.
+        let chroma = ChromaClient::try_new(MOCK_URL.to_string()).unwrap();
+        let error = chroma.create_collection("my docs").await.unwrap_err();
+        assert!(matches!(error, ChromaError::ValidationError(_)), "{}", error);
+    }
+
+    #[test]
+    fn test_add_request_alignment_names_offending_records() {
+        let mut request = AddRequest {
+            ids: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            embeddings: vec![vec![0.1; 4], vec![0.2; 4]],
+            metadatas: vec![HashMap::new(); 3],
+            documents: vec!["x".to_string(); 3],
+            uris: None,
+        };
+        let error = request.check_alignment().unwrap_err().to_string();
+        assert!(error.contains("3 ids but 2 embeddings"), "{}", error);
+        assert!(error.contains("2 ('c')"), "{}", error);
+        let wanted = ["b".to_string(), "c".to_string()].into();
+        assert!(request.select(&wanted).is_err());
+
+        request.embeddings.push(vec![0.3; 3]);
+        let error = request.check_alignment().unwrap_err().to_string();
+        assert!(error.contains("expected 4") && error.contains("[2 ('c')]"), "{}", error);
+
+        request.embeddings[2] = vec![0.3, f32::NAN, 0.3, 0.3];
+        assert!(request.check_alignment().unwrap_err().to_string().contains("[2 ('c')]"));
+
+        request.embeddings[2] = vec![0.0; 4];
+        let error = request.check_alignment().unwrap_err().to_string();
+        assert!(error.contains("all zeros") && error.contains("[2 ('c')]"), "{}", error);
+
+        request.embeddings[2] = vec![0.3; 4];
+        assert!(request.check_alignment().is_ok());
+        let selected = request.select(&wanted).unwrap();
+        assert_eq!(selected.ids, vec!["b", "c"]);
+        assert_eq!(selected.embeddings, vec![vec![0.2; 4], vec![0.3; 4]]);
+    }
+}
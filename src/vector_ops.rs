@@ -0,0 +1,86 @@
+use crate::models::DistanceSpace;
+use crate::score::Score;
+use std::fmt;
+
+/// Product of norms below which a vector pair is treated as zero-length;
+/// dividing by anything smaller only amplifies rounding noise.
+const MIN_NORM_PRODUCT: f32 = 1e-12;
+
+/// Why a vector can't take part in similarity search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorDefect {
+    Empty,
+    /// Contains NaN or an infinity, usually from a corrupt or truncated
+    /// embedding.
+    NonFinite,
+    /// All zeros (or close enough): it has no direction, so cosine similarity
+    /// is undefined.
+    Zero,
+}
+
+impl fmt::Display for VectorDefect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            VectorDefect::Empty => "empty vector",
+            VectorDefect::NonFinite => "vector contains NaN or infinite values",
+            VectorDefect::Zero => "zero vector",
+        })
+    }
+}
+
+/// First problem that makes `v` unusable for cosine similarity, if any.
+pub fn check_vector(v: &[f32]) -> Option<VectorDefect> {
+    if v.is_empty() {
+        Some(VectorDefect::Empty)
+    } else if v.iter().any(|x| !x.is_finite()) {
+        Some(VectorDefect::NonFinite)
+    } else if v.iter().map(|x| x * x).sum::<f32>() < MIN_NORM_PRODUCT {
+        Some(VectorDefect::Zero)
+    } else {
+        None
+    }
+}
+
+/// Cosine similarity, clamped to `[-1, 1]`.
+///
+/// Returns 0.0 when either vector is (near) zero, and NaN when either
+/// contains NaN or infinities; check inputs with `check_vector` where that
+/// matters.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot_product = dot_product(a, b);
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norm_a * norm_b < MIN_NORM_PRODUCT {
+        0.0
+    } else {
+        (dot_product / (norm_a * norm_b)).clamp(-1.0, 1.0)
+    }
+}
+
+pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+}
+
+pub fn squared_euclidean(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+/// Distance between `a` and `b` as Chroma computes it for `space`: squared
+/// Euclidean for `l2`, `1 - a·b` for `ip` and `1 - cos` for `cosine`.
+pub fn distance(space: DistanceSpace, a: &[f32], b: &[f32]) -> f32 {
+    match space {
+        DistanceSpace::L2 => squared_euclidean(a, b),
+        DistanceSpace::Ip => 1.0 - dot_product(a, b),
+        DistanceSpace::Cosine => 1.0 - cosine_similarity(a, b),
+    }
+}
+
+/// Higher-is-better score for `space`, `1 - distance`, the same scale as
+/// `QueryHit::score` for a Chroma collection using that space.
+pub fn similarity(space: DistanceSpace, a: &[f32], b: &[f32]) -> f32 {
+    match space {
+        DistanceSpace::Cosine => cosine_similarity(a, b),
+        space => Score::distance(space, distance(space, a, b)).to_similarity(),
+    }
+}
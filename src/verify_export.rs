@@ -0,0 +1,185 @@
+use crate::compression;
+use crate::error::{ChromaError, Result};
+use crate::export::RecordParser;
+use crate::shards::{MANIFEST_FILE, ShardInfo, ShardManifest};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// What `verify_export` found in one export file.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileCheck {
+    pub file: String,
+    pub records: usize,
+    /// From the manifest, for shards.
+    pub expected_records: Option<usize>,
+    pub checksum: String,
+    /// From the manifest, for shards exported with checksums.
+    pub expected_checksum: Option<String>,
+    /// Ids of records failing their checksum, or `line <n>` for lines that
+    /// don't parse.
+    pub damaged: Vec<String>,
+}
+
+impl FileCheck {
+    pub fn is_ok(&self) -> bool {
+        self.damaged.is_empty()
+            && self.expected_records.is_none_or(|expected| expected == self.records)
+            && self.expected_checksum.as_ref().is_none_or(|expected| *expected == self.checksum)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportVerification {
+    pub files: Vec<FileCheck>,
+}
+
+impl ExportVerification {
+    pub fn is_ok(&self) -> bool {
+        self.files.iter().all(FileCheck::is_ok)
+    }
+
+    pub fn records(&self) -> usize {
+        self.files.iter().map(|f| f.records).sum()
+    }
+}
+
+/// Re-read an export without touching Chroma: every record's checksum and,
+/// for a sharded export directory, each shard's file checksum and record
+/// count against the manifest. Run it before deleting the source
+/// collection.
+pub fn verify_export(path: &Path) -> Result<ExportVerification> {
+    if !path.is_dir() {
+        let file = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        return Ok(ExportVerification { files: vec![check_records(path, file, None)?] });
+    }
+
+    let manifest = ShardManifest::read(path)?;
+    let files = manifest
+        .shards
+        .iter()
+        .map(|shard| check_records(&path.join(&shard.file), shard.file.clone(), Some(shard)))
+        .collect::<Result<_>>()?;
+    Ok(ExportVerification { files })
+}
+
+/// Fail if `path` doesn't match the checksum `shard` lists for it.
+pub(crate) fn check_file(path: &Path, shard: &ShardInfo) -> Result<()> {
+    if shard.checksum.is_empty() {
+        return Ok(());
+    }
+    let checksum = file_checksum(path)?;
+    if checksum != shard.checksum {
+        return Err(ChromaError::ValidationError(format!(
+            "{} has checksum {}, but {} lists {}",
+            shard.file, checksum, MANIFEST_FILE, shard.checksum
+        )));
+    }
+    Ok(())
+}
+
+fn check_records(path: &Path, file: String, shard: Option<&ShardInfo>) -> Result<FileCheck> {
+    let mut records = 0;
+    let mut damaged = Vec::new();
+    let mut parser = RecordParser::default();
+    let lines = BufReader::new(compression::decoder(BufReader::new(File::open(path)?))?).lines();
+    for (number, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records += 1;
+        match parser.parse(&line)? {
+            Ok(record) if record.checksum_matches()? => {}
+            Ok(record) => damaged.push(record.id),
+            Err(_) => damaged.push(format!("line {}", number + 1)),
+        }
+    }
+
+    Ok(FileCheck {
+        file,
+        records,
+        expected_records: shard.map(|s| s.records),
+        checksum: file_checksum(path)?,
+        expected_checksum: shard.map(|s| s.checksum.clone()).filter(|c| !c.is_empty()),
+        damaged,
+    })
+}
+
+/// CRC-32 of the file's bytes as stored, matching `ExportReport::checksum`.
+fn file_checksum(path: &Path) -> Result<String> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            return Ok(format!("{:08x}", hasher.finalize()));
+        }
+        hasher.update(&buffer[..n]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::ExportConfig;
+    use crate::import::ImportConfig;
+    use crate::test_support::mock_chroma;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_export_checksums_catch_damaged_records_and_shards() {
+        let client = mock_chroma(|request| {
+            let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap_or_default();
+            if request.uri().path().ends_with("/get") {
+                let ids: Vec<String> = match body["ids"].as_array() {
+                    Some(ids) => ids.iter().filter_map(|v| v.as_str()).map(String::from).collect(),
+                    None if body["offset"] == 0 => (0..4).map(|i| format!("r{}", i)).collect(),
+                    None => Vec::new(),
+                };
+                serde_json::json!({
+                    "ids": ids,
+                    "documents": ids.iter().map(|id| format!("text of {}", id)).collect::<Vec<_>>(),
+                    "embeddings": ids.iter().map(|_| vec![0.5; 2]).collect::<Vec<_>>(),
+                })
+                .to_string()
+            } else if request.uri().path().ends_with("/count") {
+                "4".to_string()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+        let dir = std::env::temp_dir().join(format!("verify-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let file = dir.join("docs.jsonl");
+        let out = std::fs::File::create(&file).unwrap();
+        let report = client.export("docs", out, &ExportConfig::default()).await.unwrap();
+        let verification = verify_export(&file).unwrap();
+        assert!(verification.is_ok());
+        assert_eq!(verification.records(), 4);
+        assert_eq!(verification.files[0].checksum, report.checksum);
+
+        let text = std::fs::read_to_string(&file).unwrap();
+        std::fs::write(&file, text.replace("text of r2", "text of r9")).unwrap();
+        let verification = verify_export(&file).unwrap();
+        assert!(!verification.is_ok());
+        assert_eq!(verification.files[0].damaged, vec!["r2"]);
+        let error = client.import_file("docs", &file, &ImportConfig::default()).await.unwrap_err();
+        assert!(error.to_string().contains("r2"));
+
+        let shards = dir.join("shards");
+        let manifest = client.export_sharded("docs", &shards, 2, &ExportConfig::default()).await.unwrap();
+        assert!(verify_export(&shards).unwrap().is_ok());
+        let shard = shards.join(&manifest.shards[0].file);
+        let mut bytes = std::fs::read(&shard).unwrap();
+        bytes.push(b'\n');
+        std::fs::write(&shard, bytes).unwrap();
+        let verification = verify_export(&shards).unwrap();
+        assert!(!verification.is_ok() && verification.files[0].damaged.is_empty());
+        assert!(client.import_sharded("docs", &shards, &ImportConfig::default()).await.is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+use crate::chroma_client::ChromaClient;
+use crate::chunking::Chunker;
+use crate::error::Result;
+use crate::models::{Document, Include, PARENT_ID_FIELD};
+use crate::snapshot;
+use serde::Serialize;
+use serde_json::Value;
+
+const SCAN_PAGE_SIZE: u32 = 1000;
+
+/// Metadata key naming the pipeline version that wrote a record.
+pub const PIPELINE_VERSION_FIELD: &str = "pipeline_version";
+/// Metadata key holding `Chunker::config_hash` of the chunker that cut a
+/// record, or `UNCHUNKED`.
+pub const CHUNKER_CONFIG_HASH_FIELD: &str = "chunker_config_hash";
+/// `chunker_config_hash` of records ingested whole.
+pub const UNCHUNKED: &str = "none";
+
+/// Stamped by pipelines that don't set `Pipeline::with_pipeline_version`.
+pub const PIPELINE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// How records written today would be stamped, to compare stored records
+/// against with `ChromaClient::find_outdated`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RecordVersion {
+    pub pipeline_version: String,
+    pub chunker_config_hash: String,
+}
+
+impl RecordVersion {
+    /// Records written by `pipeline_version`, cut by `chunker` (or ingested
+    /// whole without one).
+    pub fn new(pipeline_version: &str, chunker: Option<&Chunker>) -> Self {
+        Self {
+            pipeline_version: pipeline_version.to_string(),
+            chunker_config_hash: chunker.map_or_else(|| UNCHUNKED.to_string(), Chunker::config_hash),
+        }
+    }
+}
+
+/// A record stamped by another pipeline version or chunker configuration.
+/// Stamps are `None` for records written before stamping.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutdatedRecord {
+    pub id: String,
+    /// The logical document to re-chunk, for chunks.
+    pub parent_id: Option<String>,
+    pub pipeline_version: Option<String>,
+    pub chunker_config_hash: Option<String>,
+}
+
+/// Records of a collection that don't match the expected `RecordVersion`,
+/// sorted by id.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OutdatedReport {
+    pub scanned: usize,
+    pub outdated: Vec<OutdatedRecord>,
+}
+
+impl OutdatedReport {
+    /// The documents to re-ingest: parent ids for chunks, record ids
+    /// otherwise. Sorted and deduplicated.
+    pub fn document_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .outdated
+            .iter()
+            .map(|r| r.parent_id.clone().unwrap_or_else(|| r.id.clone()))
+            .collect();
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+}
+
+/// Record which pipeline wrote `document`. Chunks already carry their
+/// chunker's hash from `Chunker::split`; anything else is marked unchunked.
+pub(crate) fn stamp(document: &mut Document, pipeline_version: &str) {
+    document.metadata.insert(PIPELINE_VERSION_FIELD.to_string(), pipeline_version.to_string());
+    document
+        .metadata
+        .entry(CHUNKER_CONFIG_HASH_FIELD.to_string())
+        .or_insert_with(|| UNCHUNKED.to_string());
+}
+
+/// Scan the metadata of `collection_name` for records not stamped with
+/// `expected`.
+pub(crate) async fn find_outdated(
+    client: &ChromaClient,
+    collection_name: &str,
+    expected: &RecordVersion,
+) -> Result<OutdatedReport> {
+    let mut report = OutdatedReport::default();
+
+    snapshot::scan(client, collection_name, SCAN_PAGE_SIZE, vec![Include::Metadatas], |id, _, metadata| {
+        report.scanned += 1;
+        let field = |key: &str| metadata.and_then(|m| m.get(key)).and_then(Value::as_str).map(str::to_string);
+        let pipeline_version = field(PIPELINE_VERSION_FIELD);
+        let chunker_config_hash = field(CHUNKER_CONFIG_HASH_FIELD);
+        if pipeline_version.as_deref() != Some(expected.pipeline_version.as_str())
+            || chunker_config_hash.as_deref() != Some(expected.chunker_config_hash.as_str())
+        {
+            report.outdated.push(OutdatedRecord {
+                id,
+                parent_id: field(PARENT_ID_FIELD),
+                pipeline_version,
+                chunker_config_hash,
+            });
+        }
+    })
+    .await?;
+
+    report.outdated.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::Pipeline;
+    use crate::test_support::{FixedEmbeddings, mock_chroma};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_records_are_version_stamped_and_outdated_ones_found() {
+        use std::sync::Mutex;
+
+        let chunker = Chunker::new(20, 6);
+        let hash = chunker.config_hash();
+        assert_ne!(hash, Chunker::new(20, 5).config_hash());
+        let stored = serde_json::json!({
+            "ids": ["a#0", "a#1", "b#0", "c"],
+            "metadatas": [
+                {"parent_id": "a", "pipeline_version": "2", "chunker_config_hash": hash},
+                {"parent_id": "a", "pipeline_version": "1", "chunker_config_hash": hash},
+                {"parent_id": "b", "pipeline_version": "2", "chunker_config_hash": "0123456789abcdef"},
+                {"source": "notes"},
+            ],
+        })
+        .to_string();
+        let upserts: Arc<Mutex<Vec<serde_json::Value>>> = Arc::default();
+        let recorded = upserts.clone();
+        let chroma = mock_chroma(move |request| {
+            let path = request.uri().path().to_string();
+            if path.ends_with("/get") {
+                stored.clone()
+            } else if path.ends_with("/upsert") {
+                let body: serde_json::Value = serde_json::from_slice(request.body()).unwrap();
+                recorded.lock().unwrap().push(body["metadatas"].clone());
+                "true".to_string()
+            } else if path.ends_with("/delete") {
+                "true".to_string()
+            } else {
+                r#"{"id": "c0ffee", "name": "docs"}"#.to_string()
+            }
+        });
+        let pipeline = Pipeline::new(Arc::new(chroma), Arc::new(FixedEmbeddings), "docs").with_pipeline_version("2");
+
+        let long = Document::builder().id("a").content("alpha beta gamma delta epsilon zeta eta theta").build();
+        pipeline.ingest_chunked(vec![long], &chunker).await.unwrap();
+        pipeline.ingest(vec![Document::builder().id("c").content("whole").build()]).await.unwrap();
+        let upserts = upserts.lock().unwrap().clone();
+        let chunks = upserts[0].as_array().unwrap();
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|m| m["pipeline_version"] == "2" && m["chunker_config_hash"] == hash.as_str()));
+        assert_eq!(upserts[1][0]["chunker_config_hash"], UNCHUNKED);
+
+        let report = pipeline.outdated_records(Some(&chunker)).await.unwrap();
+        assert_eq!(report.scanned, 4);
+        let ids: Vec<&str> = report.outdated.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["a#1", "b#0", "c"]);
+        assert_eq!(report.outdated[2].pipeline_version, None);
+        assert_eq!(report.document_ids(), vec!["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,275 @@
+use crate::error::Result;
+use crate::middleware::{Middleware, Next, current_attempt};
+use futures::future::BoxFuture;
+use reqwest::header::HeaderMap;
+use reqwest::{Request, Response};
+use serde_json::Value;
+use std::time::Instant;
+use tracing::debug;
+use url::Url;
+
+/// `tracing` target of wire log events, e.g. `RUST_LOG=chromadb_demo::wire=debug`.
+pub const WIRE_LOG_TARGET: &str = "chromadb_demo::wire";
+
+const DEFAULT_MAX_BODY_BYTES: usize = 2048;
+const REDACTED: &str = "[redacted]";
+
+/// Header, query parameter and JSON field names whose values are never
+/// logged. Matched case-insensitively against the whole name, so fields
+/// like `max_tokens` stay visible; `key` is Gemini's `?key=`.
+const SECRET_NAMES: &[&str] = &[
+    "authorization", "proxy-authorization", "cookie", "set-cookie", "x-chroma-token", "x-goog-api-key", "x-api-key",
+    "api-key", "api_key", "apikey", "key", "token", "access_token", "refresh_token", "secret", "client_secret",
+    "password",
+];
+
+/// Middleware logging a summary of every request and response at debug
+/// level: method, URL, status, body sizes, the start of each body, retry
+/// attempt and latency. Credentials in headers, query strings and JSON
+/// bodies are redacted before anything is logged.
+///
+/// Enable it on both clients with `WIRE_LOG=1` (and optionally
+/// `WIRE_LOG_MAX_BODY_BYTES`), or add it explicitly with `with_middleware`.
+/// Responses are buffered in memory to log them, so leave it off for bulk
+/// traffic.
+#[derive(Debug, Clone)]
+pub struct WireLog {
+    max_body_bytes: usize,
+}
+
+impl Default for WireLog {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+        }
+    }
+}
+
+impl WireLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bytes of each body to log; the rest is replaced by a size marker.
+    pub fn with_max_body_bytes(mut self, max_body_bytes: usize) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self
+    }
+
+    /// `Some` when `WIRE_LOG` is set to `1` or `true`.
+    pub fn from_env() -> Option<Self> {
+        let enabled = std::env::var("WIRE_LOG")
+            .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true"))
+            .unwrap_or(false);
+        if !enabled {
+            return None;
+        }
+
+        let max_body_bytes = std::env::var("WIRE_LOG_MAX_BODY_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_BODY_BYTES);
+        Some(Self::new().with_max_body_bytes(max_body_bytes))
+    }
+
+    fn preview(&self, body: &[u8]) -> String {
+        let redacted = match serde_json::from_slice::<Value>(body) {
+            Ok(mut json) => {
+                redact_json(&mut json);
+                json.to_string()
+            }
+            Err(_) => String::from_utf8_lossy(body).to_string(),
+        };
+
+        if redacted.len() <= self.max_body_bytes {
+            return redacted;
+        }
+        let mut end = self.max_body_bytes;
+        while !redacted.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}… ({} bytes total)", &redacted[..end], body.len())
+    }
+}
+
+impl Middleware for WireLog {
+    fn handle<'a>(&'a self, request: Request, next: Next<'a>) -> BoxFuture<'a, Result<Response>> {
+        Box::pin(async move {
+            let method = request.method().clone();
+            let url = redact_url(request.url());
+            let attempt = current_attempt();
+            let request_body = request.body().and_then(|b| b.as_bytes()).unwrap_or_default();
+            debug!(
+                target: WIRE_LOG_TARGET,
+                %method,
+                %url,
+                attempt,
+                headers = ?redact_headers(request.headers()),
+                request_bytes = request_body.len(),
+                body = %self.preview(request_body),
+                "sending request"
+            );
+
+            let started = Instant::now();
+            let response = match next.run(request).await {
+                Ok(response) => response,
+                Err(e) => {
+                    debug!(
+                        target: WIRE_LOG_TARGET,
+                        %method, %url, attempt, elapsed_ms = started.elapsed().as_millis() as u64, error = %e,
+                        "request failed"
+                    );
+                    return Err(e);
+                }
+            };
+
+            let status = response.status();
+            let version = response.version();
+            let headers = response.headers().clone();
+            let body = response.bytes().await?;
+            debug!(
+                target: WIRE_LOG_TARGET,
+                %method,
+                %url,
+                attempt,
+                status = status.as_u16(),
+                elapsed_ms = started.elapsed().as_millis() as u64,
+                response_bytes = body.len(),
+                body = %self.preview(&body),
+                "received response"
+            );
+
+            let mut rebuilt = http::Response::new(reqwest::Body::from(body));
+            *rebuilt.status_mut() = status;
+            *rebuilt.version_mut() = version;
+            *rebuilt.headers_mut() = headers;
+            Ok(Response::from(rebuilt))
+        })
+    }
+}
+
+pub(crate) fn is_secret(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SECRET_NAMES.contains(&name.as_str())
+}
+
+/// `url` with credential query parameters and userinfo replaced.
+pub(crate) fn redact_url(url: &Url) -> String {
+    let mut url = url.clone();
+    let _ = url.set_password(None);
+    if url.query().is_some() {
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(k, v)| {
+                (k.to_string(), if is_secret(&k) { REDACTED.to_string() } else { v.to_string() })
+            })
+            .collect();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    url.to_string()
+}
+
+fn redact_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if is_secret(name.as_str()) {
+                REDACTED.to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).to_string()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+pub(crate) fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if is_secret(key) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact_json(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    use crate::middleware;
+    use crate::query::QueryOptions;
+    use crate::test_support::mock_chroma;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_wire_log_redacts_secrets() {
+        let url = url::Url::parse("https://api.example.com/v1/models:embed?key=AIza123&alt=json").unwrap();
+        let redacted = redact_url(&url);
+        assert!(!redacted.contains("AIza123"));
+        assert!(redacted.contains("alt=json"));
+
+        let mut body = serde_json::json!({
+            "api_key": "s3cret",
+            "nested": [{"Token": "t"}],
+            "text": "hello",
+            "max_tokens": 256,
+            "tokenizer": "cl100k",
+        });
+        redact_json(&mut body);
+        assert_eq!(body["api_key"], "[redacted]");
+        assert_eq!(body["nested"][0]["Token"], "[redacted]");
+        assert_eq!(body["text"], "hello");
+        assert_eq!(body["max_tokens"], 256);
+        assert_eq!(body["tokenizer"], "cl100k");
+        assert!(is_secret("X-Chroma-Token") && is_secret("Authorization"));
+    }
+
+    #[tokio::test]
+    async fn test_wire_log_passes_responses_through_retries() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Mutex;
+        use crate::transport::{BoxError, HttpResponse};
+
+        struct RecordAttempts(Arc<Mutex<Vec<u32>>>);
+
+        impl Middleware for RecordAttempts {
+            fn handle<'a>(
+                &'a self,
+                request: reqwest::Request,
+                next: Next<'a>,
+            ) -> futures::future::BoxFuture<'a, Result<reqwest::Response>> {
+                self.0.lock().unwrap().push(middleware::current_attempt());
+                next.run(request)
+            }
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let call_count = calls.clone();
+        let attempts = Arc::new(Mutex::new(Vec::new()));
+        let client = mock_chroma(move |request| {
+            let result: std::result::Result<HttpResponse, BoxError> = if !request.uri().path().ends_with("/query") {
+                Ok(HttpResponse::new(r#"{"id": "c0ffee", "name": "docs"}"#.into()))
+            } else if call_count.fetch_add(1, Ordering::SeqCst) == 0 {
+                Err("connection reset by peer".into())
+            } else {
+                Ok(HttpResponse::new(r#"{"ids": [["a", "b"]], "distances": [[0.1, 0.2]]}"#.into()))
+            };
+            result
+        })
+        .with_retries(2, std::time::Duration::from_millis(1))
+        .with_middleware(WireLog::new().with_max_body_bytes(8))
+        .with_middleware(RecordAttempts(attempts.clone()));
+
+        let hits = client.query_with_options("docs", vec![0.0], &QueryOptions::new(2)).await.unwrap();
+        assert_eq!(hits.len(), 2);
+        // Collection lookup, then the failed query and its retry.
+        assert_eq!(*attempts.lock().unwrap(), vec![0, 0, 1]);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::error::{ChromaError, Result};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// Runs CPU-bound and blocking stages (chunking, hashing, reading source
+/// files) on Tokio's blocking thread pool instead of inline on the async
+/// runtime, so a large ingestion doesn't starve the tasks handling HTTP I/O.
+///
+/// At most `size` jobs run at once; `WORKER_THREADS` sets the size of the
+/// default pool (default: one per CPU). Clones share the limit.
+#[derive(Debug, Clone)]
+pub struct WorkerPool {
+    permits: Arc<Semaphore>,
+    size: usize,
+}
+
+impl Default for WorkerPool {
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+impl WorkerPool {
+    pub fn new(size: usize) -> Self {
+        let size = size.max(1);
+        Self {
+            permits: Arc::new(Semaphore::new(size)),
+            size,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let size = std::env::var("WORKER_THREADS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(4, usize::from));
+        Self::new(size)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Run `job` on a worker thread, waiting for a free slot first. A panic
+    /// in `job` is resumed on the caller.
+    pub async fn run<F, T>(&self, job: F) -> Result<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|e| ChromaError::IoError(std::io::Error::other(e)))?;
+        match tokio::task::spawn_blocking(job).await {
+            Ok(value) => Ok(value),
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) => Err(ChromaError::IoError(std::io::Error::other(e))),
+        }
+    }
+
+    /// Apply `f` to every item, spreading the items over the pool's workers.
+    /// Results keep the input order.
+    pub async fn map<T, R, F>(&self, items: Vec<T>, f: F) -> Result<Vec<R>>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        if items.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let f = Arc::new(f);
+        let per_worker = items.len().div_ceil(self.size);
+        let mut items = items.into_iter();
+        let mut jobs = Vec::new();
+        loop {
+            let batch: Vec<T> = items.by_ref().take(per_worker).collect();
+            if batch.is_empty() {
+                break;
+            }
+            let f = f.clone();
+            jobs.push(self.run(move || batch.into_iter().map(&*f).collect::<Vec<R>>()));
+        }
+
+        let batches = futures::future::try_join_all(jobs).await?;
+        Ok(batches.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunking::Chunker;
+    use crate::models::Document;
+
+    #[tokio::test]
+    async fn test_worker_pool_keeps_order_off_the_runtime() {
+        let pool = WorkerPool::new(3);
+        let runtime_thread = std::thread::current().id();
+
+        let squares = pool.map((0..10).collect(), |n: u64| n * n).await.unwrap();
+        assert_eq!(squares, (0..10).map(|n| n * n).collect::<Vec<_>>());
+
+        let worker_thread = pool.run(|| std::thread::current().id()).await.unwrap();
+        assert_ne!(worker_thread, runtime_thread);
+
+        let chunker = Chunker::new(10, 0);
+        let docs = vec![Document::builder().id("a").content("one two three four five six").build()];
+        let chunks = pool.map(docs, move |d| chunker.split(&d)).await.unwrap();
+        assert!(chunks[0].len() > 1);
+    }
+}
@@ -0,0 +1,93 @@
+//! Resilience tests driven by the fault-injecting `ChaosProxy`.
+
+use chromadb_demo::chaos::{ChaosProxy, Fault};
+use chromadb_demo::{ChromaClient, ChromaError, EmbeddingClient};
+use std::time::Duration;
+
+const HEARTBEAT: &str = "/api/v2/heartbeat";
+const COLLECTION: &str = "/collections/docs";
+
+async fn proxy_with_chroma_stubs() -> ChaosProxy {
+    let proxy = ChaosProxy::start(None).await.unwrap();
+    proxy.stub(HEARTBEAT, 200, r#"{"nanosecond heartbeat": 1}"#);
+    proxy.stub("/query", 200, r#"{"ids": [["a"]], "documents": [["doc"]], "metadatas": [[null]], "distances": [[0.1]]}"#);
+    proxy.stub(COLLECTION, 200, r#"{"id": "c0ffee", "name": "docs", "metadata": null}"#);
+    proxy
+}
+
+fn client(proxy: &ChaosProxy, max_retries: u32) -> ChromaClient {
+    ChromaClient::try_new(proxy.url()).unwrap().with_retries(max_retries, Duration::from_millis(5))
+}
+
+#[tokio::test]
+async fn retries_through_transient_server_errors() {
+    let proxy = proxy_with_chroma_stubs().await;
+    proxy.inject(Fault::Status(503));
+    proxy.inject(Fault::Status(502));
+
+    assert!(client(&proxy, 3).health_check().await.unwrap());
+    assert_eq!(proxy.requests().len(), 3);
+    assert_eq!(proxy.pending_faults(), 0);
+}
+
+#[tokio::test]
+async fn gives_up_after_max_retries() {
+    let proxy = proxy_with_chroma_stubs().await;
+    proxy.inject_times(Fault::Status(500), 5);
+
+    let result = client(&proxy, 2).health_check().await;
+    assert!(matches!(result, Err(ChromaError::ApiError(_))));
+    assert_eq!(proxy.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn client_errors_are_not_retried() {
+    let proxy = proxy_with_chroma_stubs().await;
+    proxy.inject(Fault::Status(400));
+
+    assert!(client(&proxy, 3).health_check().await.is_err());
+    assert_eq!(proxy.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn malformed_json_surfaces_as_error() {
+    let proxy = proxy_with_chroma_stubs().await;
+    let chroma = client(&proxy, 3);
+
+    // First request resolves the collection id, the second is the query itself
+    proxy.inject(Fault::Delay(Duration::from_millis(1)));
+    proxy.inject(Fault::MalformedJson);
+
+    let result = chroma.query("docs", vec![vec![1.0, 0.0]], 1).await;
+    assert!(result.is_err());
+    assert_eq!(proxy.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn slow_responses_still_succeed() {
+    let proxy = proxy_with_chroma_stubs().await;
+    proxy.inject(Fault::Delay(Duration::from_millis(50)));
+
+    let hits = client(&proxy, 0).query("docs", vec![vec![1.0, 0.0]], 1).await.unwrap();
+    assert_eq!(hits.ids[0], vec!["a"]);
+}
+
+#[tokio::test]
+async fn embedding_client_uses_configured_endpoint() {
+    let proxy = ChaosProxy::start(None).await.unwrap();
+    proxy.stub(":embedContent", 200, r#"{"embedding": {"values": [0.1, 0.2]}}"#);
+
+    let client = EmbeddingClient::try_new("test-key".to_string())
+        .unwrap()
+        .with_base_url(&proxy.url())
+        .unwrap()
+        .with_api_version("v1")
+        .with_model("text-embedding-004")
+        .with_expected_dimension(2);
+
+    assert_eq!(client.embed_text("hello").await.unwrap(), vec![0.1, 0.2]);
+    assert_eq!(
+        proxy.requests(),
+        vec!["POST /v1/models/text-embedding-004:embedContent?key=test-key".to_string()]
+    );
+}
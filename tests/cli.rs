@@ -0,0 +1,44 @@
+//! `chroma-cli` commands that never talk to Chroma must run without a
+//! usable CHROMA_HOST or a server behind it.
+
+use std::process::{Command, Output};
+use uuid::Uuid;
+
+const BAD_HOST: &str = "not a url";
+
+fn chroma_cli(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_chroma-cli"))
+        .args(args)
+        .env("CHROMA_HOST", BAD_HOST)
+        .env_remove("RUST_LOG")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn client_commands_fail_on_the_bad_host() {
+    let output = chroma_cli(&["health"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(BAD_HOST));
+}
+
+#[test]
+fn completions_and_man_pages_need_no_host() {
+    let output = chroma_cli(&["completions", "bash"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("chroma-cli"));
+
+    let output = chroma_cli(&["man"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains(".TH"));
+}
+
+#[test]
+fn verify_export_needs_no_host() {
+    let path = std::env::temp_dir().join(format!("export-{}.jsonl", Uuid::new_v4()));
+    std::fs::write(&path, "").unwrap();
+
+    let output = chroma_cli(&["verify-export", path.to_str().unwrap(), "--output", "json"]);
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
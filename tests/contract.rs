@@ -0,0 +1,93 @@
+//! Contract tests: check that server responses match the crate's models.
+//!
+//! The stubbed tests run offline. The live ones validate real Chroma and
+//! Gemini responses in `SchemaMode::Strict`, so upstream API changes show up
+//! as a named unexpected field rather than silently dropped or zeroed data:
+//!
+//! ```bash
+//! CHROMA_HOST=http://localhost:8000 GOOGLE_API_KEY=... cargo test --test contract -- --ignored
+//! ```
+
+use chromadb_demo::chaos::ChaosProxy;
+use chromadb_demo::{ChromaClient, ChromaError, Document, EmbeddingClient, SchemaMode, TempCollection};
+use std::collections::HashMap;
+use std::time::Duration;
+
+const COLLECTION: &str = "/collections/docs";
+const QUERY_WITH_EXTRA_FIELD: &str = r#"{"ids": [["a"]], "documents": [["doc"]], "metadatas": [[null]], "distances": [[0.1]], "scores": [[0.9]]}"#;
+
+async fn proxy_with_drifted_query() -> ChaosProxy {
+    let proxy = ChaosProxy::start(None).await.unwrap();
+    proxy.stub("/query", 200, QUERY_WITH_EXTRA_FIELD);
+    proxy.stub(COLLECTION, 200, r#"{"id": "c0ffee", "name": "docs", "metadata": null}"#);
+    proxy
+}
+
+fn client(proxy: &ChaosProxy, mode: SchemaMode) -> ChromaClient {
+    ChromaClient::try_new(proxy.url())
+        .unwrap()
+        .with_retries(0, Duration::from_millis(5))
+        .with_schema_mode(mode)
+}
+
+#[tokio::test]
+async fn strict_mode_rejects_unknown_fields() {
+    let proxy = proxy_with_drifted_query().await;
+
+    let result = client(&proxy, SchemaMode::Strict).query("docs", vec![vec![0.1]], 1).await;
+    match result {
+        Err(ChromaError::SchemaError(message)) => assert!(message.contains("scores")),
+        other => panic!("expected a schema error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn warn_mode_accepts_unknown_fields() {
+    let proxy = proxy_with_drifted_query().await;
+
+    let response = client(&proxy, SchemaMode::Warn).query("docs", vec![vec![0.1]], 1).await.unwrap();
+    assert_eq!(response.ids, vec![vec!["a".to_string()]]);
+}
+
+#[tokio::test]
+async fn mistyped_fields_are_schema_errors() {
+    let proxy = ChaosProxy::start(None).await.unwrap();
+    proxy.stub("/query", 200, r#"{"ids": [["a"]], "distances": [["near"]]}"#);
+    proxy.stub(COLLECTION, 200, r#"{"id": "c0ffee", "name": "docs", "metadata": null}"#);
+
+    let result = client(&proxy, SchemaMode::Off).query("docs", vec![vec![0.1]], 1).await;
+    assert!(matches!(result, Err(ChromaError::SchemaError(_))));
+}
+
+#[tokio::test]
+#[ignore = "requires a running Chroma server (CHROMA_HOST)"]
+async fn live_chroma_responses_match_models() {
+    let host = std::env::var("CHROMA_HOST").unwrap_or_else(|_| "http://localhost:8000".to_string());
+    let client = ChromaClient::try_new(host).unwrap().with_schema_mode(SchemaMode::Strict);
+    let scratch = TempCollection::create(&client).await.unwrap();
+    let name = scratch.name().to_string();
+    client.get_collection(&name).await.unwrap();
+
+    let doc = Document {
+        id: "contract-1".to_string(),
+        content: "contract test document".to_string(),
+        metadata: HashMap::from([("source".to_string(), "contract".to_string())]),
+        uri: None,
+    };
+    client.add_documents(&name, vec![doc], vec![vec![0.1, 0.2, 0.3]]).await.unwrap();
+
+    client.query(&name, vec![vec![0.1, 0.2, 0.3]], 1).await.unwrap();
+    client.get_documents(&name, None, None, Some(10)).await.unwrap();
+
+    scratch.close().await.unwrap();
+}
+
+#[tokio::test]
+#[ignore = "requires GOOGLE_API_KEY"]
+async fn live_gemini_response_matches_model() {
+    let api_key = std::env::var("GOOGLE_API_KEY").expect("GOOGLE_API_KEY must be set");
+    let client = EmbeddingClient::try_new(api_key).unwrap().with_schema_mode(SchemaMode::Strict);
+
+    let embedding = client.embed_text("contract test").await.unwrap();
+    assert_eq!(embedding.len(), EmbeddingClient::get_embedding_dimension());
+}
@@ -0,0 +1,90 @@
+use crate::{doc, start_chroma, unique_collection};
+use chromadb_demo::{CollectionMetadata, DistanceSpace, TempCollection};
+use testcontainers::clients::Cli;
+
+#[tokio::test]
+#[ignore = "requires Docker"]
+async fn collection_lifecycle() {
+    let docker = Cli::default();
+    let server = start_chroma(&docker).await;
+    let chroma = &server.client;
+    let name = unique_collection("lifecycle");
+
+    let created = chroma.create_collection(&name).await.unwrap();
+    assert_eq!(created.name, name);
+
+    let fetched = chroma.get_collection(&name).await.unwrap();
+    assert_eq!(fetched.id, created.id);
+
+    chroma.delete_collection(&name).await.unwrap();
+    assert!(chroma.get_collection(&name).await.is_err());
+}
+
+#[tokio::test]
+#[ignore = "requires Docker"]
+async fn collection_metadata() {
+    let docker = Cli::default();
+    let server = start_chroma(&docker).await;
+    let chroma = &server.client;
+    let metadata = CollectionMetadata::new(DistanceSpace::L2)
+        .with_description("integration test")
+        .with_created_by("tests");
+    let scratch = TempCollection::create_with_metadata(chroma, "metadata", &metadata).await.unwrap();
+    let name = scratch.name().to_string();
+
+    let fetched = chroma.get_collection(&name).await.unwrap().metadata.unwrap();
+    assert_eq!(fetched.space, Some(DistanceSpace::L2));
+    assert_eq!(fetched.description.as_deref(), Some("integration test"));
+
+    let update = CollectionMetadata::default().with_description("renamed");
+    chroma.modify_collection(&name, None, Some(&update)).await.unwrap();
+    let fetched = chroma.get_collection(&name).await.unwrap().metadata.unwrap();
+    assert_eq!(fetched.description.as_deref(), Some("renamed"));
+
+    scratch.close().await.unwrap();
+}
+
+#[tokio::test]
+#[ignore = "requires Docker"]
+async fn document_crud() {
+    let docker = Cli::default();
+    let server = start_chroma(&docker).await;
+    let chroma = &server.client;
+    let scratch = TempCollection::create(chroma).await.unwrap();
+    let name = scratch.name().to_string();
+
+    let docs = vec![
+        doc("a", "first document", &[("kind", "note")]),
+        doc("b", "second document", &[("kind", "note")]),
+        doc("c", "third document", &[("kind", "memo")]),
+    ];
+    let embeddings = vec![vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0], vec![0.0, 0.0, 1.0]];
+    chroma.add_documents(&name, docs, embeddings).await.unwrap();
+    assert_eq!(chroma.count(&name).await.unwrap(), 3);
+
+    let fetched = chroma
+        .get_documents(&name, Some(vec!["b".to_string()]), None, None)
+        .await
+        .unwrap();
+    assert_eq!(fetched.ids[0], vec!["b"]);
+    assert_eq!(fetched.document(0, 0), Some("second document"));
+
+    chroma
+        .update_documents(
+            &name,
+            vec![doc("b", "second document, revised", &[("kind", "note")])],
+            vec![vec![0.0, 1.0, 0.0]],
+        )
+        .await
+        .unwrap();
+    let fetched = chroma
+        .get_documents(&name, Some(vec!["b".to_string()]), None, None)
+        .await
+        .unwrap();
+    assert_eq!(fetched.document(0, 0), Some("second document, revised"));
+
+    chroma.delete_documents(&name, vec!["a".to_string()]).await.unwrap();
+    assert_eq!(chroma.count(&name).await.unwrap(), 2);
+
+    scratch.close().await.unwrap();
+}
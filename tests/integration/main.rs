@@ -0,0 +1,67 @@
+//! End-to-end tests against a real ChromaDB server started with testcontainers.
+//!
+//! These need a running Docker daemon and are ignored by default:
+//!
+//! ```bash
+//! cargo test --test integration -- --ignored
+//! ```
+//!
+//! Set `CHROMA_TEST_IMAGE` to test against a different `chromadb/chroma` tag.
+
+mod crud;
+mod query;
+
+use chromadb_demo::{ChromaClient, Document};
+use std::collections::HashMap;
+use std::time::Duration;
+use testcontainers::clients::Cli;
+use testcontainers::core::WaitFor;
+use testcontainers::{Container, GenericImage};
+
+const CHROMA_IMAGE: &str = "chromadb/chroma";
+const DEFAULT_CHROMA_TAG: &str = "1.0.0";
+const CHROMA_PORT: u16 = 8000;
+
+/// A Chroma container plus a client pointed at it. The container is removed
+/// when this is dropped.
+pub struct ChromaServer<'d> {
+    _container: Container<'d, GenericImage>,
+    pub client: ChromaClient,
+}
+
+pub async fn start_chroma(docker: &Cli) -> ChromaServer<'_> {
+    let tag = std::env::var("CHROMA_TEST_IMAGE").unwrap_or_else(|_| DEFAULT_CHROMA_TAG.to_string());
+    let image = GenericImage::new(CHROMA_IMAGE.to_string(), tag)
+        .with_exposed_port(CHROMA_PORT)
+        .with_wait_for(WaitFor::Nothing);
+
+    let container = docker.run(image);
+    let url = format!("http://127.0.0.1:{}", container.get_host_port_ipv4(CHROMA_PORT));
+    let client = ChromaClient::try_new(url).unwrap();
+
+    for _ in 0..60 {
+        if matches!(client.health_check().await, Ok(true)) {
+            return ChromaServer { _container: container, client };
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    panic!("ChromaDB container did not become healthy in time");
+}
+
+/// Collection name that won't collide with other tests sharing a server.
+pub fn unique_collection(prefix: &str) -> String {
+    format!("{}_{}", prefix, uuid::Uuid::new_v4().simple())
+}
+
+pub fn doc(id: &str, content: &str, metadata: &[(&str, &str)]) -> Document {
+    Document {
+        id: id.to_string(),
+        content: content.to_string(),
+        metadata: metadata
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>(),
+        uri: None,
+    }
+}
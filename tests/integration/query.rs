@@ -0,0 +1,73 @@
+use crate::{doc, start_chroma};
+use chromadb_demo::{QueryOptions, TempCollection};
+use serde_json::json;
+use testcontainers::clients::Cli;
+
+#[tokio::test]
+#[ignore = "requires Docker"]
+async fn query_ranks_and_filters() {
+    let docker = Cli::default();
+    let server = start_chroma(&docker).await;
+    let chroma = &server.client;
+    let scratch = TempCollection::create(chroma).await.unwrap();
+    let name = scratch.name().to_string();
+
+    let docs = vec![
+        doc("rust", "Rust is a systems language", &[("category", "programming")]),
+        doc("python", "Python is a scripting language", &[("category", "programming")]),
+        doc("docker", "Docker packages applications", &[("category", "devops")]),
+    ];
+    let embeddings = vec![vec![1.0, 0.1, 0.0], vec![0.8, 0.3, 0.0], vec![0.0, 0.2, 1.0]];
+    chroma.add_documents(&name, docs, embeddings).await.unwrap();
+
+    let results = chroma.query(&name, vec![vec![1.0, 0.0, 0.0]], 2).await.unwrap();
+    assert_eq!(results.ids[0], vec!["rust", "python"]);
+
+    let filtered = chroma
+        .query_with_filter(&name, vec![vec![1.0, 0.0, 0.0]], 3, Some(json!({"category": "devops"})))
+        .await
+        .unwrap();
+    assert_eq!(filtered.ids[0], vec!["docker"]);
+
+    let hits = chroma
+        .query_with_options(&name, vec![1.0, 0.0, 0.0], &QueryOptions::new(2).with_not_ids(["rust"]))
+        .await
+        .unwrap();
+    let ids: Vec<&str> = hits.iter().map(|h| h.id.as_str()).collect();
+    assert_eq!(ids, vec!["python", "docker"]);
+
+    scratch.close().await.unwrap();
+}
+
+#[tokio::test]
+#[ignore = "requires Docker"]
+async fn scoped_collection_isolates_owners() {
+    let docker = Cli::default();
+    let server = start_chroma(&docker).await;
+    let chroma = &server.client;
+    let scratch = TempCollection::create(chroma).await.unwrap();
+    let name = scratch.name().to_string();
+
+    let alice = chroma.scoped_to(&name, "alice");
+    let bob = chroma.scoped_to(&name, "bob");
+    alice
+        .add_documents(vec![doc("a1", "alice's note", &[])], vec![vec![1.0, 0.0, 0.0]])
+        .await
+        .unwrap();
+    bob.add_documents(vec![doc("b1", "bob's note", &[])], vec![vec![1.0, 0.0, 0.0]])
+        .await
+        .unwrap();
+
+    let hits = alice
+        .query_with_options(vec![1.0, 0.0, 0.0], &QueryOptions::new(10))
+        .await
+        .unwrap();
+    let ids: Vec<&str> = hits.iter().map(|h| h.id.as_str()).collect();
+    assert_eq!(ids, vec!["a1"]);
+
+    // Alice cannot delete Bob's document
+    alice.delete_documents(vec!["b1".to_string()]).await.unwrap();
+    assert_eq!(chroma.count(&name).await.unwrap(), 2);
+
+    scratch.close().await.unwrap();
+}
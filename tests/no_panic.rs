@@ -0,0 +1,127 @@
+//! Constructors and builders take configuration that often comes straight
+//! from the environment or user input: they must reject bad values with an
+//! error, never panic.
+
+use chromadb_demo::{ChromaClient, EmbeddingClient};
+use proptest::prelude::*;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// URLs and names that are malformed in the ways configuration tends to be.
+const HOSTILE: &[&str] = &[
+    "",
+    " ",
+    "\0",
+    "http://",
+    "http://[",
+    "http://[::1",
+    "http://host:99999",
+    "http://%00/",
+    "http://user@:80",
+    "ftp://localhost:8000",
+    "localhost:8000",
+    "//localhost",
+    "http://localhost:8000/\u{202e}",
+    "http://\u{1f980}.example/",
+    "https://localhost\r\nX-Injected: 1",
+];
+
+/// Variables the constructors read their configuration from.
+const ENV_VARS: &[&str] = &[
+    "CHROMA_FAILOVER_HOSTS",
+    "CHROMA_READ_REPLICAS",
+    "CHROMA_ENDPOINT_COOLDOWN_SECS",
+    "CHROMA_TENANT",
+    "CHROMA_DATABASE",
+    "CLIENT_APP_ID",
+    "CONNECTION_TIMEOUT_MS",
+    "REQUEST_TIMEOUT_MS",
+    "MAX_RETRIES",
+    "RETRY_DELAY_MS",
+    "GEMINI_API_BASE",
+    "GEMINI_API_VERSION",
+    "GEMINI_EMBEDDING_MODEL",
+    "GEMINI_EMBEDDING_DIMENSION",
+    "EMBEDDING_STRICT_DIMENSIONS",
+];
+
+/// Held while constructing clients, so that no test reads the environment
+/// while another one is changing it.
+static ENV: Mutex<()> = Mutex::new(());
+
+fn hostile() -> impl Strategy<Value = String> {
+    prop_oneof![
+        any::<String>(),
+        proptest::sample::select(HOSTILE).prop_map(str::to_string),
+        any::<String>().prop_map(|s| format!("http://{}", s)),
+        "[ -~]{0,64}".prop_map(|s| format!("https://{}:{}", s, s)),
+    ]
+}
+
+fn check_chroma_client(input: &str) {
+    let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    let _ = ChromaClient::try_new(input.to_string());
+    let Ok(client) = ChromaClient::try_new("http://localhost:8000".to_string()) else {
+        return;
+    };
+    let _ = client.clone().with_app_id(input);
+    let _ = client.clone().with_failover_endpoints(&[input]);
+    let _ = client.clone().with_read_replicas(&[input, input]);
+    let _ = client.with_retries(u32::MAX, Duration::MAX);
+}
+
+fn check_embedding_client(input: &str) {
+    let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    let _ = EmbeddingClient::try_new(input.to_string());
+    let Ok(client) = EmbeddingClient::try_new("test-key".to_string()) else {
+        return;
+    };
+    let _ = client.clone().with_base_url(input);
+    let _ = client.clone().with_app_id(input);
+    let _ = client.with_model(input).with_api_version(input).with_expected_dimension(usize::MAX);
+}
+
+fn check_environment(input: &str) {
+    // The environment can't hold NUL bytes at all.
+    if input.contains('\0') {
+        return;
+    }
+    let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    for key in ENV_VARS {
+        // SAFETY: the environment is only read or written while `ENV` is held.
+        unsafe { std::env::set_var(key, input) };
+        let _ = ChromaClient::try_new("http://localhost:8000".to_string());
+        let _ = EmbeddingClient::try_new("test-key".to_string());
+        unsafe { std::env::remove_var(key) };
+    }
+}
+
+#[test]
+fn constructors_reject_known_bad_configuration_without_panicking() {
+    for input in HOSTILE {
+        check_chroma_client(input);
+        check_embedding_client(input);
+        check_environment(input);
+    }
+    let long = format!("http://{}.example", "a".repeat(100_000));
+    check_chroma_client(&long);
+    check_embedding_client(&long);
+    check_environment(&long);
+}
+
+proptest! {
+    #[test]
+    fn chroma_client_constructors_never_panic(input in hostile()) {
+        check_chroma_client(&input);
+    }
+
+    #[test]
+    fn embedding_client_constructors_never_panic(input in hostile()) {
+        check_embedding_client(&input);
+    }
+
+    #[test]
+    fn malformed_environment_never_panics(input in hostile()) {
+        check_environment(&input);
+    }
+}
@@ -0,0 +1,179 @@
+//! Property-based round-trip tests for wire models, the filter DSL and
+//! metadata conversions.
+
+use chromadb_demo::filter::{Comparison, Filter, MetadataValue};
+use chromadb_demo::{AddRequest, Document, GetRequest, QueryCursor, QueryHit, QueryRequest};
+use proptest::collection::{hash_map, vec};
+use proptest::prelude::*;
+use std::collections::HashMap;
+
+/// Any unicode text, including control characters, combining marks and emoji.
+fn text() -> impl Strategy<Value = String> {
+    any::<String>()
+}
+
+/// Floats that JSON can represent: everything except NaN and infinities.
+fn finite_f32() -> impl Strategy<Value = f32> {
+    prop::num::f32::NORMAL | prop::num::f32::SUBNORMAL | prop::num::f32::ZERO
+}
+
+fn finite_f64() -> impl Strategy<Value = f64> {
+    prop::num::f64::NORMAL | prop::num::f64::SUBNORMAL | prop::num::f64::ZERO
+}
+
+fn metadata() -> impl Strategy<Value = HashMap<String, String>> {
+    hash_map(text(), text(), 0..6)
+}
+
+fn metadata_value() -> impl Strategy<Value = MetadataValue> {
+    prop_oneof![
+        text().prop_map(MetadataValue::Str),
+        any::<i64>().prop_map(MetadataValue::Int),
+        finite_f64().prop_map(MetadataValue::Float),
+        any::<bool>().prop_map(MetadataValue::Bool),
+    ]
+}
+
+fn comparison() -> impl Strategy<Value = Comparison> {
+    prop_oneof![
+        Just(Comparison::Eq),
+        Just(Comparison::Ne),
+        Just(Comparison::Gt),
+        Just(Comparison::Gte),
+        Just(Comparison::Lt),
+        Just(Comparison::Lte),
+    ]
+}
+
+/// Metadata keys must not start with `$`, which is reserved for operators.
+fn key() -> impl Strategy<Value = String> {
+    text().prop_filter("operator-like key", |k| !k.starts_with('$'))
+}
+
+fn filter() -> impl Strategy<Value = Filter> {
+    let leaf = prop_oneof![
+        (key(), comparison(), metadata_value()).prop_map(|(k, op, v)| Filter::Compare(k, op, v)),
+        (key(), vec(metadata_value(), 0..4)).prop_map(|(k, v)| Filter::In(k, v)),
+        (key(), vec(metadata_value(), 0..4)).prop_map(|(k, v)| Filter::NotIn(k, v)),
+    ];
+
+    // Groups need at least two operands; single-operand groups are unwrapped
+    leaf.prop_recursive(3, 16, 4, |inner| {
+        prop_oneof![
+            vec(inner.clone(), 2..4).prop_map(Filter::And),
+            vec(inner, 2..4).prop_map(Filter::Or),
+        ]
+    })
+}
+
+proptest! {
+    #[test]
+    fn document_round_trips(id in text(), content in text(), metadata in metadata()) {
+        let doc = Document { id, content, metadata, uri: None };
+        let json = serde_json::to_string(&doc).unwrap();
+        let parsed: Document = serde_json::from_str(&json).unwrap();
+
+        prop_assert_eq!(parsed.id, doc.id);
+        prop_assert_eq!(parsed.content, doc.content);
+        prop_assert_eq!(parsed.metadata, doc.metadata);
+    }
+
+    #[test]
+    fn add_request_round_trips(
+        rows in vec((text(), vec(finite_f32(), 0..16), metadata(), text()), 0..8)
+    ) {
+        let request = AddRequest {
+            ids: rows.iter().map(|r| r.0.clone()).collect(),
+            embeddings: rows.iter().map(|r| r.1.clone()).collect(),
+            metadatas: rows.iter().map(|r| r.2.clone()).collect(),
+            documents: rows.iter().map(|r| r.3.clone()).collect(),
+            uris: None,
+        };
+        let json = serde_json::to_string(&request).unwrap();
+        let parsed: AddRequest = serde_json::from_str(&json).unwrap();
+
+        prop_assert_eq!(parsed.ids, request.ids);
+        prop_assert_eq!(parsed.embeddings, request.embeddings);
+        prop_assert_eq!(parsed.metadatas, request.metadatas);
+        prop_assert_eq!(parsed.documents, request.documents);
+    }
+
+    #[test]
+    fn query_request_uses_where_key(embedding in vec(finite_f32(), 1..16), n in any::<u32>(), f in filter()) {
+        let where_filter = f.to_json().unwrap();
+        let request = QueryRequest {
+            query_embeddings: vec![embedding.clone()],
+            n_results: n,
+            where_filter: Some(where_filter.clone()),
+            include: None,
+        };
+        let json = serde_json::to_value(&request).unwrap();
+
+        prop_assert_eq!(&json["where"], &where_filter);
+        prop_assert!(json.get("where_filter").is_none());
+        prop_assert!(json.get("include").is_none());
+
+        let parsed: QueryRequest = serde_json::from_value(json).unwrap();
+        prop_assert_eq!(parsed.query_embeddings, vec![embedding]);
+        prop_assert_eq!(parsed.n_results, n);
+        prop_assert_eq!(parsed.where_filter, Some(where_filter));
+    }
+
+    #[test]
+    fn get_request_omits_unset_fields(limit in any::<u32>(), offset in any::<u32>(), f in filter()) {
+        let where_filter = f.to_json().unwrap();
+        let request = GetRequest {
+            where_filter: Some(where_filter.clone()),
+            limit: Some(limit),
+            offset: Some(offset),
+            ..GetRequest::default()
+        };
+        let json = serde_json::to_value(&request).unwrap();
+
+        prop_assert_eq!(&json["where"], &where_filter);
+        prop_assert_eq!(&json["offset"], &serde_json::json!(offset));
+        prop_assert!(json.get("ids").is_none());
+        prop_assert!(json.get("include").is_none());
+    }
+
+    #[test]
+    fn query_hit_and_cursor_round_trip(
+        id in text(),
+        document in proptest::option::of(text()),
+        distance in finite_f32(),
+        seen_ids in vec(text(), 0..8),
+    ) {
+        let hit = QueryHit { id, document, metadata: None, distance, score: 1.0 - distance, uri: None };
+        let parsed: QueryHit = serde_json::from_str(&serde_json::to_string(&hit).unwrap()).unwrap();
+        prop_assert_eq!(&parsed.id, &hit.id);
+        prop_assert_eq!(&parsed.document, &hit.document);
+        prop_assert_eq!(parsed.distance, hit.distance);
+
+        let cursor = QueryCursor { seen_ids };
+        let parsed: QueryCursor = serde_json::from_str(&serde_json::to_string(&cursor).unwrap()).unwrap();
+        prop_assert_eq!(parsed.seen_ids, cursor.seen_ids);
+    }
+
+    #[test]
+    fn filter_round_trips_through_json(f in filter()) {
+        let json = f.to_json().unwrap();
+        prop_assert_eq!(Filter::from_json(&json).unwrap(), f.clone());
+
+        // And through the wire format, not just the in-memory Value
+        let wire = serde_json::to_string(&json).unwrap();
+        let reparsed: serde_json::Value = serde_json::from_str(&wire).unwrap();
+        prop_assert_eq!(Filter::from_json(&reparsed).unwrap(), f);
+    }
+
+    #[test]
+    fn metadata_value_round_trips(value in metadata_value()) {
+        let json = value.to_json().unwrap();
+        prop_assert_eq!(MetadataValue::from_json(&json).unwrap(), value);
+    }
+
+    #[test]
+    fn non_finite_floats_are_rejected(f in prop_oneof![Just(f64::NAN), Just(f64::INFINITY), Just(f64::NEG_INFINITY)]) {
+        prop_assert!(MetadataValue::Float(f).to_json().is_err());
+        prop_assert!(Filter::eq("score", f).to_json().is_err());
+    }
+}